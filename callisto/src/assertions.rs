@@ -0,0 +1,626 @@
+//! Data quality assertions, checked by querying the tables and files they're made of.
+//!
+//! A file of assertions holds statements of the form `ASSERT <expectation> ON <relation>
+//! [WHERE <condition>]`, the relation being a table or a quoted path, and the condition limiting
+//! the rows checked. Expectations are:
+//!
+//! - `not_null(a, ...)`: none of the columns is null
+//! - `unique(a, ...)`: no two rows have the same values of the columns
+//! - `accepted_values(a, 'x', ...)`: the column is null or one of the values
+//! - an expression of `row_count`, e.g. `row_count BETWEEN 1 AND 1000`, which the number of rows
+//!   must satisfy
+//! - any other expression, e.g. `price >= 0`, which no row may make false
+//!
+//! Files ending `.yaml` or `.yml` hold a list of the same assertions as maps instead, e.g.
+//!
+//! ```yaml
+//! - relation: users.parquet
+//!   not_null: [id, email]
+//! - relation: orders
+//!   where: status <> 'test'
+//!   row_count: { min: 1, max: 1000 }
+//! ```
+//!
+//! with one of `not_null`, `unique`, `accepted_values: { column, values }`, `row_count: { min,
+//! max }`, or `expect`, an expression, in each.
+//!
+//! Each is checked by a query returning the rows that violate it.
+
+use std::path::Path;
+
+use anyhow::Context as _;
+use serde::Deserialize;
+use sqlparser::ast;
+use sqlparser::dialect::GenericDialect;
+use sqlparser::keywords::Keyword;
+use sqlparser::parser::Parser;
+use sqlparser::tokenizer::Token;
+
+use crate::{EngineInterface, ResultSet};
+
+/// Name standing for the number of rows in an expectation of it
+pub const ROW_COUNT: &str = "row_count";
+
+/// An expectation of the rows of a relation
+#[derive(Clone, Debug, PartialEq)]
+pub struct Assertion {
+    expectation: Expectation,
+    /// The table or file checked
+    pub relation: ast::ObjectName,
+    /// Which of its rows are checked, if not all of them
+    pub filter: Option<ast::Expr>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Expectation {
+    NotNull(Vec<ast::Expr>),
+    Unique(Vec<ast::Expr>),
+    AcceptedValues(ast::Expr, Vec<ast::Expr>),
+    RowCount(ast::Expr),
+    Predicate(ast::Expr),
+}
+
+/// How an assertion was violated
+#[derive(Clone, Debug)]
+pub struct Violation {
+    /// Rows violating it, or for `unique`, the values repeated
+    pub rows: usize,
+    /// The first of those rows
+    pub sample: ResultSet,
+}
+
+/// The assertions in the file at `path`, written in YAML if it ends `.yaml` or `.yml`
+pub fn read(path: &Path) -> anyhow::Result<Vec<Assertion>> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let yaml = path
+        .extension()
+        .is_some_and(|extension| extension == "yaml" || extension == "yml");
+    match yaml {
+        true => parse_yaml(&text),
+        false => parse(&text),
+    }
+    .with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+/// The assertions written in `text`
+pub fn parse(text: &str) -> anyhow::Result<Vec<Assertion>> {
+    let mut parser = Parser::new(&GenericDialect).try_with_sql(text)?;
+    let mut assertions = Vec::new();
+    loop {
+        while parser.consume_token(&Token::SemiColon) {}
+        if parser.peek_token().token == Token::EOF {
+            return Ok(assertions);
+        }
+        assertions.push(parse_assertion(&mut parser)?);
+        let next = parser.peek_token();
+        if !matches!(next.token, Token::SemiColon | Token::EOF) {
+            anyhow::bail!(
+                "Expected ; after an assertion, found {} at {}",
+                next.token,
+                next.location
+            );
+        }
+    }
+}
+
+fn parse_assertion(parser: &mut Parser) -> anyhow::Result<Assertion> {
+    parser.expect_keyword(Keyword::ASSERT)?;
+    // Whether the expectation is a call of the check `name`
+    let checks = |parser: &Parser, name: &str| {
+        let named = match parser.peek_token().token {
+            Token::Word(word) => word.value.eq_ignore_ascii_case(name),
+            _ => false,
+        };
+        named && parser.peek_nth_token(1).token == Token::LParen
+    };
+    let columns = |parser: &mut Parser| -> anyhow::Result<Vec<ast::Expr>> {
+        parser.next_token();
+        parser.expect_token(&Token::LParen)?;
+        let columns = parser.parse_comma_separated(Parser::parse_expr)?;
+        parser.expect_token(&Token::RParen)?;
+        Ok(columns)
+    };
+    let expectation = if checks(parser, "not_null") {
+        Expectation::NotNull(columns(parser)?)
+    } else if checks(parser, "unique") {
+        Expectation::Unique(columns(parser)?)
+    } else if checks(parser, "accepted_values") {
+        let mut arguments = columns(parser)?.into_iter();
+        let column = arguments
+            .next()
+            .context("accepted_values takes a column and the values it may have")?;
+        Expectation::AcceptedValues(column, arguments.collect())
+    } else {
+        let expression = parser.parse_expr()?;
+        if mentions_row_count(&expression) {
+            check_bounds(&expression)?;
+            Expectation::RowCount(expression)
+        } else {
+            Expectation::Predicate(expression)
+        }
+    };
+    parser.expect_keyword(Keyword::ON)?;
+    let relation = parser.parse_object_name(false)?;
+    let filter = match parser.parse_keyword(Keyword::WHERE) {
+        true => Some(parser.parse_expr()?),
+        false => None,
+    };
+    Ok(Assertion {
+        expectation,
+        relation,
+        filter,
+    })
+}
+
+/// Whether `expression` refers to [`ROW_COUNT`]
+fn mentions_row_count(expression: &ast::Expr) -> bool {
+    use core::ops::ControlFlow;
+
+    ast::visit_expressions(expression, |expression| match expression {
+        ast::Expr::Identifier(name) if name.value.eq_ignore_ascii_case(ROW_COUNT) => {
+            ControlFlow::Break(())
+        }
+        _ => ControlFlow::Continue(()),
+    })
+    .is_break()
+}
+
+/// Fail if `expression` has a `BETWEEN` whose lower bound is above its upper one, which no
+/// number of rows could satisfy
+fn check_bounds(expression: &ast::Expr) -> anyhow::Result<()> {
+    use core::ops::ControlFlow;
+
+    let number = |expression: &ast::Expr| match expression {
+        ast::Expr::Value(ast::Value::Number(number, _)) => number.parse::<f64>().ok(),
+        _ => None,
+    };
+    let flow = ast::visit_expressions(expression, |expression| match expression {
+        ast::Expr::Between {
+            negated: false,
+            low,
+            high,
+            ..
+        } => match (number(low), number(high)) {
+            (Some(low), Some(high)) if low > high => ControlFlow::Break(anyhow::anyhow!(
+                "Invalid bounds in {}: {} is above {}",
+                expression,
+                low,
+                high
+            )),
+            _ => ControlFlow::Continue(()),
+        },
+        _ => ControlFlow::Continue(()),
+    });
+    match flow {
+        ControlFlow::Break(error) => Err(error),
+        ControlFlow::Continue(()) => Ok(()),
+    }
+}
+
+/// An assertion as written in YAML, with one of its expectations
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct YamlAssertion {
+    /// A table, or the path of a file
+    relation: String,
+    #[serde(rename = "where")]
+    filter: Option<String>,
+    not_null: Option<Vec<String>>,
+    unique: Option<Vec<String>>,
+    accepted_values: Option<YamlAcceptedValues>,
+    row_count: Option<YamlRowCount>,
+    expect: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct YamlAcceptedValues {
+    column: String,
+    values: Vec<serde_yaml::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct YamlRowCount {
+    min: Option<u64>,
+    max: Option<u64>,
+}
+
+/// The assertions written as a YAML list in `text`
+pub fn parse_yaml(text: &str) -> anyhow::Result<Vec<Assertion>> {
+    let assertions: Vec<YamlAssertion> = serde_yaml::from_str(text)?;
+    assertions
+        .into_iter()
+        .enumerate()
+        .map(|(index, assertion)| {
+            let relation = assertion.relation.clone();
+            Assertion::try_from(assertion)
+                .with_context(|| format!("Invalid assertion {} on {}", index + 1, relation))
+        })
+        .collect()
+}
+
+impl TryFrom<YamlAssertion> for Assertion {
+    type Error = anyhow::Error;
+
+    fn try_from(yaml: YamlAssertion) -> anyhow::Result<Assertion> {
+        let columns = |columns: &[String]| -> anyhow::Result<Vec<ast::Expr>> {
+            if columns.is_empty() {
+                anyhow::bail!("Expected at least one column");
+            }
+            columns.iter().map(|column| expression(column)).collect()
+        };
+        let mut expectations = Vec::new();
+        if let Some(names) = &yaml.not_null {
+            expectations.push(Expectation::NotNull(columns(names)?));
+        }
+        if let Some(names) = &yaml.unique {
+            expectations.push(Expectation::Unique(columns(names)?));
+        }
+        if let Some(accepted) = &yaml.accepted_values {
+            let values = accepted
+                .values
+                .iter()
+                .map(literal)
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            expectations.push(Expectation::AcceptedValues(
+                expression(&accepted.column)?,
+                values,
+            ));
+        }
+        if let Some(bounds) = &yaml.row_count {
+            let condition = match (bounds.min, bounds.max) {
+                (Some(min), Some(max)) => format!("{} BETWEEN {} AND {}", ROW_COUNT, min, max),
+                (Some(min), None) => format!("{} >= {}", ROW_COUNT, min),
+                (None, Some(max)) => format!("{} <= {}", ROW_COUNT, max),
+                (None, None) => anyhow::bail!("row_count takes a min, a max, or both"),
+            };
+            let condition = expression(&condition)?;
+            check_bounds(&condition)?;
+            expectations.push(Expectation::RowCount(condition));
+        }
+        if let Some(condition) = &yaml.expect {
+            let condition = expression(condition)?;
+            expectations.push(match mentions_row_count(&condition) {
+                true => {
+                    check_bounds(&condition)?;
+                    Expectation::RowCount(condition)
+                }
+                false => Expectation::Predicate(condition),
+            });
+        }
+        if expectations.len() != 1 {
+            anyhow::bail!(
+                "Expected one of not_null, unique, accepted_values, row_count, or expect, not {}",
+                expectations.len()
+            );
+        }
+        Ok(Assertion {
+            expectation: expectations.remove(0),
+            relation: relation(&yaml.relation)?,
+            filter: yaml.filter.as_deref().map(expression).transpose()?,
+        })
+    }
+}
+
+/// The SQL expression `text`, all of which must be the expression
+fn expression(text: &str) -> anyhow::Result<ast::Expr> {
+    let mut parser = Parser::new(&GenericDialect).try_with_sql(text)?;
+    let expression = parser.parse_expr()?;
+    let next = parser.peek_token();
+    if next.token != Token::EOF {
+        anyhow::bail!(
+            "Unexpected {} after {} at {}",
+            next.token,
+            expression,
+            next.location
+        );
+    }
+    Ok(expression)
+}
+
+/// The relation named `name`: a path, told by a `/`, a `*`, or the extension of a dataset, as
+/// one quoted name, or else a table's name as SQL writes it
+fn relation(name: &str) -> anyhow::Result<ast::ObjectName> {
+    let path = name.contains(['/', '*'])
+        || crate::dataset::EXTENSIONS
+            .iter()
+            .any(|extension| name.ends_with(&format!(".{}", extension)));
+    if path {
+        return Ok(ast::ObjectName(vec![ast::Ident::with_quote('"', name)]));
+    }
+    let mut parser = Parser::new(&GenericDialect).try_with_sql(name)?;
+    let relation = parser.parse_object_name(false)?;
+    if parser.peek_token().token != Token::EOF {
+        anyhow::bail!("Invalid relation {}", name);
+    }
+    Ok(relation)
+}
+
+/// The YAML scalar `value` as a SQL literal
+fn literal(value: &serde_yaml::Value) -> anyhow::Result<ast::Expr> {
+    Ok(ast::Expr::Value(match value {
+        serde_yaml::Value::Null => ast::Value::Null,
+        serde_yaml::Value::Bool(value) => ast::Value::Boolean(*value),
+        serde_yaml::Value::Number(value) => ast::Value::Number(value.to_string(), false),
+        serde_yaml::Value::String(value) => ast::Value::SingleQuotedString(value.clone()),
+        value => anyhow::bail!("Accepted values are scalars, not {:?}", value),
+    }))
+}
+
+fn list(expressions: &[ast::Expr]) -> String {
+    expressions
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+impl Assertion {
+    /// The query returning the rows violating the assertion
+    pub fn query(&self) -> String {
+        let relation = &self.relation;
+        let filtered = |condition: String| match &self.filter {
+            Some(filter) => format!("({}) AND ({})", filter, condition),
+            None => condition,
+        };
+        let filter = || match &self.filter {
+            Some(filter) => format!(" WHERE {}", filter),
+            None => String::new(),
+        };
+        match &self.expectation {
+            Expectation::NotNull(columns) => {
+                let condition = columns
+                    .iter()
+                    .map(|column| format!("{} IS NULL", column))
+                    .collect::<Vec<_>>()
+                    .join(" OR ");
+                format!("SELECT * FROM {} WHERE {}", relation, filtered(condition))
+            }
+            Expectation::Unique(columns) => format!(
+                "SELECT {columns}, COUNT(*) AS occurrences FROM {}{} GROUP BY {columns} \
+                 HAVING COUNT(*) > 1",
+                relation,
+                filter(),
+                columns = list(columns)
+            ),
+            Expectation::AcceptedValues(column, values) => format!(
+                "SELECT * FROM {} WHERE {}",
+                relation,
+                filtered(format!("{} NOT IN ({})", column, list(values)))
+            ),
+            Expectation::RowCount(expectation) => format!(
+                "SELECT {count} FROM (SELECT COUNT(*) AS {count} FROM {}{}) AS counted \
+                 WHERE NOT ({})",
+                relation,
+                filter(),
+                expectation,
+                count = ROW_COUNT
+            ),
+            Expectation::Predicate(expectation) => format!(
+                "SELECT * FROM {} WHERE {}",
+                relation,
+                filtered(format!("NOT ({})", expectation))
+            ),
+        }
+    }
+
+    /// Check the assertion on `engine`, returning how it was violated, with up to `sample` of the
+    /// rows violating it, if it was
+    pub async fn check(
+        &self,
+        engine: &dyn EngineInterface,
+        sample: usize,
+    ) -> anyhow::Result<Option<Violation>> {
+        use futures::stream::TryStreamExt as _;
+
+        let mut executions = engine.execute(&self.query()).await?;
+        let execution = executions
+            .pop()
+            .context("The assertion's query ran nothing")?;
+        let schema = execution.schema;
+        let batches = execution.stream.try_collect::<Vec<_>>().await?;
+        let rows = batches.iter().map(|batch| batch.num_rows()).sum::<usize>();
+        if rows == 0 {
+            return Ok(None);
+        }
+        let mut remaining = sample;
+        let sample = batches
+            .iter()
+            .map(|batch| {
+                let rows = batch.num_rows().min(remaining);
+                remaining -= rows;
+                batch.slice(0, rows)
+            })
+            .filter(|batch| batch.num_rows() > 0)
+            .collect();
+        Ok(Some(Violation {
+            rows,
+            sample: ResultSet {
+                schema,
+                batches: sample,
+            },
+        }))
+    }
+}
+
+impl std::fmt::Display for Assertion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ASSERT ")?;
+        match &self.expectation {
+            Expectation::NotNull(columns) => write!(f, "not_null({})", list(columns))?,
+            Expectation::Unique(columns) => write!(f, "unique({})", list(columns))?,
+            Expectation::AcceptedValues(column, values) => {
+                write!(f, "accepted_values({}, {})", column, list(values))?
+            }
+            Expectation::RowCount(expectation) | Expectation::Predicate(expectation) => {
+                write!(f, "{}", expectation)?
+            }
+        }
+        write!(f, " ON {}", self.relation)?;
+        if let Some(filter) = &self.filter {
+            write!(f, " WHERE {}", filter)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn one(text: &str) -> Assertion {
+        let mut assertions = parse(text).unwrap();
+        assert_eq!(assertions.len(), 1, "{:?}", assertions);
+        assertions.remove(0)
+    }
+
+    fn one_yaml(text: &str) -> Assertion {
+        let mut assertions = parse_yaml(text).unwrap();
+        assert_eq!(assertions.len(), 1, "{:?}", assertions);
+        assertions.remove(0)
+    }
+
+    #[test]
+    fn not_null() {
+        let assertion = one("ASSERT not_null(id, email) ON users");
+        assert!(
+            matches!(&assertion.expectation, Expectation::NotNull(columns) if columns.len() == 2)
+        );
+        assert_eq!(assertion.relation.to_string(), "users");
+        assert_eq!(
+            assertion.query(),
+            "SELECT * FROM users WHERE id IS NULL OR email IS NULL"
+        );
+        assert_eq!(assertion.to_string(), "ASSERT not_null(id, email) ON users");
+    }
+
+    #[test]
+    fn unique_with_filter() {
+        let assertion = one(r#"ASSERT UNIQUE(id) ON "data/users.parquet" WHERE active"#);
+        assert!(
+            matches!(&assertion.expectation, Expectation::Unique(columns) if columns.len() == 1)
+        );
+        assert_eq!(assertion.relation.to_string(), r#""data/users.parquet""#);
+        assert_eq!(
+            assertion.query(),
+            "SELECT id, COUNT(*) AS occurrences FROM \"data/users.parquet\" WHERE active \
+             GROUP BY id HAVING COUNT(*) > 1"
+        );
+    }
+
+    #[test]
+    fn accepted_values() {
+        let assertion = one("ASSERT accepted_values(status, 'paid', 'refunded') ON orders");
+        assert!(matches!(
+            &assertion.expectation,
+            Expectation::AcceptedValues(_, values) if values.len() == 2
+        ));
+        assert_eq!(
+            assertion.query(),
+            "SELECT * FROM orders WHERE status NOT IN ('paid', 'refunded')"
+        );
+        assert!(parse("ASSERT accepted_values() ON orders").is_err());
+    }
+
+    #[test]
+    fn row_count_between() {
+        let assertion = one("ASSERT row_count BETWEEN 1 AND 1000 ON orders");
+        assert!(matches!(assertion.expectation, Expectation::RowCount(_)));
+        assert_eq!(
+            assertion.query(),
+            "SELECT row_count FROM (SELECT COUNT(*) AS row_count FROM orders) AS counted \
+             WHERE NOT (row_count BETWEEN 1 AND 1000)"
+        );
+        assert!(parse("ASSERT row_count BETWEEN 10 AND 1 ON orders").is_err());
+        assert!(parse("ASSERT row_count BETWEEN 5 AND 5 ON orders").is_ok());
+    }
+
+    #[test]
+    fn predicate() {
+        let assertion = one("ASSERT price >= 0 ON orders WHERE status = 'paid'");
+        assert!(matches!(assertion.expectation, Expectation::Predicate(_)));
+        assert_eq!(
+            assertion.query(),
+            "SELECT * FROM orders WHERE (status = 'paid') AND (NOT (price >= 0))"
+        );
+    }
+
+    #[test]
+    fn several_assertions() {
+        let assertions = parse(
+            "ASSERT not_null(id) ON users;\n\n\
+             ASSERT unique(id) ON users;\n\
+             ASSERT row_count > 0 ON users",
+        )
+        .unwrap();
+        assert_eq!(assertions.len(), 3);
+        assert!(parse("ASSERT not_null(id) ON users ASSERT unique(id) ON users").is_err());
+        assert!(parse("not_null(id) ON users").is_err());
+        assert!(parse("ASSERT not_null(id) users").is_err());
+        assert_eq!(parse(" ;; ").unwrap(), Vec::new());
+    }
+
+    #[test]
+    fn yaml_not_null_and_unique() {
+        let assertion = one_yaml("- relation: data/users.parquet\n  not_null: [id, email]\n");
+        assert_eq!(
+            assertion,
+            one(r#"ASSERT not_null(id, email) ON "data/users.parquet""#)
+        );
+        let assertion = one_yaml("- relation: users\n  where: active\n  unique: [id]\n");
+        assert_eq!(assertion, one("ASSERT unique(id) ON users WHERE active"));
+        assert!(parse_yaml("- relation: users\n  not_null: []\n").is_err());
+    }
+
+    #[test]
+    fn yaml_accepted_values() {
+        let assertion = one_yaml(
+            "- relation: orders\n  accepted_values:\n    column: status\n    \
+             values: [paid, 3, true, null]\n",
+        );
+        assert_eq!(
+            assertion,
+            one("ASSERT accepted_values(status, 'paid', 3, true, NULL) ON orders")
+        );
+        assert!(parse_yaml(
+            "- relation: orders\n  accepted_values:\n    column: status\n    values: [[paid]]\n"
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn yaml_row_count() {
+        let assertion = one_yaml("- relation: orders\n  row_count: { min: 1, max: 1000 }\n");
+        assert_eq!(
+            assertion,
+            one("ASSERT row_count BETWEEN 1 AND 1000 ON orders")
+        );
+        let assertion = one_yaml("- relation: orders\n  row_count: { min: 1 }\n");
+        assert_eq!(assertion, one("ASSERT row_count >= 1 ON orders"));
+        let assertion = one_yaml("- relation: orders\n  row_count: { max: 10 }\n");
+        assert_eq!(assertion, one("ASSERT row_count <= 10 ON orders"));
+        assert!(parse_yaml("- relation: orders\n  row_count: { min: 10, max: 1 }\n").is_err());
+        assert!(parse_yaml("- relation: orders\n  row_count: {}\n").is_err());
+        assert!(parse_yaml("- relation: orders\n  row_count: { min: -1 }\n").is_err());
+    }
+
+    #[test]
+    fn yaml_expect() {
+        let assertion = one_yaml("- relation: orders\n  expect: price >= 0\n");
+        assert_eq!(assertion, one("ASSERT price >= 0 ON orders"));
+        let assertion = one_yaml("- relation: orders\n  expect: row_count BETWEEN 1 AND 2\n");
+        assert!(matches!(assertion.expectation, Expectation::RowCount(_)));
+        assert!(parse_yaml("- relation: orders\n  expect: row_count BETWEEN 2 AND 1\n").is_err());
+        assert!(parse_yaml("- relation: orders\n  expect: price >= 0 garbage\n").is_err());
+    }
+
+    #[test]
+    fn yaml_takes_one_expectation() {
+        assert!(parse_yaml("- relation: orders\n").is_err());
+        assert!(parse_yaml("- relation: orders\n  not_null: [id]\n  unique: [id]\n").is_err());
+        assert!(parse_yaml("- relation: orders\n  not_nul: [id]\n").is_err());
+        assert!(parse_yaml("- not_null: [id]\n").is_err());
+    }
+}
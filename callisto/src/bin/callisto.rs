@@ -1,3 +1,4 @@
+use anyhow::Context as _;
 use clap::Parser;
 use serde::Serialize;
 
@@ -5,6 +6,15 @@ use serde::Serialize;
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None, arg_required_else_help = true)]
 struct Args {
+    /// Increase logging verbosity (-v for info, -vv for debug, -vvv for trace); overridden by
+    /// CALLISTO_LOG
+    #[arg(long, short, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// Configuration file to use instead of the default location
+    #[arg(long, global = true)]
+    config: Option<std::path::PathBuf>,
+
     #[command(subcommand)]
     command: Command,
 }
@@ -13,22 +23,250 @@ struct Args {
 enum Command {
     /// Execute individual commands on an engine of your choice, default being DataFusion
     Exec {
-        // TODO(akesling): Add output format control
-        /// Command to execute
-        command: String,
+        /// Command to execute, in PRQL if it starts with prql:
+        #[arg(required_unless_present = "substrait")]
+        command: Option<String>,
+
+        /// Execute the serialized Substrait plan in this file instead of a command; DataFusion
+        /// and DuckDB can run them
+        #[arg(long, conflicts_with = "command")]
+        substrait: Option<std::path::PathBuf>,
 
         /// Engine on which to execute
         #[arg(long, short, default_value_t, value_enum)]
         engine: Engine,
+
+        /// Print timing and row counts after each statement
+        #[arg(long)]
+        timing: bool,
+
+        /// How to render results; csv and json print nothing but the results themselves
+        #[arg(long, short, default_value_t, value_enum)]
+        format: OutputFormat,
+
+        /// Print each statement's plan and the files it would scan instead of executing it
+        #[arg(long, conflicts_with = "substrait")]
+        dry_run: bool,
+
+        /// Return at most this many rows from each statement, cutting longer results short
+        #[arg(long)]
+        row_limit: Option<usize>,
+
+        /// Append a JSON record of what each statement read and wrote to this file, a line each
+        #[arg(long, conflicts_with = "substrait")]
+        lineage: Option<std::path::PathBuf>,
+
+        /// Hide the values of columns of the results, e.g. "email=hash, ssn=redact", on top of
+        /// the masks of the config; masks are hash, redact, truncate(<length>), and
+        /// bucket(<width>)
+        #[arg(long)]
+        mask: Option<callisto::MaskPolicy>,
     },
     /// Drop into a read, eval, print loop for an engine of your choice, default being DataFusion
     Repl {
         /// Engine on which to execute
         #[arg(long, short, default_value_t, value_enum)]
         engine: Engine,
+
+        /// Print timing and row counts after each statement
+        #[arg(long)]
+        timing: bool,
+
+        /// File in which to persist command history
+        #[arg(long)]
+        history_file: Option<std::path::PathBuf>,
+
+        /// Don't persist command history across sessions
+        #[arg(long, conflicts_with = "history_file")]
+        no_history: bool,
+
+        /// Don't run ~/.callistorc on startup
+        #[arg(long)]
+        no_rc: bool,
+
+        /// How to render results; with piped input, csv and json print nothing but the results
+        #[arg(long, short, default_value_t, value_enum)]
+        format: OutputFormat,
+
+        /// With piped input, report failing statements and carry on instead of exiting at the
+        /// first, still exiting non-zero at the end
+        #[arg(long)]
+        continue_on_error: bool,
+
+        /// Return at most this many rows from each statement, cutting longer results short
+        #[arg(long)]
+        row_limit: Option<usize>,
+    },
+    /// Parse and check the queries in a file without executing them, exiting non-zero on any
+    /// problem
+    Validate {
+        /// File of semicolon-separated queries to check, or a PRQL query if it ends in .prql
+        #[arg(long, short)]
+        file: std::path::PathBuf,
+
+        /// Engine against which to check the queries
+        #[arg(long, short, default_value_t, value_enum)]
+        engine: Engine,
+    },
+    /// Run the queries in a file on a schedule, appending the results of each run, stamped with
+    /// its time in a run_at column, to a Parquet dataset
+    Schedule {
+        /// When to run, as a cron expression in local time, e.g. "0 * * * *" for hourly
+        #[arg(long)]
+        cron: callisto::schedule::CronSchedule,
+
+        /// File of semicolon-separated queries to run, or a PRQL query if it ends in .prql; the
+        /// results of the last statement are kept
+        #[arg(long, short)]
+        file: std::path::PathBuf,
+
+        /// Directory of the dataset each run's results are written to a file of their own in
+        #[arg(long)]
+        out: std::path::PathBuf,
+
+        /// Engine on which to execute
+        #[arg(long, short, default_value_t, value_enum)]
+        engine: Engine,
+
+        /// Stop after this many runs rather than running until interrupted
+        #[arg(long)]
+        runs: Option<usize>,
+    },
+    /// Run the .sql and .prql checks in a directory, comparing each result with the one expected
+    /// in a CSV, Parquet, or JSON file of the same name beside it, and exit non-zero if any differ
+    Test {
+        /// Directory of checks, searched recursively
+        dir: std::path::PathBuf,
+
+        /// Engine on which to run the checks, each on an engine of its own
+        #[arg(long, short, default_value_t, value_enum)]
+        engine: Engine,
+
+        /// Most two numbers may differ by and still match
+        #[arg(long, default_value_t = 0.0)]
+        tolerance: f64,
+
+        /// Most two numbers may differ by, as a fraction of the expected one, and still match
+        #[arg(long, default_value_t = 0.0)]
+        relative_tolerance: f64,
+
+        /// Compare rows regardless of their order
+        #[arg(long)]
+        ignore_order: bool,
+
+        /// Write each check's result as the one expected of it, as CSV unless there's one already
+        #[arg(long)]
+        update: bool,
+    },
+    /// Check the data quality assertions in files, e.g. `ASSERT unique(id) ON "users.parquet"`,
+    /// printing a sample of the rows violating each that fails, and exit non-zero if any do
+    Check {
+        /// Files of semicolon-separated assertions, or of YAML lists of them if ending `.yaml`
+        #[arg(required = true)]
+        files: Vec<std::path::PathBuf>,
+
+        /// Engine on which to check the assertions, one for each file
+        #[arg(long, short, default_value_t, value_enum)]
+        engine: Engine,
+
+        /// Most violating rows printed for each failing assertion
+        #[arg(long, default_value_t = 10)]
+        sample: usize,
+    },
+    /// Print SQL files in canonical format
+    Fmt {
+        /// SQL files to format
+        #[arg(required = true)]
+        files: Vec<std::path::PathBuf>,
+
+        /// Exit non-zero if any file is not already formatted, instead of printing
+        #[arg(long)]
+        check: bool,
+
+        /// Line width beyond which clauses are broken onto multiple lines
+        #[arg(long, default_value_t = 100)]
+        width: usize,
+
+        /// Case in which to emit SQL keywords
+        #[arg(long, default_value_t, value_enum)]
+        keyword_case: KeywordCase,
     },
     /// Load the full Callisto console
-    Console {},
+    Console {
+        /// Engine on which to execute, defaulting to the one the session last used or else
+        /// DataFusion
+        #[arg(long, short, value_enum)]
+        engine: Option<Engine>,
+
+        /// Named workspace whose editor, panes, and engine are restored on launch and saved on
+        /// exit
+        #[arg(long, short, default_value = callisto::console::DEFAULT_SESSION)]
+        session: String,
+    },
+    /// Serve the engines to other programs over the network
+    Serve {
+        /// Serve Arrow Flight SQL, for BI tools and other Arrow-native clients
+        #[arg(long)]
+        flight_sql: bool,
+
+        /// Address to serve Flight SQL on; nothing is authenticated, so listen only where
+        /// clients are trusted
+        #[arg(long, default_value = "127.0.0.1:50051", value_parser = listen_address)]
+        listen: std::net::SocketAddr,
+
+        /// Serve queries over HTTP on this address, e.g. :8080 for every interface
+        #[arg(long, value_parser = listen_address)]
+        http: Option<std::net::SocketAddr>,
+
+        /// Serve Prometheus metrics of the queries run on this address, e.g. :9090 for every
+        /// interface
+        #[arg(long, value_parser = listen_address)]
+        metrics: Option<std::net::SocketAddr>,
+
+        /// Engine each Flight SQL session, and the HTTP server, gets one of
+        #[arg(long, short, default_value_t, value_enum)]
+        engine: Engine,
+
+        /// File of SQL run on each new engine, e.g. to register tables
+        #[arg(long)]
+        init: Option<std::path::PathBuf>,
+
+        /// Return at most this many rows from each statement, protecting the server from clients
+        /// dumping whole tables
+        #[arg(long)]
+        row_limit: Option<usize>,
+
+        /// Directory or URI prefix, e.g. s3://bucket/public, tables may be loaded from; anywhere
+        /// if none is given
+        #[arg(long, value_name = "PATH")]
+        allow_path: Vec<String>,
+
+        /// Directory or URI prefix tables may not be loaded from, even within an allowed one
+        #[arg(long, value_name = "PATH")]
+        deny_path: Vec<String>,
+    },
+    /// Serve diagnostics, completions, and hovers for SQL files to an editor over the Language
+    /// Server Protocol on stdin and stdout
+    Lsp {
+        /// Engine whose catalog tables are checked against
+        #[arg(long, short, default_value_t, value_enum)]
+        engine: Engine,
+
+        /// File of SQL run on the engine first, e.g. to register the tables the files query
+        #[arg(long)]
+        init: Option<std::path::PathBuf>,
+    },
+}
+
+/// Parse an address to listen on, one without a host meaning every interface
+fn listen_address(address: &str) -> Result<std::net::SocketAddr, String> {
+    let address = match address.strip_prefix(':') {
+        Some(port) => format!("0.0.0.0:{}", port),
+        None => address.to_string(),
+    };
+    address
+        .parse()
+        .map_err(|error| format!("Invalid address {}: {}", address, error))
 }
 
 #[derive(clap::ValueEnum, Clone, Debug, Serialize, Default)]
@@ -39,63 +277,534 @@ enum Engine {
     DataFusion,
 }
 
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default)]
+enum OutputFormat {
+    #[default]
+    Table,
+    Csv,
+    Json,
+    Vertical,
+}
+
+impl From<OutputFormat> for callisto::OutputFormat {
+    fn from(format: OutputFormat) -> callisto::OutputFormat {
+        match format {
+            OutputFormat::Table => callisto::OutputFormat::Table,
+            OutputFormat::Csv => callisto::OutputFormat::Csv,
+            OutputFormat::Json => callisto::OutputFormat::Json,
+            OutputFormat::Vertical => callisto::OutputFormat::Vertical,
+        }
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Debug, Default)]
+enum KeywordCase {
+    #[default]
+    Upper,
+    Lower,
+}
+
+impl From<KeywordCase> for callisto::KeywordCase {
+    fn from(case: KeywordCase) -> callisto::KeywordCase {
+        match case {
+            KeywordCase::Upper => callisto::KeywordCase::Upper,
+            KeywordCase::Lower => callisto::KeywordCase::Lower,
+        }
+    }
+}
+
 impl Engine {
     pub fn new(&self) -> anyhow::Result<Box<dyn callisto::EngineInterface>> {
+        self.kind().new()
+    }
+
+    /// Configuration of a new engine of this kind, returning at most `row_limit` rows from each
+    /// statement if there is one
+    fn builder(&self, row_limit: Option<usize>) -> callisto::EngineBuilder {
+        let builder = self.kind().builder();
+        match row_limit {
+            Some(rows) => builder.row_limit(rows),
+            None => builder,
+        }
+    }
+
+    fn kind(&self) -> callisto::Engine {
         match self {
-            Engine::Polars => callisto::Engine::Polars.new(),
-            Engine::DuckDB => callisto::Engine::DuckDB.new(),
-            Engine::DataFusion => callisto::Engine::DataFusion.new(),
+            Engine::Polars => callisto::Engine::Polars,
+            Engine::DuckDB => callisto::Engine::DuckDB,
+            Engine::DataFusion => callisto::Engine::DataFusion,
         }
     }
 }
 
+/// Send logs to stderr, or to the console's `diagnostics` when given, filtered by `CALLISTO_LOG`
+/// when set and by verbosity otherwise.
+///
+/// Spans are logged as they close with how long they took from -v, and as they open too from
+/// -vv, giving a timeline of each statement.
+///
+/// The console logs Callisto's own debug events whatever the verbosity, as its log pane has room
+/// for them.
+fn init_logging(verbosity: u8, diagnostics: Option<callisto::console::Diagnostics>) {
+    use tracing_subscriber::{
+        fmt::format::FmtSpan, layer::SubscriberExt as _, util::SubscriberInitExt as _,
+    };
+
+    let filter_verbosity = if diagnostics.is_some() {
+        verbosity.max(2)
+    } else {
+        verbosity
+    };
+    let default_filter = match filter_verbosity {
+        0 => "warn",
+        1 => "warn,callisto=info,callisto_engines=info",
+        2 => "warn,callisto=debug,callisto_engines=debug",
+        _ => "trace",
+    };
+    let filter = tracing_subscriber::EnvFilter::try_from_env("CALLISTO_LOG")
+        .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new(default_filter));
+    let registry = tracing_subscriber::registry().with(filter);
+    match diagnostics {
+        // Anything written to stderr would be drawn over by the console
+        Some(diagnostics) => registry.with(diagnostics).init(),
+        None => registry
+            .with(
+                tracing_subscriber::fmt::layer()
+                    .with_span_events(match verbosity {
+                        0 => FmtSpan::NONE,
+                        1 => FmtSpan::CLOSE,
+                        _ => FmtSpan::NEW | FmtSpan::CLOSE,
+                    })
+                    .with_writer(std::io::stderr),
+            )
+            .init(),
+    }
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     use futures::stream::StreamExt as _;
     let args = Args::parse();
+    let diagnostics = matches!(args.command, Command::Console { .. })
+        .then(callisto::console::Diagnostics::default);
+    init_logging(args.verbose, diagnostics.clone());
+    let config = callisto::Config::load(args.config)?;
 
     match args.command {
         Command::Exec {
             command,
+            substrait,
             engine: engine_type,
+            timing,
+            format,
+            dry_run,
+            row_limit,
+            lineage,
+            mask,
         } => {
-            println!(
-                "Running command '{}' on engine '{}'",
-                command,
-                &serde_json::to_string(&engine_type).unwrap()
-            );
-
-            let mut engine = engine_type.new()?;
-            let executions = engine.execute(&command).await?;
-            for (statement, mut stream) in executions {
-                println!("\n$ {}", statement.to_string());
+            let format = callisto::OutputFormat::from(format);
+            let mut masks = config.masks;
+            if let Some(mask) = &mask {
+                masks.extend(mask);
+            }
+            let engine = engine_type.builder(row_limit).build()?;
+            let executions = match (command, substrait) {
+                (_, Some(path)) => {
+                    if format.is_human_readable() {
+                        println!(
+                            "Running Substrait plan '{}' on engine '{}'",
+                            path.display(),
+                            &serde_json::to_string(&engine_type).unwrap()
+                        );
+                    }
+                    let plan = std::fs::read(&path)
+                        .with_context(|| format!("Failed to read {}", path.display()))?;
+                    let (stream, metrics) = engine.execute_substrait(&plan).await?;
+                    vec![(path.display().to_string(), None, stream, metrics)]
+                }
+                (Some(command), None) => {
+                    if format.is_human_readable() {
+                        println!(
+                            "Running command '{}' on engine '{}'",
+                            command,
+                            &serde_json::to_string(&engine_type).unwrap()
+                        );
+                    }
+                    let command = callisto::prql::to_sql(
+                        &command,
+                        callisto::Language::Sql,
+                        engine_type.kind(),
+                    )?;
+                    if dry_run {
+                        for run in engine.dry_run(&command).await? {
+                            println!("\n$ {}", run.statement);
+                            println!("Files:");
+                            for file in &run.files {
+                                println!("  {} ({} bytes)", file.path, file.bytes);
+                            }
+                            println!("Scans at most {} bytes", run.bytes());
+                            match &run.plan {
+                                Some(plan) => println!("Plan:\n{}", plan),
+                                None => println!("Plan: can't be known without executing it"),
+                            }
+                        }
+                        return Ok(());
+                    }
+                    engine
+                        .execute(&command)
+                        .await?
+                        .into_iter()
+                        .map(|execution| {
+                            (
+                                execution.statement.to_string(),
+                                Some(execution.statement),
+                                execution.stream,
+                                execution.metrics,
+                            )
+                        })
+                        .collect()
+                }
+                (None, None) => anyhow::bail!("Nothing to execute: pass a command or --substrait"),
+            };
+            let mut lineage = match &lineage {
+                Some(path) => Some(
+                    std::fs::OpenOptions::new()
+                        .create(true)
+                        .append(true)
+                        .open(path)
+                        .with_context(|| format!("Failed to open {}", path.display()))?,
+                ),
+                None => None,
+            };
+            for (statement, parsed, stream, metrics) in executions {
+                let mut stream = masks.masked(stream);
                 let mut batches = Vec::new();
                 while let Some(items) = stream.next().await {
                     batches.push(items?);
                 }
-                let pretty_results =
-                    arrow::util::pretty::pretty_format_batches(&batches)?.to_string();
-                println!("Results:\n{}", pretty_results);
+                let rendered = format.render(stream.schema(), &batches)?;
+                if format.is_human_readable() {
+                    println!("\n$ {}", statement);
+                    println!("Results:\n{}", rendered);
+                } else {
+                    println!("{}", rendered);
+                }
+                if let Some(limit) = row_limit.filter(|_| metrics.truncated()) {
+                    eprintln!("Results truncated at the row limit of {} rows", limit);
+                }
+                if timing && format.is_human_readable() {
+                    println!("Timing: {}", metrics);
+                } else if timing {
+                    // Keep machine-readable output parseable
+                    eprintln!("Timing: {}", metrics);
+                }
+                if let (Some(file), Some(parsed)) = (&mut lineage, &parsed) {
+                    use std::io::Write as _;
+
+                    let record = engine.lineage(parsed, &metrics).await?;
+                    writeln!(file, "{}", serde_json::to_string(&record)?)?;
+                }
             }
             Ok(())
         }
         Command::Repl {
             engine: engine_type,
+            timing,
+            history_file,
+            no_history,
+            no_rc,
+            format,
+            continue_on_error,
+            row_limit,
         } => {
-            let mut engine = engine_type.new()?;
+            use std::io::IsTerminal as _;
+
+            let builder = engine_type.builder(row_limit);
+            let mut engine = builder.clone().build()?;
 
-            callisto::Repl::run(&mut engine, tokio::io::stdin(), tokio::io::stdout()).await?;
+            let history_file = if no_history {
+                None
+            } else {
+                history_file.or_else(|| config.repl.history_path())
+            };
+            callisto::Repl::run(
+                &mut engine,
+                tokio::io::stdin(),
+                tokio::io::stdout(),
+                callisto::ReplOptions {
+                    prompt: config.repl.prompt,
+                    timing: timing || config.repl.timing,
+                    format: format.into(),
+                    language: callisto::Language::default(),
+                    pager: config.repl.pager,
+                    max_rows: config.repl.max_rows,
+                    result_history: config.repl.result_history,
+                    variables: callisto::Variables::default(),
+                    rc_file: if no_rc { None } else { callisto::rc_path() },
+                    history_file,
+                    history_size: config.repl.history_size,
+                    interactive: std::io::stdin().is_terminal(),
+                    continue_on_error,
+                    prefetch_batches: config.repl.prefetch_batches,
+                    masks: config.masks,
+                    engine: Some(builder),
+                },
+            )
+            .await?;
             Ok(())
         }
-        Command::Console {} => {
+        Command::Schedule {
+            cron,
+            file,
+            out,
+            engine: engine_type,
+            runs,
+        } => {
+            let query = std::fs::read_to_string(&file)
+                .with_context(|| format!("Failed to read {}", file.display()))?;
+            let query = callisto::prql::to_sql(
+                &query,
+                callisto::Language::of_path(&file),
+                engine_type.kind(),
+            )?;
+            let engine = engine_type.new()?;
+            println!(
+                "Running {} on {} at \"{}\", appending its results to {}",
+                file.display(),
+                &serde_json::to_string(&engine_type).unwrap(),
+                cron,
+                out.display()
+            );
+            callisto::schedule::run(engine.as_ref(), &query, &cron, &out, runs).await?;
+            Ok(())
+        }
+        Command::Validate {
+            file,
+            engine: engine_type,
+        } => {
+            let query = std::fs::read_to_string(&file)?;
+            let query = match callisto::Language::of_path(&file) {
+                callisto::Language::Prql => callisto::prql::compile(&query, engine_type.kind())?,
+                callisto::Language::Sql => query,
+            };
+            let engine = engine_type.new()?;
+            let issues = engine.validate(&query).await?;
+            for issue in &issues {
+                println!("{}: {}", file.display(), issue);
+            }
+            if !issues.is_empty() {
+                anyhow::bail!("Found {} problem(s) in {}", issues.len(), file.display());
+            }
+            println!("{}: OK", file.display());
+            Ok(())
+        }
+        Command::Test {
+            dir,
+            engine: engine_type,
+            tolerance,
+            relative_tolerance,
+            ignore_order,
+            update,
+        } => {
+            let options = callisto::golden::GoldenOptions {
+                tolerance,
+                relative_tolerance,
+                ignore_order,
+                update,
+            };
+            let checks = callisto::golden::checks(&dir)?;
+            let mut failed = 0;
+            for check in &checks {
+                let engine = engine_type.new()?;
+                let outcome = callisto::golden::check(engine.as_ref(), check, &options)
+                    .await
+                    .unwrap_or_else(|error| {
+                        callisto::golden::Outcome::Failed(format!("{:#}", error))
+                    });
+                match outcome {
+                    callisto::golden::Outcome::Passed => println!("PASS {}", check.display()),
+                    callisto::golden::Outcome::Updated(path) => {
+                        println!("UPDATED {} -> {}", check.display(), path.display())
+                    }
+                    callisto::golden::Outcome::Failed(difference) => {
+                        failed += 1;
+                        println!("FAIL {}: {}", check.display(), difference);
+                    }
+                }
+            }
+            println!("{} passed, {} failed", checks.len() - failed, failed);
+            if failed > 0 {
+                anyhow::bail!("{} of {} check(s) failed", failed, checks.len());
+            }
+            Ok(())
+        }
+        Command::Check {
+            files,
+            engine: engine_type,
+            sample,
+        } => {
+            let (mut checked, mut failed) = (0, 0);
+            for file in &files {
+                let assertions = callisto::assertions::read(file)?;
+                let engine = engine_type.new()?;
+                for assertion in &assertions {
+                    checked += 1;
+                    match assertion.check(engine.as_ref(), sample).await {
+                        Ok(None) => println!("PASS {}", assertion),
+                        Ok(Some(violation)) => {
+                            failed += 1;
+                            println!("FAIL {}: {} violating row(s)", assertion, violation.rows);
+                            let table = callisto::OutputFormat::Table.render(
+                                violation.sample.schema.clone(),
+                                &violation.sample.batches,
+                            )?;
+                            println!("{}", table);
+                        }
+                        Err(error) => {
+                            failed += 1;
+                            println!("FAIL {}: {:#}", assertion, error);
+                        }
+                    }
+                }
+            }
+            println!("{} passed, {} failed", checked - failed, failed);
+            if failed > 0 {
+                anyhow::bail!("{} of {} assertion(s) failed", failed, checked);
+            }
+            Ok(())
+        }
+        Command::Fmt {
+            files,
+            check,
+            width,
+            keyword_case,
+        } => {
+            let options = callisto::FormatOptions {
+                width,
+                keyword_case: keyword_case.into(),
+            };
+            let mut unformatted = Vec::new();
+            for file in &files {
+                let original = std::fs::read_to_string(file)?;
+                let formatted = callisto::format_sql(&original, &options)?;
+                if !check {
+                    print!("{}", formatted);
+                } else if formatted != original {
+                    println!("{} is not formatted", file.display());
+                    unformatted.push(file);
+                }
+            }
+            if !unformatted.is_empty() {
+                anyhow::bail!("{} file(s) need formatting", unformatted.len());
+            }
+            Ok(())
+        }
+        Command::Console {
+            engine: engine_type,
+            session: session_name,
+        } => {
+            let session = callisto::console::Session::load(&session_name)?;
+            let kind = match (engine_type, session.engine()) {
+                (Some(engine_type), _) => engine_type.kind(),
+                (None, Some(kind)) => kind,
+                (None, None) => Engine::default().kind(),
+            };
+            let options = callisto::console::ConsoleOptions {
+                engine: kind.builder(),
+                theme: callisto::console::Theme::load(&config.console)?,
+                keymap: config.console.keymap,
+                remotes: callisto::Remote::open_all(&config.remotes)?,
+                masks: config.masks,
+            };
+            let engine = options.engine.clone().build()?;
+            let diagnostics = diagnostics.unwrap_or_default();
             tokio::task::spawn_blocking(move || callisto::console::setup_term_for_console())
                 .await??;
 
             let stdout = tokio_util::io::SyncIoBridge::new(tokio::io::stdout());
-            tokio::task::spawn_blocking(move || callisto::console::run_console(stdout)).await??;
+            let session = tokio::task::spawn_blocking(move || {
+                callisto::console::run_console(engine, options, diagnostics, session, stdout)
+            })
+            .await?;
 
             tokio::task::spawn_blocking(move || callisto::console::teardown_term_for_console())
                 .await??;
+            session?.save(&session_name)?;
+            Ok(())
+        }
+        Command::Serve {
+            flight_sql,
+            listen,
+            http,
+            metrics,
+            engine: engine_type,
+            init,
+            row_limit,
+            allow_path,
+            deny_path,
+        } => {
+            if !flight_sql && http.is_none() {
+                anyhow::bail!("Nothing to serve: pass --flight-sql or --http <address>");
+            }
+            if let Some(address) = metrics {
+                metrics_exporter_prometheus::PrometheusBuilder::new()
+                    .with_http_listener(address)
+                    .install()
+                    .context("Failed to serve metrics")?;
+                callisto::describe_metrics();
+                eprintln!("Serving metrics on {}", address);
+            }
+            let init = init
+                .map(|path| {
+                    std::fs::read_to_string(&path)
+                        .with_context(|| format!("Failed to read {}", path.display()))
+                })
+                .transpose()?;
+            let engine = engine_type
+                .builder(row_limit)
+                .path_policy(callisto::PathPolicy {
+                    allow: allow_path,
+                    deny: deny_path,
+                });
+            let flight_sql = async {
+                if !flight_sql {
+                    return Ok(());
+                }
+                eprintln!("Serving Flight SQL on {}", listen);
+                callisto::FlightSqlServer::new(engine.clone(), init.clone(), config.masks.clone())?
+                    .serve(listen)
+                    .await
+            };
+            let http = async {
+                let Some(address) = http else {
+                    return Ok(());
+                };
+                let server = callisto::HttpServer::new(
+                    engine.clone(),
+                    init.as_deref(),
+                    config.masks.clone(),
+                )
+                .await?;
+                eprintln!("Serving HTTP on {}", address);
+                server.serve(address).await
+            };
+            tokio::try_join!(flight_sql, http)?;
+            Ok(())
+        }
+        Command::Lsp {
+            engine: engine_type,
+            init,
+        } => {
+            let init = init
+                .map(|path| {
+                    std::fs::read_to_string(&path)
+                        .with_context(|| format!("Failed to read {}", path.display()))
+                })
+                .transpose()?;
+            callisto::LspServer::new(engine_type.kind(), init.as_deref())
+                .await?
+                .serve_stdio()
+                .await;
             Ok(())
         }
     }
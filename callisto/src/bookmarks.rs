@@ -0,0 +1,89 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use anyhow::Context as _;
+
+/// Queries saved by name to run again, shared by the REPL and the console.
+///
+/// Bookmarks are stored as a TOML table of names to SQL in `bookmarks.toml` beside the
+/// configuration file, and written back whenever one is saved or deleted.
+#[derive(Clone, Debug, Default)]
+pub struct Bookmarks {
+    queries: BTreeMap<String, String>,
+}
+
+impl Bookmarks {
+    pub fn path() -> Option<PathBuf> {
+        Some(dirs::config_dir()?.join("callisto").join("bookmarks.toml"))
+    }
+
+    /// Load the saved bookmarks, or none if nothing has been saved yet
+    pub fn load() -> anyhow::Result<Bookmarks> {
+        let Some(path) = Bookmarks::path() else {
+            return Ok(Bookmarks::default());
+        };
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(Bookmarks::default())
+            }
+            Err(error) => {
+                return Err(error)
+                    .with_context(|| format!("Failed to read bookmarks {}", path.display()))
+            }
+        };
+        let queries = toml::from_str(&text)
+            .with_context(|| format!("Failed to parse bookmarks {}", path.display()))?;
+        Ok(Bookmarks { queries })
+    }
+
+    /// Names and SQL of the bookmarks, by name
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.queries
+            .iter()
+            .map(|(name, sql)| (name.as_str(), sql.as_str()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queries.is_empty()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.queries.get(name).map(String::as_str)
+    }
+
+    /// Save `sql` as the bookmark `name`, replacing any of that name
+    pub fn save(&mut self, name: &str, sql: &str) -> anyhow::Result<()> {
+        if name.is_empty() || name.chars().any(char::is_whitespace) {
+            anyhow::bail!(
+                "Invalid bookmark name '{}': names can't contain spaces",
+                name
+            );
+        }
+        anyhow::ensure!(!sql.trim().is_empty(), "Nothing to bookmark");
+        self.queries
+            .insert(name.to_string(), sql.trim().to_string());
+        self.write()
+    }
+
+    /// Delete the bookmark `name`, returning whether there was one
+    pub fn delete(&mut self, name: &str) -> anyhow::Result<bool> {
+        if self.queries.remove(name).is_none() {
+            return Ok(false);
+        }
+        self.write()?;
+        Ok(true)
+    }
+
+    fn write(&self) -> anyhow::Result<()> {
+        let Some(path) = Bookmarks::path() else {
+            anyhow::bail!("No configuration directory to save bookmarks in");
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        std::fs::write(&path, toml::to_string(&self.queries)?)
+            .with_context(|| format!("Failed to write bookmarks {}", path.display()))
+    }
+}
@@ -0,0 +1,319 @@
+use std::ops::Range;
+
+use sqlparser::keywords::Keyword;
+use sqlparser::tokenizer::Token;
+
+use crate::schema_cache::SchemaCache;
+use crate::TableInfo;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CompletionKind {
+    Keyword,
+    Table,
+    Column,
+    Path,
+}
+
+/// A candidate replacement for part of the input
+#[derive(Clone, Debug)]
+pub struct Completion {
+    pub value: String,
+    pub kind: CompletionKind,
+    /// Byte range of the input replaced by `value`
+    pub span: Range<usize>,
+    /// Extra detail to show alongside the value, e.g. a column's type
+    pub description: Option<String>,
+}
+
+/// Completes SQL keywords, tables and columns from the schema cache, and filesystem paths inside
+/// quotes.
+///
+/// Where the statement being edited names its tables, only their columns are offered in clauses
+/// that expect columns, and only tables in clauses that expect tables.
+#[derive(Clone)]
+pub struct Completer {
+    cache: SchemaCache,
+}
+
+impl Completer {
+    pub fn new(cache: SchemaCache) -> Completer {
+        Completer { cache }
+    }
+
+    /// Completions for the input `line` with the cursor at byte offset `pos`
+    pub fn complete(&self, line: &str, pos: usize) -> Vec<Completion> {
+        let before = &line[..pos];
+        if let Some(quote_start) = open_quote(before) {
+            return complete_path(&before[quote_start + 1..], quote_start + 1);
+        }
+
+        let start = before
+            .char_indices()
+            .rev()
+            .find(|(_, c)| !(c.is_alphanumeric() || *c == '_' || *c == '.'))
+            .map(|(index, c)| index + c.len_utf8())
+            .unwrap_or(0);
+        let word = &before[start..];
+        let span = start..pos;
+        let statement_start = before.rfind(';').map_or(0, |index| index + 1);
+        let statement_end = line[pos..]
+            .find(';')
+            .map_or(line.len(), |index| pos + index);
+        let context = StatementContext::analyze(
+            &line[statement_start..statement_end],
+            &line[statement_start..start],
+        );
+
+        if let Some((qualifier, prefix)) = word.rsplit_once('.') {
+            let Some(table) = self.cache.table(context.resolve(qualifier)) else {
+                return Vec::new();
+            };
+            return columns(&table)
+                .filter(|(name, _)| starts_with_ignore_case(name, prefix))
+                .map(|(name, data_type)| Completion {
+                    value: format!("{}.{}", qualifier, name),
+                    kind: CompletionKind::Column,
+                    span: span.clone(),
+                    description: Some(data_type),
+                })
+                .collect();
+        }
+
+        let mut completions = Vec::new();
+        let referenced = context
+            .tables
+            .iter()
+            .filter_map(|(name, _)| self.cache.table(name))
+            .collect::<Vec<_>>();
+        let (tables, column_tables) = match context.clause {
+            Clause::Table => (self.cache.tables(), Vec::new()),
+            Clause::Column if !referenced.is_empty() => (Vec::new(), referenced),
+            _ => (self.cache.tables(), self.cache.tables()),
+        };
+        for table in &tables {
+            if starts_with_ignore_case(&table.name, word) {
+                completions.push(Completion {
+                    value: table.name.clone(),
+                    kind: CompletionKind::Table,
+                    span: span.clone(),
+                    description: table.source.clone(),
+                });
+            }
+        }
+
+        let mut seen_columns = std::collections::BTreeSet::new();
+        for table in &column_tables {
+            for (name, data_type) in columns(table) {
+                if starts_with_ignore_case(&name, word) && seen_columns.insert(name.clone()) {
+                    completions.push(Completion {
+                        value: name,
+                        kind: CompletionKind::Column,
+                        span: span.clone(),
+                        description: Some(data_type),
+                    });
+                }
+            }
+        }
+
+        // Every keyword matches an empty word, which would only bury the tables and columns.
+        if !word.is_empty() {
+            let lowercase = word.chars().all(|c| !c.is_uppercase());
+            for keyword in sqlparser::keywords::ALL_KEYWORDS {
+                if starts_with_ignore_case(keyword, word) {
+                    completions.push(Completion {
+                        value: if lowercase {
+                            keyword.to_lowercase()
+                        } else {
+                            keyword.to_string()
+                        },
+                        kind: CompletionKind::Keyword,
+                        span: span.clone(),
+                        description: None,
+                    });
+                }
+            }
+        }
+        completions
+    }
+}
+
+/// Kind of clause the cursor is in, judged by the last clause keyword before it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Clause {
+    /// Expecting tables, e.g. after `FROM` or `JOIN`
+    Table,
+    /// Expecting expressions over columns, e.g. after `SELECT` or `WHERE`
+    Column,
+    Other,
+}
+
+/// What the statement being edited says about the completions that fit at the cursor
+struct StatementContext {
+    /// Tables the statement names, each with the alias it's given, if any
+    tables: Vec<(String, Option<String>)>,
+    clause: Clause,
+}
+
+impl StatementContext {
+    /// Examine the whole `statement`, in which the cursor's word is preceded by `before`
+    fn analyze(statement: &str, before: &str) -> StatementContext {
+        let clause = tokenize(before)
+            .iter()
+            .rev()
+            .find_map(|token| match token {
+                Token::Word(word) => match word.keyword {
+                    Keyword::FROM
+                    | Keyword::JOIN
+                    | Keyword::INTO
+                    | Keyword::UPDATE
+                    | Keyword::TABLE => Some(Clause::Table),
+                    Keyword::SELECT
+                    | Keyword::WHERE
+                    | Keyword::ON
+                    | Keyword::BY
+                    | Keyword::HAVING
+                    | Keyword::SET
+                    | Keyword::USING => Some(Clause::Column),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .unwrap_or(Clause::Other);
+        StatementContext {
+            tables: referenced_tables(&tokenize(statement)),
+            clause,
+        }
+    }
+
+    /// The table named by `qualifier`, which may be one of the statement's aliases
+    fn resolve<'a>(&'a self, qualifier: &'a str) -> &'a str {
+        self.tables
+            .iter()
+            .find(|(_, alias)| {
+                alias
+                    .as_deref()
+                    .is_some_and(|alias| alias.eq_ignore_ascii_case(qualifier))
+            })
+            .map_or(qualifier, |(name, _)| name)
+    }
+}
+
+/// Tokens of `sql` other than whitespace, or none if it can't be tokenized
+pub(crate) fn tokenize(sql: &str) -> Vec<Token> {
+    sqlparser::tokenizer::Tokenizer::new(&sqlparser::dialect::GenericDialect {}, sql)
+        .tokenize()
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|token| !matches!(token, Token::Whitespace(_) | Token::EOF))
+        .collect()
+}
+
+/// Tables named after `FROM`, `JOIN`, `INTO`, or `UPDATE`, including comma-separated lists, each
+/// with any alias that follows it
+pub(crate) fn referenced_tables(tokens: &[Token]) -> Vec<(String, Option<String>)> {
+    let mut tables = Vec::new();
+    let mut index = 0;
+    while index < tokens.len() {
+        let introduces_table = matches!(
+            &tokens[index],
+            Token::Word(word) if matches!(
+                word.keyword,
+                Keyword::FROM | Keyword::JOIN | Keyword::INTO | Keyword::UPDATE
+            )
+        );
+        index += 1;
+        if !introduces_table {
+            continue;
+        }
+        loop {
+            // A possibly qualified name, or a quoted path for engines that query files directly
+            let mut name = match tokens.get(index) {
+                Some(Token::Word(word)) if word.keyword == Keyword::NoKeyword => word.value.clone(),
+                Some(Token::SingleQuotedString(path)) => path.clone(),
+                _ => break,
+            };
+            index += 1;
+            while let (Some(Token::Period), Some(Token::Word(word))) =
+                (tokens.get(index), tokens.get(index + 1))
+            {
+                name = format!("{}.{}", name, word.value);
+                index += 2;
+            }
+            if matches!(tokens.get(index), Some(Token::Word(word)) if word.keyword == Keyword::AS) {
+                index += 1;
+            }
+            let alias = match tokens.get(index) {
+                Some(Token::Word(word)) if word.keyword == Keyword::NoKeyword => {
+                    index += 1;
+                    Some(word.value.clone())
+                }
+                _ => None,
+            };
+            tables.push((name, alias));
+            if tokens.get(index) != Some(&Token::Comma) {
+                break;
+            }
+            index += 1;
+        }
+    }
+    tables
+}
+
+fn starts_with_ignore_case(candidate: &str, prefix: &str) -> bool {
+    candidate.len() >= prefix.len()
+        && candidate.is_char_boundary(prefix.len())
+        && candidate[..prefix.len()].eq_ignore_ascii_case(prefix)
+}
+
+/// Names and types of a table's columns, if its schema is known
+fn columns(table: &TableInfo) -> impl Iterator<Item = (String, String)> + '_ {
+    table.schema.iter().flat_map(|schema| {
+        schema
+            .fields()
+            .iter()
+            .map(|field| (field.name().clone(), field.data_type().to_string()))
+    })
+}
+
+/// Byte offset of the quote opening a string or quoted identifier left unterminated in `text`
+pub(crate) fn open_quote(text: &str) -> Option<usize> {
+    let mut open: Option<(char, usize)> = None;
+    for (index, c) in text.char_indices() {
+        match open {
+            Some((quote, _)) if c == quote => open = None,
+            None if c == '\'' || c == '"' => open = Some((c, index)),
+            _ => {}
+        }
+    }
+    open.map(|(_, index)| index)
+}
+
+/// Complete `partial` as a filesystem path, replacing from byte offset `start` onwards
+fn complete_path(partial: &str, start: usize) -> Vec<Completion> {
+    let (directory, prefix) = match partial.rfind('/') {
+        Some(index) => partial.split_at(index + 1),
+        None => ("", partial),
+    };
+    let Ok(entries) = std::fs::read_dir(if directory.is_empty() { "." } else { directory }) else {
+        return Vec::new();
+    };
+
+    let mut completions: Vec<Completion> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+            let is_dir = entry.file_type().is_ok_and(|file_type| file_type.is_dir());
+            Some(Completion {
+                value: format!("{}{}{}", directory, name, if is_dir { "/" } else { "" }),
+                kind: CompletionKind::Path,
+                span: start..start + partial.len(),
+                description: None,
+            })
+        })
+        .collect();
+    completions.sort_by(|a, b| a.value.cmp(&b.value));
+    completions
+}
@@ -0,0 +1,193 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use serde::Deserialize;
+
+/// User configuration, read from `$XDG_CONFIG_HOME/callisto/config.toml` by default
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub repl: ReplConfig,
+    pub console: ConsoleConfig,
+    /// Masks of columns of results, by column name, applied wherever results are displayed or
+    /// exported, e.g. `email = "hash"`
+    pub masks: crate::MaskPolicy,
+    /// Secret keying the digests `hash` masks replace values by, so that they're the same in every
+    /// session; each session keys them with one made at random if unset
+    pub mask_secret: Option<String>,
+    /// Object stores that can be browsed and read from, by name
+    pub remotes: BTreeMap<String, RemoteConfig>,
+}
+
+/// Defaults for `callisto repl`
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReplConfig {
+    /// Prompt template, expanding `{engine}`, `{catalog}`, and `{transaction}`
+    pub prompt: String,
+    /// Whether timing and row counts are printed after each statement
+    pub timing: bool,
+    /// Whether results taller than the terminal are shown through `$PAGER`
+    pub pager: bool,
+    /// Most rows displayed per result, or all if unset
+    pub max_rows: Option<usize>,
+    /// Number of recent results kept for `.show` and `.export`
+    pub result_history: usize,
+    /// Whether command history persists across sessions
+    pub history: bool,
+    /// Where history is stored, defaulting to `$XDG_DATA_HOME/callisto/history`
+    pub history_file: Option<PathBuf>,
+    /// Maximum number of history entries kept
+    pub history_size: usize,
+    /// Batches of a result fetched ahead while the one before them is printed
+    pub prefetch_batches: usize,
+}
+
+impl Default for ReplConfig {
+    fn default() -> ReplConfig {
+        ReplConfig {
+            prompt: crate::repl::DEFAULT_PROMPT.to_string(),
+            timing: false,
+            pager: true,
+            max_rows: None,
+            result_history: 20,
+            history: true,
+            history_file: None,
+            history_size: 10_000,
+            prefetch_batches: 4,
+        }
+    }
+}
+
+impl ReplConfig {
+    /// The history file to use, or `None` if history persistence is disabled
+    pub fn history_path(&self) -> Option<PathBuf> {
+        if !self.history {
+            return None;
+        }
+        self.history_file
+            .clone()
+            .or_else(|| Some(dirs::data_dir()?.join("callisto").join("history")))
+    }
+}
+
+/// Defaults for `callisto console`
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConsoleConfig {
+    /// Keys the SQL editor follows
+    pub keymap: Keymap,
+    /// Name of the theme the console is drawn with, built in or defined in `themes`
+    pub theme: String,
+    /// Themes defined by the user, by name
+    pub themes: BTreeMap<String, ThemeConfig>,
+}
+
+impl Default for ConsoleConfig {
+    fn default() -> ConsoleConfig {
+        ConsoleConfig {
+            keymap: Keymap::default(),
+            theme: "default".to_string(),
+            themes: BTreeMap::new(),
+        }
+    }
+}
+
+/// Key bindings of the console's SQL editor
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Keymap {
+    /// Typing inserts text and the arrows move, as in a plain text field
+    #[default]
+    Default,
+    /// Modal editing with normal, insert, and visual modes
+    Vim,
+    Emacs,
+}
+
+/// A console theme, as the styles it changes from its base theme.
+///
+/// Styles are written as modifiers and a color, optionally followed by `on` and a background
+/// color, e.g. `bold yellow on blue`.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ThemeConfig {
+    /// Built-in theme this one starts from, `default` if unset
+    pub base: Option<String>,
+    pub border: Option<String>,
+    pub focused_border: Option<String>,
+    pub selected: Option<String>,
+    pub matched: Option<String>,
+    pub header: Option<String>,
+    pub error: Option<String>,
+    pub warning: Option<String>,
+    pub status: Option<String>,
+    pub muted: Option<String>,
+    pub keyword: Option<String>,
+    pub string: Option<String>,
+    pub number: Option<String>,
+    pub comment: Option<String>,
+    pub table: Option<String>,
+    pub invalid: Option<String>,
+    pub added: Option<String>,
+    pub removed: Option<String>,
+    pub changed: Option<String>,
+    /// Colors of the lines and bars of charts
+    pub series: Option<Vec<String>>,
+}
+
+/// A bucket or container in an object store, and how to authenticate to it.
+///
+/// Credentials not given in `options` are taken from the environment, as the store's own tools
+/// would, e.g. `AWS_ACCESS_KEY_ID` or `GOOGLE_APPLICATION_CREDENTIALS`.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RemoteConfig {
+    /// Where the objects are, e.g. `s3://bucket/prefix`, `gs://bucket`, or `az://container`
+    pub url: String,
+    /// Settings of the store's client by the names `object_store` gives them, e.g. `region`,
+    /// `endpoint`, or `aws_access_key_id`
+    pub options: BTreeMap<String, String>,
+}
+
+/// Startup script run by `callisto repl` unless `--no-rc` is given
+pub fn rc_path() -> Option<PathBuf> {
+    Some(dirs::home_dir()?.join(".callistorc"))
+}
+
+impl Config {
+    pub fn default_path() -> Option<PathBuf> {
+        Some(dirs::config_dir()?.join("callisto").join("config.toml"))
+    }
+
+    /// Load configuration from `path`, or from the default location if not given.
+    ///
+    /// A missing file at the default location yields the default configuration, while a missing
+    /// file explicitly asked for is an error.
+    pub fn load(path: Option<PathBuf>) -> anyhow::Result<Config> {
+        use anyhow::Context as _;
+
+        let (path, required) = match path {
+            Some(path) => (path, true),
+            None => match Config::default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Config::default()),
+            },
+        };
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(error) if !required && error.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(Config::default())
+            }
+            Err(error) => {
+                return Err(error)
+                    .with_context(|| format!("Failed to read config file {}", path.display()))
+            }
+        };
+        let mut config: Config = toml::from_str(&text)
+            .with_context(|| format!("Failed to parse config file {}", path.display()))?;
+        if let Some(secret) = &config.mask_secret {
+            config.masks = std::mem::take(&mut config.masks).with_secret(secret);
+        }
+        Ok(config)
+    }
+}
@@ -0,0 +1,1454 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use arrow::record_batch::RecordBatch;
+use ratatui::{
+    crossterm::event::{KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind},
+    layout::{self, Rect},
+    widgets::{Block, Borders, Paragraph, Wrap},
+    Frame,
+};
+use tokio::sync::mpsc::error::TryRecvError;
+
+use super::{
+    BookmarkEvent, BookmarkPicker, Catalog, CatalogAction, Chart, Command, Completions,
+    CompletionsEvent, ConsoleOptions, Diagnostics, Diff, Editor, Files, FilesAction, Grid, Help,
+    Log, Notifications, Objects, ObjectsAction, Palette, PaletteEvent, Plan, Preview, Prompt,
+    PromptEvent, Record, Session, Summary, Theme,
+};
+use crate::{
+    completion::{Completer, CompletionKind},
+    highlight::Highlighter,
+    schema_cache::SchemaCache,
+    Bookmarks, Engine, EngineBuilder, EngineInterface, ExecutionMetrics, Interrupter, MaskPolicy,
+    OutputFormat, QueryExecution, ResultSet, TableInfo,
+};
+
+/// Width of the catalog sidebar, in columns
+const CATALOG_WIDTH: u16 = 32;
+/// Height of the notifications log, in lines
+const NOTIFICATIONS_HEIGHT: u16 = 8;
+/// Height of the event log, in lines
+const LOG_HEIGHT: u16 = 10;
+/// Share of the height beside the sidebar the editor takes, in percent, by default and at the
+/// least and most it can be resized to
+pub(super) const EDITOR_HEIGHT: u16 = 20;
+const EDITOR_HEIGHT_RANGE: std::ops::RangeInclusive<u16> = 10..=90;
+/// How much the editor grows or shrinks by a key, in percent
+const EDITOR_HEIGHT_STEP: u16 = 5;
+/// Rows shown when previewing a table from the catalog
+const PREVIEW_ROWS: usize = 20;
+const SPINNER: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+/// Which pane keys are sent to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Focus {
+    Editor,
+    Results,
+    Catalog,
+    Files,
+    Objects,
+    Notifications,
+    Log,
+}
+
+/// What the results pane shows
+pub enum Results {
+    Empty,
+    Grid(Grid),
+    Error(String),
+    Cancelled,
+}
+
+/// Another way of looking at the grid's rows, shown in its place
+pub enum View {
+    Chart(Chart),
+    /// Statistics on the column under the grid's cursor
+    Summary(Summary),
+    /// The tree of operators the query printed, or ran with
+    Plan(Plan),
+    /// The row under the grid's cursor, a line a column
+    Record(Record),
+    /// How the result differs from the one before it
+    Diff(Diff),
+}
+
+/// A popup taking keys ahead of the panes
+pub enum Dialog {
+    /// Asking where to export the displayed result
+    Export(Prompt),
+    /// Asking what to bookmark the editor's text as
+    SaveBookmark(Prompt),
+    /// Choosing a bookmark to load into the editor
+    Bookmarks(BookmarkPicker),
+    /// Choosing a command to run
+    Palette(Palette),
+    /// Listing the keys
+    Help(Help),
+}
+
+/// A query running on a background task
+struct Running {
+    outcome: tokio::sync::oneshot::Receiver<anyhow::Result<Started>>,
+    task: tokio::task::JoinHandle<()>,
+    started: std::time::Instant,
+    /// Rows and bytes of memory received so far, across every statement
+    rows: Arc<AtomicUsize>,
+    bytes: Arc<AtomicUsize>,
+    /// What interrupts the engine, once the task has it, as aborting the task doesn't stop an
+    /// engine blocking it
+    interrupter: Arc<std::sync::Mutex<Option<Interrupter>>>,
+}
+
+/// The first batch of a query's result, with the rest to come
+struct Started {
+    result: ResultSet,
+    rest: tokio::sync::mpsc::Receiver<anyhow::Result<RecordBatch>>,
+    /// Whether the last statement was an `EXPLAIN`, its result being a plan
+    explained: bool,
+    metrics: Arc<ExecutionMetrics>,
+}
+
+/// The rest of the result shown in the grid, fetched from the engine by a background task as
+/// the grid asks for it
+struct Streaming {
+    batches: tokio::sync::mpsc::Receiver<anyhow::Result<RecordBatch>>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl Drop for Streaming {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// What happens after a key has been handled
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Flow {
+    Continue,
+    Exit,
+}
+
+/// State of the console: the engine queries run on, the SQL being edited, and the latest result.
+///
+/// Queries run on a background task holding the engine, so the console keeps drawing while they
+/// do, with their outcome picked up by [`App::poll`].
+pub struct App {
+    engine: Arc<tokio::sync::Mutex<Box<dyn EngineInterface>>>,
+    engine_kind: Engine,
+    /// Configuration the engines switched to are built from
+    engine_builder: EngineBuilder,
+    runtime: tokio::runtime::Handle,
+    /// Masks applied to every result shown, whether of a query or a previewed file
+    masks: MaskPolicy,
+    pub editor: Editor,
+    pub focus: Focus,
+    pub results: Results,
+    pub catalog: Catalog,
+    pub theme: Theme,
+    pub files: Files,
+    /// The file last selected in the file browser, shown in place of the results while it's
+    /// focused
+    pub preview: Option<Preview>,
+    pub objects: Objects,
+    pub notifications: Notifications,
+    pub log: Log,
+    /// Completions for the word being typed in the editor
+    pub completions: Completions,
+    /// Whether the catalog sidebar is shown
+    pub show_catalog: bool,
+    /// Whether the file browser is shown, below the catalog
+    pub show_files: bool,
+    /// Whether the remote browser is shown, below the catalog and any file browser
+    pub show_objects: bool,
+    /// Whether the notifications log is shown, below the results
+    pub show_notifications: bool,
+    /// Whether the event log is shown, below the results and any notifications
+    pub show_log: bool,
+    /// Share of the height beside the sidebar the editor takes, in percent, the results taking
+    /// the rest
+    pub editor_height: u16,
+    /// Whether the focused pane fills the screen, hiding the others
+    pub maximized: bool,
+    /// What's shown in place of the grid, if anything
+    pub view: Option<View>,
+    /// The popup open over the panes, if any
+    pub dialog: Option<Dialog>,
+    /// Outcome of the last action, shown until the next query runs
+    pub notice: Option<String>,
+    /// The query in flight, if any
+    running: Option<Running>,
+    /// The rest of the result in the grid, if it hasn't all streamed in
+    streaming: Option<Streaming>,
+    /// How long the last query to finish took
+    last_duration: Option<std::time::Duration>,
+    /// The result shown before the latest, as much of it as was read, to compare with
+    previous: Option<ResultSet>,
+    /// What the last statement of the last query to return rows measured, including its plan
+    /// once its result is read in full
+    metrics: Option<Arc<ExecutionMetrics>>,
+    /// Whether the last statement of the last query to return rows was an `EXPLAIN`
+    explained: bool,
+    /// The engine's tables, once listed after startup or a query
+    pending_tables: Option<tokio::sync::oneshot::Receiver<anyhow::Result<Vec<TableInfo>>>>,
+    /// What became of a file being registered as a table or the engine being switched, once done
+    pending_registration: Option<tokio::sync::oneshot::Receiver<anyhow::Result<String>>>,
+    /// Events logged since last polled
+    diagnostics: Diagnostics,
+    /// The tables last listed, for highlighting and completing their names in the editor
+    schema_cache: SchemaCache,
+    highlighter: Highlighter,
+    /// Where the editor and the panes beside the sidebar were last drawn, for resizing by mouse
+    editor_area: Rect,
+    main_area: Rect,
+    /// Whether the border between the editor and results is being dragged
+    dragging: bool,
+}
+
+impl App {
+    /// Create the console's state as `session` left it and `options` set it up, running queries
+    /// on `engine` on the current Tokio runtime and notifying of whatever is logged to
+    /// `diagnostics`
+    pub fn new(
+        engine: Box<dyn EngineInterface>,
+        options: ConsoleOptions,
+        diagnostics: Diagnostics,
+        session: &Session,
+    ) -> App {
+        let schema_cache = SchemaCache::default();
+        let mut app = App {
+            engine_kind: engine.kind(),
+            engine: Arc::new(tokio::sync::Mutex::new(engine)),
+            engine_builder: options.engine,
+            runtime: tokio::runtime::Handle::current(),
+            masks: options.masks,
+            editor: Editor::with_text(options.keymap, &session.editor),
+            focus: Focus::Editor,
+            results: Results::Empty,
+            catalog: Catalog::default(),
+            theme: options.theme,
+            files: Files::new(std::path::PathBuf::from(".")),
+            preview: None,
+            objects: Objects::new(options.remotes, tokio::runtime::Handle::current()),
+            notifications: Notifications::new(),
+            log: Log::new(),
+            completions: Completions::new(Completer::new(schema_cache.clone())),
+            show_catalog: session.show_catalog,
+            show_files: session.show_files,
+            show_objects: session.show_objects,
+            show_notifications: session.show_notifications,
+            show_log: session.show_log,
+            editor_height: session
+                .editor_height
+                .clamp(*EDITOR_HEIGHT_RANGE.start(), *EDITOR_HEIGHT_RANGE.end()),
+            maximized: false,
+            view: None,
+            dialog: None,
+            notice: None,
+            running: None,
+            streaming: None,
+            last_duration: None,
+            previous: None,
+            metrics: None,
+            explained: false,
+            pending_tables: None,
+            pending_registration: None,
+            diagnostics,
+            highlighter: Highlighter::new(schema_cache.clone()),
+            schema_cache,
+            editor_area: Rect::default(),
+            main_area: Rect::default(),
+            dragging: false,
+        };
+        app.refresh_tables();
+        app
+    }
+
+    /// The state to restore when the console is next launched
+    pub fn session(&self) -> Session {
+        Session {
+            engine: Some(self.engine_kind.name().to_string()),
+            editor: self.editor.text(),
+            show_catalog: self.show_catalog,
+            show_files: self.show_files,
+            show_objects: self.show_objects,
+            show_notifications: self.show_notifications,
+            show_log: self.show_log,
+            editor_height: self.editor_height,
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.is_some()
+    }
+
+    /// Stop the query in flight, if any.
+    ///
+    /// The engine is interrupted, and aborting its task drops the engine's result streams, which
+    /// abandons any work still outstanding.
+    pub fn cancel(&mut self) {
+        if let Some(running) = self.running.take() {
+            if let Some(interrupter) = running.interrupter.lock().unwrap().as_ref() {
+                interrupter.interrupt();
+            }
+            running.task.abort();
+            tracing::info!(
+                seconds = running.started.elapsed().as_secs_f64(),
+                rows = running.rows.load(Ordering::Relaxed),
+                "Query cancelled"
+            );
+            self.results = Results::Cancelled;
+            self.refresh_tables();
+        }
+    }
+
+    /// Run the editor's SQL, see [`App::run`]
+    pub fn run_query(&mut self) {
+        self.run(self.editor.text());
+    }
+
+    /// Run `sql` on a background task, unless a query is already running.
+    ///
+    /// The result of the last statement is shown once every statement before it has run and its
+    /// first batch has arrived, with the rest streamed into the grid as it's scrolled through.
+    pub fn run(&mut self, sql: String) {
+        use futures::stream::StreamExt as _;
+
+        if self.is_running() || sql.trim().is_empty() {
+            return;
+        }
+        self.notice = None;
+        self.streaming = None;
+        let engine = self.engine.clone();
+        let masks = self.masks.clone();
+        let (sender, receiver) = tokio::sync::oneshot::channel();
+        let rows = Arc::new(AtomicUsize::new(0));
+        let bytes = Arc::new(AtomicUsize::new(0));
+        let (rows_received, bytes_received) = (rows.clone(), bytes.clone());
+        let interrupter = Arc::new(std::sync::Mutex::new(None));
+        let interrupting = interrupter.clone();
+        let task = self.runtime.spawn(async move {
+            let count = |batch: &RecordBatch| {
+                rows_received.fetch_add(batch.num_rows(), Ordering::Relaxed);
+                bytes_received.fetch_add(batch.get_array_memory_size(), Ordering::Relaxed);
+            };
+            let started = async {
+                let engine = engine.lock().await;
+                *interrupting.lock().unwrap() = Some(engine.interrupter());
+                let sql = crate::prql::to_sql(&sql, crate::Language::Sql, engine.kind())?;
+                let mut statements = engine.execute(&sql).await?;
+                drop(engine);
+                let Some(QueryExecution {
+                    statement,
+                    schema,
+                    stream: last,
+                    metrics,
+                }) = statements.pop()
+                else {
+                    anyhow::bail!("No statements to run");
+                };
+                let (schema, mut last) = (masks.schema(&schema), masks.masked(last));
+                for mut execution in statements {
+                    while let Some(batch) = execution.stream.next().await {
+                        count(&batch?);
+                    }
+                }
+                let batches = match last.next().await {
+                    Some(batch) => vec![batch?],
+                    None => Vec::new(),
+                };
+                batches.iter().for_each(count);
+                let explained = matches!(statement, sqlparser::ast::Statement::Explain { .. });
+                Ok((ResultSet { schema, batches }, last, explained, metrics))
+            }
+            .await;
+            let (result, mut stream, explained, metrics) = match started {
+                Ok(started) => started,
+                Err(error) => {
+                    let _ = sender.send(Err(error));
+                    return;
+                }
+            };
+            // Only a batch ahead is fetched before the grid takes it, so the rest of the query
+            // runs as the grid is scrolled through
+            let (batches, rest) = tokio::sync::mpsc::channel(1);
+            let started = Started {
+                result,
+                rest,
+                explained,
+                metrics,
+            };
+            if sender.send(Ok(started)).is_err() {
+                return;
+            }
+            while let Some(batch) = stream.next().await {
+                let failed = batch.is_err();
+                if batches.send(batch.map_err(Into::into)).await.is_err() || failed {
+                    break;
+                }
+            }
+        });
+        self.running = Some(Running {
+            outcome: receiver,
+            task,
+            started: std::time::Instant::now(),
+            rows,
+            bytes,
+            interrupter,
+        });
+    }
+
+    /// List the engine's tables on a background task, for the catalog
+    pub fn refresh_tables(&mut self) {
+        let engine = self.engine.clone();
+        let (sender, receiver) = tokio::sync::oneshot::channel();
+        self.runtime.spawn(async move {
+            let _ = sender.send(engine.lock().await.tables().await);
+        });
+        self.pending_tables = Some(receiver);
+    }
+
+    /// Read the dataset at `path` and register it with the engine on a background task, as a
+    /// table named after the file
+    pub fn register(&mut self, path: std::path::PathBuf) {
+        let engine = self.engine.clone();
+        let (sender, receiver) = tokio::sync::oneshot::channel();
+        self.runtime.spawn(async move {
+            let outcome = async {
+                let name = crate::dataset::table_name(&path);
+                let read = path.clone();
+                let result = tokio::task::spawn_blocking(move || crate::dataset::read(&read, None))
+                    .await??;
+                engine
+                    .lock()
+                    .await
+                    .register_batches(&name, result.schema, result.batches)
+                    .await?;
+                tracing::info!(path = %path.display(), table = %name, "Registered file");
+                Ok(format!("Registered {} as {}", path.display(), name))
+            }
+            .await;
+            let _ = sender.send(outcome);
+        });
+        self.pending_registration = Some(receiver);
+    }
+
+    /// Read the object or prefix `entry` lists from `remote` and register it with the engine on
+    /// a background task, as a table named after its last segment
+    pub fn register_remote(&mut self, remote: Arc<crate::Remote>, entry: crate::RemoteEntry) {
+        let engine = self.engine.clone();
+        let (sender, receiver) = tokio::sync::oneshot::channel();
+        self.runtime.spawn(async move {
+            let outcome = async {
+                let name = crate::dataset::table_name(std::path::Path::new(&entry.name()));
+                let result = remote.read(&entry).await?;
+                engine
+                    .lock()
+                    .await
+                    .register_batches(&name, result.schema, result.batches)
+                    .await?;
+                let location = entry.location();
+                tracing::info!(remote = %remote.name, %location, table = %name, "Registered object");
+                Ok(format!("Registered {}:{} as {}", remote.name, location, name))
+            }
+            .await;
+            let _ = sender.send(outcome);
+        });
+        self.pending_registration = Some(receiver);
+    }
+
+    /// Switch to a new engine of kind `engine` on a background task, once any query running has
+    /// finished, registering the tables of files registered with the current one again
+    pub fn switch_engine(&mut self, engine: Engine) {
+        let current = self.engine.clone();
+        let schema_cache = self.schema_cache.clone();
+        let builder = self.engine_builder.clone();
+        let (sender, receiver) = tokio::sync::oneshot::channel();
+        self.runtime.spawn(async move {
+            let mut current = current.lock().await;
+            let mut options = crate::ReplOptions {
+                engine: Some(builder),
+                ..Default::default()
+            };
+            let outcome = crate::MetaCommand::Engine(engine)
+                .execute(&mut *current, &schema_cache, &mut options)
+                .await;
+            let _ = sender.send(outcome);
+        });
+        self.pending_registration = Some(receiver);
+    }
+
+    /// Pick up the outcome of any background work that has finished
+    pub fn poll(&mut self) {
+        for notification in self.diagnostics.take() {
+            self.notifications.push(notification);
+        }
+        for entry in self.diagnostics.take_log() {
+            self.log.push(entry);
+        }
+
+        if self.focus == Focus::Files {
+            match self.files.selected_file() {
+                Some(path) if self.preview.as_ref().map(Preview::path) != Some(path) => {
+                    self.preview =
+                        Some(Preview::new(path.to_path_buf(), &self.masks, &self.runtime));
+                }
+                Some(_) => {}
+                None => self.preview = None,
+            }
+        }
+        if let Some(preview) = &mut self.preview {
+            preview.poll();
+        }
+        self.objects.poll();
+
+        if let Some(pending) = &mut self.pending_registration {
+            match pending.try_recv() {
+                Ok(outcome) => {
+                    self.notice = Some(outcome.unwrap_or_else(|error| format!("{:#}", error)));
+                    self.pending_registration = None;
+                    if let Ok(engine) = self.engine.try_lock() {
+                        self.engine_kind = engine.kind();
+                    }
+                    self.refresh_tables();
+                }
+                Err(tokio::sync::oneshot::error::TryRecvError::Empty) => {}
+                Err(tokio::sync::oneshot::error::TryRecvError::Closed) => {
+                    self.pending_registration = None
+                }
+            }
+        }
+
+        if let Some(pending) = &mut self.pending_tables {
+            match pending.try_recv() {
+                Ok(Ok(tables)) => {
+                    self.schema_cache.set(tables.clone());
+                    self.catalog.set_tables(tables);
+                    self.pending_tables = None;
+                }
+                Ok(Err(error)) => {
+                    tracing::warn!("Failed to list tables: {:#}", error);
+                    self.pending_tables = None;
+                }
+                Err(tokio::sync::oneshot::error::TryRecvError::Empty) => {}
+                Err(tokio::sync::oneshot::error::TryRecvError::Closed) => {
+                    self.pending_tables = None
+                }
+            }
+        }
+
+        self.stream(false);
+
+        let Some(running) = &mut self.running else {
+            return;
+        };
+        let outcome = match running.outcome.try_recv() {
+            Ok(outcome) => outcome,
+            Err(tokio::sync::oneshot::error::TryRecvError::Empty) => return,
+            Err(tokio::sync::oneshot::error::TryRecvError::Closed) => {
+                Err(anyhow::anyhow!("Query task ended without a result"))
+            }
+        };
+        let elapsed = running.started.elapsed();
+        self.last_duration = Some(elapsed);
+        match &outcome {
+            Ok(started) => tracing::info!(
+                engine = self.engine_kind.name(),
+                seconds = elapsed.as_secs_f64(),
+                columns = started.result.schema.fields().len(),
+                "Query returned its first rows"
+            ),
+            Err(error) => tracing::info!(
+                engine = self.engine_kind.name(),
+                seconds = elapsed.as_secs_f64(),
+                "Query failed: {:#}",
+                error
+            ),
+        }
+        let Some(running) = self.running.take() else {
+            return;
+        };
+        self.view = None;
+        self.metrics = None;
+        self.explained = false;
+        if let Results::Grid(grid) = &self.results {
+            self.previous = Some(grid.result().clone());
+        }
+        // Statements may have registered or created tables
+        self.refresh_tables();
+        self.results = match outcome.and_then(|started| Ok((Grid::new(&started.result)?, started)))
+        {
+            Ok((mut grid, started)) => {
+                grid.set_complete(false);
+                self.streaming = Some(Streaming {
+                    batches: started.rest,
+                    task: running.task,
+                });
+                self.metrics = Some(started.metrics);
+                self.explained = started.explained;
+                self.focus = Focus::Results;
+                Results::Grid(grid)
+            }
+            Err(error) => Results::Error(format!("{:#}", error)),
+        };
+        self.stream(false);
+        if self.explained {
+            match self.plan() {
+                Ok(plan) => self.view = Some(View::Plan(plan)),
+                Err(error) => self.notice = Some(format!("Can't show the plan: {:#}", error)),
+            }
+        }
+    }
+
+    /// Append the batches of the streaming result that the grid wants and have arrived, or wait
+    /// for every one of them if `all`
+    fn stream(&mut self, all: bool) {
+        let (Some(streaming), Results::Grid(grid)) = (&mut self.streaming, &mut self.results)
+        else {
+            return;
+        };
+        while all || grid.wants_more() {
+            let batch = if all {
+                streaming
+                    .batches
+                    .blocking_recv()
+                    .ok_or(TryRecvError::Disconnected)
+            } else {
+                streaming.batches.try_recv()
+            };
+            match batch {
+                Ok(Ok(batch)) => {
+                    if let Err(error) = grid.append(batch) {
+                        self.notice = Some(format!("Failed to show rows: {:#}", error));
+                        grid.set_complete(true);
+                    }
+                }
+                Ok(Err(error)) => {
+                    self.notice = Some(format!("Result stream failed: {:#}", error));
+                    grid.set_complete(true);
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => grid.set_complete(true),
+            }
+            if grid.is_complete() {
+                tracing::info!(rows = grid.result().num_rows(), "Result received in full");
+                self.streaming = None;
+                break;
+            }
+        }
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> Flow {
+        let control = key.modifiers.contains(KeyModifiers::CONTROL);
+        if let Some(dialog) = &mut self.dialog {
+            match dialog {
+                Dialog::Export(prompt) => match prompt.handle_key(key) {
+                    PromptEvent::Editing => {}
+                    PromptEvent::Cancelled => self.dialog = None,
+                    PromptEvent::Submitted(path) => {
+                        self.dialog = None;
+                        self.notice = Some(match self.export(&path) {
+                            Ok(rows) => format!("Exported {} rows to {}", rows, path),
+                            Err(error) => format!("Export failed: {:#}", error),
+                        });
+                    }
+                },
+                Dialog::SaveBookmark(prompt) => match prompt.handle_key(key) {
+                    PromptEvent::Editing => {}
+                    PromptEvent::Cancelled => self.dialog = None,
+                    PromptEvent::Submitted(name) => {
+                        self.dialog = None;
+                        let saved = Bookmarks::load()
+                            .and_then(|mut bookmarks| bookmarks.save(&name, &self.editor.text()));
+                        self.notice = Some(match saved {
+                            Ok(()) => format!("Bookmarked as {}", name),
+                            Err(error) => format!("Bookmarking failed: {:#}", error),
+                        });
+                    }
+                },
+                Dialog::Bookmarks(picker) => match picker.handle_key(key) {
+                    BookmarkEvent::Choosing => {}
+                    BookmarkEvent::Cancelled => self.dialog = None,
+                    BookmarkEvent::Picked(sql) => {
+                        self.dialog = None;
+                        self.editor.set_text(&sql);
+                        self.focus = Focus::Editor;
+                    }
+                },
+                Dialog::Help(help) => {
+                    if !help.handle_key(key) {
+                        self.dialog = None;
+                    }
+                }
+                Dialog::Palette(palette) => match palette.handle_key(key) {
+                    PaletteEvent::Choosing => {}
+                    PaletteEvent::Cancelled => self.dialog = None,
+                    PaletteEvent::Picked(command) => {
+                        self.dialog = None;
+                        return self.perform(command);
+                    }
+                },
+            }
+            return Flow::Continue;
+        }
+        if self.focus == Focus::Editor && self.completions.is_open() && !control {
+            match self.completions.handle_key(key) {
+                CompletionsEvent::Ignored => {}
+                CompletionsEvent::Handled => return Flow::Continue,
+                CompletionsEvent::Accepted(completion) => {
+                    let mut value = completion.value;
+                    if completion.kind == CompletionKind::Keyword {
+                        value.push(' ');
+                    }
+                    self.editor.replace_span(completion.span, &value);
+                    return Flow::Continue;
+                }
+            }
+        }
+        if let (Focus::Results, Results::Grid(grid)) = (self.focus, &mut self.results) {
+            if grid.captures_input() && !control {
+                grid.handle_key(key);
+                return Flow::Continue;
+            }
+        }
+        match key.code {
+            KeyCode::Char('q') if control => return Flow::Exit,
+            KeyCode::Char('c') if control && self.is_running() => self.cancel(),
+            KeyCode::Char('r') if control => self.run_query(),
+            KeyCode::F(5) => self.run_query(),
+            KeyCode::Up if control => return self.perform(Command::ShrinkEditor),
+            KeyCode::Down if control => return self.perform(Command::GrowEditor),
+            KeyCode::Char('z') if control => return self.perform(Command::Zoom),
+            KeyCode::Char('s') if control => return self.perform(Command::SaveBookmark),
+            KeyCode::F(2) => return self.perform(Command::OpenBookmark),
+            KeyCode::F(1) => return self.perform(Command::Help),
+            // The editor has first refusal of any other key, as its keymap may bind it
+            _ if self.focus == Focus::Editor && self.editor.handle_key(key) => self.complete(key),
+            KeyCode::Esc if self.is_running() => self.cancel(),
+            KeyCode::Char('q') if self.focus == Focus::Results => return Flow::Exit,
+            KeyCode::Char('p') if control => self.dialog = Some(Dialog::Palette(Palette::new())),
+            KeyCode::Char('?') => return self.perform(Command::Help),
+            KeyCode::Char('e') if control && matches!(self.results, Results::Grid(_)) => {
+                return self.perform(Command::Export);
+            }
+            KeyCode::Char('c')
+            | KeyCode::Char('i')
+            | KeyCode::Char('p')
+            | KeyCode::Char('d')
+            | KeyCode::Enter
+            | KeyCode::Esc
+                if self.focus == Focus::Results && self.view.is_some() =>
+            {
+                self.view = None;
+            }
+            KeyCode::Char('c') if self.focus == Focus::Results => {
+                return self.perform(Command::Chart)
+            }
+            KeyCode::Char('i') if self.focus == Focus::Results => {
+                return self.perform(Command::Summary)
+            }
+            KeyCode::Enter
+                if self.focus == Focus::Results && matches!(self.results, Results::Grid(_)) =>
+            {
+                return self.perform(Command::Row);
+            }
+            KeyCode::Char('d') if self.focus == Focus::Results => {
+                return self.perform(Command::Diff)
+            }
+            KeyCode::Char('p') if self.focus == Focus::Results => {
+                return self.perform(Command::Plan)
+            }
+            KeyCode::Char('y') | KeyCode::Char('Y')
+                if self.focus == Focus::Results && matches!(self.results, Results::Grid(_)) =>
+            {
+                let format = if control {
+                    Some(OutputFormat::Json)
+                } else if key.modifiers.contains(KeyModifiers::ALT) {
+                    Some(OutputFormat::Csv)
+                } else {
+                    None
+                };
+                return self.perform(Command::Copy {
+                    whole_rows: key.code == KeyCode::Char('Y'),
+                    format,
+                });
+            }
+            KeyCode::Char('b') if control => return self.perform(Command::ToggleCatalog),
+            KeyCode::Char('o') if control => return self.perform(Command::ToggleFiles),
+            KeyCode::Char('g') if control => return self.perform(Command::ToggleRemotes),
+            KeyCode::Char('l') if control => return self.perform(Command::ToggleNotifications),
+            KeyCode::Char('t') if control => return self.perform(Command::ToggleLog),
+            KeyCode::BackTab => self.cycle_focus(),
+            KeyCode::Tab if self.focus != Focus::Editor => self.cycle_focus(),
+            KeyCode::Esc if self.focus == Focus::Editor => self.cycle_focus(),
+            _ => match (&mut self.results, self.focus) {
+                (Results::Grid(grid), Focus::Results) => match &mut self.view {
+                    Some(View::Chart(chart)) => {
+                        chart.handle_key(key);
+                    }
+                    Some(View::Summary(_)) => {}
+                    Some(View::Plan(plan)) => plan.handle_key(key),
+                    Some(View::Record(record)) => record.handle_key(key, grid),
+                    Some(View::Diff(diff)) => diff.handle_key(key),
+                    None => {
+                        grid.handle_key(key);
+                    }
+                },
+                (_, Focus::Catalog) => match self.catalog.handle_key(key) {
+                    Some(CatalogAction::Insert(name)) => {
+                        self.editor.insert(&name);
+                        self.focus = Focus::Editor;
+                    }
+                    Some(CatalogAction::Preview(name)) => self.run(format!(
+                        "SELECT * FROM \"{}\" LIMIT {}",
+                        name.replace('"', "\"\""),
+                        PREVIEW_ROWS
+                    )),
+                    None => {}
+                },
+                (_, Focus::Files) => match self.files.handle_key(key) {
+                    Some(FilesAction::Insert(path)) => {
+                        self.editor.insert(&path);
+                        self.focus = Focus::Editor;
+                    }
+                    Some(FilesAction::Register(path)) => self.register(path),
+                    None => {}
+                },
+                (_, Focus::Objects) => match self.objects.handle_key(key) {
+                    Some(ObjectsAction::Register(remote, entry)) => {
+                        self.register_remote(remote, entry)
+                    }
+                    None => {}
+                },
+                (_, Focus::Notifications) => self.notifications.handle_key(key),
+                (_, Focus::Log) => self.log.handle_key(key),
+                _ => {}
+            },
+        }
+        if self.focus != Focus::Editor {
+            self.completions.close();
+        }
+        Flow::Continue
+    }
+
+    /// Do what `command` asks, as its key or the command palette would
+    pub fn perform(&mut self, command: Command) -> Flow {
+        match command {
+            Command::Run => self.run_query(),
+            Command::Cancel => self.cancel(),
+            Command::SwitchEngine(engine) => self.switch_engine(engine),
+            Command::Export => {
+                if matches!(self.results, Results::Grid(_)) {
+                    self.dialog = Some(Dialog::Export(Prompt::new(
+                        "Export to .parquet, .csv, or .json",
+                        "result.csv",
+                    )));
+                } else {
+                    self.notice = Some("No result to export".to_string());
+                }
+            }
+            Command::Copy { whole_rows, format } => {
+                self.notice = Some(
+                    self.copy(whole_rows, format)
+                        .unwrap_or_else(|error| format!("Copy failed: {:#}", error)),
+                );
+            }
+            Command::SaveBookmark => {
+                if self.editor.text().trim().is_empty() {
+                    self.notice = Some("Nothing to bookmark".to_string());
+                } else {
+                    self.dialog = Some(Dialog::SaveBookmark(Prompt::new("Bookmark as", "")));
+                }
+            }
+            Command::OpenBookmark => match Bookmarks::load() {
+                Ok(bookmarks) => {
+                    self.dialog = Some(Dialog::Bookmarks(BookmarkPicker::new(bookmarks)))
+                }
+                Err(error) => self.notice = Some(format!("{:#}", error)),
+            },
+            Command::ToggleCatalog => {
+                self.show_catalog = !self.show_catalog;
+                if !self.show_catalog && self.focus == Focus::Catalog {
+                    self.focus = Focus::Editor;
+                }
+            }
+            Command::ToggleFiles => {
+                self.show_files = !self.show_files;
+                if self.show_files {
+                    self.files.refresh();
+                    self.focus = Focus::Files;
+                } else if self.focus == Focus::Files {
+                    self.focus = Focus::Editor;
+                }
+            }
+            Command::ToggleRemotes => {
+                self.show_objects = !self.show_objects;
+                if self.show_objects {
+                    self.focus = Focus::Objects;
+                } else if self.focus == Focus::Objects {
+                    self.focus = Focus::Editor;
+                }
+            }
+            Command::ToggleNotifications => {
+                self.show_notifications = !self.show_notifications;
+                if self.show_notifications {
+                    self.focus = Focus::Notifications;
+                } else if self.focus == Focus::Notifications {
+                    self.focus = Focus::Editor;
+                }
+            }
+            Command::ToggleLog => {
+                self.show_log = !self.show_log;
+                if self.show_log {
+                    self.focus = Focus::Log;
+                } else if self.focus == Focus::Log {
+                    self.focus = Focus::Editor;
+                }
+            }
+            Command::Zoom => self.maximized = !self.maximized,
+            Command::GrowEditor => self.resize_editor(EDITOR_HEIGHT_STEP as i16),
+            Command::ShrinkEditor => self.resize_editor(-(EDITOR_HEIGHT_STEP as i16)),
+            Command::NextPane => self.cycle_focus(),
+            Command::Chart => {
+                let chart = match &self.results {
+                    Results::Grid(grid) => grid.displayed().and_then(|result| Chart::new(&result)),
+                    _ => Err(anyhow::anyhow!("No result")),
+                };
+                match chart {
+                    Ok(chart) => self.show(View::Chart(chart)),
+                    Err(error) => self.notice = Some(format!("Can't chart: {:#}", error)),
+                }
+            }
+            Command::Summary => {
+                let summary = match &self.results {
+                    Results::Grid(grid) => grid
+                        .displayed()
+                        .and_then(|result| Summary::new(&result, grid.column())),
+                    _ => Err(anyhow::anyhow!("No result")),
+                };
+                match summary {
+                    Ok(summary) => self.show(View::Summary(summary)),
+                    Err(error) => self.notice = Some(format!("Can't summarize: {:#}", error)),
+                }
+            }
+            Command::Plan => match self.plan() {
+                Ok(plan) => self.show(View::Plan(plan)),
+                Err(error) => self.notice = Some(format!("Can't show the plan: {:#}", error)),
+            },
+            Command::Diff => match self.diff() {
+                Ok(diff) => self.show(View::Diff(diff)),
+                Err(error) => self.notice = Some(format!("Can't compare: {:#}", error)),
+            },
+            Command::Row => {
+                if matches!(self.results, Results::Grid(_)) {
+                    self.show(View::Record(Record::new()));
+                } else {
+                    self.notice = Some("No row to show".to_string());
+                }
+            }
+            Command::Help => self.dialog = Some(Dialog::Help(Help::new(&self.editor))),
+            Command::Quit => return Flow::Exit,
+        }
+        if self.focus != Focus::Editor {
+            self.completions.close();
+        }
+        Flow::Continue
+    }
+
+    /// Drag the border between the editor and results to resize them
+    pub fn handle_mouse(&mut self, mouse: MouseEvent) {
+        let border = self.editor_area.bottom().saturating_sub(1);
+        match mouse.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                self.dragging = !self.maximized
+                    && (border..=border + 1).contains(&mouse.row)
+                    && (self.main_area.left()..self.main_area.right()).contains(&mouse.column);
+            }
+            MouseEventKind::Drag(MouseButton::Left) if self.dragging => {
+                let height = (mouse.row + 1).saturating_sub(self.main_area.y);
+                let percent = u32::from(height) * 100 / u32::from(self.main_area.height.max(1));
+                self.editor_height = (percent as u16)
+                    .clamp(*EDITOR_HEIGHT_RANGE.start(), *EDITOR_HEIGHT_RANGE.end());
+            }
+            MouseEventKind::Up(MouseButton::Left) => self.dragging = false,
+            _ => {}
+        }
+    }
+
+    pub fn render(&mut self, frame: &mut Frame) {
+        let [panes_area, status_area] =
+            layout::Layout::vertical([layout::Constraint::Min(0), layout::Constraint::Length(1)])
+                .areas(frame.size());
+        let [catalog_area, main_area] = layout::Layout::horizontal([
+            layout::Constraint::Length(
+                if self.show_catalog || self.show_files || self.show_objects {
+                    CATALOG_WIDTH
+                } else {
+                    0
+                },
+            ),
+            layout::Constraint::Min(0),
+        ])
+        .areas(panes_area);
+        let [mut editor_area, mut results_area, mut notifications_area, mut log_area] =
+            layout::Layout::vertical([
+                layout::Constraint::Percentage(self.editor_height),
+                layout::Constraint::Fill(1),
+                layout::Constraint::Length(if self.show_notifications {
+                    NOTIFICATIONS_HEIGHT
+                } else {
+                    0
+                }),
+                layout::Constraint::Length(if self.show_log { LOG_HEIGHT } else { 0 }),
+            ])
+            .areas(main_area);
+
+        let pane = |title: &str, focused: bool| {
+            Block::new()
+                .borders(Borders::ALL)
+                .border_style(if focused {
+                    self.theme.focused_border
+                } else {
+                    self.theme.border
+                })
+                .title(title.to_string())
+        };
+        let [mut catalog_area, mut files_area, mut objects_area] = layout::Layout::vertical([
+            layout::Constraint::Fill(u16::from(self.show_catalog)),
+            layout::Constraint::Fill(u16::from(self.show_files)),
+            layout::Constraint::Fill(u16::from(self.show_objects)),
+        ])
+        .areas(catalog_area);
+        if self.maximized {
+            for (pane, area) in [
+                (Focus::Catalog, &mut catalog_area),
+                (Focus::Files, &mut files_area),
+                (Focus::Objects, &mut objects_area),
+                (Focus::Editor, &mut editor_area),
+                (Focus::Results, &mut results_area),
+                (Focus::Notifications, &mut notifications_area),
+                (Focus::Log, &mut log_area),
+            ] {
+                *area = if self.focus == pane {
+                    panes_area
+                } else {
+                    Rect::default()
+                };
+            }
+        }
+        self.editor_area = editor_area;
+        self.main_area = main_area;
+
+        if self.show_catalog && !catalog_area.is_empty() {
+            self.catalog.render(
+                frame,
+                catalog_area,
+                pane("Tables", self.focus == Focus::Catalog),
+                &self.theme,
+            );
+        }
+        if self.show_files && !files_area.is_empty() {
+            self.files.render(
+                frame,
+                files_area,
+                pane("Files", self.focus == Focus::Files),
+                &self.theme,
+            );
+        }
+        if self.show_objects && !objects_area.is_empty() {
+            let title = self
+                .objects
+                .location()
+                .unwrap_or_else(|| "Remotes".to_string());
+            self.objects.render(
+                frame,
+                objects_area,
+                pane(&title, self.focus == Focus::Objects),
+                &self.theme,
+            );
+        }
+        let editor_focused = self.focus == Focus::Editor;
+        let editor_title = match self.editor.mode() {
+            Some(mode) => format!("SQL [{}]", mode),
+            None => "SQL".to_string(),
+        };
+        if !editor_area.is_empty() {
+            self.editor.render(
+                frame,
+                editor_area,
+                pane(&editor_title, editor_focused),
+                editor_focused,
+                &self.theme,
+                &self.highlighter,
+            );
+        }
+
+        let title = match &self.running {
+            Some(running) => format!(
+                "Results ({} running {:.1}s)",
+                SPINNER[(running.started.elapsed().as_millis() / 100) as usize % SPINNER.len()],
+                running.started.elapsed().as_secs_f64()
+            ),
+            None => match &self.notice {
+                Some(notice) => format!("Results ({})", notice),
+                None => "Results".to_string(),
+            },
+        };
+        let block = pane(&title, self.focus == Focus::Results);
+        match &mut self.results {
+            _ if results_area.is_empty() => {}
+            _ if self.focus == Focus::Files && self.preview.is_some() => {
+                if let Some(preview) = &mut self.preview {
+                    let name = preview
+                        .path()
+                        .file_name()
+                        .map(|name| name.to_string_lossy().into_owned())
+                        .unwrap_or_default();
+                    preview.render(
+                        frame,
+                        results_area,
+                        pane(&format!("Preview of {}", name), false),
+                        &self.theme,
+                    );
+                }
+            }
+            Results::Empty => {
+                frame.render_widget(Paragraph::new("No results yet").block(block), results_area)
+            }
+            Results::Grid(grid) => match &mut self.view {
+                Some(View::Chart(chart)) => chart.render(frame, results_area, block, &self.theme),
+                Some(View::Summary(summary)) => {
+                    summary.render(frame, results_area, block, &self.theme)
+                }
+                Some(View::Plan(plan)) => plan.render(frame, results_area, block, &self.theme),
+                Some(View::Record(record)) => {
+                    record.render(frame, results_area, block, grid, &self.theme)
+                }
+                Some(View::Diff(diff)) => diff.render(frame, results_area, block, &self.theme),
+                None => grid.render(frame, results_area, block, &self.theme),
+            },
+            Results::Cancelled => {
+                frame.render_widget(Paragraph::new("Query cancelled").block(block), results_area)
+            }
+            Results::Error(error) => frame.render_widget(
+                Paragraph::new(error.as_str())
+                    .style(self.theme.error)
+                    .wrap(Wrap { trim: false })
+                    .block(block),
+                results_area,
+            ),
+        }
+
+        if self.show_notifications && !notifications_area.is_empty() {
+            self.notifications.render(
+                frame,
+                notifications_area,
+                pane(
+                    &format!("Notifications ({})", self.notifications.len()),
+                    self.focus == Focus::Notifications,
+                ),
+                &self.theme,
+            );
+        } else {
+            // Over whichever pane fills the screen, if the results are hidden
+            let toasts_area = if results_area.is_empty() {
+                panes_area
+            } else {
+                results_area
+            };
+            self.notifications
+                .render_toasts(frame, toasts_area, &self.theme);
+        }
+        if self.show_log && !log_area.is_empty() {
+            self.log.render(
+                frame,
+                log_area,
+                pane(
+                    &format!("Log ({}, {} and up)", self.log.len(), self.log.level()),
+                    self.focus == Focus::Log,
+                ),
+                &self.theme,
+            );
+        }
+
+        frame.render_widget(
+            Paragraph::new(self.status()).style(self.theme.status),
+            status_area,
+        );
+
+        // Drawn last of the panes, as it may hang over the results
+        if self.focus == Focus::Editor {
+            self.completions.render(
+                frame,
+                frame.size(),
+                self.editor.screen_cursor(),
+                &self.theme,
+            );
+        }
+
+        match &mut self.dialog {
+            Some(Dialog::Export(prompt) | Dialog::SaveBookmark(prompt)) => {
+                prompt.render(frame, frame.size(), &self.theme)
+            }
+            Some(Dialog::Bookmarks(picker)) => picker.render(frame, frame.size(), &self.theme),
+            Some(Dialog::Palette(palette)) => palette.render(frame, frame.size(), &self.theme),
+            Some(Dialog::Help(help)) => help.render(frame, frame.size(), &self.theme),
+            None => {}
+        }
+    }
+
+    /// The engine, how the last query went, and the keys the focused pane takes
+    fn status(&self) -> String {
+        let mut parts = vec![self.engine_kind.name().to_string()];
+        match (&self.running, &self.results) {
+            (Some(running), _) => {
+                parts.push(format!("{:.1}s", running.started.elapsed().as_secs_f64()));
+                parts.push(format!(
+                    "{} rows so far",
+                    running.rows.load(Ordering::Relaxed)
+                ));
+                parts.push(bytes(running.bytes.load(Ordering::Relaxed)));
+            }
+            (None, Results::Grid(grid)) => {
+                if let Some(duration) = self.last_duration {
+                    parts.push(format!("{:.3}s", duration.as_secs_f64()));
+                }
+                parts.push(format!(
+                    "{}{} rows",
+                    grid.result().num_rows(),
+                    if grid.is_complete() { "" } else { "+" }
+                ));
+                parts.push(bytes(grid.result().memory_size()));
+                parts.extend(grid.search_status());
+            }
+            (None, _) => {}
+        }
+        parts.push(self.hints());
+        parts.join(" │ ")
+    }
+
+    /// Keys the focused pane takes, in brief, from the tables the help overlay lists
+    fn hints(&self) -> String {
+        use super::help::owned;
+
+        let command = |command: Command| {
+            let title = command.title();
+            let mut action = title.chars();
+            let action = action
+                .next()
+                .map(|first| first.to_lowercase().chain(action).collect::<String>())
+                .unwrap_or_default();
+            (command.key().unwrap_or_default().to_string(), action)
+        };
+        let mut keys = match (self.focus, &self.results, &self.view) {
+            _ if self.is_running() => vec![command(Command::Cancel)],
+            (Focus::Editor, _, _) => {
+                let mut keys = vec![command(Command::Run)];
+                keys.extend(owned(self.editor.keys()));
+                keys
+            }
+            (Focus::Results, Results::Grid(_), Some(View::Chart(_))) => {
+                Help::view("c", Chart::KEYS)
+            }
+            (Focus::Results, Results::Grid(_), Some(View::Summary(_))) => Help::view("i", &[]),
+            (Focus::Results, Results::Grid(_), Some(View::Plan(_))) => Help::view("p", Plan::KEYS),
+            (Focus::Results, Results::Grid(_), Some(View::Record(_))) => {
+                Help::view("Enter", Record::KEYS)
+            }
+            (Focus::Results, Results::Grid(_), Some(View::Diff(_))) => Help::view("d", Diff::KEYS),
+            (Focus::Results, Results::Grid(_), None) => Help::results(),
+            (Focus::Results, _, _) => vec![command(Command::NextPane)],
+            (Focus::Catalog, _, _) => owned(Catalog::KEYS),
+            (Focus::Files, _, _) => owned(Files::KEYS),
+            (Focus::Objects, _, _) => owned(Objects::KEYS),
+            (Focus::Notifications, _, _) => owned(Notifications::KEYS),
+            (Focus::Log, _, _) => owned(Log::KEYS),
+        };
+        keys.push((
+            Command::Help.key().unwrap_or_default().to_string(),
+            "keys".to_string(),
+        ));
+        Help::brief(&keys)
+    }
+
+    /// Grow the editor by `percent` of the height beside the sidebar, shrinking the results, or
+    /// the other way around if negative
+    fn resize_editor(&mut self, percent: i16) {
+        self.maximized = false;
+        self.editor_height = self
+            .editor_height
+            .saturating_add_signed(percent)
+            .clamp(*EDITOR_HEIGHT_RANGE.start(), *EDITOR_HEIGHT_RANGE.end());
+    }
+
+    /// Offer completions after `key` typed or deleted part of a word in the editor, or stop
+    /// offering them after any other edit or movement
+    fn complete(&mut self, key: KeyEvent) {
+        let typed = matches!(key.code, KeyCode::Char(_) | KeyCode::Backspace)
+            && !key
+                .modifiers
+                .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT);
+        if typed && self.editor.is_inserting() {
+            self.completions
+                .update(&self.editor.text(), self.editor.cursor());
+        } else {
+            self.completions.close();
+        }
+    }
+
+    /// Show `view` in place of the grid, focusing it
+    fn show(&mut self, view: View) {
+        self.view = Some(view);
+        self.focus = Focus::Results;
+    }
+
+    /// Copy the grid's selection, or the rows it spans if `whole_rows`, to the clipboard: in
+    /// `format`, or as tab-separated values if none
+    fn copy(&self, whole_rows: bool, format: Option<OutputFormat>) -> anyhow::Result<String> {
+        let Results::Grid(grid) = &self.results else {
+            anyhow::bail!("No result to copy from");
+        };
+        let result = grid.selection(whole_rows)?;
+        let (text, name) = match format {
+            Some(format) => (
+                format.render(result.schema.clone(), &result.batches)?,
+                format.name().to_uppercase(),
+            ),
+            None => (tab_separated(&result)?, "TSV".to_string()),
+        };
+        let destination = super::clipboard::copy(&text)?;
+        Ok(format!(
+            "Copied {} rows as {} to the {}",
+            result.num_rows(),
+            name,
+            destination
+        ))
+    }
+
+    /// Write the rows shown in the grid to `path`, returning how many there were.
+    ///
+    /// A result still streaming in is fetched in full first.
+    fn export(&mut self, path: &str) -> anyhow::Result<usize> {
+        self.stream(true);
+        let Results::Grid(grid) = &self.results else {
+            anyhow::bail!("No result to export");
+        };
+        let result = grid.displayed()?;
+        result.export(std::path::Path::new(path))?;
+        Ok(result.num_rows())
+    }
+
+    /// The plan of the last query: the one it printed if it was an `EXPLAIN`, otherwise the one
+    /// it ran with what each operator measured, once its result is read in full
+    fn plan(&self) -> anyhow::Result<Plan> {
+        let Results::Grid(grid) = &self.results else {
+            anyhow::bail!("No result to show the plan of");
+        };
+        if self.explained {
+            return Plan::from_explain(grid.result());
+        }
+        match self.metrics.as_ref().and_then(|metrics| metrics.plan()) {
+            Some(root) => Ok(Plan::new(vec![root])),
+            None if self.streaming.is_some() => {
+                anyhow::bail!("The plan is measured once the result is read in full")
+            }
+            None => anyhow::bail!(
+                "{} doesn't report the plan it ran, run EXPLAIN instead",
+                self.engine_kind.name()
+            ),
+        }
+    }
+
+    /// How the result differs from the one before it, matching rows by the selected columns.
+    ///
+    /// A result still streaming in is fetched in full first.
+    fn diff(&mut self) -> anyhow::Result<Diff> {
+        use anyhow::Context as _;
+
+        self.stream(true);
+        let Results::Grid(grid) = &self.results else {
+            anyhow::bail!("No result to compare");
+        };
+        let previous = self
+            .previous
+            .as_ref()
+            .context("No earlier result to compare with, run a query again first")?;
+        anyhow::ensure!(
+            !previous.schema.fields().is_empty() && !grid.result().schema.fields().is_empty(),
+            "No columns to compare"
+        );
+        Diff::new(previous, grid.result(), grid.selected_columns())
+    }
+
+    /// Move focus to the next pane shown, from the editor to the results to the notifications to
+    /// the log to the catalog to the files to the remotes
+    fn cycle_focus(&mut self) {
+        self.focus = match self.focus {
+            Focus::Editor => Focus::Results,
+            Focus::Results if self.show_notifications => Focus::Notifications,
+            Focus::Results | Focus::Notifications if self.show_log => Focus::Log,
+            Focus::Results | Focus::Notifications | Focus::Log if self.show_catalog => {
+                Focus::Catalog
+            }
+            Focus::Results | Focus::Notifications | Focus::Log | Focus::Catalog
+                if self.show_files =>
+            {
+                Focus::Files
+            }
+            Focus::Results | Focus::Notifications | Focus::Log | Focus::Catalog | Focus::Files
+                if self.show_objects =>
+            {
+                Focus::Objects
+            }
+            Focus::Results
+            | Focus::Notifications
+            | Focus::Log
+            | Focus::Catalog
+            | Focus::Files
+            | Focus::Objects => Focus::Editor,
+        };
+    }
+}
+
+/// `result` as tab-separated values with a header line, or just the value of a single cell, ready
+/// to paste into a spreadsheet
+fn tab_separated(result: &ResultSet) -> anyhow::Result<String> {
+    let mut rows = Vec::new();
+    for batch in &result.batches {
+        rows.extend(crate::output_format::format_cells(batch, "")?);
+    }
+    let clean = |cell: &String| cell.replace(['\t', '\n', '\r'], " ");
+    if let [row] = rows.as_slice() {
+        if let [cell] = row.as_slice() {
+            return Ok(clean(cell));
+        }
+    }
+    let header = result
+        .schema
+        .fields()
+        .iter()
+        .map(|field| clean(field.name()))
+        .collect::<Vec<_>>();
+    Ok(std::iter::once(header)
+        .chain(rows.iter().map(|row| row.iter().map(clean).collect()))
+        .map(|row| row.join("\t"))
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+/// `count` bytes in the largest unit it makes at least one of
+pub(super) fn bytes(count: usize) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if count < 1024 {
+        return format!("{} B", count);
+    }
+    let mut size = count as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
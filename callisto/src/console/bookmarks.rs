@@ -0,0 +1,147 @@
+use ratatui::{
+    crossterm::event::{KeyCode, KeyEvent},
+    layout::{self, Rect},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph},
+    Frame,
+};
+
+use super::Theme;
+use crate::Bookmarks;
+
+/// Most bookmarks listed in the picker at once
+const PICKER_HEIGHT: u16 = 12;
+
+/// What became of the bookmark picker after a key
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BookmarkEvent {
+    /// Still choosing
+    Choosing,
+    /// The SQL of the bookmark chosen
+    Picked(String),
+    Cancelled,
+}
+
+/// A popup listing the bookmarked queries, narrowed by typing part of a name
+pub struct BookmarkPicker {
+    bookmarks: Bookmarks,
+    filter: String,
+    /// Index of the selected bookmark among those matching the filter
+    selected: usize,
+    /// Why the last bookmark deleted couldn't be
+    error: Option<String>,
+}
+
+impl BookmarkPicker {
+    pub fn new(bookmarks: Bookmarks) -> BookmarkPicker {
+        BookmarkPicker {
+            bookmarks,
+            filter: String::new(),
+            selected: 0,
+            error: None,
+        }
+    }
+
+    /// Narrow, move through, pick, or delete bookmarks in response to `key`
+    pub fn handle_key(&mut self, key: KeyEvent) -> BookmarkEvent {
+        let shown = self.shown();
+        let last = shown.len().saturating_sub(1);
+        let selected = shown
+            .get(self.selected)
+            .map(|(name, sql)| (name.to_string(), sql.to_string()));
+        match key.code {
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down => self.selected = (self.selected + 1).min(last),
+            KeyCode::Enter => {
+                if let Some((_, sql)) = selected {
+                    return BookmarkEvent::Picked(sql);
+                }
+            }
+            KeyCode::Esc => return BookmarkEvent::Cancelled,
+            KeyCode::Delete => {
+                if let Some((name, _)) = selected {
+                    self.error = self
+                        .bookmarks
+                        .delete(&name)
+                        .err()
+                        .map(|error| format!("{:#}", error));
+                    self.selected = self.selected.min(self.shown().len().saturating_sub(1));
+                }
+            }
+            KeyCode::Char(c) => {
+                self.filter.push(c);
+                self.selected = 0;
+            }
+            KeyCode::Backspace => {
+                self.filter.pop();
+                self.selected = 0;
+            }
+            _ => {}
+        }
+        BookmarkEvent::Choosing
+    }
+
+    /// Draw the picker as a popup centred in `area`
+    pub fn render(&self, frame: &mut Frame, area: Rect, theme: &Theme) {
+        let width = area.width.min(80);
+        let [row] = layout::Layout::vertical([layout::Constraint::Length(PICKER_HEIGHT + 3)])
+            .flex(layout::Flex::Center)
+            .areas(area);
+        let [popup] = layout::Layout::horizontal([layout::Constraint::Length(width)])
+            .flex(layout::Flex::Center)
+            .areas(row);
+        let block = Block::new()
+            .borders(Borders::ALL)
+            .border_style(theme.focused_border)
+            .title("Bookmarks (Enter to open, Delete to delete, Esc to cancel)");
+        let inner = block.inner(popup);
+        frame.render_widget(Clear, popup);
+        frame.render_widget(block, popup);
+        let [filter_area, list_area] =
+            layout::Layout::vertical([layout::Constraint::Length(1), layout::Constraint::Min(0)])
+                .areas(inner);
+
+        let filter = match &self.error {
+            Some(error) => Line::styled(error.as_str(), theme.error),
+            None => Line::from(vec![
+                Span::styled("Filter: ", theme.muted),
+                Span::raw(self.filter.as_str()),
+            ]),
+        };
+        frame.render_widget(Paragraph::new(filter), filter_area);
+
+        let shown = self.shown();
+        let items = if self.bookmarks.is_empty() {
+            vec![ListItem::new(Line::styled(
+                "No bookmarks yet: save the editor as one with Ctrl-S",
+                theme.muted,
+            ))]
+        } else {
+            shown
+                .iter()
+                .map(|(name, sql)| {
+                    ListItem::new(Line::from(vec![
+                        Span::raw(format!("{} ", name)),
+                        Span::styled(
+                            sql.split_whitespace().collect::<Vec<_>>().join(" "),
+                            theme.muted,
+                        ),
+                    ]))
+                })
+                .collect()
+        };
+        let list = List::new(items).highlight_style(theme.selected);
+        let mut state =
+            ListState::default().with_selected((!shown.is_empty()).then_some(self.selected));
+        frame.render_stateful_widget(list, list_area, &mut state);
+    }
+
+    /// Bookmarks whose names contain the filter, ignoring case
+    fn shown(&self) -> Vec<(&str, &str)> {
+        let filter = self.filter.to_lowercase();
+        self.bookmarks
+            .iter()
+            .filter(|(name, _)| name.to_lowercase().contains(&filter))
+            .collect()
+    }
+}
@@ -0,0 +1,166 @@
+use std::collections::BTreeSet;
+
+use ratatui::{
+    crossterm::event::{KeyCode, KeyEvent},
+    layout::Rect,
+    text::Line,
+    widgets::{Block, List, ListItem, ListState},
+    Frame,
+};
+
+use super::{Binding, Theme};
+use crate::TableInfo;
+
+/// What the user asked for by picking a table in the catalog
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CatalogAction {
+    /// Insert the table's name into the editor
+    Insert(String),
+    /// Show the table's first rows
+    Preview(String),
+}
+
+/// An entry shown in the catalog: a table, or one of the columns of an expanded table
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Entry {
+    Table(usize),
+    Column(usize, usize),
+}
+
+/// Tree of the tables registered with the engine, each expandable into its columns and types
+#[derive(Default)]
+pub struct Catalog {
+    tables: Vec<TableInfo>,
+    /// Names of the tables whose columns are shown
+    expanded: BTreeSet<String>,
+    /// Index of the selected entry
+    selected: usize,
+}
+
+impl Catalog {
+    /// Replace the listed tables, keeping tables expanded if they're still registered
+    pub fn set_tables(&mut self, tables: Vec<TableInfo>) {
+        self.expanded
+            .retain(|name| tables.iter().any(|table| &table.name == name));
+        self.tables = tables;
+        self.selected = self.selected.min(self.entries().len().saturating_sub(1));
+    }
+
+    /// Keys the catalog handles, for the help overlay
+    pub const KEYS: &'static [Binding] = &[
+        ("j/k", "move"),
+        ("l/h, Space", "expand/collapse"),
+        ("Enter", "insert name"),
+        ("p", "preview rows"),
+    ];
+
+    /// Move through or act on the catalog in response to `key`
+    pub fn handle_key(&mut self, key: KeyEvent) -> Option<CatalogAction> {
+        let entries = self.entries();
+        let selected = entries.get(self.selected).copied()?;
+        let table_index = match selected {
+            Entry::Table(table) | Entry::Column(table, _) => table,
+        };
+        let table = &self.tables[table_index];
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.selected = (self.selected + 1).min(entries.len() - 1)
+            }
+            KeyCode::Right | KeyCode::Char('l') | KeyCode::Char(' ') => {
+                self.expanded.insert(table.name.clone());
+            }
+            KeyCode::Left | KeyCode::Char('h') => {
+                let name = table.name.clone();
+                self.expanded.remove(&name);
+                // Keep the selection on the collapsed table rather than whatever follows it
+                if let Some(index) = self
+                    .entries()
+                    .iter()
+                    .position(|entry| entry == &Entry::Table(table_index))
+                {
+                    self.selected = index;
+                }
+            }
+            KeyCode::Enter => {
+                return Some(match selected {
+                    Entry::Table(_) => CatalogAction::Insert(quote(&table.name)),
+                    Entry::Column(_, column) => CatalogAction::Insert(quote(
+                        table
+                            .schema
+                            .as_ref()
+                            .map(|schema| schema.field(column).name().as_str())
+                            .unwrap_or_default(),
+                    )),
+                })
+            }
+            KeyCode::Char('p') => return Some(CatalogAction::Preview(table.name.clone())),
+            _ => {}
+        }
+        None
+    }
+
+    pub fn render(&self, frame: &mut Frame, area: Rect, block: Block, theme: &Theme) {
+        let items = self
+            .entries()
+            .into_iter()
+            .map(|entry| match entry {
+                Entry::Table(index) => {
+                    let table = &self.tables[index];
+                    let marker = if self.expanded.contains(&table.name) {
+                        '▾'
+                    } else {
+                        '▸'
+                    };
+                    ListItem::new(Line::from(format!("{} {}", marker, table.name)))
+                }
+                Entry::Column(table, column) => {
+                    let field = self.tables[table]
+                        .schema
+                        .as_ref()
+                        .map(|schema| schema.field(column).clone());
+                    ListItem::new(Line::from(match field {
+                        Some(field) => format!("    {}: {}", field.name(), field.data_type()),
+                        None => String::new(),
+                    }))
+                }
+            })
+            .collect::<Vec<_>>();
+        let empty = items.is_empty();
+        let list = List::new(items)
+            .block(block)
+            .highlight_style(theme.selected);
+        let mut state = ListState::default().with_selected((!empty).then_some(self.selected));
+        frame.render_stateful_widget(list, area, &mut state);
+    }
+
+    /// Entries in display order, with the columns of expanded tables following them
+    fn entries(&self) -> Vec<Entry> {
+        let mut entries = Vec::new();
+        for (index, table) in self.tables.iter().enumerate() {
+            entries.push(Entry::Table(index));
+            if self.expanded.contains(&table.name) {
+                let columns = table
+                    .schema
+                    .as_ref()
+                    .map_or(0, |schema| schema.fields().len());
+                entries.extend((0..columns).map(|column| Entry::Column(index, column)));
+            }
+        }
+        entries
+    }
+}
+
+/// `name` as an identifier, quoted if it wouldn't otherwise parse as one
+pub(super) fn quote(name: &str) -> String {
+    let plain = name
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if plain {
+        name.to_string()
+    } else {
+        format!("\"{}\"", name.replace('"', "\"\""))
+    }
+}
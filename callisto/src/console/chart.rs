@@ -0,0 +1,313 @@
+use std::collections::BTreeSet;
+
+use arrow::array::{Array as _, ArrayRef};
+use ratatui::{
+    crossterm::event::{KeyCode, KeyEvent},
+    layout::{self, Constraint, Rect},
+    style::{Modifier, Style},
+    symbols,
+    text::{Line, Span},
+    widgets::{Axis, Bar, BarChart, BarGroup, Block, Dataset, GraphType, Paragraph},
+    Frame,
+};
+
+use super::{Binding, Theme};
+use crate::ResultSet;
+
+/// Bar heights are scaled to integers out of this
+const BAR_SCALE: f64 = 1000.0;
+
+/// How a chart draws its values
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChartKind {
+    Line,
+    Bar,
+}
+
+/// A result plotted as lines or bars, with one column along the x axis and numeric columns
+/// along the y axis.
+///
+/// Columns are picked with a cursor over their names: `x` plots along the x axis the column under
+/// it, and space adds or removes it from the y columns.
+pub struct Chart {
+    headers: Vec<String>,
+    /// Display text of each value, column by column, for labelling the x axis
+    labels: Vec<Vec<String>>,
+    /// Each column's values as floats, if it is numeric
+    values: Vec<Option<Vec<Option<f64>>>>,
+    x: usize,
+    ys: BTreeSet<usize>,
+    /// Column under the cursor
+    column: usize,
+    kind: ChartKind,
+}
+
+impl Chart {
+    /// Chart `result`, starting with the first column along the x axis and the first numeric
+    /// column after it along the y axis
+    pub fn new(result: &ResultSet) -> anyhow::Result<Chart> {
+        let headers = result
+            .schema
+            .fields()
+            .iter()
+            .map(|field| field.name().clone())
+            .collect::<Vec<_>>();
+        let mut labels = vec![Vec::new(); headers.len()];
+        let mut values = result
+            .schema
+            .fields()
+            .iter()
+            .map(|field| field.data_type().is_numeric().then(Vec::new))
+            .collect::<Vec<_>>();
+        for batch in &result.batches {
+            for row in crate::output_format::format_cells(batch, "NULL")? {
+                for (column, cell) in labels.iter_mut().zip(row) {
+                    column.push(cell);
+                }
+            }
+            for (index, column) in values.iter_mut().enumerate() {
+                if let Some(column) = column {
+                    column.extend(floats(batch.column(index))?);
+                }
+            }
+        }
+        let ys = values
+            .iter()
+            .enumerate()
+            .skip(1)
+            .find(|(_, column)| column.is_some())
+            .map(|(index, _)| index)
+            .into_iter()
+            .collect();
+        Ok(Chart {
+            headers,
+            labels,
+            values,
+            x: 0,
+            ys,
+            column: 0,
+            kind: ChartKind::Line,
+        })
+    }
+
+    /// Keys the chart handles, for the help overlay
+    pub const KEYS: &'static [Binding] = &[
+        ("h/l", "previous/next column"),
+        ("x", "plot along x"),
+        ("Space, y", "plot along y"),
+        ("t", "lines or bars"),
+    ];
+
+    /// Pick columns or switch between lines and bars in response to `key`, returning whether the
+    /// key was used
+    pub fn handle_key(&mut self, key: KeyEvent) -> bool {
+        let last_column = self.headers.len().saturating_sub(1);
+        match key.code {
+            KeyCode::Left | KeyCode::Char('h') => self.column = self.column.saturating_sub(1),
+            KeyCode::Right | KeyCode::Char('l') => self.column = (self.column + 1).min(last_column),
+            KeyCode::Char('x') if !self.headers.is_empty() => {
+                self.x = self.column;
+                self.ys.remove(&self.column);
+            }
+            KeyCode::Char(' ') | KeyCode::Char('y') => {
+                if !self.ys.remove(&self.column)
+                    && self.column != self.x
+                    && self.values.get(self.column).is_some_and(Option::is_some)
+                {
+                    self.ys.insert(self.column);
+                }
+            }
+            KeyCode::Char('t') => {
+                self.kind = match self.kind {
+                    ChartKind::Line => ChartKind::Bar,
+                    ChartKind::Bar => ChartKind::Line,
+                }
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    pub fn render(&self, frame: &mut Frame, area: Rect, block: Block, theme: &Theme) {
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+        let [columns_area, chart_area, footer_area] = layout::Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .areas(inner);
+
+        let columns = self
+            .headers
+            .iter()
+            .enumerate()
+            .flat_map(|(index, header)| {
+                let mut style = match self.ys.iter().position(|&y| y == index) {
+                    Some(series) => theme.series(series),
+                    None if self.values[index].is_none() => theme.muted,
+                    None => Style::new(),
+                };
+                if index == self.x {
+                    style = style.add_modifier(Modifier::UNDERLINED);
+                }
+                if index == self.column {
+                    style = style.add_modifier(Modifier::REVERSED);
+                }
+                [Span::styled(header.clone(), style), Span::raw(" ")]
+            })
+            .collect::<Vec<_>>();
+        frame.render_widget(Paragraph::new(Line::from(columns)), columns_area);
+
+        if self.ys.is_empty() {
+            frame.render_widget(
+                Paragraph::new("Pick a numeric column to plot with space"),
+                chart_area,
+            );
+        } else {
+            match self.kind {
+                ChartKind::Line => self.render_lines(frame, chart_area, theme),
+                ChartKind::Bar => self.render_bars(frame, chart_area, theme),
+            }
+        }
+
+        let footer = format!(
+            "{} chart of {} rows",
+            match self.kind {
+                ChartKind::Line => "Line",
+                ChartKind::Bar => "Bar",
+            },
+            self.labels.first().map_or(0, Vec::len),
+        );
+        frame.render_widget(
+            Paragraph::new(Line::from(footer)).style(theme.status),
+            footer_area,
+        );
+    }
+
+    fn render_lines(&self, frame: &mut Frame, area: Rect, theme: &Theme) {
+        let rows = self.labels.first().map_or(0, Vec::len);
+        // Values along a column that isn't numeric are plotted in row order
+        let xs = match &self.values[self.x] {
+            Some(values) => values.clone(),
+            None => (0..rows).map(|row| Some(row as f64)).collect(),
+        };
+        let series = self
+            .ys
+            .iter()
+            .map(|&y| {
+                xs.iter()
+                    .zip(self.values[y].as_deref().unwrap_or_default())
+                    .filter_map(|(x, y)| Some(((*x)?, (*y)?)))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let x_bounds = bounds(series.iter().flatten().map(|(x, _)| *x));
+        let y_bounds = bounds(series.iter().flatten().map(|(_, y)| *y));
+        let datasets = self
+            .ys
+            .iter()
+            .zip(&series)
+            .enumerate()
+            .map(|(index, (&y, points))| {
+                Dataset::default()
+                    .name(self.headers[y].clone())
+                    .marker(symbols::Marker::Braille)
+                    .graph_type(GraphType::Line)
+                    .style(theme.series(index))
+                    .data(points)
+            })
+            .collect();
+        let x_labels = match &self.values[self.x] {
+            Some(_) => vec![label(x_bounds[0]), label(x_bounds[1])],
+            None => vec![
+                self.labels[self.x].first().cloned().unwrap_or_default(),
+                self.labels[self.x].last().cloned().unwrap_or_default(),
+            ],
+        };
+        let chart = ratatui::widgets::Chart::new(datasets)
+            .x_axis(
+                Axis::default()
+                    .title(self.headers[self.x].clone())
+                    .bounds(x_bounds)
+                    .labels(x_labels.into_iter().map(Span::from).collect()),
+            )
+            .y_axis(
+                Axis::default()
+                    .bounds(y_bounds)
+                    .labels(vec![label(y_bounds[0]).into(), label(y_bounds[1]).into()]),
+            );
+        frame.render_widget(chart, area);
+    }
+
+    fn render_bars(&self, frame: &mut Frame, area: Rect, theme: &Theme) {
+        // Bars are drawn with integer heights, so values are scaled to the largest of them, with
+        // negative values drawn as empty bars
+        let largest = self
+            .ys
+            .iter()
+            .flat_map(|&y| self.values[y].iter().flatten().flatten())
+            .fold(0.0_f64, |largest, value| largest.max(*value));
+        let mut chart = BarChart::default().bar_width(3).group_gap(2);
+        for (row, label) in self.labels[self.x].iter().enumerate() {
+            let bars = self
+                .ys
+                .iter()
+                .enumerate()
+                .map(|(index, &y)| {
+                    let value = self.values[y].as_ref().and_then(|values| values[row]);
+                    let height = match value {
+                        Some(value) if largest > 0.0 => {
+                            (value.max(0.0) / largest * BAR_SCALE) as u64
+                        }
+                        _ => 0,
+                    };
+                    Bar::default()
+                        .value(height)
+                        .text_value(value.map_or("NULL".to_string(), self::label))
+                        .style(theme.series(index))
+                })
+                .collect::<Vec<_>>();
+            chart = chart.data(
+                BarGroup::default()
+                    .label(Line::from(label.clone()))
+                    .bars(&bars),
+            );
+        }
+        frame.render_widget(chart, area);
+    }
+}
+
+/// Values of a numeric `column` as floats
+fn floats(column: &ArrayRef) -> anyhow::Result<Vec<Option<f64>>> {
+    let column = arrow::compute::cast(column, &arrow::datatypes::DataType::Float64)?;
+    let column = column
+        .as_any()
+        .downcast_ref::<arrow::array::Float64Array>()
+        .ok_or_else(|| anyhow::anyhow!("Numeric column didn't cast to floats"))?;
+    Ok((0..column.len())
+        .map(|index| column.is_valid(index).then(|| column.value(index)))
+        .collect())
+}
+
+/// The range spanned by `values`, widened if they're all the same so there's something to draw
+fn bounds(values: impl Iterator<Item = f64>) -> [f64; 2] {
+    let (low, high) = values
+        .filter(|value| value.is_finite())
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(low, high), value| {
+            (low.min(value), high.max(value))
+        });
+    if low > high {
+        [0.0, 1.0]
+    } else if low == high {
+        [low - 1.0, high + 1.0]
+    } else {
+        [low, high]
+    }
+}
+
+/// `value` as an axis or bar label, without trailing zeros
+fn label(value: f64) -> String {
+    let text = format!("{:.2}", value);
+    text.trim_end_matches('0').trim_end_matches('.').to_string()
+}
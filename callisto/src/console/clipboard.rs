@@ -0,0 +1,64 @@
+use std::io::Write as _;
+
+/// Commands that set the system clipboard from their input, tried in turn
+const COMMANDS: [&[&str]; 5] = [
+    &["pbcopy"],
+    &["wl-copy"],
+    &["xclip", "-selection", "clipboard"],
+    &["xsel", "--clipboard", "--input"],
+    &["clip.exe"],
+];
+
+/// Put `text` on the clipboard, returning how it got there.
+///
+/// Over SSH, or where no clipboard command works, the terminal is asked to set the clipboard
+/// with an OSC 52 escape sequence, which many terminals honor for programs on remote hosts.
+pub fn copy(text: &str) -> anyhow::Result<&'static str> {
+    let remote =
+        std::env::var_os("SSH_CONNECTION").is_some() || std::env::var_os("SSH_TTY").is_some();
+    if !remote && COMMANDS.iter().any(|command| pipe(command, text).is_ok()) {
+        return Ok("clipboard");
+    }
+    let mut stdout = std::io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", base64(text.as_bytes()))?;
+    stdout.flush()?;
+    Ok("terminal clipboard")
+}
+
+/// Run `command` with `text` as its input, failing unless it succeeds
+fn pipe(command: &[&str], text: &str) -> anyhow::Result<()> {
+    let mut child = std::process::Command::new(command[0])
+        .args(&command[1..])
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(text.as_bytes())?;
+    }
+    let status = child.wait()?;
+    anyhow::ensure!(status.success(), "{} failed with {}", command[0], status);
+    Ok(())
+}
+
+/// `bytes` in standard base64, padded
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |group, (index, &byte)| {
+                group | (u32::from(byte) << (16 - 8 * index))
+            });
+        for index in 0..4 {
+            if index <= chunk.len() {
+                encoded.push(ALPHABET[((group >> (18 - 6 * index)) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
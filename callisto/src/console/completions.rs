@@ -0,0 +1,146 @@
+use ratatui::{
+    crossterm::event::{KeyCode, KeyEvent},
+    layout::Rect,
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState},
+    Frame,
+};
+
+use super::Theme;
+use crate::completion::{Completer, Completion, CompletionKind};
+
+/// Most completions listed at once
+const POPUP_HEIGHT: u16 = 8;
+const POPUP_WIDTH: u16 = 48;
+
+/// What became of the completions popup after a key
+#[derive(Clone, Debug)]
+pub enum CompletionsEvent {
+    /// The key wasn't for the popup, and should go to the editor
+    Ignored,
+    /// The key moved through or closed the popup
+    Handled,
+    /// The selected completion was chosen, to replace its span of the editor's text
+    Accepted(Completion),
+}
+
+/// Completions for the word being typed in the editor, shown in a popup below the cursor and
+/// chosen from with the arrows and Tab
+pub struct Completions {
+    completer: Completer,
+    entries: Vec<Completion>,
+    selected: usize,
+}
+
+impl Completions {
+    pub fn new(completer: Completer) -> Completions {
+        Completions {
+            completer,
+            entries: Vec::new(),
+            selected: 0,
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        !self.entries.is_empty()
+    }
+
+    pub fn close(&mut self) {
+        self.entries.clear();
+        self.selected = 0;
+    }
+
+    /// Offer completions for `text` with the cursor at byte offset `cursor`.
+    ///
+    /// Nothing is offered until part of a word or path has been typed, as every table and column
+    /// would match otherwise.
+    pub fn update(&mut self, text: &str, cursor: usize) {
+        self.entries = self
+            .completer
+            .complete(text, cursor)
+            .into_iter()
+            .filter(|completion| {
+                !completion.span.is_empty() || completion.kind == CompletionKind::Path
+            })
+            // Nothing to offer if the word is already typed out in full
+            .filter(|completion| text[completion.span.clone()] != completion.value)
+            .collect();
+        self.selected = 0;
+    }
+
+    /// Move through the completions, or choose or dismiss one, in response to `key`
+    pub fn handle_key(&mut self, key: KeyEvent) -> CompletionsEvent {
+        let count = self.entries.len().max(1);
+        match key.code {
+            KeyCode::Up => self.selected = (self.selected + count - 1) % count,
+            KeyCode::Down => self.selected = (self.selected + 1) % count,
+            KeyCode::Tab | KeyCode::Enter => {
+                let Some(completion) = self.entries.get(self.selected).cloned() else {
+                    return CompletionsEvent::Ignored;
+                };
+                self.close();
+                return CompletionsEvent::Accepted(completion);
+            }
+            KeyCode::Esc => self.close(),
+            _ => return CompletionsEvent::Ignored,
+        }
+        CompletionsEvent::Handled
+    }
+
+    /// Draw the popup below `cursor`, or above it if there isn't room, within `area`
+    pub fn render(&self, frame: &mut Frame, area: Rect, cursor: (u16, u16), theme: &Theme) {
+        if !self.is_open() {
+            return;
+        }
+        let height = (self.entries.len() as u16).min(POPUP_HEIGHT) + 2;
+        let width = POPUP_WIDTH.min(area.width);
+        let (x, y) = cursor;
+        let top = if y + 1 + height <= area.bottom() {
+            y + 1
+        } else {
+            y.saturating_sub(height).max(area.y)
+        };
+        let left = x.min(area.right().saturating_sub(width));
+        let popup = Rect::new(
+            left,
+            top,
+            width,
+            height.min(area.bottom().saturating_sub(top)),
+        );
+
+        let items = self
+            .entries
+            .iter()
+            .map(|completion| {
+                let mut spans = vec![
+                    Span::styled(format!("{} ", marker(completion.kind)), theme.muted),
+                    Span::raw(completion.value.clone()),
+                ];
+                if let Some(description) = &completion.description {
+                    spans.push(Span::styled(format!("  {}", description), theme.muted));
+                }
+                ListItem::new(Line::from(spans))
+            })
+            .collect::<Vec<_>>();
+        let list = List::new(items)
+            .block(
+                Block::new()
+                    .borders(Borders::ALL)
+                    .border_style(theme.focused_border),
+            )
+            .highlight_style(theme.selected);
+        let mut state = ListState::default().with_selected(Some(self.selected));
+        frame.render_widget(Clear, popup);
+        frame.render_stateful_widget(list, popup, &mut state);
+    }
+}
+
+/// A letter telling kinds of completion apart
+fn marker(kind: CompletionKind) -> char {
+    match kind {
+        CompletionKind::Keyword => 'k',
+        CompletionKind::Table => 't',
+        CompletionKind::Column => 'c',
+        CompletionKind::Path => 'f',
+    }
+}
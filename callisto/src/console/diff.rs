@@ -0,0 +1,309 @@
+use std::collections::{HashMap, VecDeque};
+use std::ops::RangeInclusive;
+
+use ratatui::{
+    crossterm::event::{KeyCode, KeyEvent},
+    layout::{self, Constraint, Rect},
+    text::{Line, Span},
+    widgets::{Block, List, ListItem, ListState, Paragraph},
+    Frame,
+};
+
+use super::{Binding, Theme};
+use crate::ResultSet;
+
+/// Widest a column is drawn, with longer values truncated
+const MAX_COLUMN_WIDTH: usize = 30;
+
+/// How a row differs between two runs of a query
+enum Change {
+    Added,
+    Removed,
+    /// Matched by its key, with which of its values differ
+    Changed(Vec<bool>),
+}
+
+struct DiffRow {
+    change: Change,
+    /// Values in the latest run, or in the earlier one if removed
+    cells: Vec<String>,
+    /// Values in the earlier run, if changed
+    old: Vec<String>,
+}
+
+/// The rows added, removed, and changed between the previous result and the latest, matching
+/// rows up by the values of key columns
+pub struct Diff {
+    headers: Vec<String>,
+    /// Indices of the columns rows are matched by
+    keys: Vec<usize>,
+    rows: Vec<DiffRow>,
+    /// Rows matched with the same values in both runs, not listed
+    unchanged: usize,
+    selected: usize,
+    /// Rows that fit in view as of the last render, for paging
+    page: usize,
+}
+
+impl Diff {
+    /// Compare `latest` with `previous`, matching rows whose `keys` columns of `latest` have the
+    /// same values in the column of the same name in `previous`.
+    ///
+    /// Rows with the same key are matched in the order they come, so a key needn't be unique.
+    pub fn new(
+        previous: &ResultSet,
+        latest: &ResultSet,
+        keys: RangeInclusive<usize>,
+    ) -> anyhow::Result<Diff> {
+        let headers = latest
+            .schema
+            .fields()
+            .iter()
+            .map(|field| field.name().clone())
+            .collect::<Vec<_>>();
+        let keys = keys.collect::<Vec<_>>();
+        for &key in &keys {
+            anyhow::ensure!(
+                previous.schema.index_of(&headers[key]).is_ok(),
+                "The previous result has no column {} to match rows by",
+                headers[key]
+            );
+        }
+        // The previous result's values rearranged into the latest's columns, blank if it didn't
+        // have one
+        let columns = headers
+            .iter()
+            .map(|header| previous.schema.index_of(header).ok())
+            .collect::<Vec<_>>();
+        let old_rows = cells(previous)?
+            .into_iter()
+            .map(|row| {
+                columns
+                    .iter()
+                    .map(|column| column.map(|column| row[column].clone()).unwrap_or_default())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let key_of = |row: &[String]| keys.iter().map(|&key| row[key].clone()).collect::<Vec<_>>();
+
+        let mut unmatched = HashMap::<Vec<String>, VecDeque<usize>>::new();
+        for (index, row) in old_rows.iter().enumerate() {
+            unmatched.entry(key_of(row)).or_default().push_back(index);
+        }
+        let mut rows = Vec::new();
+        let mut matched = vec![false; old_rows.len()];
+        let mut unchanged = 0;
+        for row in cells(latest)? {
+            let old = unmatched
+                .get_mut(&key_of(&row))
+                .and_then(VecDeque::pop_front);
+            match old {
+                Some(index) => {
+                    matched[index] = true;
+                    let old = &old_rows[index];
+                    let differs = row
+                        .iter()
+                        .zip(old)
+                        .map(|(new, old)| new != old)
+                        .collect::<Vec<_>>();
+                    if differs.contains(&true) {
+                        rows.push(DiffRow {
+                            change: Change::Changed(differs),
+                            cells: row,
+                            old: old.clone(),
+                        });
+                    } else {
+                        unchanged += 1;
+                    }
+                }
+                None => rows.push(DiffRow {
+                    change: Change::Added,
+                    cells: row,
+                    old: Vec::new(),
+                }),
+            }
+        }
+        rows.extend(
+            old_rows
+                .into_iter()
+                .zip(matched)
+                .filter(|(_, matched)| !matched)
+                .map(|(row, _)| DiffRow {
+                    change: Change::Removed,
+                    cells: row,
+                    old: Vec::new(),
+                }),
+        );
+        Ok(Diff {
+            headers,
+            keys,
+            rows,
+            unchanged,
+            selected: 0,
+            page: 1,
+        })
+    }
+
+    /// Keys the comparison handles, for the help overlay
+    pub const KEYS: &'static [Binding] = &[
+        ("j/k", "move"),
+        ("PgUp/PgDn", "page"),
+        ("g/G", "first/last"),
+    ];
+
+    pub fn handle_key(&mut self, key: KeyEvent) {
+        let last = self.rows.len().saturating_sub(1);
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => self.selected = (self.selected + 1).min(last),
+            KeyCode::PageUp => self.selected = self.selected.saturating_sub(self.page),
+            KeyCode::PageDown => self.selected = (self.selected + self.page).min(last),
+            KeyCode::Home | KeyCode::Char('g') => self.selected = 0,
+            KeyCode::End | KeyCode::Char('G') => self.selected = last,
+            _ => {}
+        }
+    }
+
+    pub fn render(&mut self, frame: &mut Frame, area: Rect, block: Block, theme: &Theme) {
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+        let [summary_area, header_area, rows_area] = layout::Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Length(1),
+            Constraint::Min(0),
+        ])
+        .areas(inner);
+        self.page = usize::from(rows_area.height).max(1);
+
+        let count = |change: fn(&Change) -> bool| {
+            self.rows.iter().filter(|row| change(&row.change)).count()
+        };
+        let keys = self
+            .keys
+            .iter()
+            .map(|&key| self.headers[key].as_str())
+            .collect::<Vec<_>>();
+        frame.render_widget(
+            Paragraph::new(Line::from(vec![
+                Span::styled(
+                    format!("{} added", count(|change| matches!(change, Change::Added))),
+                    theme.added,
+                ),
+                Span::styled(" │ ", theme.status),
+                Span::styled(
+                    format!(
+                        "{} removed",
+                        count(|change| matches!(change, Change::Removed))
+                    ),
+                    theme.removed,
+                ),
+                Span::styled(" │ ", theme.status),
+                Span::styled(
+                    format!(
+                        "{} changed",
+                        count(|change| matches!(change, Change::Changed(_)))
+                    ),
+                    theme.changed,
+                ),
+                Span::styled(
+                    format!(
+                        " │ {} unchanged │ matched by {}",
+                        self.unchanged,
+                        keys.join(", ")
+                    ),
+                    theme.status,
+                ),
+            ])),
+            summary_area,
+        );
+
+        let widths = self
+            .headers
+            .iter()
+            .enumerate()
+            .map(|(column, header)| {
+                self.rows
+                    .iter()
+                    .map(|row| match row.change {
+                        Change::Changed(ref differs) if differs[column] => {
+                            row.old[column].chars().count() + row.cells[column].chars().count() + 3
+                        }
+                        _ => row.cells[column].chars().count(),
+                    })
+                    .chain(std::iter::once(header.chars().count() + 1))
+                    .max()
+                    .unwrap_or(0)
+                    .min(MAX_COLUMN_WIDTH)
+            })
+            .collect::<Vec<_>>();
+        let mut header = vec![Span::raw("  ")];
+        for (column, (name, &width)) in self.headers.iter().zip(&widths).enumerate() {
+            let marker = if self.keys.contains(&column) { "*" } else { "" };
+            header.push(Span::styled(
+                pad(&format!("{}{}", name, marker), width),
+                theme.header,
+            ));
+        }
+        frame.render_widget(Paragraph::new(Line::from(header)), header_area);
+
+        let items = self
+            .rows
+            .iter()
+            .map(|row| {
+                let (marker, style) = match row.change {
+                    Change::Added => ("+ ", theme.added),
+                    Change::Removed => ("- ", theme.removed),
+                    Change::Changed(_) => ("~ ", theme.changed),
+                };
+                let mut spans = vec![Span::styled(marker, style)];
+                for (column, &width) in widths.iter().enumerate() {
+                    spans.push(match &row.change {
+                        Change::Changed(differs) if differs[column] => Span::styled(
+                            pad(
+                                &format!("{} → {}", row.old[column], row.cells[column]),
+                                width,
+                            ),
+                            theme.changed,
+                        ),
+                        Change::Changed(_) => Span::raw(pad(&row.cells[column], width)),
+                        _ => Span::styled(pad(&row.cells[column], width), style),
+                    });
+                }
+                ListItem::new(Line::from(spans))
+            })
+            .collect::<Vec<_>>();
+        if items.is_empty() {
+            frame.render_widget(
+                Paragraph::new("No differences").style(theme.muted),
+                rows_area,
+            );
+            return;
+        }
+        let list = List::new(items).highlight_style(theme.selected);
+        let mut state = ListState::default().with_selected(Some(self.selected));
+        frame.render_stateful_widget(list, rows_area, &mut state);
+    }
+}
+
+/// Display text of every value of `result`, row by row
+fn cells(result: &ResultSet) -> anyhow::Result<Vec<Vec<String>>> {
+    let mut rows = Vec::new();
+    for batch in &result.batches {
+        rows.extend(crate::output_format::format_cells(batch, "NULL")?);
+    }
+    Ok(rows)
+}
+
+/// `text` padded or truncated to `width` characters, with a space to separate it from the next
+fn pad(text: &str, width: usize) -> String {
+    let count = text.chars().count();
+    if count > width {
+        let truncated = text
+            .chars()
+            .take(width.saturating_sub(1))
+            .collect::<String>();
+        format!("{}… ", truncated)
+    } else {
+        format!("{}{} ", text, " ".repeat(width - count))
+    }
+}
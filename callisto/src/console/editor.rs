@@ -0,0 +1,898 @@
+use std::ops::Range;
+
+use ratatui::{
+    crossterm::event::{KeyCode, KeyEvent, KeyModifiers},
+    layout::Rect,
+    text::{Line, Span},
+    widgets::{Block, Paragraph},
+    Frame,
+};
+
+use super::{Binding, Theme};
+use crate::{
+    highlight::{Highlighter, TokenClass},
+    Keymap,
+};
+
+/// Most edits kept to undo
+const UNDO_LIMIT: usize = 200;
+
+/// Mode of the Vim keymap
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum VimMode {
+    Normal,
+    Insert,
+    /// Selecting from an anchor to the cursor, by character or by whole lines
+    Visual {
+        linewise: bool,
+    },
+}
+
+/// A buffer state to return to on undo
+#[derive(Clone, Debug)]
+struct Snapshot {
+    lines: Vec<String>,
+    row: usize,
+    column: usize,
+}
+
+/// A multi-line text buffer for writing SQL, with a cursor the view scrolls to follow.
+///
+/// Keys are bound as in a plain text field by default, or as in Vim or Emacs.
+#[derive(Clone, Debug)]
+pub struct Editor {
+    lines: Vec<String>,
+    /// Cursor line, and position within it in characters
+    row: usize,
+    column: usize,
+    /// First line and column in view
+    row_offset: usize,
+    column_offset: usize,
+    keymap: Keymap,
+    vim_mode: VimMode,
+    /// Keys of a Vim command typed so far, like the count and operator of `2dw`
+    pending: String,
+    /// Where a Vim visual selection started, as a character offset into the buffer
+    anchor: usize,
+    /// Text last deleted or yanked, and whether it was whole lines
+    register: (String, bool),
+    undo: Vec<Snapshot>,
+    /// Whether the last key typed text, so a run of typing is undone at once
+    typing: bool,
+    /// Where the cursor was last drawn on screen
+    screen_cursor: (u16, u16),
+}
+
+impl Default for Editor {
+    fn default() -> Editor {
+        Editor::new(Keymap::default())
+    }
+}
+
+impl Editor {
+    pub fn new(keymap: Keymap) -> Editor {
+        Editor {
+            lines: vec![String::new()],
+            row: 0,
+            column: 0,
+            row_offset: 0,
+            column_offset: 0,
+            keymap,
+            vim_mode: VimMode::Normal,
+            pending: String::new(),
+            anchor: 0,
+            register: (String::new(), false),
+            undo: Vec::new(),
+            typing: false,
+            screen_cursor: (0, 0),
+        }
+    }
+
+    /// An editor following `keymap` with `text` already in its buffer, nothing to undo
+    pub fn with_text(keymap: Keymap, text: &str) -> Editor {
+        let mut editor = Editor::new(keymap);
+        editor.set_text(text);
+        editor.undo.clear();
+        editor
+    }
+
+    /// The whole buffer, lines joined by newlines
+    pub fn text(&self) -> String {
+        self.lines.join("\n")
+    }
+
+    /// Replace the buffer with `text`, leaving the cursor at its end
+    pub fn set_text(&mut self, text: &str) {
+        self.snapshot();
+        self.lines = text.split('\n').map(str::to_string).collect();
+        self.row = self.lines.len() - 1;
+        self.column = self.lines[self.row].chars().count();
+    }
+
+    /// Insert `text` at the cursor, leaving the cursor after it
+    pub fn insert(&mut self, text: &str) {
+        for c in text.chars() {
+            if c == '\n' {
+                self.newline();
+            } else {
+                let index = self.byte_index();
+                self.lines[self.row].insert(index, c);
+                self.column += 1;
+            }
+        }
+    }
+
+    /// Whether typed characters go into the buffer, rather than being taken as Vim commands
+    pub fn is_inserting(&self) -> bool {
+        self.keymap != Keymap::Vim || self.vim_mode == VimMode::Insert
+    }
+
+    /// The cursor as a byte offset into [`Editor::text`]
+    pub fn cursor(&self) -> usize {
+        self.lines[..self.row]
+            .iter()
+            .map(|line| line.len() + 1)
+            .sum::<usize>()
+            + self.byte_index()
+    }
+
+    /// Where the cursor was last drawn on screen, as a column and row
+    pub fn screen_cursor(&self) -> (u16, u16) {
+        self.screen_cursor
+    }
+
+    /// Replace the bytes of [`Editor::text`] in `span` with `text` as a single edit, leaving the
+    /// cursor after it
+    pub fn replace_span(&mut self, span: Range<usize>, text: &str) {
+        let buffer = self.text();
+        let start = buffer[..span.start].chars().count();
+        let end = start + buffer[span].chars().count();
+        self.snapshot();
+        self.replace(start..end, text);
+        self.typing = false;
+    }
+
+    /// Name of the Vim mode the editor is in, if following Vim's keys
+    pub fn mode(&self) -> Option<&'static str> {
+        match (self.keymap, self.vim_mode) {
+            (Keymap::Vim, VimMode::Normal) => Some("NORMAL"),
+            (Keymap::Vim, VimMode::Insert) => Some("INSERT"),
+            (Keymap::Vim, VimMode::Visual { linewise: false }) => Some("VISUAL"),
+            (Keymap::Vim, VimMode::Visual { linewise: true }) => Some("VISUAL LINE"),
+            _ => None,
+        }
+    }
+
+    /// Keys the editor handles under its keymap, for the help overlay
+    pub fn keys(&self) -> &'static [Binding] {
+        match self.keymap {
+            Keymap::Default => &[
+                ("arrows", "move"),
+                ("Home/End", "line start/end"),
+                ("Tab", "indent, or accept a completion"),
+                ("Enter", "new line"),
+                ("Backspace/Delete", "delete back/forward"),
+            ],
+            Keymap::Emacs => &[
+                ("Ctrl-F/B, arrows", "forward/back"),
+                ("Ctrl-N/P", "next/previous line"),
+                ("Ctrl-A/E", "line start/end"),
+                ("Alt-F/B", "word forward/back"),
+                ("Alt-</>", "buffer start/end"),
+                ("Ctrl-D/H", "delete forward/back"),
+                ("Ctrl-K", "kill to line end"),
+                ("Ctrl-U", "kill to line start"),
+                ("Ctrl-W", "kill word back"),
+                ("Alt-D", "kill word forward"),
+                ("Ctrl-Y", "yank"),
+                ("Ctrl-/", "undo"),
+            ],
+            Keymap::Vim => &[
+                ("i/a/I/A/o/O", "insert"),
+                ("Esc", "normal mode"),
+                ("v/V", "visual/visual line mode"),
+                ("hjkl", "move"),
+                ("w/b/e", "word forward/back/end"),
+                ("0/^/$", "line start/first non-blank/end"),
+                ("gg/G", "first/last line"),
+                (
+                    "d/c/y",
+                    "delete/change/yank, then a motion or doubled for lines",
+                ),
+                ("x/X", "delete character forward/back"),
+                ("D/C", "delete/change to line end"),
+                ("p/P", "put after/before"),
+                ("u", "undo"),
+                ("a count", "repeat, e.g. 3w"),
+            ],
+        }
+    }
+
+    /// Edit the buffer in response to `key`, returning whether the key was used
+    pub fn handle_key(&mut self, key: KeyEvent) -> bool {
+        let typing = matches!(key.code, KeyCode::Char(_))
+            && !key
+                .modifiers
+                .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT);
+        let used = match (self.keymap, self.vim_mode) {
+            (Keymap::Default, _) => self.handle_text_key(key),
+            (Keymap::Emacs, _) => self.handle_emacs_key(key),
+            (Keymap::Vim, VimMode::Insert) if key.code == KeyCode::Esc => {
+                self.vim_mode = VimMode::Normal;
+                self.column = self.column.saturating_sub(1);
+                true
+            }
+            (Keymap::Vim, VimMode::Insert) => self.handle_text_key(key),
+            (Keymap::Vim, _) => self.handle_vim_key(key),
+        };
+        self.typing = typing && used;
+        used
+    }
+
+    /// Draw the buffer within `block` highlighted by `highlighter`, placing the terminal cursor if
+    /// `focused`
+    pub fn render(
+        &mut self,
+        frame: &mut Frame,
+        area: Rect,
+        block: Block,
+        focused: bool,
+        theme: &Theme,
+        highlighter: &Highlighter,
+    ) {
+        let inner = block.inner(area);
+        let (height, width) = (usize::from(inner.height), usize::from(inner.width));
+        if self.row < self.row_offset {
+            self.row_offset = self.row;
+        } else if height > 0 && self.row >= self.row_offset + height {
+            self.row_offset = self.row + 1 - height;
+        }
+        if self.column < self.column_offset {
+            self.column_offset = self.column;
+        } else if width > 0 && self.column >= self.column_offset + width {
+            self.column_offset = self.column + 1 - width;
+        }
+        let selection = self.selection().unwrap_or(0..0);
+        // Classified as a whole, as comments and strings can span lines
+        let text = self.text();
+        let mut classes = Vec::with_capacity(text.len());
+        for (class, range) in highlighter.classify(&text) {
+            classes.extend(text[range].chars().map(|_| class));
+        }
+        let mut start = self.lines[..self.row_offset]
+            .iter()
+            .map(|line| line.chars().count() + 1)
+            .sum::<usize>();
+        let mut lines = Vec::new();
+        for line in self.lines.iter().skip(self.row_offset).take(height) {
+            // Run together characters styled alike, the selection drawn over their highlighting
+            let mut spans: Vec<Span> = Vec::new();
+            for (index, c) in line.chars().enumerate().skip(self.column_offset) {
+                let offset = start + index;
+                let class = classes.get(offset).copied().unwrap_or(TokenClass::Plain);
+                let mut style = theme.token(class);
+                if selection.contains(&offset) {
+                    style = style.patch(theme.selected);
+                }
+                match spans.last_mut() {
+                    Some(span) if span.style == style => span.content.to_mut().push(c),
+                    _ => spans.push(Span::styled(c.to_string(), style)),
+                }
+            }
+            lines.push(Line::from(spans));
+            start += line.chars().count() + 1;
+        }
+        frame.render_widget(Paragraph::new(lines).block(block), area);
+        self.screen_cursor = (
+            inner.x + (self.column - self.column_offset) as u16,
+            inner.y + (self.row - self.row_offset) as u16,
+        );
+        if focused {
+            frame.set_cursor(self.screen_cursor.0, self.screen_cursor.1);
+        }
+    }
+
+    /// Keys as in a plain text field: typing, deleting, and moving with the arrows
+    fn handle_text_key(&mut self, key: KeyEvent) -> bool {
+        if key
+            .modifiers
+            .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+        {
+            return false;
+        }
+        let edits = matches!(
+            key.code,
+            KeyCode::Char(_) | KeyCode::Tab | KeyCode::Enter | KeyCode::Backspace | KeyCode::Delete
+        );
+        if edits && !(self.typing && matches!(key.code, KeyCode::Char(_))) {
+            self.snapshot();
+        }
+        match key.code {
+            KeyCode::Char(c) => self.insert(&c.to_string()),
+            KeyCode::Tab => self.insert("    "),
+            KeyCode::Enter => self.newline(),
+            KeyCode::Backspace if self.column > 0 => {
+                self.column -= 1;
+                let index = self.byte_index();
+                self.lines[self.row].remove(index);
+            }
+            KeyCode::Backspace if self.row > 0 => {
+                let line = self.lines.remove(self.row);
+                self.row -= 1;
+                self.column = self.lines[self.row].chars().count();
+                self.lines[self.row].push_str(&line);
+            }
+            KeyCode::Delete if self.column < self.line_length() => {
+                let index = self.byte_index();
+                self.lines[self.row].remove(index);
+            }
+            KeyCode::Delete if self.row + 1 < self.lines.len() => {
+                let line = self.lines.remove(self.row + 1);
+                self.lines[self.row].push_str(&line);
+            }
+            KeyCode::Left if self.column > 0 => self.column -= 1,
+            KeyCode::Left if self.row > 0 => {
+                self.row -= 1;
+                self.column = self.line_length();
+            }
+            KeyCode::Right if self.column < self.line_length() => self.column += 1,
+            KeyCode::Right if self.row + 1 < self.lines.len() => {
+                self.row += 1;
+                self.column = 0;
+            }
+            KeyCode::Up if self.row > 0 => {
+                self.row -= 1;
+                self.column = self.column.min(self.line_length());
+            }
+            KeyCode::Down if self.row + 1 < self.lines.len() => {
+                self.row += 1;
+                self.column = self.column.min(self.line_length());
+            }
+            KeyCode::Home => self.column = 0,
+            KeyCode::End => self.column = self.line_length(),
+            KeyCode::Backspace
+            | KeyCode::Delete
+            | KeyCode::Left
+            | KeyCode::Right
+            | KeyCode::Up
+            | KeyCode::Down => {}
+            _ => return false,
+        }
+        true
+    }
+
+    /// Emacs's movement and kill keys, and otherwise those of a plain text field
+    fn handle_emacs_key(&mut self, key: KeyEvent) -> bool {
+        let offset = self.offset();
+        if key.modifiers.contains(KeyModifiers::CONTROL) {
+            match key.code {
+                KeyCode::Char('a') => self.column = 0,
+                KeyCode::Char('e') => self.column = self.line_length(),
+                KeyCode::Char('f') => return self.handle_text_key(KeyEvent::from(KeyCode::Right)),
+                KeyCode::Char('b') => return self.handle_text_key(KeyEvent::from(KeyCode::Left)),
+                KeyCode::Char('n') => return self.handle_text_key(KeyEvent::from(KeyCode::Down)),
+                KeyCode::Char('p') => return self.handle_text_key(KeyEvent::from(KeyCode::Up)),
+                KeyCode::Char('d') => return self.handle_text_key(KeyEvent::from(KeyCode::Delete)),
+                KeyCode::Char('h') => {
+                    return self.handle_text_key(KeyEvent::from(KeyCode::Backspace))
+                }
+                // Kill to the end of the line, or the line break if already there
+                KeyCode::Char('k') => {
+                    let end = if self.column == self.line_length() {
+                        offset + usize::from(self.row + 1 < self.lines.len())
+                    } else {
+                        offset + self.line_length() - self.column
+                    };
+                    self.kill(offset..end);
+                }
+                KeyCode::Char('u') => self.kill(offset - self.column..offset),
+                KeyCode::Char('w') => self.kill(word_backward(&self.chars(), offset)..offset),
+                KeyCode::Char('y') => {
+                    self.snapshot();
+                    let text = self.register.0.clone();
+                    self.insert(&text);
+                }
+                KeyCode::Char('/') | KeyCode::Char('_') => self.undo(),
+                _ => return false,
+            }
+            return true;
+        }
+        if key.modifiers.contains(KeyModifiers::ALT) {
+            match key.code {
+                KeyCode::Char('f') => self.set_offset(word_forward_end(&self.chars(), offset)),
+                KeyCode::Char('b') => self.set_offset(word_backward(&self.chars(), offset)),
+                KeyCode::Char('d') => self.kill(offset..word_forward_end(&self.chars(), offset)),
+                KeyCode::Char('<') => self.set_offset(0),
+                KeyCode::Char('>') => self.set_offset(self.chars().len()),
+                _ => return false,
+            }
+            return true;
+        }
+        self.handle_text_key(key)
+    }
+
+    /// Vim's normal and visual mode commands, with counts, operators, and common motions
+    fn handle_vim_key(&mut self, key: KeyEvent) -> bool {
+        let KeyCode::Char(c) = key.code else {
+            return match key.code {
+                KeyCode::Esc if !self.pending.is_empty() => {
+                    self.pending.clear();
+                    true
+                }
+                KeyCode::Esc if matches!(self.vim_mode, VimMode::Visual { .. }) => {
+                    self.vim_mode = VimMode::Normal;
+                    true
+                }
+                KeyCode::Left | KeyCode::Right | KeyCode::Up | KeyCode::Down => {
+                    self.handle_text_key(key);
+                    self.clamp_to_line();
+                    true
+                }
+                _ => false,
+            };
+        };
+        if key.modifiers.contains(KeyModifiers::CONTROL) {
+            return false;
+        }
+        if c.is_ascii_digit() && (c != '0' || self.pending.chars().any(|c| c.is_ascii_digit())) {
+            self.pending.push(c);
+            return true;
+        }
+        let count = self
+            .pending
+            .chars()
+            .filter(char::is_ascii_digit)
+            .collect::<String>()
+            .parse::<usize>()
+            .unwrap_or(1);
+        let operator = self.pending.chars().find(|c| !c.is_ascii_digit());
+        self.pending.clear();
+
+        let offset = self.offset();
+        let chars = self.chars();
+        let visual = match self.vim_mode {
+            VimMode::Visual { linewise } => Some(linewise),
+            _ => None,
+        };
+
+        match (operator, c) {
+            // Operators wait for a motion, or apply to whole lines when doubled
+            (None, 'd' | 'c' | 'y') if visual.is_none() => {
+                self.pending = format!("{}{}", count, c);
+            }
+            (Some(operator), c) if operator == c && operator != 'g' => {
+                let last = (self.row + count - 1).min(self.lines.len() - 1);
+                self.operate_lines(operator, self.row..last + 1);
+            }
+            (None, 'g') => self.pending = format!("{}g", count),
+            (Some('g'), 'g') | (None, 'G') => {
+                let row = if operator.is_some() || count > 1 {
+                    count.min(self.lines.len()) - 1
+                } else if c == 'G' {
+                    self.lines.len() - 1
+                } else {
+                    0
+                };
+                self.row = row;
+                self.column = 0;
+            }
+            (Some(operator), 'j' | 'k') => {
+                let rows = if c == 'j' {
+                    self.row..(self.row + count).min(self.lines.len() - 1) + 1
+                } else {
+                    self.row.saturating_sub(count)..self.row + 1
+                };
+                self.operate_lines(operator, rows);
+            }
+            (Some('g'), _) => {}
+            (Some(operator), motion) => {
+                let on_word = chars.get(offset).is_some_and(|c| !c.is_whitespace());
+                let (target, inclusive) = if operator == 'c' && motion == 'w' && on_word {
+                    // As in Vim, `cw` on a word changes to its end, leaving the space after it
+                    let mut end = offset;
+                    while end + 1 < chars.len() && class(chars[end + 1]) == class(chars[offset]) {
+                        end += 1;
+                    }
+                    ((1..count).fold(end, |at, _| word_end(&chars, at)), true)
+                } else {
+                    match self.motion(&chars, offset, motion, count) {
+                        Some(motion) => motion,
+                        None => return true,
+                    }
+                };
+                let mut end = (target.max(offset) + usize::from(inclusive)).min(chars.len());
+                // As in Vim, a word motion from within a line stops at its end
+                let line_end = offset - self.column + self.line_length();
+                if motion == 'w' && offset < line_end {
+                    end = end.min(line_end);
+                }
+                self.operate(operator, target.min(offset)..end);
+            }
+            (None, 'd' | 'x' | 'y' | 'c') if visual.is_some() => {
+                let operator = if c == 'x' { 'd' } else { c };
+                match visual {
+                    Some(true) => {
+                        let (start, _) = self.position(self.anchor);
+                        self.operate_lines(operator, start.min(self.row)..start.max(self.row) + 1);
+                    }
+                    _ => {
+                        let selection = self.selection().unwrap_or(offset..offset);
+                        self.operate(operator, selection);
+                    }
+                }
+                if self.vim_mode != VimMode::Insert {
+                    self.vim_mode = VimMode::Normal;
+                }
+            }
+            (None, 'v') => {
+                self.vim_mode = match visual {
+                    Some(false) => VimMode::Normal,
+                    _ => VimMode::Visual { linewise: false },
+                };
+                self.anchor = offset;
+            }
+            (None, 'V') => {
+                self.vim_mode = match visual {
+                    Some(true) => VimMode::Normal,
+                    _ => VimMode::Visual { linewise: true },
+                };
+                self.anchor = offset;
+            }
+            (None, 'i') => self.start_insert(),
+            (None, 'a') => {
+                self.column = (self.column + 1).min(self.line_length());
+                self.start_insert();
+            }
+            (None, 'I') => {
+                self.column = first_non_blank(&self.lines[self.row]);
+                self.start_insert();
+            }
+            (None, 'A') => {
+                self.column = self.line_length();
+                self.start_insert();
+            }
+            (None, 'o' | 'O') => {
+                self.snapshot();
+                let row = if c == 'o' { self.row + 1 } else { self.row };
+                self.lines.insert(row, String::new());
+                self.row = row;
+                self.column = 0;
+                self.vim_mode = VimMode::Insert;
+            }
+            (None, 'x') => {
+                let end = (offset + count).min(offset + self.line_length() - self.column);
+                self.operate('d', offset..end);
+            }
+            (None, 'X') => self.operate('d', offset.saturating_sub(count.min(self.column))..offset),
+            (None, 'D') => self.operate('d', offset..offset + self.line_length() - self.column),
+            (None, 'C') => self.operate('c', offset..offset + self.line_length() - self.column),
+            (None, 'p' | 'P') => self.put(c == 'p', count),
+            (None, 'u') => {
+                for _ in 0..count {
+                    self.undo();
+                }
+            }
+            (None, motion) => match self.motion(&chars, offset, motion, count) {
+                Some((target, _)) => self.set_offset(target),
+                None => return false,
+            },
+        }
+        if self.vim_mode != VimMode::Insert {
+            self.clamp_to_line();
+        }
+        true
+    }
+
+    /// Where `motion` repeated `count` times moves the cursor from `offset`, and whether an
+    /// operator applied with it takes in the character there
+    fn motion(
+        &self,
+        chars: &[char],
+        offset: usize,
+        motion: char,
+        count: usize,
+    ) -> Option<(usize, bool)> {
+        let line_start = offset - self.column;
+        let line_end = line_start + self.line_length();
+        let repeat = |step: &dyn Fn(usize) -> usize| (0..count).fold(offset, |at, _| step(at));
+        Some(match motion {
+            'h' => (offset - count.min(self.column), false),
+            'l' => ((offset + count).min(line_end), false),
+            'j' | 'k' => {
+                let row = if motion == 'j' {
+                    (self.row + count).min(self.lines.len() - 1)
+                } else {
+                    self.row.saturating_sub(count)
+                };
+                let column = self.column.min(self.lines[row].chars().count());
+                (self.offset_of(row, column), false)
+            }
+            'w' => (repeat(&|at| word_forward(chars, at)), false),
+            'b' => (repeat(&|at| word_backward(chars, at)), false),
+            'e' => (repeat(&|at| word_end(chars, at)), true),
+            '0' => (line_start, false),
+            '^' => (line_start + first_non_blank(&self.lines[self.row]), false),
+            '$' => (
+                line_end.saturating_sub(1).max(line_start),
+                line_end > line_start,
+            ),
+            _ => return None,
+        })
+    }
+
+    /// Delete, change, or yank the characters in `range`
+    fn operate(&mut self, operator: char, range: Range<usize>) {
+        if range.is_empty() && operator != 'c' {
+            return;
+        }
+        let text = self.chars()[range.clone()].iter().collect::<String>();
+        self.register = (text, false);
+        match operator {
+            'y' => self.set_offset(range.start),
+            _ => {
+                self.snapshot();
+                self.replace(range, "");
+                if operator == 'c' {
+                    self.vim_mode = VimMode::Insert;
+                }
+            }
+        }
+    }
+
+    /// Delete, change, or yank the whole lines `rows`
+    fn operate_lines(&mut self, operator: char, rows: Range<usize>) {
+        let mut text = self.lines[rows.clone()].join("\n");
+        text.push('\n');
+        self.register = (text, true);
+        match operator {
+            'y' => self.row = rows.start,
+            'c' => {
+                self.snapshot();
+                self.lines.splice(rows.clone(), [String::new()]);
+                self.row = rows.start;
+                self.column = 0;
+                self.vim_mode = VimMode::Insert;
+            }
+            _ => {
+                self.snapshot();
+                self.lines.drain(rows.clone());
+                if self.lines.is_empty() {
+                    self.lines.push(String::new());
+                }
+                self.row = rows.start.min(self.lines.len() - 1);
+                self.column = first_non_blank(&self.lines[self.row]);
+            }
+        }
+    }
+
+    /// Put the register's text after the cursor, or before it, `count` times
+    fn put(&mut self, after: bool, count: usize) {
+        let (text, linewise) = self.register.clone();
+        if text.is_empty() {
+            return;
+        }
+        self.snapshot();
+        if linewise {
+            let row = if after { self.row + 1 } else { self.row };
+            let lines = vec![text.trim_end_matches('\n'); count].join("\n");
+            let lines = lines.split('\n').map(str::to_string).collect::<Vec<_>>();
+            self.lines.splice(row..row, lines);
+            self.row = row;
+            self.column = first_non_blank(&self.lines[row]);
+        } else {
+            if after && self.line_length() > 0 {
+                self.column += 1;
+            }
+            self.insert(&text.repeat(count));
+            self.column = self.column.saturating_sub(1);
+        }
+    }
+
+    fn start_insert(&mut self) {
+        self.snapshot();
+        self.vim_mode = VimMode::Insert;
+    }
+
+    /// Remove `range` into the register, as Emacs's kill commands do
+    fn kill(&mut self, range: Range<usize>) {
+        if range.is_empty() {
+            return;
+        }
+        self.snapshot();
+        self.register = (self.replace(range, ""), false);
+    }
+
+    /// Remember the buffer as it is, to come back to on undo
+    fn snapshot(&mut self) {
+        if self.undo.len() == UNDO_LIMIT {
+            self.undo.remove(0);
+        }
+        self.undo.push(Snapshot {
+            lines: self.lines.clone(),
+            row: self.row,
+            column: self.column,
+        });
+    }
+
+    fn undo(&mut self) {
+        if let Some(snapshot) = self.undo.pop() {
+            self.lines = snapshot.lines;
+            self.row = snapshot.row;
+            self.column = snapshot.column;
+        }
+    }
+
+    /// Keep the cursor on a character, as Vim does outside insert mode
+    fn clamp_to_line(&mut self) {
+        self.column = self.column.min(self.line_length().saturating_sub(1));
+    }
+
+    /// The characters selected in visual mode, as offsets into the buffer
+    fn selection(&self) -> Option<Range<usize>> {
+        let VimMode::Visual { linewise } = self.vim_mode else {
+            return None;
+        };
+        let offset = self.offset();
+        if linewise {
+            let (anchor_row, _) = self.position(self.anchor);
+            let (first, last) = (anchor_row.min(self.row), anchor_row.max(self.row));
+            let end = self.offset_of(last, 0) + self.lines[last].chars().count();
+            Some(self.offset_of(first, 0)..end)
+        } else {
+            let end = (self.anchor.max(offset) + 1).min(self.chars().len());
+            Some(self.anchor.min(offset)..end)
+        }
+    }
+
+    /// Replace the characters in `range` with `text`, leaving the cursor after it, and return
+    /// what was replaced
+    fn replace(&mut self, range: Range<usize>, text: &str) -> String {
+        let mut chars = self.chars();
+        let removed = chars
+            .splice(range.clone(), text.chars())
+            .collect::<String>();
+        self.lines = chars
+            .into_iter()
+            .collect::<String>()
+            .split('\n')
+            .map(str::to_string)
+            .collect();
+        self.set_offset(range.start + text.chars().count());
+        removed
+    }
+
+    fn chars(&self) -> Vec<char> {
+        self.text().chars().collect()
+    }
+
+    /// The cursor as a character offset into the buffer
+    fn offset(&self) -> usize {
+        self.offset_of(self.row, self.column)
+    }
+
+    fn offset_of(&self, row: usize, column: usize) -> usize {
+        self.lines[..row]
+            .iter()
+            .map(|line| line.chars().count() + 1)
+            .sum::<usize>()
+            + column
+    }
+
+    fn set_offset(&mut self, offset: usize) {
+        (self.row, self.column) = self.position(offset);
+    }
+
+    /// The line and column of a character offset into the buffer
+    fn position(&self, mut offset: usize) -> (usize, usize) {
+        for (row, line) in self.lines.iter().enumerate() {
+            let length = line.chars().count();
+            if offset <= length {
+                return (row, offset);
+            }
+            offset -= length + 1;
+        }
+        let row = self.lines.len() - 1;
+        (row, self.lines[row].chars().count())
+    }
+
+    fn newline(&mut self) {
+        let index = self.byte_index();
+        let rest = self.lines[self.row].split_off(index);
+        self.row += 1;
+        self.column = 0;
+        self.lines.insert(self.row, rest);
+    }
+
+    fn line_length(&self) -> usize {
+        self.lines[self.row].chars().count()
+    }
+
+    /// Byte offset of the cursor within its line
+    fn byte_index(&self) -> usize {
+        self.lines[self.row]
+            .char_indices()
+            .nth(self.column)
+            .map_or(self.lines[self.row].len(), |(index, _)| index)
+    }
+}
+
+/// Kinds of character words are made of, a word being a run of one kind
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum CharClass {
+    Space,
+    Word,
+    Punctuation,
+}
+
+fn class(c: char) -> CharClass {
+    if c.is_whitespace() {
+        CharClass::Space
+    } else if c.is_alphanumeric() || c == '_' {
+        CharClass::Word
+    } else {
+        CharClass::Punctuation
+    }
+}
+
+/// Start of the next word after `offset`, as Vim's `w`
+fn word_forward(chars: &[char], mut offset: usize) -> usize {
+    if let Some(&c) = chars.get(offset) {
+        let start = class(c);
+        while offset < chars.len() && class(chars[offset]) == start && start != CharClass::Space {
+            offset += 1;
+        }
+    }
+    while offset < chars.len() && class(chars[offset]) == CharClass::Space {
+        offset += 1;
+    }
+    offset
+}
+
+/// Start of the word before `offset`, as Vim's `b` and Emacs's `M-b`
+fn word_backward(chars: &[char], mut offset: usize) -> usize {
+    while offset > 0 && class(chars[offset - 1]) == CharClass::Space {
+        offset -= 1;
+    }
+    if offset > 0 {
+        let start = class(chars[offset - 1]);
+        while offset > 0 && class(chars[offset - 1]) == start {
+            offset -= 1;
+        }
+    }
+    offset
+}
+
+/// Last character of the word ending after `offset`, as Vim's `e`
+fn word_end(chars: &[char], offset: usize) -> usize {
+    let mut offset = offset + 1;
+    while offset < chars.len() && class(chars[offset]) == CharClass::Space {
+        offset += 1;
+    }
+    if offset >= chars.len() {
+        return chars.len().saturating_sub(1);
+    }
+    let start = class(chars[offset]);
+    while offset + 1 < chars.len() && class(chars[offset + 1]) == start {
+        offset += 1;
+    }
+    offset
+}
+
+/// Just past the end of the word at or after `offset`, as Emacs's `M-f`
+fn word_forward_end(chars: &[char], mut offset: usize) -> usize {
+    while offset < chars.len() && class(chars[offset]) != CharClass::Word {
+        offset += 1;
+    }
+    while offset < chars.len() && class(chars[offset]) == CharClass::Word {
+        offset += 1;
+    }
+    offset
+}
+
+/// Column of the first character of `line` that isn't whitespace
+fn first_non_blank(line: &str) -> usize {
+    line.chars()
+        .position(|c| !c.is_whitespace())
+        .unwrap_or(line.chars().count().saturating_sub(1))
+        .min(line.chars().count())
+}
@@ -0,0 +1,187 @@
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+};
+
+use ratatui::{
+    crossterm::event::{KeyCode, KeyEvent},
+    layout::Rect,
+    text::Line,
+    widgets::{Block, List, ListItem, ListState},
+    Frame,
+};
+
+use super::{catalog::quote, Binding, Theme};
+use crate::dataset;
+
+/// What the user asked for by picking a file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FilesAction {
+    /// Insert the file's path into the editor, quoted as a table
+    Insert(String),
+    /// Read the file and register it with the engine as a table
+    Register(PathBuf),
+}
+
+/// A directory or data file shown in the tree, nested `depth` directories below the root
+#[derive(Clone, Debug)]
+struct Entry {
+    path: PathBuf,
+    depth: usize,
+    is_dir: bool,
+}
+
+/// Tree of the directories under the working directory and the data files in them
+pub struct Files {
+    root: PathBuf,
+    /// Directories whose contents are shown
+    expanded: BTreeSet<PathBuf>,
+    entries: Vec<Entry>,
+    /// Index of the selected entry
+    selected: usize,
+}
+
+impl Files {
+    /// Browse the files under `root`
+    pub fn new(root: PathBuf) -> Files {
+        let mut files = Files {
+            root,
+            expanded: BTreeSet::new(),
+            entries: Vec::new(),
+            selected: 0,
+        };
+        files.refresh();
+        files
+    }
+
+    /// List the tree again, picking up files created since
+    pub fn refresh(&mut self) {
+        self.entries.clear();
+        self.list(&self.root.clone(), 0);
+        self.selected = self.selected.min(self.entries.len().saturating_sub(1));
+    }
+
+    /// The file selected, if a file rather than a directory is
+    pub fn selected_file(&self) -> Option<&Path> {
+        self.entries
+            .get(self.selected)
+            .filter(|entry| !entry.is_dir)
+            .map(|entry| entry.path.as_path())
+    }
+
+    /// Keys the file browser handles, for the help overlay
+    pub const KEYS: &'static [Binding] = &[
+        ("j/k", "move"),
+        ("l/h, Space", "expand/collapse"),
+        ("Enter", "insert path"),
+        ("r", "register as a table"),
+        ("R", "refresh"),
+    ];
+
+    /// Move through or act on the tree in response to `key`
+    pub fn handle_key(&mut self, key: KeyEvent) -> Option<FilesAction> {
+        let entry = self.entries.get(self.selected)?.clone();
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.selected = (self.selected + 1).min(self.entries.len() - 1)
+            }
+            KeyCode::Right | KeyCode::Char('l') | KeyCode::Char(' ') if entry.is_dir => {
+                self.expanded.insert(entry.path);
+                self.refresh();
+            }
+            KeyCode::Left | KeyCode::Char('h') => {
+                // Collapse the selected directory, or else the one the selection is in
+                let directory = if self.expanded.contains(&entry.path) {
+                    entry.path
+                } else {
+                    match entry.path.parent() {
+                        Some(parent) if parent != self.root => parent.to_path_buf(),
+                        _ => return None,
+                    }
+                };
+                self.expanded.remove(&directory);
+                self.refresh();
+                if let Some(index) = self.entries.iter().position(|e| e.path == directory) {
+                    self.selected = index;
+                }
+            }
+            KeyCode::Enter if !entry.is_dir => {
+                return Some(FilesAction::Insert(quote(&self.display(&entry.path))))
+            }
+            KeyCode::Char('r') if !entry.is_dir => return Some(FilesAction::Register(entry.path)),
+            KeyCode::Char('R') => self.refresh(),
+            _ => {}
+        }
+        None
+    }
+
+    pub fn render(&self, frame: &mut Frame, area: Rect, block: Block, theme: &Theme) {
+        let items = self
+            .entries
+            .iter()
+            .map(|entry| {
+                let name = entry
+                    .path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                let marker = match (entry.is_dir, self.expanded.contains(&entry.path)) {
+                    (true, true) => "▾ ",
+                    (true, false) => "▸ ",
+                    (false, _) => "  ",
+                };
+                ListItem::new(Line::from(format!(
+                    "{}{}{}",
+                    "  ".repeat(entry.depth),
+                    marker,
+                    name
+                )))
+            })
+            .collect::<Vec<_>>();
+        let empty = items.is_empty();
+        let list = List::new(items)
+            .block(block)
+            .highlight_style(theme.selected);
+        let mut state = ListState::default().with_selected((!empty).then_some(self.selected));
+        frame.render_stateful_widget(list, area, &mut state);
+    }
+
+    /// Add the contents of `directory` to the tree, each followed by its contents if expanded
+    fn list(&mut self, directory: &Path, depth: usize) {
+        let Ok(entries) = std::fs::read_dir(directory) else {
+            return;
+        };
+        let mut children = entries
+            .filter_map(Result::ok)
+            .filter(|entry| !entry.file_name().to_string_lossy().starts_with('.'))
+            .filter_map(|entry| {
+                let is_dir = entry.file_type().ok()?.is_dir();
+                let path = entry.path();
+                (is_dir || dataset::is_dataset(&path)).then_some(Entry {
+                    path,
+                    depth,
+                    is_dir,
+                })
+            })
+            .collect::<Vec<_>>();
+        // Directories first, then files, each by name
+        children.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.path.cmp(&b.path)));
+        for child in children {
+            let expanded = child.is_dir && self.expanded.contains(&child.path);
+            let path = child.path.clone();
+            self.entries.push(child);
+            if expanded {
+                self.list(&path, depth + 1);
+            }
+        }
+    }
+
+    /// `path` relative to the root, as it would be written in a query run from there
+    fn display(&self, path: &Path) -> String {
+        path.strip_prefix(&self.root)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .into_owned()
+    }
+}
@@ -0,0 +1,577 @@
+use std::{ops::RangeInclusive, sync::Arc};
+
+use ratatui::{
+    crossterm::event::{KeyCode, KeyEvent},
+    layout::{self, Constraint, Rect},
+    style::{Modifier, Style},
+    text::Line,
+    widgets::{Block, Cell, Paragraph, Row, Table},
+    Frame,
+};
+
+use super::{Binding, Theme};
+use crate::ResultSet;
+
+/// Widest a column is sized to, with longer values truncated
+const MAX_COLUMN_WIDTH: usize = 40;
+/// Pages of rows kept loaded beyond the cursor while a result streams in
+const FETCH_AHEAD_PAGES: usize = 2;
+const TRUNCATED: char = '…';
+
+/// A result shown as a scrollable table, with the header kept in view and a cell cursor that the
+/// viewport follows.
+///
+/// Rows can be sorted by a column, narrowed by a quick filter, and searched, all working on the
+/// result already at hand rather than querying again. A result can also be shown while it's
+/// still streaming in, with batches [appended](Grid::append) as the cursor nears the end.
+pub struct Grid {
+    result: ResultSet,
+    /// Whether every batch of the result has been appended
+    complete: bool,
+    /// Each column's values across all batches, for sorting
+    columns: Vec<arrow::array::ArrayRef>,
+    headers: Vec<String>,
+    /// Display text of each value, row by row
+    cells: Vec<Vec<String>>,
+    widths: Vec<usize>,
+    /// Indices of the rows shown, in the order shown
+    rows: Vec<usize>,
+    /// Column rows are sorted by, and whether descending
+    sort: Option<(usize, bool)>,
+    /// Text that shown rows must contain in some cell, ignoring case
+    filter: String,
+    /// Whether keys are being typed into the filter
+    editing_filter: bool,
+    /// Text searched for with `/`, ignoring case
+    search: String,
+    /// Whether keys are being typed into the search
+    editing_search: bool,
+    /// Cells containing the search text, as positions in `rows` and columns, in the order shown
+    matches: Vec<(usize, usize)>,
+    /// Cursor position, as a position in `rows` and a column
+    row: usize,
+    column: usize,
+    /// Corner of the range selected, opposite the cursor, if one is being selected
+    anchor: Option<(usize, usize)>,
+    /// First row and column in view
+    row_offset: usize,
+    column_offset: usize,
+    /// Rows that fit in view as of the last render, for paging
+    page_rows: usize,
+}
+
+impl Grid {
+    pub fn new(result: &ResultSet) -> anyhow::Result<Grid> {
+        let headers = result
+            .schema
+            .fields()
+            .iter()
+            .map(|field| field.name().clone())
+            .collect::<Vec<_>>();
+        let columns = result
+            .schema
+            .fields()
+            .iter()
+            .map(|field| arrow::array::new_empty_array(field.data_type()))
+            .collect();
+        // Headers leave room for the marker shown when sorting by their column
+        let widths = headers
+            .iter()
+            .map(|header| (header.chars().count() + 2).min(MAX_COLUMN_WIDTH))
+            .collect();
+        let mut grid = Grid {
+            result: ResultSet {
+                schema: result.schema.clone(),
+                batches: Vec::new(),
+            },
+            complete: true,
+            columns,
+            headers,
+            cells: Vec::new(),
+            widths,
+            rows: Vec::new(),
+            sort: None,
+            filter: String::new(),
+            editing_filter: false,
+            search: String::new(),
+            editing_search: false,
+            matches: Vec::new(),
+            row: 0,
+            column: 0,
+            anchor: None,
+            row_offset: 0,
+            column_offset: 0,
+            page_rows: 1,
+        };
+        for batch in &result.batches {
+            grid.append(batch.clone())?;
+        }
+        Ok(grid)
+    }
+
+    /// Add `batch` to the end of the result, as more of it streams in
+    pub fn append(&mut self, batch: arrow::record_batch::RecordBatch) -> anyhow::Result<()> {
+        let cells = crate::output_format::format_cells(&batch, "NULL")?;
+        for row in &cells {
+            for (width, cell) in self.widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count().min(MAX_COLUMN_WIDTH));
+            }
+        }
+        self.columns = self
+            .columns
+            .iter()
+            .zip(batch.columns())
+            .map(|(column, more)| arrow::compute::concat(&[column.as_ref(), more.as_ref()]))
+            .collect::<Result<_, _>>()?;
+        self.cells.extend(cells);
+        self.result.batches.push(batch);
+        self.arrange();
+        Ok(())
+    }
+
+    /// Mark whether every batch of the result has been appended
+    pub fn set_complete(&mut self, complete: bool) {
+        self.complete = complete;
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.complete
+    }
+
+    /// Whether more of a streaming result should be appended: once the cursor nears the last row
+    /// loaded, or when sorting, which needs every row
+    pub fn wants_more(&self) -> bool {
+        !self.complete
+            && (self.sort.is_some()
+                || self.row + self.page_rows * FETCH_AHEAD_PAGES >= self.rows.len())
+    }
+
+    /// The result shown, in its original order
+    pub fn result(&self) -> &ResultSet {
+        &self.result
+    }
+
+    /// The rows shown, in the order shown, after any sorting and filtering
+    pub fn displayed(&self) -> anyhow::Result<ResultSet> {
+        let indices =
+            arrow::array::UInt32Array::from_iter_values(self.rows.iter().map(|&row| row as u32));
+        let columns = self
+            .columns
+            .iter()
+            .map(|column| arrow::compute::take(column.as_ref(), &indices, None))
+            .collect::<Result<Vec<_>, _>>()?;
+        let batch = arrow::record_batch::RecordBatch::try_new(self.result.schema.clone(), columns)?;
+        Ok(ResultSet {
+            schema: self.result.schema.clone(),
+            batches: vec![batch],
+        })
+    }
+
+    /// The range selected, or else the cell under the cursor, as a result of its own; the
+    /// selected rows with every column if `whole_rows`
+    pub fn selection(&self, whole_rows: bool) -> anyhow::Result<ResultSet> {
+        anyhow::ensure!(!self.headers.is_empty(), "No columns to select");
+        let (rows, columns) = self.selected();
+        let columns = if whole_rows {
+            0..=self.headers.len() - 1
+        } else {
+            columns
+        };
+        let indices = arrow::array::UInt32Array::from_iter_values(
+            self.rows
+                .get(rows)
+                .unwrap_or_default()
+                .iter()
+                .map(|&row| row as u32),
+        );
+        let schema = Arc::new(
+            self.result
+                .schema
+                .project(&columns.clone().collect::<Vec<_>>())?,
+        );
+        let columns = self.columns[columns]
+            .iter()
+            .map(|column| arrow::compute::take(column.as_ref(), &indices, None))
+            .collect::<Result<Vec<_>, _>>()?;
+        let batch = arrow::record_batch::RecordBatch::try_new(schema.clone(), columns)?;
+        Ok(ResultSet {
+            schema,
+            batches: vec![batch],
+        })
+    }
+
+    /// Index of the column under the cursor
+    pub fn column(&self) -> usize {
+        self.column
+    }
+
+    /// The row under the cursor as its columns' fields and values, with its position among the
+    /// rows shown and how many are shown
+    pub fn record(&self) -> Option<(Vec<(&arrow::datatypes::Field, &str)>, usize, usize)> {
+        let cells = &self.cells[*self.rows.get(self.row)?];
+        let fields = self
+            .result
+            .schema
+            .fields()
+            .iter()
+            .map(|field| field.as_ref())
+            .zip(cells.iter().map(String::as_str))
+            .collect();
+        Some((fields, self.row, self.rows.len()))
+    }
+
+    /// Columns of the range selected, or else the column under the cursor
+    pub fn selected_columns(&self) -> RangeInclusive<usize> {
+        self.selected().1
+    }
+
+    /// Whether keys are taken as text, e.g. while typing a filter, rather than commands
+    pub fn captures_input(&self) -> bool {
+        self.editing_filter || self.editing_search
+    }
+
+    /// How the search is going, e.g. which of its matches the cursor is on
+    pub fn search_status(&self) -> Option<String> {
+        if self.search.is_empty() {
+            return None;
+        }
+        let current = self
+            .matches
+            .iter()
+            .position(|&cell| cell == (self.row, self.column));
+        Some(match current {
+            Some(index) => format!(
+                "match {} of {} for /{}",
+                index + 1,
+                self.matches.len(),
+                self.search
+            ),
+            None => format!("{} matches for /{}", self.matches.len(), self.search),
+        })
+    }
+
+    /// Keys the grid handles, for the help overlay
+    pub const KEYS: &'static [Binding] = &[
+        ("hjkl, arrows", "move"),
+        ("PgUp/PgDn", "page"),
+        ("g/G", "first/last row"),
+        ("0/$", "first/last column"),
+        ("s", "sort by column, again to reverse"),
+        ("S", "clear sort and selection"),
+        ("f", "filter"),
+        ("/", "search"),
+        ("n/N", "next/previous match"),
+        ("v", "select"),
+        ("Esc", "clear selection"),
+    ];
+
+    /// Move the cursor, sort, or filter in response to `key`, returning whether the key was used
+    pub fn handle_key(&mut self, key: KeyEvent) -> bool {
+        if self.editing_filter {
+            match key.code {
+                KeyCode::Char(c) => self.filter.push(c),
+                KeyCode::Backspace => {
+                    self.filter.pop();
+                }
+                KeyCode::Enter => self.editing_filter = false,
+                KeyCode::Esc => {
+                    self.filter.clear();
+                    self.editing_filter = false;
+                }
+                _ => return false,
+            }
+            // Rows move about when filtered or sorted, so what was selected no longer forms a range
+            self.anchor = None;
+            self.arrange();
+            return true;
+        }
+        if self.editing_search {
+            match key.code {
+                KeyCode::Char(c) => self.search.push(c),
+                KeyCode::Backspace => {
+                    self.search.pop();
+                }
+                KeyCode::Enter => {
+                    self.editing_search = false;
+                    self.jump(true, true);
+                }
+                KeyCode::Esc => {
+                    self.search.clear();
+                    self.editing_search = false;
+                }
+                _ => return false,
+            }
+            self.find();
+            return true;
+        }
+        let last_row = self.rows.len().saturating_sub(1);
+        let last_column = self.headers.len().saturating_sub(1);
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => self.row = self.row.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => self.row = (self.row + 1).min(last_row),
+            KeyCode::PageUp => self.row = self.row.saturating_sub(self.page_rows),
+            KeyCode::PageDown => self.row = (self.row + self.page_rows).min(last_row),
+            KeyCode::Home | KeyCode::Char('g') => self.row = 0,
+            KeyCode::End | KeyCode::Char('G') => self.row = last_row,
+            KeyCode::Left | KeyCode::Char('h') => self.column = self.column.saturating_sub(1),
+            KeyCode::Right | KeyCode::Char('l') => self.column = (self.column + 1).min(last_column),
+            KeyCode::Char('0') => self.column = 0,
+            KeyCode::Char('$') => self.column = last_column,
+            KeyCode::Char('s') if !self.headers.is_empty() => {
+                self.sort = match self.sort {
+                    Some((column, descending)) if column == self.column => {
+                        Some((column, !descending))
+                    }
+                    _ => Some((self.column, false)),
+                };
+                self.anchor = None;
+                self.arrange();
+            }
+            KeyCode::Char('S') => {
+                self.sort = None;
+                self.anchor = None;
+                self.arrange();
+            }
+            KeyCode::Char('f') => self.editing_filter = true,
+            KeyCode::Char('/') => {
+                self.search.clear();
+                self.editing_search = true;
+                self.find();
+            }
+            KeyCode::Char('n') => self.jump(true, false),
+            KeyCode::Char('N') => self.jump(false, false),
+            KeyCode::Char('v') => {
+                self.anchor = match self.anchor {
+                    Some(_) => None,
+                    None => Some((self.row, self.column)),
+                }
+            }
+            KeyCode::Esc if self.anchor.is_some() => self.anchor = None,
+            _ => return false,
+        }
+        true
+    }
+
+    pub fn render(&mut self, frame: &mut Frame, area: Rect, block: Block, theme: &Theme) {
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+        let [table_area, footer_area] =
+            layout::Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(inner);
+
+        // The header takes a row of its own
+        self.page_rows = usize::from(table_area.height).saturating_sub(1).max(1);
+        if self.row < self.row_offset {
+            self.row_offset = self.row;
+        } else if self.row >= self.row_offset + self.page_rows {
+            self.row_offset = self.row + 1 - self.page_rows;
+        }
+        let columns = self.visible_columns(usize::from(table_area.width));
+        // A column wider than the whole table is cut to fit
+        let width = |column: usize| self.widths[column].min(usize::from(table_area.width));
+
+        let header = Row::new(columns.clone().map(|column| {
+            let marker = match self.sort {
+                Some((sorted, false)) if sorted == column => " ▲",
+                Some((sorted, true)) if sorted == column => " ▼",
+                _ => "",
+            };
+            truncate(
+                &format!("{}{}", self.headers[column], marker),
+                width(column),
+            )
+        }))
+        .style(theme.header);
+        let search = self.search.to_lowercase();
+        let (selected_rows, selected_columns) = self.selected();
+        let rows = self
+            .rows
+            .iter()
+            .map(|&row| &self.cells[row])
+            .enumerate()
+            .skip(self.row_offset)
+            .take(self.page_rows)
+            .map(|(index, cells)| {
+                Row::new(columns.clone().map(|column| {
+                    let cell = Cell::new(truncate(&cells[column], width(column)));
+                    if selected_rows.contains(&index) && selected_columns.contains(&column) {
+                        cell.style(theme.selected)
+                    } else if !search.is_empty() && cells[column].to_lowercase().contains(&search) {
+                        cell.style(theme.matched)
+                    } else {
+                        cell
+                    }
+                }))
+                .style(if index == self.row {
+                    Style::new().add_modifier(Modifier::BOLD)
+                } else {
+                    Style::new()
+                })
+            });
+        let widths = columns
+            .clone()
+            .map(|column| Constraint::Length(width(column) as u16));
+        frame.render_widget(Table::new(rows, widths).header(header), table_area);
+
+        // Until the result has streamed in, there are more rows than are loaded
+        let more = if self.complete { "" } else { "+" };
+        let mut footer = if self.rows.is_empty() {
+            format!("0{} rows, {} columns", more, self.headers.len())
+        } else {
+            format!(
+                "Row {} of {}{}, column {} of {}{}",
+                self.row + 1,
+                self.rows.len(),
+                more,
+                self.column + 1,
+                self.headers.len(),
+                if self.column_offset > 0 || columns.end < self.headers.len() {
+                    " (scroll with ←/→)"
+                } else {
+                    ""
+                }
+            )
+        };
+        if self.anchor.is_some() {
+            footer = format!(
+                "{} rows by {} columns selected",
+                selected_rows.count(),
+                selected_columns.count()
+            );
+        }
+        if self.editing_search {
+            footer = format!("/{}_ | {} matches", self.search, self.matches.len());
+        } else if self.editing_filter || !self.filter.is_empty() {
+            footer = format!(
+                "Filter: {}{} | {} of {}{} rows match",
+                self.filter,
+                if self.editing_filter { "_" } else { "" },
+                self.rows.len(),
+                self.cells.len(),
+                more
+            );
+        }
+        frame.render_widget(
+            Paragraph::new(Line::from(footer)).style(theme.status),
+            footer_area,
+        );
+    }
+
+    /// Work out which rows to show, and in what order, from the sort column and filter
+    fn arrange(&mut self) {
+        let order = match self.sort {
+            Some((column, descending)) => {
+                let options = arrow::compute::SortOptions {
+                    descending,
+                    nulls_first: false,
+                };
+                match arrow::compute::sort_to_indices(&self.columns[column], Some(options), None) {
+                    Ok(indices) => indices
+                        .values()
+                        .iter()
+                        .map(|&index| index as usize)
+                        .collect(),
+                    // Columns of types that can't be compared stay in their original order
+                    Err(_) => (0..self.cells.len()).collect(),
+                }
+            }
+            None => (0..self.cells.len()).collect::<Vec<_>>(),
+        };
+        let filter = self.filter.to_lowercase();
+        self.rows = order
+            .into_iter()
+            .filter(|&row| {
+                filter.is_empty()
+                    || self.cells[row]
+                        .iter()
+                        .any(|cell| cell.to_lowercase().contains(&filter))
+            })
+            .collect();
+        self.row = self.row.min(self.rows.len().saturating_sub(1));
+        self.find();
+    }
+
+    /// Positions in `rows` and columns of the range selected, or else of the cell under the cursor
+    fn selected(&self) -> (RangeInclusive<usize>, RangeInclusive<usize>) {
+        let (row, column) = self.anchor.unwrap_or((self.row, self.column));
+        (
+            row.min(self.row)..=row.max(self.row),
+            column.min(self.column)..=column.max(self.column),
+        )
+    }
+
+    /// Find the cells shown that contain the search text
+    fn find(&mut self) {
+        let search = self.search.to_lowercase();
+        self.matches.clear();
+        if search.is_empty() {
+            return;
+        }
+        for (position, &row) in self.rows.iter().enumerate() {
+            for (column, cell) in self.cells[row].iter().enumerate() {
+                if cell.to_lowercase().contains(&search) {
+                    self.matches.push((position, column));
+                }
+            }
+        }
+    }
+
+    /// Move the cursor to the next match after it, or the one before it if not `forward`,
+    /// wrapping around at the ends; a match under the cursor counts if `inclusive`
+    fn jump(&mut self, forward: bool, inclusive: bool) {
+        let cursor = (self.row, self.column);
+        let next = if forward {
+            self.matches
+                .iter()
+                .find(|&&cell| cell > cursor || (inclusive && cell == cursor))
+                .or(self.matches.first())
+        } else {
+            self.matches
+                .iter()
+                .rev()
+                .find(|&&cell| cell < cursor)
+                .or(self.matches.last())
+        };
+        if let Some(&(row, column)) = next {
+            (self.row, self.column) = (row, column);
+        }
+    }
+
+    /// The columns in view given `width` to fit them in, scrolled to keep the cursor in view
+    fn visible_columns(&mut self, width: usize) -> std::ops::Range<usize> {
+        // Each column is separated from the next by a space
+        let fits = |start: usize, end: usize| {
+            self.widths[start..=end]
+                .iter()
+                .map(|width| width + 1)
+                .sum::<usize>()
+                <= width + 1
+        };
+        if self.column < self.column_offset {
+            self.column_offset = self.column;
+        }
+        while self.column_offset < self.column && !fits(self.column_offset, self.column) {
+            self.column_offset += 1;
+        }
+        let mut end = self.column_offset;
+        while end < self.headers.len()
+            && (end == self.column_offset || fits(self.column_offset, end))
+        {
+            end += 1;
+        }
+        self.column_offset..end
+    }
+}
+
+/// `text` on a single line, cut down to `width` characters and marking where it was cut
+fn truncate(text: &str, width: usize) -> String {
+    let text = text.replace(['\n', '\r'], " ");
+    if text.chars().count() <= width {
+        return text;
+    }
+    let mut truncated = text
+        .chars()
+        .take(width.saturating_sub(1))
+        .collect::<String>();
+    truncated.push(TRUNCATED);
+    truncated
+}
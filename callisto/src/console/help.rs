@@ -0,0 +1,169 @@
+use ratatui::{
+    crossterm::event::{KeyCode, KeyEvent},
+    layout::{self, Rect},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph},
+    Frame,
+};
+
+use super::{
+    Catalog, Chart, Command, Diff, Editor, Files, Focus, Grid, Log, Notifications, Objects, Plan,
+    Record, Theme,
+};
+
+/// A key, or keys written together, and what it does
+pub type Binding = (&'static str, &'static str);
+
+/// `keys` as owned text, as the overlay lists them
+pub fn owned(keys: &[Binding]) -> Vec<(String, String)> {
+    keys.iter()
+        .map(|(key, action)| (key.to_string(), action.to_string()))
+        .collect()
+}
+
+/// A popup listing every key the console handles, pane by pane.
+///
+/// The list is put together from the commands and the keys each pane declares beside its key
+/// handling, with the editor's following its keymap, so it shows what the keys actually do.
+pub struct Help {
+    sections: Vec<(String, Vec<(String, String)>)>,
+    /// Lines scrolled past
+    scroll: u16,
+    /// Lines that fit in view as of the last render, for paging
+    page: u16,
+}
+
+impl Help {
+    pub fn new(editor: &Editor) -> Help {
+        let commands = |pane: Option<Focus>| {
+            Command::all()
+                .into_iter()
+                .filter(|command| command.pane() == pane)
+                .filter_map(|command| Some((command.key()?.to_string(), command.title())))
+                .collect::<Vec<_>>()
+        };
+
+        let mut anywhere = commands(None);
+        anywhere.push(("Ctrl-P".to_string(), "Open the command palette".to_string()));
+        anywhere.push((
+            "?".to_string(),
+            "Show the keys, outside the editor".to_string(),
+        ));
+        Help {
+            sections: vec![
+                ("Anywhere".to_string(), anywhere),
+                ("Editor".to_string(), owned(editor.keys())),
+                ("Results".to_string(), Help::results()),
+                ("Chart".to_string(), Help::view("c", Chart::KEYS)),
+                ("Summary".to_string(), Help::view("i", &[])),
+                ("Plan".to_string(), Help::view("p", Plan::KEYS)),
+                ("Row".to_string(), Help::view("Enter", Record::KEYS)),
+                ("Comparison".to_string(), Help::view("d", Diff::KEYS)),
+                ("Tables".to_string(), owned(Catalog::KEYS)),
+                ("Files".to_string(), owned(Files::KEYS)),
+                ("Remotes".to_string(), owned(Objects::KEYS)),
+                ("Notifications".to_string(), owned(Notifications::KEYS)),
+                ("Log".to_string(), owned(Log::KEYS)),
+            ],
+            scroll: 0,
+            page: 1,
+        }
+    }
+
+    /// The keys the result grid takes, its own and those of the commands only it takes
+    pub fn results() -> Vec<(String, String)> {
+        let mut keys = owned(Grid::KEYS);
+        keys.extend(
+            Command::all()
+                .into_iter()
+                .filter(|command| command.pane() == Some(Focus::Results))
+                .filter_map(|command| Some((command.key()?.to_string(), command.title()))),
+        );
+        keys
+    }
+
+    /// The `keys` of a view of the result grid, opened and closed by `key`
+    pub fn view(key: &str, keys: &[Binding]) -> Vec<(String, String)> {
+        let mut keys = owned(keys);
+        keys.push((format!("{}, Esc", key), "back to the grid".to_string()));
+        keys
+    }
+
+    /// `keys` in brief, as the status bar lists them, e.g. `j/k move, c clear`
+    pub fn brief(keys: &[(String, String)]) -> String {
+        keys.iter()
+            .map(|(key, action)| format!("{} {}", key, action))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Scroll the list in response to `key`, returning whether the overlay stays open
+    pub fn handle_key(&mut self, key: KeyEvent) -> bool {
+        let last = self.lines().saturating_sub(self.page);
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => self.scroll = self.scroll.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => self.scroll = (self.scroll + 1).min(last),
+            KeyCode::PageUp => self.scroll = self.scroll.saturating_sub(self.page),
+            KeyCode::PageDown | KeyCode::Char(' ') => {
+                self.scroll = (self.scroll + self.page).min(last)
+            }
+            KeyCode::Home | KeyCode::Char('g') => self.scroll = 0,
+            KeyCode::End | KeyCode::Char('G') => self.scroll = last,
+            KeyCode::Esc | KeyCode::Char('q') | KeyCode::Char('?') | KeyCode::F(1) => return false,
+            _ => {}
+        }
+        true
+    }
+
+    /// Draw the overlay as a popup centred in `area`
+    pub fn render(&mut self, frame: &mut Frame, area: Rect, theme: &Theme) {
+        let [row] = layout::Layout::vertical([layout::Constraint::Percentage(80)])
+            .flex(layout::Flex::Center)
+            .areas(area);
+        let [popup] = layout::Layout::horizontal([layout::Constraint::Length(area.width.min(80))])
+            .flex(layout::Flex::Center)
+            .areas(row);
+        let block = Block::new()
+            .borders(Borders::ALL)
+            .border_style(theme.focused_border)
+            .title("Keys (j/k scroll, Esc to close)");
+        let inner = block.inner(popup);
+        self.page = inner.height.max(1);
+        self.scroll = self.scroll.min(self.lines().saturating_sub(self.page));
+
+        let width = self
+            .sections
+            .iter()
+            .flat_map(|(_, keys)| keys.iter().map(|(key, _)| key.chars().count()))
+            .max()
+            .unwrap_or(0);
+        let mut lines = Vec::new();
+        for (title, keys) in &self.sections {
+            if !lines.is_empty() {
+                lines.push(Line::default());
+            }
+            lines.push(Line::styled(title.as_str(), theme.header));
+            for (key, action) in keys {
+                lines.push(Line::from(vec![
+                    Span::raw(format!("  {:<width$}  ", key)),
+                    Span::styled(action.as_str(), theme.muted),
+                ]));
+            }
+        }
+        frame.render_widget(Clear, popup);
+        frame.render_widget(
+            Paragraph::new(lines).block(block).scroll((self.scroll, 0)),
+            popup,
+        );
+    }
+
+    /// Lines the list takes, with a title and a blank line between each section
+    fn lines(&self) -> u16 {
+        let lines = self
+            .sections
+            .iter()
+            .map(|(_, keys)| keys.len() + 2)
+            .sum::<usize>();
+        lines.saturating_sub(1) as u16
+    }
+}
@@ -0,0 +1,156 @@
+use std::time::Instant;
+
+use ratatui::{
+    crossterm::event::{KeyCode, KeyEvent},
+    layout::Rect,
+    text::{Line, Span},
+    widgets::{Block, List, ListItem, ListState},
+    Frame,
+};
+use tracing::Level;
+
+use super::{Binding, Theme};
+
+/// Most events kept in the log, dropping the oldest beyond it
+const LOG_LIMIT: usize = 5000;
+
+/// An event logged through `tracing` while the console runs, like a table being registered or a
+/// query finishing
+#[derive(Clone, Debug)]
+pub struct LogEntry {
+    pub level: Level,
+    /// Module that logged it, e.g. `callisto_engines`
+    pub target: String,
+    pub message: String,
+    /// The event's other fields, by name, e.g. the table registered
+    pub fields: Vec<(String, String)>,
+    pub time: Instant,
+}
+
+/// Every event logged since the console started, newest last, filtered by level
+pub struct Log {
+    entries: Vec<LogEntry>,
+    /// Index of the selected entry among those shown
+    selected: usize,
+    /// Least severe level shown
+    level: Level,
+    started: Instant,
+}
+
+impl Log {
+    pub fn new() -> Log {
+        Log {
+            entries: Vec::new(),
+            selected: 0,
+            level: Level::DEBUG,
+            started: Instant::now(),
+        }
+    }
+
+    pub fn push(&mut self, entry: LogEntry) {
+        let following = self.selected + 2 >= self.shown().count();
+        self.entries.push(entry);
+        if self.entries.len() > LOG_LIMIT {
+            self.entries.remove(0);
+        }
+        // Keep following the newest unless scrolled back through the log
+        if following {
+            self.selected = self.shown().count().saturating_sub(1);
+        }
+    }
+
+    /// Number of entries shown at the current level
+    pub fn len(&self) -> usize {
+        self.shown().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Least severe level shown
+    pub fn level(&self) -> Level {
+        self.level
+    }
+
+    /// Keys the event log handles, for the help overlay
+    pub const KEYS: &'static [Binding] = &[
+        ("j/k", "move"),
+        ("g/G", "first/last"),
+        ("f", "cycle level"),
+        ("c", "clear"),
+    ];
+
+    /// Move through the log, change the level shown, or clear it, in response to `key`
+    pub fn handle_key(&mut self, key: KeyEvent) {
+        let last = self.len().saturating_sub(1);
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => self.selected = (self.selected + 1).min(last),
+            KeyCode::Home | KeyCode::Char('g') => self.selected = 0,
+            KeyCode::End | KeyCode::Char('G') => self.selected = last,
+            KeyCode::Char('f') => {
+                self.level = match self.level {
+                    Level::INFO => Level::WARN,
+                    Level::WARN => Level::ERROR,
+                    Level::ERROR => Level::DEBUG,
+                    _ => Level::INFO,
+                };
+                self.selected = self.len().saturating_sub(1);
+            }
+            KeyCode::Char('c') => {
+                self.entries.clear();
+                self.selected = 0;
+            }
+            _ => {}
+        }
+    }
+
+    pub fn render(&self, frame: &mut Frame, area: Rect, block: Block, theme: &Theme) {
+        let items = self
+            .shown()
+            .map(|entry| {
+                let elapsed = entry.time.duration_since(self.started).as_secs();
+                let style = match entry.level {
+                    Level::ERROR => theme.error,
+                    Level::WARN => theme.warning,
+                    Level::INFO => ratatui::style::Style::new(),
+                    _ => theme.muted,
+                };
+                let mut spans = vec![
+                    Span::styled(
+                        format!("{:02}:{:02} ", elapsed / 60, elapsed % 60),
+                        theme.muted,
+                    ),
+                    Span::styled(format!("{:<5}", entry.level), style),
+                    Span::raw(format!(" {}: {}", entry.target, entry.message)),
+                ];
+                for (name, value) in &entry.fields {
+                    spans.push(Span::styled(format!(" {}=", name), theme.muted));
+                    spans.push(Span::raw(value.clone()));
+                }
+                ListItem::new(Line::from(spans))
+            })
+            .collect::<Vec<_>>();
+        let empty = items.is_empty();
+        let list = List::new(items)
+            .block(block)
+            .highlight_style(theme.selected);
+        let mut state = ListState::default().with_selected((!empty).then_some(self.selected));
+        frame.render_stateful_widget(list, area, &mut state);
+    }
+
+    /// Entries at the level shown or more severe
+    fn shown(&self) -> impl Iterator<Item = &LogEntry> {
+        // Levels compare as more verbose being greater
+        self.entries
+            .iter()
+            .filter(|entry| entry.level <= self.level)
+    }
+}
+
+impl Default for Log {
+    fn default() -> Log {
+        Log::new()
+    }
+}
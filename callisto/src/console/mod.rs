@@ -5,64 +5,125 @@ use std::time::Duration;
 use ratatui::{
     backend::CrosstermBackend,
     crossterm::{
-        event::{self, KeyCode, KeyEventKind},
+        event::{self, DisableMouseCapture, EnableMouseCapture, KeyEventKind},
         terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
         ExecutableCommand,
     },
-    layout,
-    widgets::{Block, Borders, Paragraph},
     Terminal,
 };
 
+use crate::{EngineBuilder, EngineInterface, Keymap};
+
+mod app;
+mod bookmarks;
+mod catalog;
+mod chart;
+mod clipboard;
+mod completions;
+mod diff;
+mod editor;
+mod files;
+mod grid;
+mod help;
+mod log;
+mod notifications;
+mod objects;
+mod palette;
+mod plan;
+mod preview;
+mod prompt;
+mod record;
+mod session;
+mod summary;
+mod theme;
+
+pub use app::{App, Dialog, Flow, Focus, Results, View};
+pub use bookmarks::{BookmarkEvent, BookmarkPicker};
+pub use catalog::{Catalog, CatalogAction};
+pub use chart::{Chart, ChartKind};
+pub use completions::{Completions, CompletionsEvent};
+pub use diff::Diff;
+pub use editor::Editor;
+pub use files::{Files, FilesAction};
+pub use grid::Grid;
+pub use help::{Binding, Help};
+pub use log::{Log, LogEntry};
+pub use notifications::{Diagnostics, Notification, Notifications, Severity};
+pub use objects::{Objects, ObjectsAction};
+pub use palette::{Command, Palette, PaletteEvent};
+pub use plan::Plan;
+pub use preview::Preview;
+pub use prompt::{Prompt, PromptEvent};
+pub use record::Record;
+pub use session::{Session, DEFAULT_SESSION};
+pub use summary::Summary;
+pub use theme::{Theme, BUILT_IN_THEMES};
+
 pub fn setup_term_for_console() -> anyhow::Result<()> {
     io::stdout().execute(EnterAlternateScreen)?;
+    io::stdout().execute(EnableMouseCapture)?;
     enable_raw_mode()?;
     Ok(())
 }
 
 pub fn teardown_term_for_console() -> anyhow::Result<()> {
+    io::stdout().execute(DisableMouseCapture)?;
     io::stdout().execute(LeaveAlternateScreen)?;
     disable_raw_mode()?;
     Ok(())
 }
 
-pub fn run_console<Output>(output: Output) -> anyhow::Result<()>
+/// How the console is set up, beyond the session it starts as
+pub struct ConsoleOptions {
+    /// Configuration the engines the console is switched to are built from, e.g. that of the one
+    /// it starts on, so that they keep its row limit and path policy
+    pub engine: EngineBuilder,
+    pub theme: Theme,
+    /// Keys the SQL editor follows
+    pub keymap: Keymap,
+    /// Object stores browsed in the remote pane
+    pub remotes: Vec<crate::Remote>,
+    /// Masks applied to every result shown
+    pub masks: crate::MaskPolicy,
+}
+
+/// Run the console on `engine`, set up as `options` say, until the user quits, logging and
+/// notifying of whatever is logged to `diagnostics`.
+///
+/// The console starts as `session` left it, with its state on exit returned to be saved.
+///
+/// This blocks, so should be called from a blocking task of the Tokio runtime on which queries are
+/// to run.
+pub fn run_console<Output>(
+    engine: Box<dyn EngineInterface>,
+    options: ConsoleOptions,
+    diagnostics: Diagnostics,
+    session: Session,
+    output: Output,
+) -> anyhow::Result<Session>
 where
     Output: std::io::Write,
 {
     let mut terminal = Terminal::new(CrosstermBackend::new(output))?;
     terminal.clear()?;
 
-    let layout = layout::Layout::default()
-        .direction(layout::Direction::Vertical)
-        .constraints(vec![
-            layout::Constraint::Percentage(20),
-            layout::Constraint::Percentage(80),
-        ]);
-
+    let mut app = App::new(engine, options, diagnostics, &session);
     loop {
-        terminal.draw(|frame| {
-            let layout = layout.split(frame.size());
-
-            frame.render_widget(
-                Paragraph::new("Code console goes here! (press 'q' to quit)")
-                    .block(Block::new().borders(Borders::ALL)),
-                layout[0],
-            );
-            frame.render_widget(
-                Paragraph::new("Data console goes here!").block(Block::new().borders(Borders::ALL)),
-                layout[1],
-            );
-        })?;
+        app.poll();
+        terminal.draw(|frame| app.render(frame))?;
 
         if event::poll(Duration::from_millis(16))? {
-            if let event::Event::Key(key) = event::read()? {
-                if key.kind == KeyEventKind::Press && key.code == KeyCode::Char('q') {
-                    break;
+            match event::read()? {
+                event::Event::Key(key) => {
+                    if key.kind == KeyEventKind::Press && app.handle_key(key) == Flow::Exit {
+                        break;
+                    }
                 }
+                event::Event::Mouse(mouse) => app.handle_mouse(mouse),
+                _ => {}
             }
         }
     }
 
-    Ok(())
+    Ok(app.session())
 }
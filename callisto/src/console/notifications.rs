@@ -0,0 +1,278 @@
+use std::{
+    fmt::Write as _,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use ratatui::{
+    crossterm::event::{KeyCode, KeyEvent},
+    layout::Rect,
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Wrap},
+    Frame,
+};
+
+use super::{Binding, LogEntry, Theme};
+
+/// How long a notification stays on screen as a toast
+const TOAST_DURATION: Duration = Duration::from_secs(5);
+/// Most toasts shown at once, newest first
+const TOASTS: usize = 3;
+const TOAST_WIDTH: u16 = 48;
+/// Most notifications kept in the log, dropping the oldest beyond it
+const LOG_LIMIT: usize = 1000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+/// A warning or error raised while the console runs
+#[derive(Clone, Debug)]
+pub struct Notification {
+    pub severity: Severity,
+    /// Module that raised it, e.g. `callisto_engines`
+    pub target: String,
+    pub message: String,
+    pub time: Instant,
+}
+
+/// Events logged through `tracing`, collected to be shown in the console: every one in its log,
+/// and warnings and errors as notifications too.
+///
+/// Installed as a layer of the subscriber in place of the usual writer to stderr, which the
+/// console's alternate screen would hide or draw over.
+#[derive(Clone, Default)]
+pub struct Diagnostics {
+    pending: Arc<Mutex<Vec<Notification>>>,
+    logged: Arc<Mutex<Vec<LogEntry>>>,
+}
+
+impl Diagnostics {
+    /// The notifications raised since last taken
+    pub fn take(&self) -> Vec<Notification> {
+        self.pending
+            .lock()
+            .map(|mut pending| std::mem::take(&mut *pending))
+            .unwrap_or_default()
+    }
+
+    /// The events logged since last taken
+    pub fn take_log(&self) -> Vec<LogEntry> {
+        self.logged
+            .lock()
+            .map(|mut logged| std::mem::take(&mut *logged))
+            .unwrap_or_default()
+    }
+}
+
+impl<S: tracing::Subscriber> tracing_subscriber::Layer<S> for Diagnostics {
+    fn on_event(&self, event: &tracing::Event<'_>, _: tracing_subscriber::layer::Context<'_, S>) {
+        let metadata = event.metadata();
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        let time = Instant::now();
+        let severity = match *metadata.level() {
+            tracing::Level::ERROR => Some(Severity::Error),
+            tracing::Level::WARN => Some(Severity::Warning),
+            _ => None,
+        };
+        if let (Some(severity), Ok(mut pending)) = (severity, self.pending.lock()) {
+            let mut message = fields.message.clone();
+            for (name, value) in &fields.rest {
+                let _ = write!(message, " {}={}", name, value);
+            }
+            pending.push(Notification {
+                severity,
+                target: metadata.target().to_string(),
+                message,
+                time,
+            });
+        }
+        if let Ok(mut logged) = self.logged.lock() {
+            logged.push(LogEntry {
+                level: *metadata.level(),
+                target: metadata.target().to_string(),
+                message: fields.message,
+                fields: fields.rest,
+                time,
+            });
+        }
+    }
+}
+
+/// An event's message and its other fields, by name
+#[derive(Default)]
+struct Fields {
+    message: String,
+    rest: Vec<(String, String)>,
+}
+
+impl tracing::field::Visit for Fields {
+    fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+        if field.name() == "message" {
+            self.message.push_str(value);
+        } else {
+            self.rest
+                .push((field.name().to_string(), value.to_string()));
+        }
+    }
+
+    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{:?}", value);
+        } else {
+            self.rest
+                .push((field.name().to_string(), format!("{:?}", value)));
+        }
+    }
+}
+
+/// Log of the notifications raised since the console started, newest last, shown as toasts as
+/// they arrive
+pub struct Notifications {
+    entries: Vec<Notification>,
+    /// Index of the selected entry
+    selected: usize,
+    started: Instant,
+}
+
+impl Notifications {
+    pub fn new() -> Notifications {
+        Notifications {
+            entries: Vec::new(),
+            selected: 0,
+            started: Instant::now(),
+        }
+    }
+
+    pub fn push(&mut self, notification: Notification) {
+        self.entries.push(notification);
+        if self.entries.len() > LOG_LIMIT {
+            self.entries.remove(0);
+        }
+        // Keep following the newest unless scrolled back through the log
+        if self.selected + 2 >= self.entries.len() {
+            self.selected = self.entries.len() - 1;
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Keys the notifications log handles, for the help overlay
+    pub const KEYS: &'static [Binding] = &[("j/k", "move"), ("g/G", "first/last"), ("c", "clear")];
+
+    /// Move through the log, or clear it, in response to `key`
+    pub fn handle_key(&mut self, key: KeyEvent) {
+        let last = self.entries.len().saturating_sub(1);
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => self.selected = (self.selected + 1).min(last),
+            KeyCode::Home | KeyCode::Char('g') => self.selected = 0,
+            KeyCode::End | KeyCode::Char('G') => self.selected = last,
+            KeyCode::Char('c') => {
+                self.entries.clear();
+                self.selected = 0;
+            }
+            _ => {}
+        }
+    }
+
+    pub fn render(&self, frame: &mut Frame, area: Rect, block: Block, theme: &Theme) {
+        let items = self
+            .entries
+            .iter()
+            .map(|notification| {
+                let elapsed = notification.time.duration_since(self.started).as_secs();
+                ListItem::new(Line::from(vec![
+                    Span::styled(
+                        format!("{:02}:{:02} ", elapsed / 60, elapsed % 60),
+                        theme.muted,
+                    ),
+                    Span::styled(label(notification.severity), style(notification, theme)),
+                    Span::raw(format!(
+                        " {}: {}",
+                        notification.target, notification.message
+                    )),
+                ]))
+            })
+            .collect::<Vec<_>>();
+        let empty = items.is_empty();
+        let list = List::new(items)
+            .block(block)
+            .highlight_style(theme.selected);
+        let mut state = ListState::default().with_selected((!empty).then_some(self.selected));
+        frame.render_stateful_widget(list, area, &mut state);
+    }
+
+    /// Draw the notifications raised in the last few seconds as toasts in the top right of
+    /// `area`, newest on top
+    pub fn render_toasts(&self, frame: &mut Frame, area: Rect, theme: &Theme) {
+        let width = TOAST_WIDTH.min(area.width);
+        let mut top = area.y;
+        let recent = self
+            .entries
+            .iter()
+            .rev()
+            .take_while(|notification| notification.time.elapsed() < TOAST_DURATION)
+            .take(TOASTS);
+        for notification in recent {
+            // Messages wrap within the toast's borders
+            let lines = notification
+                .message
+                .chars()
+                .count()
+                .div_ceil(usize::from(width.saturating_sub(2).max(1)))
+                .clamp(1, 4) as u16;
+            let toast = Rect::new(
+                area.right().saturating_sub(width),
+                top,
+                width,
+                (lines + 2).min(area.bottom().saturating_sub(top)),
+            );
+            if toast.height < 3 {
+                break;
+            }
+            frame.render_widget(Clear, toast);
+            frame.render_widget(
+                Paragraph::new(notification.message.as_str())
+                    .wrap(Wrap { trim: true })
+                    .block(
+                        Block::new()
+                            .borders(Borders::ALL)
+                            .border_style(style(notification, theme))
+                            .title(label(notification.severity)),
+                    ),
+                toast,
+            );
+            top += toast.height;
+        }
+    }
+}
+
+impl Default for Notifications {
+    fn default() -> Notifications {
+        Notifications::new()
+    }
+}
+
+fn label(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Warning => "warning",
+        Severity::Error => "error",
+    }
+}
+
+fn style(notification: &Notification, theme: &Theme) -> ratatui::style::Style {
+    match notification.severity {
+        Severity::Warning => theme.warning,
+        Severity::Error => theme.error,
+    }
+}
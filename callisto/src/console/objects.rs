@@ -0,0 +1,283 @@
+use std::sync::Arc;
+
+use object_store::path::Path;
+use ratatui::{
+    crossterm::event::{KeyCode, KeyEvent},
+    layout::{self, Constraint, Rect},
+    text::{Line, Span},
+    widgets::{Block, List, ListItem, ListState, Paragraph},
+    Frame,
+};
+use tokio::sync::oneshot;
+
+use super::{app::bytes, Binding, Theme};
+use crate::{ParquetSummary, Remote, RemoteEntry};
+
+/// Most lines the schema of the selected object takes up below the listing
+const SCHEMA_HEIGHT: u16 = 12;
+
+/// What the user asked for by picking an object or prefix
+pub enum ObjectsAction {
+    /// Read the object, or every data file under the prefix, and register it with the engine as
+    /// a table
+    Register(Arc<Remote>, RemoteEntry),
+}
+
+/// Something fetched from a remote on a background task, or why it couldn't be
+enum Fetch<T> {
+    Pending(oneshot::Receiver<anyhow::Result<T>>),
+    Done(Result<T, String>),
+}
+
+impl<T> Fetch<T> {
+    fn spawn<F>(runtime: &tokio::runtime::Handle, fetch: F) -> Fetch<T>
+    where
+        F: std::future::Future<Output = anyhow::Result<T>> + Send + 'static,
+        T: Send + 'static,
+    {
+        let (sender, receiver) = oneshot::channel();
+        runtime.spawn(async move {
+            let _ = sender.send(fetch.await);
+        });
+        Fetch::Pending(receiver)
+    }
+
+    fn poll(&mut self) {
+        let Fetch::Pending(receiver) = self else {
+            return;
+        };
+        *self = Fetch::Done(match receiver.try_recv() {
+            Ok(outcome) => outcome.map_err(|error| format!("{:#}", error)),
+            Err(oneshot::error::TryRecvError::Empty) => return,
+            Err(oneshot::error::TryRecvError::Closed) => Err("Fetch ended early".to_string()),
+        });
+    }
+}
+
+/// Browser of the object stores configured as remotes, listing the prefixes and data files at one
+/// level at a time, with the schema of the selected Parquet object read from its footer
+pub struct Objects {
+    remotes: Vec<Arc<Remote>>,
+    runtime: tokio::runtime::Handle,
+    /// The remote browsed and the prefix within it, or `None` while choosing a remote
+    location: Option<(usize, Path)>,
+    entries: Fetch<Vec<RemoteEntry>>,
+    /// Index of the selected remote or entry
+    selected: usize,
+    /// The selected object and what its footer says, if it's Parquet
+    summary: Option<(Path, Fetch<ParquetSummary>)>,
+}
+
+impl Objects {
+    pub fn new(remotes: Vec<Remote>, runtime: tokio::runtime::Handle) -> Objects {
+        Objects {
+            remotes: remotes.into_iter().map(Arc::new).collect(),
+            runtime,
+            location: None,
+            entries: Fetch::Done(Ok(Vec::new())),
+            selected: 0,
+            summary: None,
+        }
+    }
+
+    /// Pick up listings and schemas once fetched
+    pub fn poll(&mut self) {
+        self.entries.poll();
+        if let Some((_, summary)) = &mut self.summary {
+            summary.poll();
+        }
+        self.summarize();
+    }
+
+    /// Keys the remote browser handles, for the help overlay
+    pub const KEYS: &'static [Binding] = &[
+        ("j/k", "move"),
+        ("l, Enter", "open"),
+        ("h, Backspace", "up"),
+        ("r", "register as a table"),
+        ("R", "refresh"),
+    ];
+
+    /// Move through or act on the listing in response to `key`
+    pub fn handle_key(&mut self, key: KeyEvent) -> Option<ObjectsAction> {
+        let last = self.len().saturating_sub(1);
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => self.selected = (self.selected + 1).min(last),
+            KeyCode::Enter | KeyCode::Right | KeyCode::Char('l') => match &self.location {
+                None if self.selected < self.remotes.len() => {
+                    let root = self.remotes[self.selected].root().clone();
+                    self.open(self.selected, root);
+                }
+                Some((remote, _)) => {
+                    let remote = *remote;
+                    if let Some(RemoteEntry::Prefix(prefix)) = self.entry().cloned() {
+                        self.open(remote, prefix);
+                    }
+                }
+                None => {}
+            },
+            KeyCode::Left | KeyCode::Backspace | KeyCode::Char('h') => {
+                if let Some((remote, prefix)) = self.location.clone() {
+                    let root = self.remotes[remote].root();
+                    if prefix == *root {
+                        self.selected = remote;
+                        self.location = None;
+                        self.entries = Fetch::Done(Ok(Vec::new()));
+                    } else {
+                        let mut parts = prefix.parts().collect::<Vec<_>>();
+                        parts.pop();
+                        self.open(remote, parts.into_iter().collect());
+                    }
+                }
+            }
+            KeyCode::Char('R') => {
+                if let Some((remote, prefix)) = self.location.clone() {
+                    self.open(remote, prefix);
+                }
+            }
+            KeyCode::Char('r') => {
+                if let (Some((remote, _)), Some(entry)) = (&self.location, self.entry()) {
+                    return Some(ObjectsAction::Register(
+                        self.remotes[*remote].clone(),
+                        entry.clone(),
+                    ));
+                }
+            }
+            _ => {}
+        }
+        None
+    }
+
+    pub fn render(&self, frame: &mut Frame, area: Rect, block: Block, theme: &Theme) {
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+        let details = match &self.summary {
+            Some((_, Fetch::Done(Ok(summary)))) => std::iter::once(Line::styled(
+                format!("{} rows │ {} row groups", summary.rows, summary.row_groups),
+                theme.status,
+            ))
+            .chain(summary.schema.fields().iter().map(|field| {
+                Line::styled(
+                    format!("{}: {}", field.name(), field.data_type()),
+                    theme.muted,
+                )
+            }))
+            .collect(),
+            Some((_, Fetch::Done(Err(error)))) => vec![Line::styled(error.clone(), theme.error)],
+            Some((_, Fetch::Pending(_))) => vec![Line::styled("Reading footer…", theme.muted)],
+            None => Vec::new(),
+        };
+        let [list_area, details_area] = layout::Layout::vertical([
+            Constraint::Min(0),
+            Constraint::Length((details.len() as u16).min(SCHEMA_HEIGHT)),
+        ])
+        .areas(inner);
+
+        let items = match (&self.location, &self.entries) {
+            (None, _) if self.remotes.is_empty() => vec![ListItem::new(Line::styled(
+                "No remotes configured",
+                theme.muted,
+            ))],
+            (None, _) => self
+                .remotes
+                .iter()
+                .map(|remote| {
+                    ListItem::new(Line::from(vec![
+                        Span::raw(format!("{} ", remote.name)),
+                        Span::styled(remote.url.clone(), theme.muted),
+                    ]))
+                })
+                .collect(),
+            (Some(_), Fetch::Pending(_)) => {
+                vec![ListItem::new(Line::styled("Listing…", theme.muted))]
+            }
+            (Some(_), Fetch::Done(Err(error))) => {
+                vec![ListItem::new(Line::styled(error.clone(), theme.error))]
+            }
+            (Some(_), Fetch::Done(Ok(entries))) => entries
+                .iter()
+                .map(|entry| match entry {
+                    RemoteEntry::Prefix(_) => ListItem::new(format!("▸ {}/", entry.name())),
+                    RemoteEntry::Object(meta) => ListItem::new(Line::from(vec![
+                        Span::raw(format!("  {} ", entry.name())),
+                        Span::styled(bytes(meta.size), theme.muted),
+                    ])),
+                })
+                .collect(),
+        };
+        let list = List::new(items).highlight_style(theme.selected);
+        let selected = (self.len() > 0).then_some(self.selected);
+        let mut state = ListState::default().with_selected(selected);
+        frame.render_stateful_widget(list, list_area, &mut state);
+        frame.render_widget(Paragraph::new(details), details_area);
+    }
+
+    /// Where the browser is, as a URL, for the pane's title
+    pub fn location(&self) -> Option<String> {
+        let (remote, prefix) = self.location.as_ref()?;
+        let url = &self.remotes[*remote].url;
+        let bucket = url.split('/').take(3).collect::<Vec<_>>().join("/");
+        Some(format!("{}/{}", bucket, prefix))
+    }
+
+    /// List what's under `prefix` in the `remote`th remote
+    fn open(&mut self, remote: usize, prefix: Path) {
+        let store = self.remotes[remote].clone();
+        let listed = prefix.clone();
+        self.entries = Fetch::spawn(&self.runtime, async move { store.list(&listed).await });
+        self.location = Some((remote, prefix));
+        self.selected = 0;
+        self.summary = None;
+    }
+
+    /// Number of remotes or entries listed
+    fn len(&self) -> usize {
+        match (&self.location, &self.entries) {
+            (None, _) => self.remotes.len(),
+            (Some(_), Fetch::Done(Ok(entries))) => entries.len(),
+            (Some(_), _) => 0,
+        }
+    }
+
+    fn entry(&self) -> Option<&RemoteEntry> {
+        match &self.entries {
+            Fetch::Done(Ok(entries)) if self.location.is_some() => entries.get(self.selected),
+            _ => None,
+        }
+    }
+
+    /// Read the footer of the selected object if it's Parquet, unless it's already read
+    fn summarize(&mut self) {
+        let selected = match (&self.location, self.entry()) {
+            (Some((remote, _)), Some(RemoteEntry::Object(meta))) => Some((*remote, meta.clone())),
+            _ => None,
+        };
+        let Some((remote, meta)) = selected else {
+            self.summary = None;
+            return;
+        };
+        if self
+            .summary
+            .as_ref()
+            .is_some_and(|(location, _)| *location == meta.location)
+        {
+            return;
+        }
+        let is_parquet = meta
+            .location
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("parquet"));
+        if !is_parquet {
+            self.summary = None;
+            return;
+        }
+        let store = self.remotes[remote].clone();
+        let location = meta.location.clone();
+        let fetch = Fetch::spawn(
+            &self.runtime,
+            async move { store.parquet_summary(&meta).await },
+        );
+        self.summary = Some((location, fetch));
+    }
+}
@@ -0,0 +1,331 @@
+use ratatui::{
+    crossterm::event::{KeyCode, KeyEvent},
+    layout::{self, Rect},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph},
+    Frame,
+};
+
+use super::{Focus, Theme};
+use crate::{Engine, OutputFormat};
+
+/// Most commands listed in the palette at once
+const PALETTE_HEIGHT: u16 = 14;
+
+/// Something the console can be asked to do, by a key or from the command palette
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Command {
+    Run,
+    Cancel,
+    SwitchEngine(Engine),
+    Export,
+    /// Copy the selection, or the rows it spans if `whole_rows`, as tab-separated values or
+    /// else in the format given
+    Copy {
+        whole_rows: bool,
+        format: Option<OutputFormat>,
+    },
+    SaveBookmark,
+    OpenBookmark,
+    ToggleCatalog,
+    ToggleFiles,
+    ToggleRemotes,
+    ToggleNotifications,
+    ToggleLog,
+    Zoom,
+    GrowEditor,
+    ShrinkEditor,
+    NextPane,
+    Chart,
+    Summary,
+    Plan,
+    Diff,
+    Row,
+    Help,
+    Quit,
+}
+
+impl Command {
+    /// Every command, in the order the palette lists them
+    pub fn all() -> Vec<Command> {
+        let mut commands = vec![Command::Run, Command::Cancel];
+        commands.extend(Engine::ALL.into_iter().map(Command::SwitchEngine));
+        commands.extend([
+            Command::Export,
+            Command::Copy {
+                whole_rows: false,
+                format: None,
+            },
+            Command::Copy {
+                whole_rows: false,
+                format: Some(OutputFormat::Csv),
+            },
+            Command::Copy {
+                whole_rows: false,
+                format: Some(OutputFormat::Json),
+            },
+            Command::Copy {
+                whole_rows: true,
+                format: None,
+            },
+            Command::SaveBookmark,
+            Command::OpenBookmark,
+            Command::ToggleCatalog,
+            Command::ToggleFiles,
+            Command::ToggleRemotes,
+            Command::ToggleNotifications,
+            Command::ToggleLog,
+            Command::Zoom,
+            Command::GrowEditor,
+            Command::ShrinkEditor,
+            Command::NextPane,
+            Command::Chart,
+            Command::Summary,
+            Command::Plan,
+            Command::Diff,
+            Command::Row,
+            Command::Help,
+            Command::Quit,
+        ]);
+        commands
+    }
+
+    pub fn title(&self) -> String {
+        match self {
+            Command::Run => "Run the query".to_string(),
+            Command::Cancel => "Cancel the running query".to_string(),
+            Command::SwitchEngine(engine) => format!("Switch engine to {}", engine.name()),
+            Command::Export => "Export the result to a file".to_string(),
+            Command::Copy {
+                whole_rows: true, ..
+            } => "Copy the selected rows".to_string(),
+            Command::Copy { format: None, .. } => "Copy the selection as TSV".to_string(),
+            Command::Copy {
+                format: Some(format),
+                ..
+            } => format!("Copy the selection as {}", format.name().to_uppercase()),
+            Command::SaveBookmark => "Bookmark the editor".to_string(),
+            Command::OpenBookmark => "Open a bookmark".to_string(),
+            Command::ToggleCatalog => "Show or hide tables".to_string(),
+            Command::ToggleFiles => "Show or hide files".to_string(),
+            Command::ToggleRemotes => "Show or hide remotes".to_string(),
+            Command::ToggleNotifications => "Show or hide notifications".to_string(),
+            Command::ToggleLog => "Show or hide the log".to_string(),
+            Command::Zoom => "Zoom the focused pane".to_string(),
+            Command::GrowEditor => "Grow the editor".to_string(),
+            Command::ShrinkEditor => "Shrink the editor".to_string(),
+            Command::NextPane => "Focus the next pane".to_string(),
+            Command::Chart => "Chart the result".to_string(),
+            Command::Summary => "Summarize the column".to_string(),
+            Command::Plan => "Show the query plan".to_string(),
+            Command::Diff => "Compare with the previous result".to_string(),
+            Command::Row => "Show the row's values".to_string(),
+            Command::Help => "Show the keys".to_string(),
+            Command::Quit => "Quit".to_string(),
+        }
+    }
+
+    /// The key that does the same, if any, in [`Command::pane`]
+    pub fn key(&self) -> Option<&'static str> {
+        Some(match self {
+            Command::Run => "Ctrl-R",
+            Command::Cancel => "Ctrl-C",
+            Command::SwitchEngine(_) => return None,
+            Command::Export => "Ctrl-E",
+            Command::Copy {
+                whole_rows: true, ..
+            } => "Y",
+            Command::Copy { format: None, .. } => "y",
+            Command::Copy {
+                format: Some(OutputFormat::Csv),
+                ..
+            } => "Alt-y",
+            Command::Copy {
+                format: Some(OutputFormat::Json),
+                ..
+            } => "Ctrl-y",
+            Command::Copy { .. } => return None,
+            Command::SaveBookmark => "Ctrl-S",
+            Command::OpenBookmark => "F2",
+            Command::ToggleCatalog => "Ctrl-B",
+            Command::ToggleFiles => "Ctrl-O",
+            Command::ToggleRemotes => "Ctrl-G",
+            Command::ToggleNotifications => "Ctrl-L",
+            Command::ToggleLog => "Ctrl-T",
+            Command::Zoom => "Ctrl-Z",
+            Command::GrowEditor => "Ctrl-↓",
+            Command::ShrinkEditor => "Ctrl-↑",
+            Command::NextPane => "Tab",
+            Command::Chart => "c",
+            Command::Summary => "i",
+            Command::Plan => "p",
+            Command::Diff => "d",
+            Command::Row => "Enter",
+            Command::Help => "F1",
+            Command::Quit => "Ctrl-Q",
+        })
+    }
+
+    /// The pane whose key does the same, if only that pane's does
+    pub fn pane(&self) -> Option<Focus> {
+        match self {
+            Command::Copy { .. }
+            | Command::Chart
+            | Command::Summary
+            | Command::Plan
+            | Command::Diff
+            | Command::Row => Some(Focus::Results),
+            _ => None,
+        }
+    }
+}
+
+/// What became of the palette after a key
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PaletteEvent {
+    /// Still choosing
+    Choosing,
+    Picked(Command),
+    Cancelled,
+}
+
+/// A popup listing every command, narrowed to those whose titles fuzzily match what's typed
+pub struct Palette {
+    commands: Vec<Command>,
+    filter: String,
+    /// Index of the selected command among those matching
+    selected: usize,
+}
+
+impl Palette {
+    pub fn new() -> Palette {
+        Palette {
+            commands: Command::all(),
+            filter: String::new(),
+            selected: 0,
+        }
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> PaletteEvent {
+        let shown = self.shown();
+        match key.code {
+            KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down => self.selected = (self.selected + 1).min(shown.len().saturating_sub(1)),
+            KeyCode::Enter => {
+                if let Some(&command) = shown.get(self.selected) {
+                    return PaletteEvent::Picked(command);
+                }
+            }
+            KeyCode::Esc => return PaletteEvent::Cancelled,
+            KeyCode::Char(c) => {
+                self.filter.push(c);
+                self.selected = 0;
+            }
+            KeyCode::Backspace => {
+                self.filter.pop();
+                self.selected = 0;
+            }
+            _ => {}
+        }
+        PaletteEvent::Choosing
+    }
+
+    /// Draw the palette as a popup centred in `area`
+    pub fn render(&self, frame: &mut Frame, area: Rect, theme: &Theme) {
+        let width = area.width.min(60);
+        let [row] = layout::Layout::vertical([layout::Constraint::Length(PALETTE_HEIGHT + 3)])
+            .flex(layout::Flex::Center)
+            .areas(area);
+        let [popup] = layout::Layout::horizontal([layout::Constraint::Length(width)])
+            .flex(layout::Flex::Center)
+            .areas(row);
+        let block = Block::new()
+            .borders(Borders::ALL)
+            .border_style(theme.focused_border)
+            .title("Commands (Enter to run, Esc to cancel)");
+        let inner = block.inner(popup);
+        frame.render_widget(Clear, popup);
+        frame.render_widget(block, popup);
+        let [filter_area, list_area] =
+            layout::Layout::vertical([layout::Constraint::Length(1), layout::Constraint::Min(0)])
+                .areas(inner);
+        frame.render_widget(
+            Paragraph::new(Line::from(vec![
+                Span::styled("> ", theme.muted),
+                Span::raw(self.filter.as_str()),
+            ])),
+            filter_area,
+        );
+        frame.set_cursor(
+            filter_area.x + 2 + (self.filter.chars().count() as u16).min(filter_area.width),
+            filter_area.y,
+        );
+
+        let shown = self.shown();
+        let items = shown
+            .iter()
+            .map(|command| {
+                let title = command.title();
+                let key = match (command.key(), command.pane()) {
+                    (Some(key), Some(Focus::Results)) => format!("{} in results", key),
+                    (key, _) => key.unwrap_or_default().to_string(),
+                };
+                let gap = usize::from(list_area.width)
+                    .saturating_sub(title.chars().count() + key.chars().count())
+                    .max(1);
+                ListItem::new(Line::from(vec![
+                    Span::raw(title),
+                    Span::styled(format!("{}{}", " ".repeat(gap), key), theme.muted),
+                ]))
+            })
+            .collect::<Vec<_>>();
+        let list = List::new(items).highlight_style(theme.selected);
+        let mut state =
+            ListState::default().with_selected((!shown.is_empty()).then_some(self.selected));
+        frame.render_stateful_widget(list, list_area, &mut state);
+    }
+
+    /// Commands whose titles contain the filter's characters in order, best matches first
+    fn shown(&self) -> Vec<Command> {
+        let mut scored = self
+            .commands
+            .iter()
+            .filter_map(|command| Some((fuzzy_score(&self.filter, &command.title())?, *command)))
+            .collect::<Vec<_>>();
+        // Stable, so commands matching as well stay in the order they're listed
+        scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        scored.into_iter().map(|(_, command)| command).collect()
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Palette {
+        Palette::new()
+    }
+}
+
+/// How well `text` matches `pattern`, if it has every character of the pattern in order,
+/// ignoring case: higher for characters that run on from each other or start words
+fn fuzzy_score(pattern: &str, text: &str) -> Option<usize> {
+    let text = text.to_lowercase().chars().collect::<Vec<_>>();
+    let mut score = 0;
+    let mut position = 0;
+    let mut previous = None;
+    for wanted in pattern
+        .to_lowercase()
+        .chars()
+        .filter(|c| !c.is_whitespace())
+    {
+        let found = position + text[position..].iter().position(|&c| c == wanted)?;
+        if previous.is_some_and(|previous| previous + 1 == found) {
+            score += 2;
+        }
+        if found == 0 || text[found - 1] == ' ' {
+            score += 3;
+        }
+        score += 1;
+        previous = Some(found);
+        position = found + 1;
+    }
+    Some(score)
+}
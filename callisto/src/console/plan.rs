@@ -0,0 +1,197 @@
+use std::collections::BTreeSet;
+
+use ratatui::{
+    crossterm::event::{KeyCode, KeyEvent},
+    layout::{self, Constraint, Rect},
+    text::{Line, Span},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap},
+    Frame,
+};
+
+use super::{Binding, Theme};
+use crate::{PlanNode, ResultSet};
+
+/// Lines below the tree given to the selected operator's arguments in full
+const DETAILS_HEIGHT: u16 = 3;
+
+/// A query plan as a tree of operators, each collapsible into a line, with the rows and time each
+/// took when the plan comes from running the query
+pub struct Plan {
+    roots: Vec<PlanNode>,
+    /// Nodes whose inputs are hidden, each as the indices of the children leading to it from
+    /// the roots
+    collapsed: BTreeSet<Vec<usize>>,
+    /// Index of the selected node among those shown
+    selected: usize,
+}
+
+impl Plan {
+    pub fn new(roots: Vec<PlanNode>) -> Plan {
+        Plan {
+            roots,
+            collapsed: BTreeSet::new(),
+            selected: 0,
+        }
+    }
+
+    /// The plan an `EXPLAIN` statement returned, as text in its last column.
+    ///
+    /// Engines returning a plan a row, like DataFusion's logical and physical plans, have each
+    /// shown under the label in the row's first column.
+    pub fn from_explain(result: &ResultSet) -> anyhow::Result<Plan> {
+        let mut rows = Vec::new();
+        for batch in &result.batches {
+            rows.extend(crate::output_format::format_cells(batch, "")?);
+        }
+        let roots = match rows.as_slice() {
+            [] => anyhow::bail!("The result has no plan"),
+            [row] => PlanNode::parse(row.last().map_or("", String::as_str)),
+            _ => rows
+                .iter()
+                .map(|row| match row.as_slice() {
+                    [label, .., text] => PlanNode {
+                        name: label.clone(),
+                        children: PlanNode::parse(text),
+                        ..PlanNode::default()
+                    },
+                    _ => PlanNode {
+                        children: PlanNode::parse(row.last().map_or("", String::as_str)),
+                        ..PlanNode::default()
+                    },
+                })
+                .collect(),
+        };
+        Ok(Plan::new(roots))
+    }
+
+    /// Keys the plan handles, for the help overlay
+    pub const KEYS: &'static [Binding] = &[
+        ("j/k", "move"),
+        ("g/G", "first/last"),
+        ("l, Space", "expand"),
+        ("h", "collapse or go to parent"),
+        ("E", "expand all"),
+    ];
+
+    /// Move through the tree or collapse and expand its nodes in response to `key`
+    pub fn handle_key(&mut self, key: KeyEvent) {
+        let paths = self
+            .entries()
+            .into_iter()
+            .map(|(path, node)| (path, node.children.is_empty()))
+            .collect::<Vec<_>>();
+        let last = paths.len().saturating_sub(1);
+        let Some((path, leaf)) = paths.get(self.selected).cloned() else {
+            return;
+        };
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => self.selected = (self.selected + 1).min(last),
+            KeyCode::Home | KeyCode::Char('g') => self.selected = 0,
+            KeyCode::End | KeyCode::Char('G') => self.selected = last,
+            KeyCode::Right | KeyCode::Char('l') | KeyCode::Char(' ') => {
+                self.collapsed.remove(&path);
+            }
+            KeyCode::Left | KeyCode::Char('h') => {
+                // Collapse the node, or move up to its parent if there's nothing to collapse
+                if leaf || self.collapsed.contains(&path) {
+                    let parent = &path[..path.len() - 1];
+                    if let Some(index) = paths.iter().position(|(path, _)| path == parent) {
+                        self.selected = index;
+                    }
+                } else {
+                    self.collapsed.insert(path);
+                }
+            }
+            KeyCode::Char('E') => self.collapsed.clear(),
+            _ => {}
+        }
+    }
+
+    pub fn render(&self, frame: &mut Frame, area: Rect, block: Block, theme: &Theme) {
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+        let [tree_area, details_area] =
+            layout::Layout::vertical([Constraint::Min(0), Constraint::Length(DETAILS_HEIGHT)])
+                .areas(inner);
+
+        let entries = self.entries();
+        let items = entries
+            .iter()
+            .map(|(path, node)| {
+                let marker = match (node.children.is_empty(), self.collapsed.contains(path)) {
+                    (true, _) => ' ',
+                    (false, true) => '▸',
+                    (false, false) => '▾',
+                };
+                let mut spans = vec![Span::raw(format!(
+                    "{}{} {}",
+                    "  ".repeat(path.len() - 1),
+                    marker,
+                    node.name
+                ))];
+                let mut measured = Vec::new();
+                if let Some(rows) = node.rows {
+                    measured.push(format!("{} rows", rows));
+                }
+                if let Some(elapsed) = node.elapsed {
+                    measured.push(format!("{:.2?}", elapsed));
+                }
+                if !measured.is_empty() {
+                    spans.push(Span::styled(
+                        format!(" [{}]", measured.join(", ")),
+                        theme.status,
+                    ));
+                }
+                if !node.details.is_empty() {
+                    spans.push(Span::styled(format!(" {}", node.details), theme.muted));
+                }
+                ListItem::new(Line::from(spans))
+            })
+            .collect::<Vec<_>>();
+        let empty = items.is_empty();
+        let list = List::new(items).highlight_style(theme.selected);
+        let mut state = ListState::default().with_selected((!empty).then_some(self.selected));
+        frame.render_stateful_widget(list, tree_area, &mut state);
+
+        let details = entries
+            .get(self.selected)
+            .map(|(_, node)| node.details.clone())
+            .unwrap_or_default();
+        frame.render_widget(
+            Paragraph::new(details)
+                .style(theme.muted)
+                .wrap(Wrap { trim: false })
+                .block(
+                    Block::new()
+                        .borders(Borders::TOP)
+                        .border_style(theme.border),
+                ),
+            details_area,
+        );
+    }
+
+    /// Nodes shown, in order, each with its path from the roots, skipping the inputs of
+    /// collapsed nodes
+    fn entries(&self) -> Vec<(Vec<usize>, &PlanNode)> {
+        fn visit<'a>(
+            plan: &'a Plan,
+            nodes: &'a [PlanNode],
+            path: &mut Vec<usize>,
+            entries: &mut Vec<(Vec<usize>, &'a PlanNode)>,
+        ) {
+            for (index, node) in nodes.iter().enumerate() {
+                path.push(index);
+                entries.push((path.clone(), node));
+                if !plan.collapsed.contains(&*path) {
+                    visit(plan, &node.children, path, entries);
+                }
+                path.pop();
+            }
+        }
+
+        let mut entries = Vec::new();
+        visit(self, &self.roots, &mut Vec::new(), &mut entries);
+        entries
+    }
+}
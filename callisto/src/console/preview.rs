@@ -0,0 +1,144 @@
+use std::path::{Path, PathBuf};
+
+use arrow::datatypes::SchemaRef;
+use ratatui::{
+    layout::{self, Constraint, Rect},
+    text::{Line, Span},
+    widgets::{Block, Borders, Paragraph},
+    Frame,
+};
+
+use super::{app::bytes, Grid, Theme};
+use crate::{dataset, MaskPolicy, ResultSet};
+
+/// Rows read from a file to preview it
+const SAMPLE_ROWS: usize = 20;
+/// Most lines the schema takes up above the rows
+const SCHEMA_HEIGHT: u16 = 12;
+
+/// The first rows of a previewed file, read on a background task
+enum Sample {
+    Loading(tokio::sync::oneshot::Receiver<anyhow::Result<ResultSet>>),
+    Loaded(Grid),
+    Failed(String),
+}
+
+/// A data file's schema, what its metadata says of its size, and its first rows, shown before
+/// writing a query against it
+pub struct Preview {
+    path: PathBuf,
+    /// The file's schema and metadata, or why they couldn't be read
+    details: Result<(SchemaRef, dataset::Metadata), String>,
+    sample: Sample,
+}
+
+impl Preview {
+    /// Preview the file at `path`, reading its first rows on `runtime`'s blocking threads and
+    /// masking them with `masks`.
+    ///
+    /// The schema and metadata are read straight away, as they don't need more than the start or
+    /// end of the file.
+    pub fn new(path: PathBuf, masks: &MaskPolicy, runtime: &tokio::runtime::Handle) -> Preview {
+        let details = dataset::schema(&path)
+            .and_then(|schema| Ok((schema, dataset::metadata(&path)?)))
+            .map_err(|error| format!("{:#}", error));
+        let sample = match &details {
+            Ok(_) => {
+                let (sender, receiver) = tokio::sync::oneshot::channel();
+                let (read, masks) = (path.clone(), masks.clone());
+                runtime.spawn_blocking(move || {
+                    let sample = dataset::read(&read, Some(SAMPLE_ROWS)).and_then(|result| {
+                        Ok(ResultSet {
+                            schema: masks.schema(&result.schema),
+                            batches: result
+                                .batches
+                                .iter()
+                                .map(|batch| masks.apply(batch))
+                                .collect::<anyhow::Result<_>>()?,
+                        })
+                    });
+                    let _ = sender.send(sample);
+                });
+                Sample::Loading(receiver)
+            }
+            Err(error) => Sample::Failed(error.clone()),
+        };
+        Preview {
+            path,
+            details,
+            sample,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Pick up the file's first rows, once read
+    pub fn poll(&mut self) {
+        let Sample::Loading(receiver) = &mut self.sample else {
+            return;
+        };
+        self.sample = match receiver.try_recv() {
+            Ok(result) => match result.and_then(|result| Grid::new(&result)) {
+                Ok(grid) => Sample::Loaded(grid),
+                Err(error) => Sample::Failed(format!("{:#}", error)),
+            },
+            Err(tokio::sync::oneshot::error::TryRecvError::Empty) => return,
+            Err(tokio::sync::oneshot::error::TryRecvError::Closed) => {
+                Sample::Failed("Reading the file ended without a result".to_string())
+            }
+        };
+    }
+
+    pub fn render(&mut self, frame: &mut Frame, area: Rect, block: Block, theme: &Theme) {
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+        let lines = match &self.details {
+            Ok((schema, metadata)) => {
+                let mut summary = vec![bytes(metadata.bytes as usize)];
+                if let Some(rows) = metadata.rows {
+                    summary.push(format!("{} rows", rows));
+                }
+                if let Some(row_groups) = metadata.row_groups {
+                    summary.push(format!("{} row groups", row_groups));
+                }
+                std::iter::once(Line::styled(summary.join(" │ "), theme.status))
+                    .chain(schema.fields().iter().map(|field| {
+                        Line::from(vec![
+                            Span::raw(field.name().clone()),
+                            Span::styled(format!(": {}", field.data_type()), theme.muted),
+                        ])
+                    }))
+                    .collect::<Vec<_>>()
+            }
+            Err(error) => vec![Line::styled(error.clone(), theme.error)],
+        };
+        let [schema_area, sample_area] = layout::Layout::vertical([
+            Constraint::Length((lines.len() as u16).min(SCHEMA_HEIGHT)),
+            Constraint::Fill(1),
+        ])
+        .areas(inner);
+        frame.render_widget(Paragraph::new(lines), schema_area);
+
+        let block = Block::new()
+            .borders(Borders::TOP)
+            .border_style(theme.border)
+            .title(format!("First {} rows", SAMPLE_ROWS));
+        match &mut self.sample {
+            Sample::Loading(_) => frame.render_widget(
+                Paragraph::new("Reading…").style(theme.muted).block(block),
+                sample_area,
+            ),
+            Sample::Loaded(grid) => grid.render(frame, sample_area, block, theme),
+            // Already shown in place of the schema
+            Sample::Failed(_) if self.details.is_err() => {}
+            Sample::Failed(error) => frame.render_widget(
+                Paragraph::new(error.as_str())
+                    .style(theme.error)
+                    .block(block),
+                sample_area,
+            ),
+        }
+    }
+}
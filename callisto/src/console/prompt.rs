@@ -0,0 +1,70 @@
+use ratatui::{
+    crossterm::event::{KeyCode, KeyEvent},
+    layout::{self, Rect},
+    widgets::{Block, Borders, Clear, Paragraph},
+    Frame,
+};
+
+use super::Theme;
+
+/// What became of a prompt after a key
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PromptEvent {
+    /// Still being edited
+    Editing,
+    Submitted(String),
+    Cancelled,
+}
+
+/// A single line of input asked for in a popup, e.g. a path to export to
+#[derive(Clone, Debug)]
+pub struct Prompt {
+    title: String,
+    input: String,
+}
+
+impl Prompt {
+    pub fn new(title: impl Into<String>, initial: impl Into<String>) -> Prompt {
+        Prompt {
+            title: title.into(),
+            input: initial.into(),
+        }
+    }
+
+    pub fn input(&self) -> &str {
+        &self.input
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> PromptEvent {
+        match key.code {
+            KeyCode::Char(c) => self.input.push(c),
+            KeyCode::Backspace => {
+                self.input.pop();
+            }
+            KeyCode::Enter => return PromptEvent::Submitted(self.input.clone()),
+            KeyCode::Esc => return PromptEvent::Cancelled,
+            _ => {}
+        }
+        PromptEvent::Editing
+    }
+
+    /// Draw the prompt as a popup centred in `area`
+    pub fn render(&self, frame: &mut Frame, area: Rect, theme: &Theme) {
+        let width = area.width.min(60);
+        let [row] = layout::Layout::vertical([layout::Constraint::Length(3)])
+            .flex(layout::Flex::Center)
+            .areas(area);
+        let [popup] = layout::Layout::horizontal([layout::Constraint::Length(width)])
+            .flex(layout::Flex::Center)
+            .areas(row);
+        let block = Block::new()
+            .borders(Borders::ALL)
+            .border_style(theme.focused_border)
+            .title(format!("{} (Enter to confirm, Esc to cancel)", self.title));
+        let inner = block.inner(popup);
+        frame.render_widget(Clear, popup);
+        frame.render_widget(Paragraph::new(self.input.as_str()).block(block), popup);
+        let cursor = (self.input.chars().count() as u16).min(inner.width.saturating_sub(1));
+        frame.set_cursor(inner.x + cursor, inner.y);
+    }
+}
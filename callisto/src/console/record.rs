@@ -0,0 +1,123 @@
+use ratatui::{
+    crossterm::event::{KeyCode, KeyEvent},
+    layout::{self, Constraint, Rect},
+    text::{Line, Span},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap},
+    Frame,
+};
+
+use super::{Binding, Grid, Theme};
+
+/// Widest column names are padded to, so values line up
+const MAX_NAME_WIDTH: usize = 32;
+/// Lines below the fields given to the selected value in full
+const VALUE_HEIGHT: u16 = 4;
+
+/// The row under the grid's cursor turned on its side, a line a column, for reading rows too wide
+/// to scroll across
+#[derive(Default)]
+pub struct Record {
+    /// Index of the selected column
+    selected: usize,
+    /// Fields that fit in view as of the last render, for paging
+    page: usize,
+}
+
+impl Record {
+    pub fn new() -> Record {
+        Record::default()
+    }
+
+    /// Keys the row view handles, for the help overlay
+    pub const KEYS: &'static [Binding] = &[
+        ("j/k", "move"),
+        ("PgUp/PgDn", "page"),
+        ("g/G", "first/last"),
+        ("h/l", "previous/next row"),
+    ];
+
+    /// Move through the row's fields, or to the rows before or after it in `grid`, in response
+    /// to `key`
+    pub fn handle_key(&mut self, key: KeyEvent, grid: &mut Grid) {
+        let last = grid
+            .record()
+            .map_or(0, |(fields, _, _)| fields.len().saturating_sub(1));
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => self.selected = (self.selected + 1).min(last),
+            KeyCode::PageUp => self.selected = self.selected.saturating_sub(self.page),
+            KeyCode::PageDown => self.selected = (self.selected + self.page).min(last),
+            KeyCode::Home | KeyCode::Char('g') => self.selected = 0,
+            KeyCode::End | KeyCode::Char('G') => self.selected = last,
+            KeyCode::Left | KeyCode::Char('h') => {
+                grid.handle_key(KeyEvent::from(KeyCode::Up));
+            }
+            KeyCode::Right | KeyCode::Char('l') => {
+                grid.handle_key(KeyEvent::from(KeyCode::Down));
+            }
+            _ => {}
+        }
+    }
+
+    pub fn render(
+        &mut self,
+        frame: &mut Frame,
+        area: Rect,
+        block: Block,
+        grid: &Grid,
+        theme: &Theme,
+    ) {
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+        let Some((fields, row, rows)) = grid.record() else {
+            frame.render_widget(Paragraph::new("No row selected").style(theme.muted), inner);
+            return;
+        };
+        let [position_area, fields_area, value_area] = layout::Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Min(0),
+            Constraint::Length(VALUE_HEIGHT),
+        ])
+        .areas(inner);
+        self.page = usize::from(fields_area.height).max(1);
+        self.selected = self.selected.min(fields.len().saturating_sub(1));
+
+        frame.render_widget(
+            Paragraph::new(format!("Row {} of {}", row + 1, rows)).style(theme.status),
+            position_area,
+        );
+        let width = fields
+            .iter()
+            .map(|(field, _)| field.name().chars().count())
+            .max()
+            .unwrap_or(0)
+            .min(MAX_NAME_WIDTH);
+        let items = fields
+            .iter()
+            .map(|(field, value)| {
+                ListItem::new(Line::from(vec![
+                    Span::styled(format!("{:<width$} ", field.name()), theme.muted),
+                    Span::raw(value.to_string()),
+                    Span::styled(format!("  {}", field.data_type()), theme.muted),
+                ]))
+            })
+            .collect::<Vec<_>>();
+        let empty = items.is_empty();
+        let list = List::new(items).highlight_style(theme.selected);
+        let mut state = ListState::default().with_selected((!empty).then_some(self.selected));
+        frame.render_stateful_widget(list, fields_area, &mut state);
+
+        let value = fields
+            .get(self.selected)
+            .map(|(_, value)| value.to_string())
+            .unwrap_or_default();
+        frame.render_widget(
+            Paragraph::new(value).wrap(Wrap { trim: false }).block(
+                Block::new()
+                    .borders(Borders::TOP)
+                    .border_style(theme.border),
+            ),
+            value_area,
+        );
+    }
+}
@@ -0,0 +1,108 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::Engine;
+
+/// Session used when `--session` isn't given
+pub const DEFAULT_SESSION: &str = "default";
+
+/// Console state kept across launches: the SQL being edited, which panes are shown and how big,
+/// and the engine queries run on.
+///
+/// Sessions are stored by name as JSON under `$XDG_DATA_HOME/callisto/sessions`, saved when the
+/// console exits and restored when it's next launched with the same name.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    /// Name of the engine queries ran on, if known
+    pub engine: Option<String>,
+    /// Text of the SQL editor
+    pub editor: String,
+    pub show_catalog: bool,
+    pub show_files: bool,
+    pub show_objects: bool,
+    pub show_notifications: bool,
+    pub show_log: bool,
+    /// Share of the height beside the sidebar the editor takes, in percent
+    pub editor_height: u16,
+}
+
+impl Default for Session {
+    fn default() -> Session {
+        Session {
+            engine: None,
+            editor: String::new(),
+            show_catalog: true,
+            show_files: false,
+            show_objects: false,
+            show_notifications: false,
+            show_log: false,
+            editor_height: super::app::EDITOR_HEIGHT,
+        }
+    }
+}
+
+impl Session {
+    /// Where the session called `name` is stored, or `None` if there's no data directory
+    pub fn path(name: &str) -> anyhow::Result<Option<PathBuf>> {
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '-' || c == '_' || c == '.')
+            || name.starts_with('.')
+        {
+            anyhow::bail!(
+                "Invalid session name '{}': use letters, digits, '-', '_', and '.'",
+                name
+            );
+        }
+        Ok(dirs::data_dir().map(|dir| {
+            dir.join("callisto")
+                .join("sessions")
+                .join(format!("{}.json", name))
+        }))
+    }
+
+    /// Load the session called `name`, or a fresh one if it hasn't been saved before
+    pub fn load(name: &str) -> anyhow::Result<Session> {
+        use anyhow::Context as _;
+
+        let Some(path) = Session::path(name)? else {
+            return Ok(Session::default());
+        };
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(Session::default())
+            }
+            Err(error) => {
+                return Err(error)
+                    .with_context(|| format!("Failed to read session file {}", path.display()))
+            }
+        };
+        serde_json::from_str(&text)
+            .with_context(|| format!("Failed to parse session file {}", path.display()))
+    }
+
+    /// Save this session as `name`, replacing whatever was saved under it
+    pub fn save(&self, name: &str) -> anyhow::Result<()> {
+        use anyhow::Context as _;
+
+        let Some(path) = Session::path(name)? else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        std::fs::write(&path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write session file {}", path.display()))
+    }
+
+    /// The engine queries ran on, if it's one still known
+    pub fn engine(&self) -> Option<Engine> {
+        let name = self.engine.as_deref()?;
+        Engine::ALL.into_iter().find(|engine| engine.name() == name)
+    }
+}
@@ -0,0 +1,213 @@
+use std::collections::HashMap;
+
+use arrow::array::{Array as _, ArrayRef};
+use ratatui::{
+    layout::{self, Constraint, Rect},
+    text::Line,
+    widgets::{Block, Paragraph, Sparkline},
+    Frame,
+};
+
+use super::Theme;
+use crate::ResultSet;
+
+/// Most frequent values listed for columns that aren't numeric
+const TOP_VALUES: usize = 10;
+/// Widest the bars beside the most frequent values are drawn
+const BAR_WIDTH: usize = 30;
+/// Most ranges numeric values are counted in for the histogram
+const HISTOGRAM_BINS: usize = 20;
+
+/// Statistics on the distribution of one column of a result
+pub struct Summary {
+    name: String,
+    data_type: arrow::datatypes::DataType,
+    rows: usize,
+    nulls: usize,
+    distinct: usize,
+    min: Option<String>,
+    max: Option<String>,
+    /// Values of a numeric column that aren't null, in ascending order
+    sorted: Option<Vec<f64>>,
+    /// Most frequent values with how often they occur, most frequent first
+    top: Vec<(String, usize)>,
+}
+
+impl Summary {
+    /// Summarize the `column`th column of `result`
+    pub fn new(result: &ResultSet, column: usize) -> anyhow::Result<Summary> {
+        let field = result.schema.field(column);
+        let arrays = result
+            .batches
+            .iter()
+            .map(|batch| batch.column(column).as_ref())
+            .collect::<Vec<_>>();
+        let array = if arrays.is_empty() {
+            arrow::array::new_empty_array(field.data_type())
+        } else {
+            arrow::compute::concat(&arrays)?
+        };
+        let mut cells = Vec::with_capacity(array.len());
+        for batch in &result.batches {
+            let column = batch.project(&[column])?;
+            cells.extend(
+                crate::output_format::format_cells(&column, "NULL")?
+                    .into_iter()
+                    .map(|mut row| row.remove(0)),
+            );
+        }
+
+        let mut counts = HashMap::<&str, usize>::new();
+        for (index, cell) in cells.iter().enumerate() {
+            if array.is_valid(index) {
+                *counts.entry(cell).or_default() += 1;
+            }
+        }
+        let mut top = counts
+            .iter()
+            .map(|(value, count)| (value.to_string(), *count))
+            .collect::<Vec<_>>();
+        top.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        top.truncate(TOP_VALUES);
+
+        // Nulls sort last, so the non-null values run from the first index to the last before them
+        let non_null = array.len() - array.null_count();
+        let options = arrow::compute::SortOptions {
+            descending: false,
+            nulls_first: false,
+        };
+        let (min, max) = match arrow::compute::sort_to_indices(&array, Some(options), None) {
+            Ok(indices) if non_null > 0 => (
+                Some(cells[indices.value(0) as usize].clone()),
+                Some(cells[indices.value(non_null - 1) as usize].clone()),
+            ),
+            // Values of types that can't be compared have no least or greatest
+            _ => (None, None),
+        };
+
+        let sorted = if field.data_type().is_numeric() {
+            let mut values = floats(&array)?;
+            values.sort_by(f64::total_cmp);
+            Some(values)
+        } else {
+            None
+        };
+        Ok(Summary {
+            name: field.name().clone(),
+            data_type: field.data_type().clone(),
+            rows: array.len(),
+            nulls: array.null_count(),
+            distinct: counts.len(),
+            min,
+            max,
+            sorted,
+            top,
+        })
+    }
+
+    pub fn render(&self, frame: &mut Frame, area: Rect, block: Block, theme: &Theme) {
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+
+        let mut lines = vec![
+            Line::from(format!("{}: {}", self.name, self.data_type)),
+            Line::from(format!(
+                "{} rows, {} null, {} distinct",
+                self.rows, self.nulls, self.distinct
+            )),
+            Line::from(format!(
+                "min {}, max {}",
+                self.min.as_deref().unwrap_or("-"),
+                self.max.as_deref().unwrap_or("-")
+            )),
+        ];
+        match &self.sorted {
+            Some(sorted) if !sorted.is_empty() => {
+                let quantiles = [0.25, 0.5, 0.75, 0.9, 0.99]
+                    .iter()
+                    .map(|&q| format!("p{} {}", (q * 100.0) as u32, quantile(sorted, q)))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                lines.push(Line::from(quantiles));
+            }
+            _ => {
+                lines.push(Line::from(""));
+                let most = self.top.first().map_or(1, |(_, count)| *count);
+                let width = self
+                    .top
+                    .iter()
+                    .map(|(value, _)| value.chars().count())
+                    .max()
+                    .unwrap_or(0)
+                    .min(usize::from(inner.width) / 2);
+                lines.extend(self.top.iter().map(|(value, count)| {
+                    let value = value.chars().take(width).collect::<String>();
+                    Line::from(format!(
+                        "{:width$} {} {}",
+                        value,
+                        "█".repeat((count * BAR_WIDTH).div_ceil(most)),
+                        count,
+                        width = width
+                    ))
+                }));
+            }
+        }
+        let [stats_area, histogram_area] = layout::Layout::vertical([
+            Constraint::Length(lines.len() as u16 + 1),
+            Constraint::Min(0),
+        ])
+        .areas(inner);
+        frame.render_widget(Paragraph::new(lines), stats_area);
+
+        if let Some(sorted) = &self.sorted {
+            // Each range is drawn as wide as fits, as the sparkline gives each value a column
+            let width = usize::from(histogram_area.width);
+            let bins = width.min(HISTOGRAM_BINS);
+            let histogram = histogram(sorted, bins)
+                .into_iter()
+                .flat_map(|count| std::iter::repeat(count).take(width / bins.max(1)))
+                .collect::<Vec<_>>();
+            frame.render_widget(
+                Sparkline::default().data(&histogram).style(theme.series(0)),
+                histogram_area,
+            );
+        }
+    }
+}
+
+/// Values of a numeric `column` that aren't null, as floats
+fn floats(column: &ArrayRef) -> anyhow::Result<Vec<f64>> {
+    let column = arrow::compute::cast(column, &arrow::datatypes::DataType::Float64)?;
+    let column = column
+        .as_any()
+        .downcast_ref::<arrow::array::Float64Array>()
+        .ok_or_else(|| anyhow::anyhow!("Numeric column didn't cast to floats"))?;
+    Ok(column.iter().flatten().collect())
+}
+
+/// The value `q` of the way through `sorted`, by nearest rank
+fn quantile(sorted: &[f64], q: f64) -> f64 {
+    let rank = ((q * sorted.len() as f64).ceil() as usize).clamp(1, sorted.len());
+    sorted[rank - 1]
+}
+
+/// How many of `sorted` fall in each of `bins` equal ranges between the least and greatest
+fn histogram(sorted: &[f64], bins: usize) -> Vec<u64> {
+    let mut counts = vec![0; bins];
+    let (Some(&low), Some(&high)) = (sorted.first(), sorted.last()) else {
+        return counts;
+    };
+    if bins == 0 {
+        return counts;
+    }
+    let span = high - low;
+    for value in sorted {
+        let bin = if span > 0.0 {
+            (((value - low) / span) * bins as f64) as usize
+        } else {
+            0
+        };
+        counts[bin.min(bins - 1)] += 1;
+    }
+    counts
+}
@@ -0,0 +1,241 @@
+use std::str::FromStr as _;
+
+use ratatui::style::{Color, Modifier, Style};
+
+use crate::{highlight::TokenClass, ConsoleConfig, ThemeConfig};
+
+/// Themes built in, by name
+pub const BUILT_IN_THEMES: [&str; 3] = ["default", "light", "none"];
+
+/// Styles the console is drawn with
+#[derive(Clone, Debug)]
+pub struct Theme {
+    pub border: Style,
+    /// Border of the pane keys are sent to
+    pub focused_border: Style,
+    /// Selected cell, row, or entry
+    pub selected: Style,
+    /// Cells matching a search
+    pub matched: Style,
+    pub header: Style,
+    pub error: Style,
+    pub warning: Style,
+    /// Lines describing a pane's state, like the grid's position
+    pub status: Style,
+    /// Text of less interest, like columns that can't be charted
+    pub muted: Style,
+    pub keyword: Style,
+    pub string: Style,
+    pub number: Style,
+    pub comment: Style,
+    pub table: Style,
+    /// Unterminated strings and comments and unbalanced parentheses
+    pub invalid: Style,
+    /// Rows a query returns that it didn't the time before, those it no longer does, and values
+    /// that differ between them
+    pub added: Style,
+    pub removed: Style,
+    pub changed: Style,
+    /// Colors given to the lines and bars of charts, in turn
+    pub series: Vec<Color>,
+}
+
+impl Theme {
+    /// The theme named by `config`, built in or defined there.
+    ///
+    /// Setting `NO_COLOR` in the environment picks the theme without colors whatever is
+    /// configured.
+    pub fn load(config: &ConsoleConfig) -> anyhow::Result<Theme> {
+        if std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty()) {
+            return Theme::built_in("none");
+        }
+        match config.themes.get(&config.theme) {
+            Some(theme) => Theme::define(theme),
+            None => Theme::built_in(&config.theme),
+        }
+    }
+
+    /// The built-in theme called `name`
+    pub fn built_in(name: &str) -> anyhow::Result<Theme> {
+        let reversed = Style::new().add_modifier(Modifier::REVERSED);
+        let bold = Style::new().add_modifier(Modifier::BOLD);
+        let fg = |color: Color| Style::new().fg(color);
+        Ok(match name {
+            "default" => Theme {
+                border: Style::new(),
+                focused_border: fg(Color::Cyan),
+                selected: reversed,
+                matched: fg(Color::Black).bg(Color::Yellow),
+                header: bold,
+                error: fg(Color::Red),
+                warning: fg(Color::Yellow),
+                status: fg(Color::Gray),
+                muted: fg(Color::DarkGray),
+                keyword: fg(Color::Magenta).add_modifier(Modifier::BOLD),
+                string: fg(Color::Green),
+                number: fg(Color::Yellow),
+                comment: fg(Color::DarkGray).add_modifier(Modifier::ITALIC),
+                table: fg(Color::Cyan),
+                invalid: fg(Color::Red).add_modifier(Modifier::UNDERLINED),
+                added: fg(Color::Green),
+                removed: fg(Color::Red),
+                changed: fg(Color::Yellow),
+                series: vec![
+                    Color::Cyan,
+                    Color::Yellow,
+                    Color::Magenta,
+                    Color::Green,
+                    Color::Red,
+                    Color::Blue,
+                ],
+            },
+            "light" => Theme {
+                border: fg(Color::Gray),
+                focused_border: fg(Color::Blue),
+                selected: fg(Color::Black).bg(Color::LightBlue),
+                matched: fg(Color::Black).bg(Color::LightYellow),
+                header: bold.fg(Color::Black),
+                error: fg(Color::Red),
+                warning: fg(Color::Magenta),
+                status: fg(Color::DarkGray),
+                muted: fg(Color::Gray),
+                keyword: fg(Color::Blue).add_modifier(Modifier::BOLD),
+                string: fg(Color::Green),
+                number: fg(Color::Magenta),
+                comment: fg(Color::Gray).add_modifier(Modifier::ITALIC),
+                table: fg(Color::Cyan),
+                invalid: fg(Color::Red).add_modifier(Modifier::UNDERLINED),
+                added: fg(Color::Green),
+                removed: fg(Color::Red),
+                changed: fg(Color::Magenta),
+                series: vec![
+                    Color::Blue,
+                    Color::Red,
+                    Color::Green,
+                    Color::Magenta,
+                    Color::Cyan,
+                    Color::Black,
+                ],
+            },
+            // For terminals without colors, telling things apart by weight and reversal alone
+            "none" => Theme {
+                border: Style::new(),
+                focused_border: bold,
+                selected: reversed,
+                matched: bold.add_modifier(Modifier::UNDERLINED),
+                header: bold,
+                error: bold,
+                warning: bold,
+                status: Style::new(),
+                muted: Style::new().add_modifier(Modifier::DIM),
+                keyword: bold,
+                string: Style::new(),
+                number: Style::new(),
+                comment: Style::new().add_modifier(Modifier::DIM),
+                table: Style::new(),
+                invalid: Style::new().add_modifier(Modifier::UNDERLINED),
+                added: bold,
+                removed: Style::new().add_modifier(Modifier::CROSSED_OUT),
+                changed: Style::new().add_modifier(Modifier::UNDERLINED),
+                series: Vec::new(),
+            },
+            _ => anyhow::bail!(
+                "Unknown theme {:?}, expected one defined in config or one of {}",
+                name,
+                BUILT_IN_THEMES.join(", ")
+            ),
+        })
+    }
+
+    /// A theme defined in config, as its base theme with the styles it sets
+    fn define(config: &ThemeConfig) -> anyhow::Result<Theme> {
+        use anyhow::Context as _;
+
+        let mut theme = Theme::built_in(config.base.as_deref().unwrap_or("default"))?;
+        for (name, style, spec) in [
+            ("border", &mut theme.border, &config.border),
+            (
+                "focused_border",
+                &mut theme.focused_border,
+                &config.focused_border,
+            ),
+            ("selected", &mut theme.selected, &config.selected),
+            ("matched", &mut theme.matched, &config.matched),
+            ("header", &mut theme.header, &config.header),
+            ("error", &mut theme.error, &config.error),
+            ("warning", &mut theme.warning, &config.warning),
+            ("status", &mut theme.status, &config.status),
+            ("muted", &mut theme.muted, &config.muted),
+            ("keyword", &mut theme.keyword, &config.keyword),
+            ("string", &mut theme.string, &config.string),
+            ("number", &mut theme.number, &config.number),
+            ("comment", &mut theme.comment, &config.comment),
+            ("table", &mut theme.table, &config.table),
+            ("invalid", &mut theme.invalid, &config.invalid),
+            ("added", &mut theme.added, &config.added),
+            ("removed", &mut theme.removed, &config.removed),
+            ("changed", &mut theme.changed, &config.changed),
+        ] {
+            if let Some(spec) = spec {
+                *style = parse_style(spec)
+                    .with_context(|| format!("Invalid {} style in theme", name))?;
+            }
+        }
+        if let Some(series) = &config.series {
+            theme.series = series
+                .iter()
+                .map(|color| parse_color(color))
+                .collect::<anyhow::Result<_>>()
+                .context("Invalid series color in theme")?;
+        }
+        Ok(theme)
+    }
+
+    /// Style of the `index`th line or bar of a chart
+    pub fn series(&self, index: usize) -> Style {
+        match self.series.len() {
+            0 => Style::new(),
+            colors => Style::new().fg(self.series[index % colors]),
+        }
+    }
+
+    /// Style of SQL highlighted as `class`
+    pub(crate) fn token(&self, class: TokenClass) -> Style {
+        match class {
+            TokenClass::Plain => Style::new(),
+            TokenClass::Keyword => self.keyword,
+            TokenClass::String => self.string,
+            TokenClass::Number => self.number,
+            TokenClass::Comment => self.comment,
+            TokenClass::Table => self.table,
+            TokenClass::Error => self.invalid,
+        }
+    }
+}
+
+/// A style written as modifiers and a color, optionally followed by `on` and a background color,
+/// e.g. `bold yellow on blue`
+fn parse_style(spec: &str) -> anyhow::Result<Style> {
+    let mut style = Style::new();
+    let mut words = spec.split_whitespace();
+    while let Some(word) = words.next() {
+        style = match word.to_lowercase().as_str() {
+            "bold" => style.add_modifier(Modifier::BOLD),
+            "dim" => style.add_modifier(Modifier::DIM),
+            "italic" => style.add_modifier(Modifier::ITALIC),
+            "underlined" => style.add_modifier(Modifier::UNDERLINED),
+            "reversed" => style.add_modifier(Modifier::REVERSED),
+            "on" => match words.next() {
+                Some(color) => style.bg(parse_color(color)?),
+                None => anyhow::bail!("Expected a background color after \"on\" in {:?}", spec),
+            },
+            _ => style.fg(parse_color(word)?),
+        };
+    }
+    Ok(style)
+}
+
+/// A color by name, like `lightblue`, as `#rrggbb`, or as an index into the terminal's palette
+fn parse_color(color: &str) -> anyhow::Result<Color> {
+    Color::from_str(color).map_err(|_| anyhow::anyhow!("Unknown color {:?}", color))
+}
@@ -0,0 +1,211 @@
+use std::{fs::File, io::BufReader, path::Path, sync::Arc};
+
+use anyhow::Context as _;
+use arrow::datatypes::SchemaRef;
+
+use crate::ResultSet;
+
+/// Extensions of the files read as datasets
+pub const EXTENSIONS: [&str; 4] = ["parquet", "csv", "json", "ndjson"];
+/// Rows read to infer the schema of files that don't record one
+const INFER_ROWS: usize = 1000;
+
+/// Formats of files read as datasets
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Format {
+    Parquet,
+    Csv,
+    /// Newline-delimited JSON objects
+    Json,
+}
+
+impl Format {
+    fn of(path: &Path) -> Option<Format> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "parquet" => Some(Format::Parquet),
+            "csv" => Some(Format::Csv),
+            "json" | "ndjson" => Some(Format::Json),
+            _ => None,
+        }
+    }
+}
+
+/// Whether `path` has the extension of a file that can be read as a dataset
+pub fn is_dataset(path: &Path) -> bool {
+    Format::of(path).is_some()
+}
+
+/// The schema of the dataset at `path`, inferred from its first rows if it doesn't record one
+pub fn schema(path: &Path) -> anyhow::Result<SchemaRef> {
+    let file = open(path)?;
+    Ok(match format(path)? {
+        Format::Parquet => {
+            parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder::try_new(file)?
+                .schema()
+                .clone()
+        }
+        Format::Csv => Arc::new(
+            arrow::csv::reader::Format::default()
+                .with_header(true)
+                .infer_schema(file, Some(INFER_ROWS))?
+                .0,
+        ),
+        Format::Json => Arc::new(
+            arrow::json::reader::infer_json_schema_from_seekable(
+                BufReader::new(file),
+                Some(INFER_ROWS),
+            )?
+            .0,
+        ),
+    })
+}
+
+/// What a dataset's file says of its size without reading its rows
+#[derive(Clone, Copy, Debug)]
+pub struct Metadata {
+    /// Size of the file, in bytes
+    pub bytes: u64,
+    /// Number of rows, if the format records it
+    pub rows: Option<usize>,
+    /// Number of Parquet row groups
+    pub row_groups: Option<usize>,
+}
+
+/// What the file at `path` says of the dataset's size, from its metadata alone
+pub fn metadata(path: &Path) -> anyhow::Result<Metadata> {
+    let file = open(path)?;
+    let bytes = file
+        .metadata()
+        .with_context(|| format!("Failed to read metadata of {}", path.display()))?
+        .len();
+    Ok(match format(path)? {
+        Format::Parquet => {
+            let builder =
+                parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder::try_new(file)?;
+            let metadata = builder.metadata();
+            Metadata {
+                bytes,
+                rows: Some(metadata.file_metadata().num_rows() as usize),
+                row_groups: Some(metadata.num_row_groups()),
+            }
+        }
+        Format::Csv | Format::Json => Metadata {
+            bytes,
+            rows: None,
+            row_groups: None,
+        },
+    })
+}
+
+/// Read the dataset at `path` into memory, or only its first `limit` rows
+pub fn read(path: &Path, limit: Option<usize>) -> anyhow::Result<ResultSet> {
+    let schema = schema(path)?;
+    let file = open(path)?;
+    let batches: Box<dyn Iterator<Item = Result<_, arrow::error::ArrowError>>> = match format(path)?
+    {
+        Format::Parquet => {
+            let mut builder =
+                parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder::try_new(file)?;
+            if let Some(limit) = limit {
+                builder = builder.with_limit(limit);
+            }
+            Box::new(builder.build()?)
+        }
+        Format::Csv => Box::new(
+            arrow::csv::ReaderBuilder::new(schema.clone())
+                .with_header(true)
+                .build(file)?,
+        ),
+        Format::Json => {
+            Box::new(arrow::json::ReaderBuilder::new(schema.clone()).build(BufReader::new(file))?)
+        }
+    };
+
+    let mut result = ResultSet {
+        schema,
+        batches: Vec::new(),
+    };
+    let mut remaining = limit.unwrap_or(usize::MAX);
+    for batch in batches {
+        if remaining == 0 {
+            break;
+        }
+        let batch = batch.with_context(|| format!("Failed to read {}", path.display()))?;
+        let batch = batch.slice(0, batch.num_rows().min(remaining));
+        remaining -= batch.num_rows();
+        result.batches.push(batch);
+    }
+    Ok(result)
+}
+
+/// Read a CSV or JSON dataset already in memory as `buffer`, its format told by `path`.
+///
+/// Parquet isn't read this way, as its readers fetch just the parts of a file they need.
+pub fn read_buffer(path: &Path, buffer: &[u8]) -> anyhow::Result<ResultSet> {
+    use std::io::Cursor;
+
+    let (schema, batches): (
+        _,
+        Box<dyn Iterator<Item = Result<_, arrow::error::ArrowError>>>,
+    ) = match format(path)? {
+        Format::Parquet => anyhow::bail!("Parquet must be read from a file or object store"),
+        Format::Csv => {
+            let (schema, _) = arrow::csv::reader::Format::default()
+                .with_header(true)
+                .infer_schema(Cursor::new(buffer), Some(INFER_ROWS))?;
+            let schema = Arc::new(schema);
+            let reader = arrow::csv::ReaderBuilder::new(schema.clone())
+                .with_header(true)
+                .build(Cursor::new(buffer))?;
+            (schema, Box::new(reader))
+        }
+        Format::Json => {
+            let (schema, _) = arrow::json::reader::infer_json_schema_from_seekable(
+                Cursor::new(buffer),
+                Some(INFER_ROWS),
+            )?;
+            let schema = Arc::new(schema);
+            let reader =
+                arrow::json::ReaderBuilder::new(schema.clone()).build(Cursor::new(buffer))?;
+            (schema, Box::new(reader))
+        }
+    };
+    Ok(ResultSet {
+        schema,
+        batches: batches
+            .collect::<Result<_, _>>()
+            .with_context(|| format!("Failed to read {}", path.display()))?,
+    })
+}
+
+/// A table name for the dataset at `path`, from its file name without the extension
+pub fn table_name(path: &Path) -> String {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let name = stem
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect::<String>();
+    if name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        name
+    } else {
+        format!("t_{}", name)
+    }
+}
+
+fn open(path: &Path) -> anyhow::Result<File> {
+    File::open(path).with_context(|| format!("Failed to open {}", path.display()))
+}
+
+fn format(path: &Path) -> anyhow::Result<Format> {
+    Format::of(path).ok_or_else(|| {
+        anyhow::anyhow!(
+            "Cannot tell what format {} is in, expected one of the extensions {}",
+            path.display(),
+            EXTENSIONS.join(", ")
+        )
+    })
+}
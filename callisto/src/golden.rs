@@ -0,0 +1,427 @@
+//! Regression checks of queries against the results they're expected to return.
+//!
+//! Each `.sql` or `.prql` file of a directory is a check, its expected result kept beside it in a
+//! file of the same name but a dataset's extension, e.g. `revenue.sql` and `revenue.csv` or
+//! `revenue.parquet`. The result of a check's last statement is compared with it cell by cell:
+//! numbers by value, within a tolerance, and anything else by its text.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Context as _;
+use arrow::array::{Array as _, ArrayRef, Float64Array};
+use arrow::datatypes::DataType;
+
+use crate::{dataset, EngineInterface, Language, ResultSet};
+
+/// Most differing cells described when a check fails
+const DIFFERENCES_SHOWN: usize = 5;
+
+/// How results are compared with those expected
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GoldenOptions {
+    /// Most two numbers may differ by and still match
+    pub tolerance: f64,
+    /// Most two numbers may differ by as a fraction of the expected one and still match
+    pub relative_tolerance: f64,
+    /// Compare the rows as sets rather than in order, for queries without an ORDER BY
+    pub ignore_order: bool,
+    /// Write each check's result as its expected one rather than comparing them
+    pub update: bool,
+}
+
+/// How a check fared
+#[derive(Clone, Debug, PartialEq)]
+pub enum Outcome {
+    Passed,
+    /// Its result was written as the expected one, to this file
+    Updated(PathBuf),
+    /// Its result differed from the expected one, as described
+    Failed(String),
+}
+
+/// The checks in `dir` and its subdirectories, in order of their paths
+pub fn checks(dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut checks = Vec::new();
+    let entries =
+        std::fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))?;
+    for entry in entries {
+        let path = entry?.path();
+        if path.is_dir() {
+            checks.extend(self::checks(&path)?);
+        } else if path
+            .extension()
+            .is_some_and(|extension| extension == "sql" || extension == "prql")
+        {
+            checks.push(path);
+        }
+    }
+    checks.sort();
+    Ok(checks)
+}
+
+/// The file holding the expected result of the check `query`, if there is one
+pub fn expected_path(query: &Path) -> Option<PathBuf> {
+    dataset::EXTENSIONS
+        .iter()
+        .map(|extension| query.with_extension(extension))
+        .find(|path| path.exists())
+}
+
+/// Run the check `query` on `engine` and compare its result with the one expected
+pub async fn check(
+    engine: &dyn EngineInterface,
+    query: &Path,
+    options: &GoldenOptions,
+) -> anyhow::Result<Outcome> {
+    use futures::stream::TryStreamExt as _;
+
+    let text = std::fs::read_to_string(query)
+        .with_context(|| format!("Failed to read {}", query.display()))?;
+    let sql = crate::prql::to_sql(&text, Language::of_path(query), engine.kind())?;
+    let mut executions = engine.execute(&sql).await?;
+    let Some(last) = executions.pop() else {
+        anyhow::bail!("{} has no statements", query.display());
+    };
+    for execution in executions {
+        execution.stream.try_for_each(|_| async { Ok(()) }).await?;
+    }
+    let actual = ResultSet {
+        schema: last.schema,
+        batches: last.stream.try_collect().await?,
+    };
+
+    let expected_path = expected_path(query);
+    if options.update {
+        let path = expected_path.unwrap_or_else(|| query.with_extension("csv"));
+        actual.export(&path)?;
+        return Ok(Outcome::Updated(path));
+    }
+    let Some(expected_path) = expected_path else {
+        return Ok(Outcome::Failed(format!(
+            "There's no expected result beside it, e.g. {}; write one with --update",
+            query.with_extension("csv").display()
+        )));
+    };
+    let expected = dataset::read(&expected_path, None)?;
+    Ok(match compare(&actual, &expected, options)? {
+        Some(difference) => Outcome::Failed(difference),
+        None => Outcome::Passed,
+    })
+}
+
+/// A value of a result, as it's compared
+#[derive(Clone, Debug, PartialEq)]
+enum Cell {
+    Null,
+    Number(f64),
+    Text(String),
+}
+
+impl Cell {
+    fn matches(&self, expected: &Cell, options: &GoldenOptions) -> bool {
+        match (self, expected) {
+            (Cell::Number(actual), Cell::Number(expected)) => {
+                let difference = (actual - expected).abs();
+                actual == expected
+                    || (actual.is_nan() && expected.is_nan())
+                    || difference <= options.tolerance
+                    || difference <= options.relative_tolerance * expected.abs()
+            }
+            // A number may have been read from CSV as text, e.g. in a column of mixed values
+            (Cell::Number(_), Cell::Text(text)) => text
+                .parse()
+                .is_ok_and(|expected| self.matches(&Cell::Number(expected), options)),
+            (Cell::Text(_), Cell::Number(_)) => expected.matches(self, options),
+            // CSV doesn't tell an empty string from a missing one
+            (Cell::Null, Cell::Text(text)) | (Cell::Text(text), Cell::Null) => text.is_empty(),
+            (actual, expected) => actual == expected,
+        }
+    }
+
+    /// How the cell sorts among others, to compare rows regardless of their order: nulls first,
+    /// then numbers by value, then text. Text of a number sorts as the number, and empty text as
+    /// null, as each would match it.
+    fn order(&self, other: &Cell) -> std::cmp::Ordering {
+        fn key(cell: &Cell) -> (u8, f64, &str) {
+            match cell {
+                Cell::Null => (0, 0.0, ""),
+                Cell::Number(number) => (1, *number, ""),
+                Cell::Text(text) if text.is_empty() => (0, 0.0, ""),
+                Cell::Text(text) => match text.parse() {
+                    Ok(number) => (1, number, ""),
+                    Err(_) => (2, 0.0, text.as_str()),
+                },
+            }
+        }
+        let (this, other) = (key(self), key(other));
+        this.0
+            .cmp(&other.0)
+            .then(this.1.total_cmp(&other.1))
+            .then(this.2.cmp(other.2))
+    }
+}
+
+impl std::fmt::Display for Cell {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Cell::Null => f.write_str("null"),
+            Cell::Number(number) => write!(f, "{}", number),
+            Cell::Text(text) => write!(f, "{:?}", text),
+        }
+    }
+}
+
+/// The rows of `result`, each a cell per column
+fn rows(result: &ResultSet) -> anyhow::Result<Vec<Vec<Cell>>> {
+    let options = arrow::util::display::FormatOptions::default();
+    let mut rows = Vec::new();
+    for batch in &result.batches {
+        let columns = batch
+            .columns()
+            .iter()
+            .map(|column| column_cells(column, &options))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        rows.extend((0..batch.num_rows()).map(|row| {
+            columns
+                .iter()
+                .map(|column| column[row].clone())
+                .collect::<Vec<_>>()
+        }));
+    }
+    Ok(rows)
+}
+
+/// The cells of `column`: numbers of numeric columns and the text of any other
+fn column_cells(
+    column: &ArrayRef,
+    options: &arrow::util::display::FormatOptions,
+) -> anyhow::Result<Vec<Cell>> {
+    if column.data_type().is_numeric() {
+        let numbers = arrow::compute::cast(column, &DataType::Float64)?;
+        let numbers = numbers
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .context("Numbers weren't cast to Float64")?;
+        return Ok(numbers
+            .iter()
+            .map(|number| number.map_or(Cell::Null, Cell::Number))
+            .collect());
+    }
+    let formatter = arrow::util::display::ArrayFormatter::try_new(column.as_ref(), options)?;
+    Ok((0..column.len())
+        .map(|row| {
+            if column.is_valid(row) {
+                Cell::Text(formatter.value(row).to_string())
+            } else {
+                Cell::Null
+            }
+        })
+        .collect())
+}
+
+/// How `actual` differs from `expected`, if it does
+fn compare(
+    actual: &ResultSet,
+    expected: &ResultSet,
+    options: &GoldenOptions,
+) -> anyhow::Result<Option<String>> {
+    let names = |result: &ResultSet| {
+        result
+            .schema
+            .fields()
+            .iter()
+            .map(|field| field.name().clone())
+            .collect::<Vec<_>>()
+    };
+    let (actual_names, expected_names) = (names(actual), names(expected));
+    if actual_names != expected_names {
+        return Ok(Some(format!(
+            "Its columns are {} rather than {}",
+            actual_names.join(", "),
+            expected_names.join(", ")
+        )));
+    }
+    let (mut actual_rows, mut expected_rows) = (rows(actual)?, rows(expected)?);
+    if actual_rows.len() != expected_rows.len() {
+        return Ok(Some(format!(
+            "It returned {} rows rather than {}",
+            actual_rows.len(),
+            expected_rows.len()
+        )));
+    }
+    if options.ignore_order {
+        let order = |this: &Vec<Cell>, other: &Vec<Cell>| {
+            this.iter()
+                .zip(other)
+                .map(|(this, other)| this.order(other))
+                .find(|ordering| ordering.is_ne())
+                .unwrap_or(std::cmp::Ordering::Equal)
+        };
+        actual_rows.sort_by(order);
+        expected_rows.sort_by(order);
+    }
+    let differences = actual_rows
+        .iter()
+        .zip(&expected_rows)
+        .enumerate()
+        .flat_map(|(row, (actual, expected))| {
+            actual
+                .iter()
+                .zip(expected)
+                .zip(&actual_names)
+                .filter(|((actual, expected), _)| !actual.matches(expected, options))
+                .map(move |((actual, expected), column)| {
+                    format!(
+                        "row {}, column {}: {} rather than {}",
+                        row + 1,
+                        column,
+                        actual,
+                        expected
+                    )
+                })
+        })
+        .collect::<Vec<_>>();
+    if differences.is_empty() {
+        return Ok(None);
+    }
+    let mut description = format!("{} value(s) differ", differences.len());
+    for difference in differences.iter().take(DIFFERENCES_SHOWN) {
+        description.push_str("\n  ");
+        description.push_str(difference);
+    }
+    if differences.len() > DIFFERENCES_SHOWN {
+        description.push_str("\n  ...");
+    }
+    Ok(Some(description))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow::array::{Float64Array, StringArray};
+    use arrow::datatypes::{Field, Schema};
+    use arrow::record_batch::RecordBatch;
+
+    use super::*;
+
+    fn number(number: f64) -> Cell {
+        Cell::Number(number)
+    }
+
+    fn text(text: &str) -> Cell {
+        Cell::Text(text.to_string())
+    }
+
+    /// A result of a column of numbers `n` and one of text `s`
+    fn result(numbers: Vec<Option<f64>>, texts: Vec<Option<&str>>) -> ResultSet {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("n", DataType::Float64, true),
+            Field::new("s", DataType::Utf8, true),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Float64Array::from(numbers)),
+                Arc::new(StringArray::from(texts)),
+            ],
+        )
+        .unwrap();
+        ResultSet {
+            schema,
+            batches: vec![batch],
+        }
+    }
+
+    #[test]
+    fn numbers_match_within_tolerance() {
+        let options = GoldenOptions {
+            tolerance: 0.01,
+            ..GoldenOptions::default()
+        };
+        assert!(number(1.0).matches(&number(1.0), &GoldenOptions::default()));
+        assert!(!number(1.001).matches(&number(1.0), &GoldenOptions::default()));
+        assert!(number(1.005).matches(&number(1.0), &options));
+        assert!(number(0.995).matches(&number(1.0), &options));
+        assert!(!number(1.02).matches(&number(1.0), &options));
+    }
+
+    #[test]
+    fn numbers_match_within_relative_tolerance() {
+        let options = GoldenOptions {
+            relative_tolerance: 0.01,
+            ..GoldenOptions::default()
+        };
+        assert!(number(1005.0).matches(&number(1000.0), &options));
+        assert!(!number(1020.0).matches(&number(1000.0), &options));
+        assert!(number(-995.0).matches(&number(-1000.0), &options));
+        assert!(!number(0.5).matches(&number(0.0), &options));
+    }
+
+    #[test]
+    fn nan_matches_nan() {
+        let options = GoldenOptions::default();
+        assert!(number(f64::NAN).matches(&number(f64::NAN), &options));
+        assert!(number(f64::NAN).matches(&text("NaN"), &options));
+        assert!(!number(f64::NAN).matches(&number(0.0), &options));
+        assert!(!number(0.0).matches(&number(f64::NAN), &options));
+    }
+
+    #[test]
+    fn text_and_numbers_are_coerced() {
+        let options = GoldenOptions {
+            tolerance: 0.01,
+            ..GoldenOptions::default()
+        };
+        assert!(number(1.5).matches(&text("1.5"), &options));
+        assert!(text("1.5").matches(&number(1.5), &options));
+        assert!(number(1.5).matches(&text("1.501"), &options));
+        assert!(!number(1.5).matches(&text("one and a half"), &options));
+        assert!(!text("abc").matches(&number(0.0), &options));
+        assert!(text("abc").matches(&text("abc"), &options));
+        assert!(!text("abc").matches(&text("ABC"), &options));
+    }
+
+    #[test]
+    fn null_matches_empty_text() {
+        let options = GoldenOptions::default();
+        assert!(Cell::Null.matches(&Cell::Null, &options));
+        assert!(Cell::Null.matches(&text(""), &options));
+        assert!(text("").matches(&Cell::Null, &options));
+        assert!(!Cell::Null.matches(&text("null"), &options));
+        assert!(!Cell::Null.matches(&number(0.0), &options));
+    }
+
+    #[test]
+    fn rows_compared_in_order_unless_ignored() {
+        let actual = result(
+            vec![Some(10.0), Some(9.0), None],
+            vec![Some("b"), Some("a"), None],
+        );
+        let expected = result(
+            vec![None, Some(9.0), Some(10.0)],
+            vec![None, Some("a"), Some("b")],
+        );
+        let ordered = GoldenOptions::default();
+        assert!(compare(&actual, &expected, &ordered).unwrap().is_some());
+        let unordered = GoldenOptions {
+            ignore_order: true,
+            ..GoldenOptions::default()
+        };
+        assert_eq!(compare(&actual, &expected, &unordered).unwrap(), None);
+    }
+
+    #[test]
+    fn unordered_rows_sort_numbers_by_value() {
+        // By their text, 10 would sort before 9 and pair with the wrong row
+        let actual = result(vec![Some(9.0), Some(10.0)], vec![Some("x"), Some("y")]);
+        let expected = result(vec![Some(10.0), Some(9.0)], vec![Some("y"), Some("x")]);
+        let options = GoldenOptions {
+            ignore_order: true,
+            ..GoldenOptions::default()
+        };
+        assert_eq!(compare(&actual, &expected, &options).unwrap(), None);
+        let expected = result(vec![Some(10.0), Some(9.0)], vec![Some("x"), Some("y")]);
+        assert!(compare(&actual, &expected, &options).unwrap().is_some());
+    }
+}
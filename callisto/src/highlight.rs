@@ -0,0 +1,143 @@
+use std::ops::Range;
+
+use crate::schema_cache::SchemaCache;
+
+/// How a span of SQL input should be highlighted
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenClass {
+    Plain,
+    Keyword,
+    String,
+    Number,
+    Comment,
+    /// A table known to the schema cache, by name or source path
+    Table,
+    /// An unterminated string, identifier, or comment, or an unbalanced parenthesis
+    Error,
+}
+
+/// Classifies SQL input for highlighting.
+///
+/// This is a forgiving lexer rather than a parser, so it copes with incomplete input and flags
+/// unterminated strings and unbalanced parentheses instead of failing on them.
+#[derive(Clone)]
+pub struct Highlighter {
+    cache: SchemaCache,
+}
+
+impl Highlighter {
+    pub fn new(cache: SchemaCache) -> Highlighter {
+        Highlighter { cache }
+    }
+
+    /// Split `text` into contiguous classified byte ranges covering all of it
+    pub fn classify(&self, text: &str) -> Vec<(TokenClass, Range<usize>)> {
+        let chars: Vec<(usize, char)> = text.char_indices().collect();
+        let offset = |index: usize| chars.get(index).map_or(text.len(), |(offset, _)| *offset);
+        let mut spans = Vec::new();
+        let mut open_parens = Vec::new();
+
+        let mut index = 0;
+        while index < chars.len() {
+            let start = index;
+            let c = chars[index].1;
+            let next = chars.get(index + 1).map(|(_, c)| *c);
+            let class = if c == '-' && next == Some('-') {
+                while index < chars.len() && chars[index].1 != '\n' {
+                    index += 1;
+                }
+                TokenClass::Comment
+            } else if c == '/' && next == Some('*') {
+                index += 2;
+                loop {
+                    if index + 1 >= chars.len() {
+                        index = chars.len();
+                        break TokenClass::Error;
+                    }
+                    if chars[index].1 == '*' && chars[index + 1].1 == '/' {
+                        index += 2;
+                        break TokenClass::Comment;
+                    }
+                    index += 1;
+                }
+            } else if c == '\'' || c == '"' {
+                index += 1;
+                let terminated = loop {
+                    match chars.get(index) {
+                        None => break false,
+                        // A doubled quote is an escaped quote, not the end of the string
+                        Some((_, q))
+                            if *q == c && chars.get(index + 1).map(|(_, c)| *c) == Some(c) =>
+                        {
+                            index += 2
+                        }
+                        Some((_, q)) if *q == c => {
+                            index += 1;
+                            break true;
+                        }
+                        Some(_) => index += 1,
+                    }
+                };
+                if !terminated {
+                    TokenClass::Error
+                } else if c == '\'' {
+                    TokenClass::String
+                } else if self.is_table(&text[offset(start) + 1..offset(index) - 1]) {
+                    TokenClass::Table
+                } else {
+                    TokenClass::Plain
+                }
+            } else if c.is_ascii_digit() || (c == '.' && next.is_some_and(|c| c.is_ascii_digit())) {
+                while index < chars.len()
+                    && (chars[index].1.is_ascii_alphanumeric() || chars[index].1 == '.')
+                {
+                    index += 1;
+                }
+                TokenClass::Number
+            } else if c.is_alphabetic() || c == '_' {
+                while index < chars.len()
+                    && (chars[index].1.is_alphanumeric() || chars[index].1 == '_')
+                {
+                    index += 1;
+                }
+                let word = &text[offset(start)..offset(index)];
+                if self.is_table(word) {
+                    TokenClass::Table
+                } else if is_keyword(word) {
+                    TokenClass::Keyword
+                } else {
+                    TokenClass::Plain
+                }
+            } else if c == '(' {
+                index += 1;
+                open_parens.push(spans.len());
+                TokenClass::Plain
+            } else if c == ')' {
+                index += 1;
+                match open_parens.pop() {
+                    Some(_) => TokenClass::Plain,
+                    None => TokenClass::Error,
+                }
+            } else {
+                index += 1;
+                TokenClass::Plain
+            };
+            spans.push((class, offset(start)..offset(index)));
+        }
+
+        for unclosed in open_parens {
+            spans[unclosed].0 = TokenClass::Error;
+        }
+        spans
+    }
+
+    fn is_table(&self, name: &str) -> bool {
+        self.cache.table(name).is_some()
+    }
+}
+
+fn is_keyword(word: &str) -> bool {
+    sqlparser::keywords::ALL_KEYWORDS
+        .binary_search(&word.to_uppercase().as_str())
+        .is_ok()
+}
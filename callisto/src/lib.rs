@@ -1,71 +1,41 @@
-pub use callisto_engines::{Engine, EngineInterface};
-
+#[cfg(feature = "polars-engine")]
+pub use callisto_engines::polars;
+pub use callisto_engines::{
+    datafusion, describe_metrics, format_sql, query_references, BatchSizing, CacheStats,
+    CallistoError, ColumnReference, DryRun, Engine, EngineBuilder, EngineInfo, EngineInterface,
+    ExecutionMetrics, ExecutionObserver, ExecutionStats, FileRead, FormatOptions, Interrupter,
+    KeywordCase, Lineage, LineageInput, ParquetOptions, PathPolicy, PlanNode, PreparedStatement,
+    QueryExecution, RelationReference, ScannedFile, SourceSpan, StatementReferences,
+    StatementRewriter, TableInfo,
+};
+
+pub mod assertions;
+mod bookmarks;
+mod completion;
+mod config;
 pub mod console;
-
-pub struct Repl<Output> {
-    output: Output,
-}
-
-impl<Output> Repl<Output>
-where
-    Output: tokio::io::AsyncWriteExt + Unpin,
-{
-    async fn print(&mut self, text: &str) -> tokio::io::Result<()> {
-        self.output.write_all(text.as_bytes()).await
-    }
-
-    async fn println(&mut self, text: &str) -> tokio::io::Result<()> {
-        self.print(text).await?;
-        self.print("\n").await
-    }
-
-    pub async fn run<Input>(
-        engine: &mut Box<dyn EngineInterface>,
-        input: Input,
-        output: Output,
-    ) -> anyhow::Result<()>
-    where
-        Input: tokio::io::AsyncRead + Unpin,
-    {
-        use futures::stream::StreamExt as _;
-        use tokio::io::AsyncBufReadExt as _;
-
-        let mut repl = Repl { output };
-
-        let reader = tokio::io::BufReader::new(input);
-        let mut lines = reader.lines();
-
-        while let Some(line) = {
-            repl.print("> ").await?;
-            repl.output.flush().await?;
-            lines.next_line().await.unwrap()
-        } {
-            let command = line.trim();
-            if ["exit", "bye", "q", "quit"].contains(&command.to_lowercase().as_str()) {
-                break;
-            }
-
-            let executions = match engine.execute(&command).await {
-                Ok(e) => e,
-                Err(error) => {
-                    repl.println(&format!("Error: {:?}", error)).await?;
-                    continue;
-                }
-            };
-            for (statement, mut stream) in executions {
-                repl.println(&format!("\n$ {}", statement.to_string()))
-                    .await?;
-                let mut batches = Vec::new();
-                while let Some(items) = stream.next().await {
-                    batches.push(items?);
-                }
-                let pretty_results =
-                    arrow::util::pretty::pretty_format_batches(&batches)?.to_string();
-                repl.println(&format!("Results:\n{}", pretty_results))
-                    .await?;
-            }
-        }
-        repl.println("\nGoodbye!").await?;
-        Ok(())
-    }
-}
+mod dataset;
+pub mod golden;
+mod highlight;
+mod mask;
+mod output_format;
+pub mod prql;
+mod remote;
+mod repl;
+mod result_set;
+pub mod schedule;
+mod schema_cache;
+pub mod serialize;
+mod server;
+mod session;
+
+pub use bookmarks::Bookmarks;
+pub use config::{rc_path, Config, ConsoleConfig, Keymap, RemoteConfig, ReplConfig, ThemeConfig};
+pub use mask::{Mask, MaskPolicy};
+pub use output_format::{OutputFormat, Renderer};
+pub use prql::Language;
+pub use remote::{ParquetSummary, Remote, RemoteEntry};
+pub use repl::{MetaCommand, Repl, ReplOptions, Variables};
+pub use result_set::ResultSet;
+pub use server::{FlightSqlServer, HttpServer, LspServer};
+pub use session::Session;
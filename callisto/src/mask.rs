@@ -0,0 +1,525 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use anyhow::Context as _;
+use arrow::array::{Array as _, ArrayRef, Float64Array, StringArray};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use arrow::record_batch::{RecordBatch, RecordBatchOptions};
+use serde::Deserialize;
+
+use crate::datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+use crate::datafusion::physical_plan::SendableRecordBatchStream;
+
+/// How a column's values are hidden when results are displayed or exported, every masked column
+/// becoming one of text, its nulls left as they are
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Mask {
+    /// Replace each value by a digest of it keyed by the policy's secret, so equal values can
+    /// still be told apart from others, but values can't be found by hashing guesses of them
+    Hash,
+    /// Replace each value by `***`
+    Redact,
+    /// Keep the first this many characters of each value
+    Truncate(usize),
+    /// Replace each number by the range of this width it falls in, e.g. `30-40`
+    Bucket(u64),
+}
+
+impl std::str::FromStr for Mask {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> anyhow::Result<Mask> {
+        let text = text.trim();
+        let argument = |name: &str| {
+            text.strip_prefix(name)
+                .and_then(|rest| rest.strip_prefix('('))
+                .and_then(|rest| rest.strip_suffix(')'))
+                .map(str::trim)
+        };
+        let positive = |width: &str| {
+            width
+                .parse::<u64>()
+                .ok()
+                .filter(|width| *width > 0)
+                .with_context(|| format!("Invalid width {}, not a positive whole number", width))
+        };
+        match text {
+            "hash" => Ok(Mask::Hash),
+            "redact" => Ok(Mask::Redact),
+            _ => match (argument("truncate"), argument("bucket")) {
+                (Some(length), _) => length
+                    .parse()
+                    .map(Mask::Truncate)
+                    .with_context(|| format!("Invalid length {}", length)),
+                (_, Some(width)) => Ok(Mask::Bucket(positive(width)?)),
+                _ => anyhow::bail!(
+                    "Unknown mask '{}', expected hash, redact, truncate(<length>), or \
+                     bucket(<width>)",
+                    text
+                ),
+            },
+        }
+    }
+}
+
+impl TryFrom<String> for Mask {
+    type Error = anyhow::Error;
+
+    fn try_from(text: String) -> anyhow::Result<Mask> {
+        text.parse()
+    }
+}
+
+impl std::fmt::Display for Mask {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Mask::Hash => f.write_str("hash"),
+            Mask::Redact => f.write_str("redact"),
+            Mask::Truncate(length) => write!(f, "truncate({})", length),
+            Mask::Bucket(width) => write!(f, "bucket({})", width),
+        }
+    }
+}
+
+impl Mask {
+    /// `column` with its values hidden, hashed with `key`
+    fn apply(&self, column: &ArrayRef, key: &HashKey) -> anyhow::Result<ArrayRef> {
+        let values: Vec<Option<String>> = match self {
+            Mask::Bucket(width) => {
+                if !column.data_type().is_numeric() {
+                    anyhow::bail!("Only numbers can be bucketed, not {}", column.data_type());
+                }
+                let width = *width as f64;
+                let numbers = arrow::compute::cast(column, &DataType::Float64)?;
+                let numbers = numbers
+                    .as_any()
+                    .downcast_ref::<Float64Array>()
+                    .context("Numbers weren't cast to Float64")?;
+                numbers
+                    .iter()
+                    .map(|number| {
+                        number.map(|number| {
+                            let low = (number / width).floor() * width;
+                            format!("{}-{}", low, low + width)
+                        })
+                    })
+                    .collect()
+            }
+            _ => {
+                let options = arrow::util::display::FormatOptions::default();
+                let formatter =
+                    arrow::util::display::ArrayFormatter::try_new(column.as_ref(), &options)?;
+                (0..column.len())
+                    .map(|row| {
+                        column
+                            .is_valid(row)
+                            .then(|| self.hide(&formatter.value(row).to_string(), key))
+                    })
+                    .collect()
+            }
+        };
+        Ok(Arc::new(StringArray::from(values)))
+    }
+
+    /// `text` hidden by any mask but bucketing, hashed with `key`
+    fn hide(&self, text: &str, key: &HashKey) -> String {
+        use hmac::Mac as _;
+
+        match self {
+            Mask::Hash => {
+                let mut digest = hmac::Hmac::<sha2::Sha256>::new_from_slice(&key.0)
+                    .expect("HMAC takes keys of any length");
+                digest.update(text.as_bytes());
+                digest
+                    .finalize()
+                    .into_bytes()
+                    .iter()
+                    .take(8)
+                    .map(|byte| format!("{:02x}", byte))
+                    .collect()
+            }
+            Mask::Truncate(length) => {
+                let kept = text.chars().take(*length).collect::<String>();
+                if kept.len() < text.len() {
+                    format!("{}…", kept)
+                } else {
+                    kept
+                }
+            }
+            Mask::Redact | Mask::Bucket(_) => "***".to_string(),
+        }
+    }
+}
+
+/// The secret key of the HMAC-SHA256 digests [`Mask::Hash`] replaces values by.
+///
+/// Unkeyed digests of low-entropy values, like emails or phone numbers, are reversed by hashing
+/// every likely value, so the digests are keyed: by default with a key made at random for each
+/// session, so that digests can be joined within a session but not across sessions, or with one
+/// derived from a configured secret, so that they can be joined across sessions by those who know
+/// it.
+#[derive(Clone)]
+struct HashKey([u8; 32]);
+
+impl HashKey {
+    fn random() -> HashKey {
+        let mut key = [0; 32];
+        getrandom::getrandom(&mut key).expect("No source of randomness to make a key from");
+        HashKey(key)
+    }
+
+    fn from_secret(secret: &str) -> HashKey {
+        use sha2::Digest as _;
+
+        let mut key = [0; 32];
+        key.copy_from_slice(&sha2::Sha256::digest(secret.as_bytes()));
+        HashKey(key)
+    }
+}
+
+impl Default for HashKey {
+    fn default() -> HashKey {
+        HashKey::random()
+    }
+}
+
+impl std::fmt::Debug for HashKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("HashKey(..)")
+    }
+}
+
+/// Masks of the columns of results, by column name, matched regardless of case, so that
+/// screenshots and exports of exploratory sessions don't show personal data; written as
+/// `email=hash, ssn=redact`.
+///
+/// Hashes are keyed by a secret made at random for each policy, and kept by its clones, unless
+/// one is given with [`MaskPolicy::with_secret`].
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(transparent)]
+pub struct MaskPolicy {
+    masks: BTreeMap<String, Mask>,
+    #[serde(skip)]
+    key: HashKey,
+}
+
+impl std::str::FromStr for MaskPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(text: &str) -> anyhow::Result<MaskPolicy> {
+        let mut policy = MaskPolicy::default();
+        for assignment in text.split(',').filter(|part| !part.trim().is_empty()) {
+            let (column, mask) = assignment
+                .split_once('=')
+                .with_context(|| format!("Expected <column>=<mask>, not '{}'", assignment))?;
+            policy.set(column.trim(), Some(mask.parse()?));
+        }
+        Ok(policy)
+    }
+}
+
+impl std::fmt::Display for MaskPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let masks = self
+            .masks
+            .iter()
+            .map(|(column, mask)| format!("{}={}", column, mask))
+            .collect::<Vec<_>>();
+        f.write_str(&masks.join(", "))
+    }
+}
+
+impl MaskPolicy {
+    /// The policy with its hashes keyed by `secret`, so that they're the same in every session
+    /// given it
+    pub fn with_secret(mut self, secret: &str) -> MaskPolicy {
+        self.key = HashKey::from_secret(secret);
+        self
+    }
+
+    /// Whether no columns are masked
+    pub fn is_empty(&self) -> bool {
+        self.masks.is_empty()
+    }
+
+    /// Mask `column` with `mask`, or stop masking it if `None`
+    pub fn set(&mut self, column: &str, mask: Option<Mask>) {
+        self.masks
+            .retain(|masked, _| !masked.eq_ignore_ascii_case(column));
+        if let Some(mask) = mask {
+            self.masks.insert(column.to_string(), mask);
+        }
+    }
+
+    /// Add the masks of `other`, replacing those of the same columns, keeping this policy's key
+    pub fn extend(&mut self, other: &MaskPolicy) {
+        for (column, mask) in &other.masks {
+            self.set(column, Some(*mask));
+        }
+    }
+
+    /// The mask of the column called `name`, if it's masked
+    pub fn mask(&self, name: &str) -> Option<Mask> {
+        self.masks
+            .iter()
+            .find(|(column, _)| column.eq_ignore_ascii_case(name))
+            .map(|(_, mask)| *mask)
+    }
+
+    /// `schema` with its masked columns made text
+    pub fn schema(&self, schema: &SchemaRef) -> SchemaRef {
+        if self.is_empty() {
+            return schema.clone();
+        }
+        let fields = schema
+            .fields()
+            .iter()
+            .map(|field| match self.mask(field.name()) {
+                Some(_) => Arc::new(Field::new(field.name(), DataType::Utf8, true)),
+                None => field.clone(),
+            })
+            .collect::<Vec<_>>();
+        Arc::new(Schema::new_with_metadata(fields, schema.metadata().clone()))
+    }
+
+    /// `batch` with its masked columns' values hidden
+    pub fn apply(&self, batch: &RecordBatch) -> anyhow::Result<RecordBatch> {
+        if self.is_empty() {
+            return Ok(batch.clone());
+        }
+        let schema = batch.schema();
+        let columns = schema
+            .fields()
+            .iter()
+            .zip(batch.columns())
+            .map(|(field, column)| match self.mask(field.name()) {
+                Some(mask) => mask
+                    .apply(column, &self.key)
+                    .with_context(|| format!("Failed to mask {}", field.name())),
+                None => Ok(column.clone()),
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let options = RecordBatchOptions::new().with_row_count(Some(batch.num_rows()));
+        Ok(RecordBatch::try_new_with_options(
+            self.schema(&schema),
+            columns,
+            &options,
+        )?)
+    }
+
+    /// `stream` with its masked columns' values hidden
+    pub fn masked(&self, stream: SendableRecordBatchStream) -> SendableRecordBatchStream {
+        use futures::stream::StreamExt as _;
+
+        if self.is_empty() {
+            return stream;
+        }
+        let schema = self.schema(&stream.schema());
+        let policy = self.clone();
+        let batches = stream.map(move |batch| {
+            policy
+                .apply(&batch?)
+                .map_err(|error| crate::datafusion::common::DataFusionError::External(error.into()))
+        });
+        Box::pin(RecordBatchStreamAdapter::new(schema, batches))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::{Int32Array, StringArray};
+    use arrow::datatypes::DataType;
+
+    use super::*;
+
+    /// The values of a masked column
+    fn values(column: &ArrayRef) -> Vec<Option<String>> {
+        let column = column.as_any().downcast_ref::<StringArray>().unwrap();
+        column
+            .iter()
+            .map(|value| value.map(str::to_string))
+            .collect()
+    }
+
+    fn masked(mask: Mask, column: ArrayRef) -> Vec<Option<String>> {
+        values(&mask.apply(&column, &HashKey::random()).unwrap())
+    }
+
+    fn text(values: &[Option<&str>]) -> ArrayRef {
+        Arc::new(StringArray::from(values.to_vec()))
+    }
+
+    #[test]
+    fn masks_round_trip() {
+        for text in ["hash", "redact", "truncate(0)", "truncate(3)", "bucket(10)"] {
+            let mask = text.parse::<Mask>().unwrap();
+            assert_eq!(mask.to_string(), text);
+        }
+        assert_eq!(
+            " truncate( 3 ) ".parse::<Mask>().unwrap(),
+            Mask::Truncate(3)
+        );
+        assert_eq!("bucket(5)".parse::<Mask>().unwrap(), Mask::Bucket(5));
+    }
+
+    #[test]
+    fn invalid_masks() {
+        for text in [
+            "",
+            "blur",
+            "Hash",
+            "truncate",
+            "truncate()",
+            "truncate(x)",
+            "truncate(-1)",
+            "truncate(3",
+            "bucket(0)",
+            "bucket(-10)",
+            "bucket(1.5)",
+        ] {
+            assert!(text.parse::<Mask>().is_err(), "{} parsed", text);
+        }
+    }
+
+    #[test]
+    fn policies_round_trip() {
+        let policy = "ssn=redact, email=hash,name = truncate(1),"
+            .parse::<MaskPolicy>()
+            .unwrap();
+        assert_eq!(
+            policy.to_string(),
+            "email=hash, name=truncate(1), ssn=redact"
+        );
+        let reparsed = policy.to_string().parse::<MaskPolicy>().unwrap();
+        assert_eq!(reparsed.to_string(), policy.to_string());
+        assert_eq!(policy.mask("EMAIL"), Some(Mask::Hash));
+        assert_eq!(policy.mask("phone"), None);
+        assert!("".parse::<MaskPolicy>().unwrap().is_empty());
+    }
+
+    #[test]
+    fn invalid_policies() {
+        assert!("email".parse::<MaskPolicy>().is_err());
+        assert!("email=blur".parse::<MaskPolicy>().is_err());
+        assert!("email=hash, ssn".parse::<MaskPolicy>().is_err());
+    }
+
+    #[test]
+    fn setting_a_mask_replaces_it_regardless_of_case() {
+        let mut policy = "Email=hash".parse::<MaskPolicy>().unwrap();
+        policy.set("EMAIL", Some(Mask::Redact));
+        assert_eq!(policy.to_string(), "EMAIL=redact");
+        policy.set("email", None);
+        assert!(policy.is_empty());
+    }
+
+    #[test]
+    fn nulls_are_kept() {
+        let column = text(&[Some("alice"), None]);
+        for mask in [Mask::Hash, Mask::Redact, Mask::Truncate(2)] {
+            assert_eq!(masked(mask, column.clone())[1], None, "{}", mask);
+        }
+        let numbers = Arc::new(Int32Array::from(vec![Some(5), None])) as ArrayRef;
+        assert_eq!(
+            masked(Mask::Bucket(10), numbers),
+            vec![Some("0-10".to_string()), None]
+        );
+    }
+
+    #[test]
+    fn redact() {
+        assert_eq!(
+            masked(Mask::Redact, text(&[Some("alice"), Some("")])),
+            vec![Some("***".to_string()), Some("***".to_string())]
+        );
+    }
+
+    #[test]
+    fn truncate_multibyte_text() {
+        let column = text(&[
+            Some("héllo wörld"),
+            Some("日本語テキスト"),
+            Some("héllo"),
+            Some(""),
+        ]);
+        assert_eq!(
+            masked(Mask::Truncate(5), column),
+            vec![
+                Some("héllo…".to_string()),
+                Some("日本語テキ…".to_string()),
+                Some("héllo".to_string()),
+                Some("".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn bucket_negative_numbers() {
+        let numbers = Arc::new(Int32Array::from(vec![-5, -10, -11, 0, 15])) as ArrayRef;
+        assert_eq!(
+            masked(Mask::Bucket(10), numbers),
+            ["-10-0", "-10-0", "-20--10", "0-10", "10-20"]
+                .iter()
+                .map(|bucket| Some(bucket.to_string()))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn bucket_rejects_non_numbers() {
+        let column = text(&[Some("12")]);
+        let error = Mask::Bucket(10)
+            .apply(&column, &HashKey::random())
+            .unwrap_err();
+        assert!(error.to_string().contains("Only numbers"), "{}", error);
+    }
+
+    #[test]
+    fn hashes_are_keyed() {
+        let column = text(&[
+            Some("alice@example.com"),
+            Some("alice@example.com"),
+            Some("bob"),
+        ]);
+        let key = HashKey::random();
+        let hashed = values(&Mask::Hash.apply(&column, &key).unwrap());
+        assert_eq!(hashed[0], hashed[1]);
+        assert_ne!(hashed[0], hashed[2]);
+        assert_eq!(hashed[0].as_ref().unwrap().len(), 16);
+        let rekeyed = values(&Mask::Hash.apply(&column, &HashKey::random()).unwrap());
+        assert_ne!(hashed[0], rekeyed[0]);
+    }
+
+    #[test]
+    fn secrets_key_hashes_alike() {
+        let batch =
+            RecordBatch::try_from_iter([("email", text(&[Some("alice@example.com")]))]).unwrap();
+        let hash = |policy: MaskPolicy| values(policy.apply(&batch).unwrap().column(0));
+        let policy = || "email=hash".parse::<MaskPolicy>().unwrap();
+        assert_eq!(
+            hash(policy().with_secret("pepper")),
+            hash(policy().with_secret("pepper"))
+        );
+        assert_ne!(
+            hash(policy().with_secret("pepper")),
+            hash(policy().with_secret("salt"))
+        );
+        assert_ne!(hash(policy()), hash(policy()));
+    }
+
+    #[test]
+    fn masked_columns_become_text() {
+        let batch = RecordBatch::try_from_iter([
+            ("id", Arc::new(Int32Array::from(vec![1, 2])) as ArrayRef),
+            ("email", text(&[Some("a"), None])),
+        ])
+        .unwrap();
+        let policy = "id=bucket(10)".parse::<MaskPolicy>().unwrap();
+        let masked = policy.apply(&batch).unwrap();
+        assert_eq!(masked.schema().field(0).data_type(), &DataType::Utf8);
+        assert_eq!(masked.schema().field(1).data_type(), &DataType::Utf8);
+        assert_eq!(masked.num_rows(), 2);
+        assert_eq!(masked.column(1), batch.column(1));
+    }
+}
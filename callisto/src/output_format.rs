@@ -0,0 +1,249 @@
+use arrow::datatypes::SchemaRef;
+use arrow::record_batch::RecordBatch;
+
+use crate::serialize;
+
+/// How result batches are rendered as text
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Bordered table with a header row
+    #[default]
+    Table,
+    /// Comma-separated values with a header row
+    Csv,
+    /// JSON array of objects, one per row
+    Json,
+    /// One `column | value` line per field, with rows separated by a record marker
+    Vertical,
+}
+
+impl OutputFormat {
+    pub const ALL: [OutputFormat; 4] = [
+        OutputFormat::Table,
+        OutputFormat::Csv,
+        OutputFormat::Json,
+        OutputFormat::Vertical,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            OutputFormat::Table => "table",
+            OutputFormat::Csv => "csv",
+            OutputFormat::Json => "json",
+            OutputFormat::Vertical => "vertical",
+        }
+    }
+
+    /// Whether output is meant to be read by people rather than other programs
+    pub fn is_human_readable(&self) -> bool {
+        matches!(self, OutputFormat::Table | OutputFormat::Vertical)
+    }
+
+    /// Render `batches` with the given `schema` in this format, without a trailing newline
+    pub fn render(&self, schema: SchemaRef, batches: &[RecordBatch]) -> anyhow::Result<String> {
+        let mut renderer = Renderer::new(*self, schema);
+        // With every batch at hand, table columns can be sized to fit all of them
+        if *self == OutputFormat::Table {
+            for batch in batches {
+                renderer.fit(&format_cells(batch, "")?);
+            }
+        }
+        let mut text = String::new();
+        for batch in batches {
+            text.push_str(&renderer.push(batch)?);
+        }
+        text.push_str(&renderer.finish()?);
+        text.truncate(text.trim_end_matches('\n').len());
+        Ok(text)
+    }
+}
+
+impl std::str::FromStr for OutputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(name: &str) -> anyhow::Result<OutputFormat> {
+        OutputFormat::ALL
+            .into_iter()
+            .find(|format| format.name().eq_ignore_ascii_case(name))
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Unknown output format '{}', expected one of {}",
+                    name,
+                    OutputFormat::ALL.map(|format| format.name()).join(", ")
+                )
+            })
+    }
+}
+
+/// Renders a result one batch at a time, so rows can be shown as soon as they arrive.
+///
+/// Table column widths are fixed by the header and the first batch, with wider values in later
+/// batches overflowing their column rather than realigning rows already shown.
+pub struct Renderer {
+    format: OutputFormat,
+    schema: SchemaRef,
+    /// Column widths, set once the table header has been rendered
+    widths: Option<Vec<usize>>,
+    rows: usize,
+}
+
+impl Renderer {
+    pub fn new(format: OutputFormat, schema: SchemaRef) -> Renderer {
+        Renderer {
+            format,
+            schema,
+            widths: None,
+            rows: 0,
+        }
+    }
+
+    /// Rows rendered so far
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// Render the rows of `batch`, preceded by any header if this is the first batch
+    pub fn push(&mut self, batch: &RecordBatch) -> anyhow::Result<String> {
+        if batch.num_rows() == 0 {
+            return Ok(String::new());
+        }
+        let text = match self.format {
+            OutputFormat::Table => self.push_table(batch)?,
+            OutputFormat::Csv => {
+                serialize::to_csv(&self.schema, std::slice::from_ref(batch), self.rows == 0)?
+            }
+            OutputFormat::Json => {
+                // Splice this batch's objects into the single array spanning all batches
+                let objects = serialize::json_objects(batch)?;
+                format!("{}{}", if self.rows == 0 { "[" } else { "," }, objects)
+            }
+            OutputFormat::Vertical => self.push_vertical(batch)?,
+        };
+        self.rows += batch.num_rows();
+        Ok(text)
+    }
+
+    /// Render whatever follows the last row, or the whole output of an empty result
+    pub fn finish(&mut self) -> anyhow::Result<String> {
+        Ok(match self.format {
+            OutputFormat::Table => {
+                if self.widths.is_none() {
+                    self.fit(&[]);
+                }
+                let widths = self.widths.as_deref().unwrap_or_default();
+                let mut text = String::new();
+                if self.rows == 0 {
+                    text.push_str(&header(widths, &self.schema));
+                }
+                text.push_str(&border(widths));
+                text.push('\n');
+                text
+            }
+            OutputFormat::Csv if self.rows == 0 => serialize::to_csv(&self.schema, &[], true)?,
+            OutputFormat::Csv => String::new(),
+            OutputFormat::Json if self.rows == 0 => "[]\n".to_string(),
+            OutputFormat::Json => "]\n".to_string(),
+            OutputFormat::Vertical if self.rows == 0 => "(0 rows)\n".to_string(),
+            OutputFormat::Vertical => String::new(),
+        })
+    }
+
+    fn push_table(&mut self, batch: &RecordBatch) -> anyhow::Result<String> {
+        let cells = format_cells(batch, "")?;
+        if self.widths.is_none() {
+            self.fit(&cells);
+        }
+        let widths = self.widths.as_deref().unwrap_or_default();
+        let mut text = String::new();
+        if self.rows == 0 {
+            text.push_str(&header(widths, &self.schema));
+        }
+        for cells in &cells {
+            text.push_str(&row(widths, cells));
+            text.push('\n');
+        }
+        Ok(text)
+    }
+
+    /// Widen table columns to fit `cells`, starting from the width of the column names
+    fn fit(&mut self, cells: &[Vec<String>]) {
+        let widths = self.widths.get_or_insert_with(|| {
+            self.schema
+                .fields()
+                .iter()
+                .map(|field| field.name().chars().count())
+                .collect()
+        });
+        for row in cells {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+    }
+
+    fn push_vertical(&mut self, batch: &RecordBatch) -> anyhow::Result<String> {
+        use std::fmt::Write as _;
+
+        let cells = format_cells(batch, "NULL")?;
+        let width = self
+            .schema
+            .fields()
+            .iter()
+            .map(|field| field.name().chars().count())
+            .max()
+            .unwrap_or(0);
+        let mut text = String::new();
+        for (index, values) in cells.iter().enumerate() {
+            writeln!(text, "-[ RECORD {} ]-", self.rows + index + 1)?;
+            for (field, value) in self.schema.fields().iter().zip(values) {
+                writeln!(text, "{:width$} | {}", field.name(), value, width = width)?;
+            }
+        }
+        Ok(text)
+    }
+}
+
+/// Display each value of `batch`, row by row
+pub(crate) fn format_cells(batch: &RecordBatch, null: &str) -> anyhow::Result<Vec<Vec<String>>> {
+    let options = arrow::util::display::FormatOptions::default().with_null(null);
+    let formatters = batch
+        .columns()
+        .iter()
+        .map(|column| arrow::util::display::ArrayFormatter::try_new(column, &options))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((0..batch.num_rows())
+        .map(|row| {
+            formatters
+                .iter()
+                .map(|formatter| formatter.value(row).to_string())
+                .collect()
+        })
+        .collect())
+}
+
+fn border(widths: &[usize]) -> String {
+    let mut border = String::from("+");
+    for width in widths {
+        border.push_str(&"-".repeat(width + 2));
+        border.push('+');
+    }
+    border
+}
+
+fn header(widths: &[usize], schema: &SchemaRef) -> String {
+    let names = schema
+        .fields()
+        .iter()
+        .map(|field| field.name().clone())
+        .collect::<Vec<_>>();
+    let border = border(widths);
+    format!("{}\n{}\n{}\n", border, row(widths, &names), border)
+}
+
+fn row(widths: &[usize], cells: &[String]) -> String {
+    let mut row = String::from("|");
+    for (width, cell) in widths.iter().zip(cells) {
+        row.push_str(&format!(" {:width$} |", cell, width = width));
+    }
+    row
+}
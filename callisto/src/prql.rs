@@ -0,0 +1,81 @@
+use std::path::Path;
+
+use crate::Engine;
+
+/// Prefix marking a query as PRQL, whatever language is otherwise in use
+pub const PRQL_PREFIX: &str = "prql:";
+
+/// Language in which queries are written, all of them run as SQL in the end
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Language {
+    #[default]
+    Sql,
+    /// Pipelined Relational Query Language, compiled to SQL for the engine
+    Prql,
+}
+
+impl Language {
+    pub const ALL: [Language; 2] = [Language::Sql, Language::Prql];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Language::Sql => "sql",
+            Language::Prql => "prql",
+        }
+    }
+
+    /// The language of the file at `path`, judged by its extension
+    pub fn of_path(path: &Path) -> Language {
+        match path.extension() {
+            Some(extension) if extension.eq_ignore_ascii_case("prql") => Language::Prql,
+            _ => Language::Sql,
+        }
+    }
+}
+
+impl std::str::FromStr for Language {
+    type Err = anyhow::Error;
+
+    fn from_str(name: &str) -> anyhow::Result<Language> {
+        Language::ALL
+            .into_iter()
+            .find(|language| language.name().eq_ignore_ascii_case(name))
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Unknown language '{}', expected one of {}",
+                    name,
+                    Language::ALL.map(|language| language.name()).join(", ")
+                )
+            })
+    }
+}
+
+/// The SQL for `engine` to run for `query`, which is written in `language` unless it starts with
+/// [`PRQL_PREFIX`]
+pub fn to_sql(query: &str, language: Language, engine: Engine) -> anyhow::Result<String> {
+    let trimmed = query.trim_start();
+    match trimmed.strip_prefix(PRQL_PREFIX) {
+        Some(prql) => compile(prql, engine),
+        None if language == Language::Prql => compile(trimmed, engine),
+        None => Ok(query.to_string()),
+    }
+}
+
+/// Compile the PRQL query `prql` to SQL in the dialect of `engine`.
+///
+/// A trailing semicolon is dropped, as the REPL wants one to end PRQL input as it does SQL.
+pub fn compile(prql: &str, engine: Engine) -> anyhow::Result<String> {
+    let dialect = match engine {
+        Engine::DuckDB => prqlc::sql::Dialect::DuckDb,
+        Engine::Polars | Engine::DataFusion => prqlc::sql::Dialect::Generic,
+    };
+    let options = prqlc::Options::default()
+        .with_target(prqlc::Target::Sql(Some(dialect)))
+        .with_signature_comment(false)
+        .with_color(false);
+    let prql = prql.trim().trim_end_matches(';');
+    let sql = prqlc::compile(prql, &options)
+        .map_err(|errors| anyhow::anyhow!("Failed to compile PRQL: {}", errors))?;
+    tracing::debug!(%sql, "Compiled PRQL");
+    Ok(sql)
+}
@@ -0,0 +1,229 @@
+use std::sync::Arc;
+
+use anyhow::Context as _;
+use arrow::datatypes::SchemaRef;
+use futures::stream::TryStreamExt as _;
+use object_store::{path::Path, ObjectMeta, ObjectStore};
+use parquet::arrow::{async_reader::ParquetObjectReader, ParquetRecordBatchStreamBuilder};
+
+use crate::{dataset, RemoteConfig, ResultSet};
+
+/// A directory-like prefix or an object listed in a remote
+#[derive(Clone, Debug)]
+pub enum RemoteEntry {
+    Prefix(Path),
+    Object(ObjectMeta),
+}
+
+impl RemoteEntry {
+    pub fn location(&self) -> &Path {
+        match self {
+            RemoteEntry::Prefix(path) => path,
+            RemoteEntry::Object(meta) => &meta.location,
+        }
+    }
+
+    /// Last segment of the entry's location, as a file or directory name
+    pub fn name(&self) -> String {
+        self.location()
+            .parts()
+            .last()
+            .map(|part| part.as_ref().to_string())
+            .unwrap_or_default()
+    }
+}
+
+/// What a Parquet object's footer says of it, read without fetching its rows
+#[derive(Clone, Debug)]
+pub struct ParquetSummary {
+    pub schema: SchemaRef,
+    pub rows: usize,
+    pub row_groups: usize,
+}
+
+/// A bucket or container in an object store, as configured under `remotes`
+pub struct Remote {
+    pub name: String,
+    pub url: String,
+    store: Arc<dyn ObjectStore>,
+    /// Prefix within the bucket the URL points at
+    root: Path,
+}
+
+impl Remote {
+    /// Connect to every remote in `configs`, by name
+    pub fn open_all(
+        configs: &std::collections::BTreeMap<String, RemoteConfig>,
+    ) -> anyhow::Result<Vec<Remote>> {
+        configs
+            .iter()
+            .map(|(name, config)| Remote::open(name, config))
+            .collect()
+    }
+
+    /// Connect to the remote `name` is configured as, taking credentials not given in its
+    /// options from the environment
+    pub fn open(name: &str, config: &RemoteConfig) -> anyhow::Result<Remote> {
+        use object_store::{aws, azure, gcp};
+
+        let url = config.url.as_str();
+        let (scheme, rest) = url
+            .split_once("://")
+            .with_context(|| format!("Remote {} has no scheme in its URL {:?}", name, url))?;
+        let option = |key: &str| format!("Unknown option {:?} for remote {}", key, name);
+        let store: Arc<dyn ObjectStore> = match scheme {
+            "s3" | "s3a" => {
+                let mut builder = aws::AmazonS3Builder::from_env().with_url(url);
+                for (key, value) in &config.options {
+                    let key = key.parse().with_context(|| option(key))?;
+                    builder = builder.with_config(key, value);
+                }
+                Arc::new(builder.build()?)
+            }
+            "gs" => {
+                let mut builder = gcp::GoogleCloudStorageBuilder::from_env().with_url(url);
+                for (key, value) in &config.options {
+                    let key = key.parse().with_context(|| option(key))?;
+                    builder = builder.with_config(key, value);
+                }
+                Arc::new(builder.build()?)
+            }
+            "az" | "adl" | "azure" | "abfs" | "abfss" => {
+                let mut builder = azure::MicrosoftAzureBuilder::from_env().with_url(url);
+                for (key, value) in &config.options {
+                    let key = key.parse().with_context(|| option(key))?;
+                    builder = builder.with_config(key, value);
+                }
+                Arc::new(builder.build()?)
+            }
+            _ => anyhow::bail!(
+                "Remote {} has unsupported scheme {:?}, expected s3, gs, or az",
+                name,
+                scheme
+            ),
+        };
+        let root = rest.split_once('/').map_or("", |(_, prefix)| prefix);
+        Ok(Remote {
+            name: name.to_string(),
+            url: config.url.clone(),
+            store,
+            root: Path::from(root),
+        })
+    }
+
+    /// Where the remote's URL points within its bucket, where browsing starts
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The prefixes and data files directly under `prefix`, prefixes first, each by name
+    pub async fn list(&self, prefix: &Path) -> anyhow::Result<Vec<RemoteEntry>> {
+        let prefix = (!prefix.as_ref().is_empty()).then_some(prefix);
+        let listing = self
+            .store
+            .list_with_delimiter(prefix)
+            .await
+            .with_context(|| format!("Failed to list {}", self.describe(prefix)))?;
+        let mut objects = listing
+            .objects
+            .into_iter()
+            .filter(|meta| dataset::is_dataset(std::path::Path::new(meta.location.as_ref())))
+            .collect::<Vec<_>>();
+        objects.sort_by(|a, b| a.location.cmp(&b.location));
+        let mut prefixes = listing.common_prefixes;
+        prefixes.sort();
+        Ok(prefixes
+            .into_iter()
+            .map(RemoteEntry::Prefix)
+            .chain(objects.into_iter().map(RemoteEntry::Object))
+            .collect())
+    }
+
+    /// The schema and size of the Parquet object `meta` describes, from its footer alone
+    pub async fn parquet_summary(&self, meta: &ObjectMeta) -> anyhow::Result<ParquetSummary> {
+        let reader = ParquetObjectReader::new(self.store.clone(), meta.clone());
+        let builder = ParquetRecordBatchStreamBuilder::new(reader)
+            .await
+            .with_context(|| format!("Failed to read the footer of {}", meta.location))?;
+        Ok(ParquetSummary {
+            schema: builder.schema().clone(),
+            rows: builder.metadata().file_metadata().num_rows() as usize,
+            row_groups: builder.metadata().num_row_groups(),
+        })
+    }
+
+    /// Read the object, or every data file under the prefix, into memory as one result.
+    ///
+    /// Files under a prefix must share a schema, as they're read as parts of the same table.
+    pub async fn read(&self, entry: &RemoteEntry) -> anyhow::Result<ResultSet> {
+        let objects = match entry {
+            RemoteEntry::Object(meta) => vec![meta.clone()],
+            RemoteEntry::Prefix(prefix) => {
+                let mut objects = self
+                    .store
+                    .list(Some(prefix))
+                    .try_filter(|meta| {
+                        let data =
+                            dataset::is_dataset(std::path::Path::new(meta.location.as_ref()));
+                        futures::future::ready(data)
+                    })
+                    .try_collect::<Vec<_>>()
+                    .await
+                    .with_context(|| format!("Failed to list {}", self.describe(Some(prefix))))?;
+                objects.sort_by(|a, b| a.location.cmp(&b.location));
+                objects
+            }
+        };
+        let mut result: Option<ResultSet> = None;
+        for meta in &objects {
+            let part = self.read_object(meta).await?;
+            match &mut result {
+                None => result = Some(part),
+                Some(whole) if whole.schema.fields() == part.schema.fields() => {
+                    whole.batches.extend(part.batches)
+                }
+                Some(_) => anyhow::bail!(
+                    "{} has a different schema from the files before it",
+                    meta.location
+                ),
+            }
+        }
+        result.with_context(|| format!("No data files in {}", entry.location()))
+    }
+
+    async fn read_object(&self, meta: &ObjectMeta) -> anyhow::Result<ResultSet> {
+        let path = std::path::Path::new(meta.location.as_ref());
+        if path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("parquet"))
+        {
+            let reader = ParquetObjectReader::new(self.store.clone(), meta.clone());
+            let stream = ParquetRecordBatchStreamBuilder::new(reader)
+                .await?
+                .build()?;
+            let schema = stream.schema().clone();
+            let batches = stream
+                .try_collect()
+                .await
+                .with_context(|| format!("Failed to read {}", meta.location))?;
+            return Ok(ResultSet { schema, batches });
+        }
+        let bytes = self
+            .store
+            .get(&meta.location)
+            .await?
+            .bytes()
+            .await
+            .with_context(|| format!("Failed to fetch {}", meta.location))?;
+        dataset::read_buffer(path, &bytes)
+    }
+
+    /// `prefix` as a URL within the remote, for messages
+    fn describe(&self, prefix: Option<&Path>) -> String {
+        format!(
+            "{}/{}",
+            self.url.split('/').take(3).collect::<Vec<_>>().join("/"),
+            prefix.map_or("", |prefix| prefix.as_ref())
+        )
+    }
+}
@@ -0,0 +1,547 @@
+use super::ReplOptions;
+use crate::schema_cache::SchemaCache;
+use crate::{Bookmarks, Engine, EngineInterface, Language, Mask, OutputFormat};
+
+/// Meta-commands are written on a line of their own beginning with `.` or `\`, e.g. `.tables`
+const HELP: &str = "\
+.help            Show this message
+.tables          List registered tables
+.schema <table>  Show the columns of a table, by name or source path
+.engines         List the engines, marking the active one, with its version and settings
+.engine <name>   Switch to another engine, carrying over tables opened from files
+.cache [clear]   Show how the caches of parsed statements and plans have fared, or empty
+                 them; SHOW CACHE queries the same
+.timing on|off   Print wall time and row counts after each statement
+.format <format> Render results as table, csv, json, or vertical
+.lang sql|prql   Write queries in SQL or PRQL, ending PRQL with ; too; prql: before a
+                 query, or a .prql script, makes it PRQL either way
+.pager on|off    Show results taller than the terminal through $PAGER
+.maxrows <n|off> Limit the rows displayed per result
+.open <path>     Register a file as a table
+.edit            Edit the last statement in $EDITOR, running what's saved
+.read <path> [--force]  Run a script of statements and meta-commands, continuing past errors
+                 with --force
+.set <name> <value>  Set a variable, substituted into statements as ${name}
+.unset <name>    Remove a variable
+.vars            List variables
+.results         List the results kept from earlier statements, which queries can read as
+                 tables named _r<n>, or _last for the most recent
+.show [n]        Display result n again, or the last result
+.last            Display the last result again
+.export <path>   Save the last result as .parquet, .csv, or .json
+.mask [column=mask, ...]  List the masked columns, or hide the values of columns in results
+                 from now on, with hash, redact, truncate(<length>), bucket(<width>), or
+                 off to show them again; .mask off shows all
+.bookmark [name] List bookmarked queries, or run the one called name
+.bookmark save <name>    Bookmark the last statement, shared with the console
+.bookmark delete <name>  Delete a bookmark
+.exit            Leave the REPL
+
+Commands may also begin with a backslash, e.g. \\set";
+
+/// A command handled by callisto itself rather than sent to the engine as SQL
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MetaCommand {
+    Help,
+    Tables,
+    Schema(String),
+    Engines,
+    Engine(Engine),
+    /// Show the cache stats, or empty the caches if `true`
+    Cache(bool),
+    Open(String),
+    Read {
+        path: String,
+        force: bool,
+    },
+    Edit,
+    Export(String),
+    /// List the bookmarked queries
+    Bookmarks,
+    RunBookmark(String),
+    SaveBookmark(String),
+    DeleteBookmark(String),
+    Show(Option<usize>),
+    Results,
+    Timing(bool),
+    Format(OutputFormat),
+    Lang(Language),
+    Pager(bool),
+    MaxRows(Option<usize>),
+    /// List the masked columns
+    Masks,
+    /// Mask each column, or stop masking it if `None`
+    Mask(Vec<(String, Option<Mask>)>),
+    /// Stop masking every column
+    ClearMasks,
+    Set(String, String),
+    Unset(String),
+    Vars,
+    Exit,
+}
+
+impl MetaCommand {
+    /// Parse `line` as a meta-command, returning `None` if it isn't one
+    pub fn parse(line: &str) -> Option<anyhow::Result<MetaCommand>> {
+        let line = line.trim();
+        let line = line.strip_prefix('.').or_else(|| line.strip_prefix('\\'))?;
+        let (name, argument) = match line.split_once(char::is_whitespace) {
+            Some((name, argument)) => (name, argument.trim()),
+            None => (line, ""),
+        };
+        let required = |usage: &str| {
+            if argument.is_empty() {
+                Err(anyhow::anyhow!("Usage: {}", usage))
+            } else {
+                Ok(unquote(argument).to_string())
+            }
+        };
+        Some(match name {
+            "help" => Ok(MetaCommand::Help),
+            "tables" => Ok(MetaCommand::Tables),
+            "schema" => required(".schema <table>").map(MetaCommand::Schema),
+            "engines" => Ok(MetaCommand::Engines),
+            "engine" => required(".engine <name>").and_then(|name| {
+                Engine::ALL
+                    .into_iter()
+                    .find(|kind| kind.name().eq_ignore_ascii_case(&name))
+                    .map(MetaCommand::Engine)
+                    .ok_or_else(|| {
+                        anyhow::anyhow!(
+                            "Unknown engine '{}', see .engines for available engines",
+                            name
+                        )
+                    })
+            }),
+            "cache" => match argument {
+                "" => Ok(MetaCommand::Cache(false)),
+                "clear" => Ok(MetaCommand::Cache(true)),
+                _ => Err(anyhow::anyhow!("Usage: .cache [clear]")),
+            },
+            "open" => required(".open <path>").map(MetaCommand::Open),
+            "read" => {
+                let (path, force) = match argument.strip_suffix("--force") {
+                    Some(path) => (path.trim(), true),
+                    None => (argument, false),
+                };
+                if path.is_empty() {
+                    Err(anyhow::anyhow!("Usage: .read <path> [--force]"))
+                } else {
+                    Ok(MetaCommand::Read {
+                        path: unquote(path).to_string(),
+                        force,
+                    })
+                }
+            }
+            "edit" => Ok(MetaCommand::Edit),
+            "timing" => on_off(argument)
+                .map(MetaCommand::Timing)
+                .ok_or_else(|| anyhow::anyhow!("Usage: .timing on|off")),
+            "pager" => on_off(argument)
+                .map(MetaCommand::Pager)
+                .ok_or_else(|| anyhow::anyhow!("Usage: .pager on|off")),
+            "format" => required(".format table|csv|json|vertical")
+                .and_then(|name| name.parse())
+                .map(MetaCommand::Format),
+            "lang" => required(".lang sql|prql")
+                .and_then(|name| name.parse())
+                .map(MetaCommand::Lang),
+            "set" => match argument.split_once(char::is_whitespace) {
+                Some((name, value)) => {
+                    Ok(MetaCommand::Set(name.to_string(), value.trim().to_string()))
+                }
+                None if argument.is_empty() => Ok(MetaCommand::Vars),
+                None => Err(anyhow::anyhow!("Usage: .set <name> <value>")),
+            },
+            "maxrows" => match argument {
+                "off" => Ok(MetaCommand::MaxRows(None)),
+                _ => argument
+                    .parse()
+                    .map(|max_rows| MetaCommand::MaxRows(Some(max_rows)))
+                    .map_err(|_| anyhow::anyhow!("Usage: .maxrows <n|off>")),
+            },
+            "show" if argument.is_empty() => Ok(MetaCommand::Show(None)),
+            "show" => argument
+                .parse()
+                .map(|id| MetaCommand::Show(Some(id)))
+                .map_err(|_| anyhow::anyhow!("Usage: .show [n]")),
+            "last" => Ok(MetaCommand::Show(None)),
+            "results" => Ok(MetaCommand::Results),
+            "mask" | "masks" => match argument {
+                "" => Ok(MetaCommand::Masks),
+                "off" => Ok(MetaCommand::ClearMasks),
+                _ => argument
+                    .split(',')
+                    .map(|assignment| {
+                        let (column, mask) = assignment.split_once('=').ok_or_else(|| {
+                            anyhow::anyhow!("Usage: .mask [column=mask, ...] or .mask off")
+                        })?;
+                        let mask = match mask.trim() {
+                            "off" => None,
+                            mask => Some(mask.parse()?),
+                        };
+                        Ok((column.trim().to_string(), mask))
+                    })
+                    .collect::<anyhow::Result<Vec<_>>>()
+                    .map(MetaCommand::Mask),
+            },
+            "export" => required(".export <path>").map(MetaCommand::Export),
+            "bookmark" | "bookmarks" => match argument.split_once(char::is_whitespace) {
+                _ if argument.is_empty() => Ok(MetaCommand::Bookmarks),
+                Some(("save", name)) => Ok(MetaCommand::SaveBookmark(name.trim().to_string())),
+                Some(("delete", name)) => Ok(MetaCommand::DeleteBookmark(name.trim().to_string())),
+                None if argument != "save" && argument != "delete" => {
+                    Ok(MetaCommand::RunBookmark(argument.to_string()))
+                }
+                _ => Err(anyhow::anyhow!(
+                    "Usage: .bookmark [name], .bookmark save <name>, or .bookmark delete <name>"
+                )),
+            },
+            "unset" => required(".unset <name>").map(MetaCommand::Unset),
+            "vars" => Ok(MetaCommand::Vars),
+            "exit" | "quit" => Ok(MetaCommand::Exit),
+            _ => Err(anyhow::anyhow!(
+                "Unknown command '.{}', see .help for available commands",
+                name
+            )),
+        })
+    }
+
+    /// Run the command against `engine` and the session's `options`, returning the text to display
+    pub async fn execute(
+        &self,
+        engine: &mut Box<dyn EngineInterface>,
+        schema_cache: &SchemaCache,
+        options: &mut ReplOptions,
+    ) -> anyhow::Result<String> {
+        Ok(match self {
+            MetaCommand::Help => HELP.to_string(),
+            MetaCommand::Tables => {
+                schema_cache.refresh(engine).await?;
+                let tables = schema_cache.tables();
+                if tables.is_empty() {
+                    "No tables registered".to_string()
+                } else {
+                    tables
+                        .iter()
+                        .map(|table| match &table.source {
+                            Some(source) => format!("{} ({})", table.name, source),
+                            None => table.name.clone(),
+                        })
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                }
+            }
+            MetaCommand::Schema(name) => {
+                schema_cache.refresh(engine).await?;
+                let table = schema_cache
+                    .table(name)
+                    .ok_or_else(|| anyhow::anyhow!("No table named '{}'", name))?;
+                let schema = table
+                    .schema
+                    .ok_or_else(|| anyhow::anyhow!("Schema of '{}' is unknown", name))?;
+                schema
+                    .fields()
+                    .iter()
+                    .map(|field| {
+                        format!(
+                            "{} {}{}",
+                            field.name(),
+                            field.data_type(),
+                            if field.is_nullable() { "" } else { " NOT NULL" }
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            }
+            MetaCommand::Engines => {
+                let info = engine.info();
+                Engine::ALL
+                    .iter()
+                    .map(|kind| {
+                        if *kind == info.engine {
+                            let settings = info
+                                .settings
+                                .iter()
+                                .map(|(name, value)| format!("{}={}", name, value))
+                                .collect::<Vec<_>>()
+                                .join(", ");
+                            format!("* {} {} ({})", kind.name(), info.library_version, settings)
+                        } else if kind.is_available() {
+                            format!("  {}", kind.name())
+                        } else {
+                            format!("  {} (not built in)", kind.name())
+                        }
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            }
+            MetaCommand::Cache(true) => {
+                engine.clear_caches();
+                "Caches cleared".to_string()
+            }
+            MetaCommand::Cache(false) => engine
+                .cache_stats()
+                .iter()
+                .map(|stats| {
+                    format!(
+                        "{}: {} of {} kept, {} hits, {} misses",
+                        stats.cache, stats.entries, stats.capacity, stats.hits, stats.misses
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+            MetaCommand::Engine(kind) => {
+                if *kind == engine.kind() {
+                    return Ok(format!("Already using {}", kind.name()));
+                }
+                schema_cache.refresh(engine).await?;
+                let builder = match &options.engine {
+                    Some(builder) => builder.clone().engine(*kind),
+                    None => kind.builder(),
+                };
+                let mut replacement = builder.build()?;
+                let mut carried = Vec::new();
+                let mut dropped = Vec::new();
+                for table in schema_cache.tables() {
+                    match &table.source {
+                        Some(source) => {
+                            open(&mut replacement, source).await?;
+                            carried.push(table.name);
+                        }
+                        None => dropped.push(table.name),
+                    }
+                }
+                *engine = replacement;
+                schema_cache.refresh(engine).await?;
+
+                let mut text = format!("Switched to {}", kind.name());
+                if !carried.is_empty() {
+                    text.push_str(&format!(", re-registered {}", carried.join(", ")));
+                }
+                if !dropped.is_empty() {
+                    text.push_str(&format!(
+                        "\nTables not backed by a file were left behind: {}",
+                        dropped.join(", ")
+                    ));
+                }
+                text
+            }
+            MetaCommand::Open(path) => {
+                open(engine, path).await?;
+                schema_cache.refresh(engine).await?;
+                let table = schema_cache
+                    .table(path)
+                    .ok_or_else(|| anyhow::anyhow!("Failed to register '{}'", path))?;
+                format!("Opened {} as {}", path, table.name)
+            }
+            MetaCommand::Timing(timing) => {
+                options.timing = *timing;
+                format!("Timing is {}", if *timing { "on" } else { "off" })
+            }
+            MetaCommand::Format(format) => {
+                options.format = *format;
+                format!("Output format is {}", format.name())
+            }
+            MetaCommand::Lang(language) => {
+                options.language = *language;
+                format!("Queries are written in {}", language.name())
+            }
+            MetaCommand::Pager(pager) => {
+                options.pager = *pager;
+                format!("Pager is {}", if *pager { "on" } else { "off" })
+            }
+            MetaCommand::Set(name, value) => {
+                options.variables.set(name, value)?;
+                String::new()
+            }
+            MetaCommand::Unset(name) => {
+                if !options.variables.unset(name) {
+                    anyhow::bail!("Variable '{}' is not set", name);
+                }
+                String::new()
+            }
+            MetaCommand::Vars => options.variables.to_string(),
+            MetaCommand::MaxRows(max_rows) => {
+                options.max_rows = *max_rows;
+                match max_rows {
+                    Some(max_rows) => format!("Showing at most {} rows per result", max_rows),
+                    None => "Showing all rows".to_string(),
+                }
+            }
+            MetaCommand::Mask(masks) => {
+                for (column, mask) in masks {
+                    options.masks.set(column, *mask);
+                }
+                masking(options)
+            }
+            MetaCommand::ClearMasks => {
+                options.masks = Default::default();
+                masking(options)
+            }
+            MetaCommand::Masks => masking(options),
+            MetaCommand::Bookmarks => {
+                let bookmarks = Bookmarks::load()?;
+                if bookmarks.is_empty() {
+                    "No bookmarks saved, see .bookmark save <name>".to_string()
+                } else {
+                    bookmarks
+                        .iter()
+                        .map(|(name, sql)| {
+                            format!(
+                                "{}: {}",
+                                name,
+                                sql.split_whitespace().collect::<Vec<_>>().join(" ")
+                            )
+                        })
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                }
+            }
+            MetaCommand::DeleteBookmark(name) => {
+                if !Bookmarks::load()?.delete(name)? {
+                    anyhow::bail!("No bookmark named '{}'", name);
+                }
+                format!("Deleted bookmark {}", name)
+            }
+            MetaCommand::Read { .. }
+            | MetaCommand::Edit
+            | MetaCommand::RunBookmark(_)
+            | MetaCommand::SaveBookmark(_) => {
+                anyhow::bail!("Scripts can only be read and edited by the REPL")
+            }
+            MetaCommand::Export(_) | MetaCommand::Show(_) | MetaCommand::Results => {
+                anyhow::bail!("Results are only kept by the REPL")
+            }
+            MetaCommand::Exit => String::new(),
+        })
+    }
+}
+
+/// Register the file at `path` with `engine`.
+///
+/// Referencing a path in a query is what registers it, so this runs an empty query over it.
+async fn open(engine: &mut Box<dyn EngineInterface>, path: &str) -> anyhow::Result<()> {
+    use futures::stream::StreamExt as _;
+
+    let probe = format!("SELECT * FROM \"{}\" LIMIT 0", path.replace('"', "\"\""));
+    for mut execution in engine.execute(&probe).await? {
+        while let Some(batch) = execution.stream.next().await {
+            batch?;
+        }
+    }
+    Ok(())
+}
+
+/// Which columns results are masked in
+fn masking(options: &ReplOptions) -> String {
+    if options.masks.is_empty() {
+        "No columns are masked".to_string()
+    } else {
+        format!("Masking {}", options.masks)
+    }
+}
+
+fn on_off(argument: &str) -> Option<bool> {
+    match argument {
+        "on" => Some(true),
+        "off" => Some(false),
+        _ => None,
+    }
+}
+
+fn unquote(argument: &str) -> &str {
+    ['\'', '"']
+        .iter()
+        .find_map(|quote| {
+            argument
+                .strip_prefix(*quote)
+                .and_then(|rest| rest.strip_suffix(*quote))
+        })
+        .unwrap_or(argument)
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::stream::TryStreamExt as _;
+
+    use super::*;
+
+    /// Switch `engine` to `kind` with `.engine`, as a session built from `builder` would
+    async fn switch(
+        engine: &mut Box<dyn EngineInterface>,
+        builder: &crate::EngineBuilder,
+        kind: Engine,
+    ) {
+        let mut options = ReplOptions {
+            engine: Some(builder.clone()),
+            ..Default::default()
+        };
+        MetaCommand::Engine(kind)
+            .execute(engine, &SchemaCache::default(), &mut options)
+            .await
+            .unwrap();
+        assert_eq!(engine.kind(), kind);
+    }
+
+    async fn rows(engine: &dyn EngineInterface, query: &str) -> anyhow::Result<usize> {
+        let mut rows = 0;
+        for execution in engine.execute(query).await? {
+            let batches: Vec<_> = execution.stream.try_collect().await?;
+            rows = batches.iter().map(|batch| batch.num_rows()).sum();
+        }
+        Ok(rows)
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn switched_engines_keep_the_row_limit() {
+        let builder = Engine::DataFusion.builder().row_limit(2);
+        let mut engine = builder.clone().build().unwrap();
+        let query = "SELECT * FROM (VALUES (1), (2), (3), (4)) AS t (n)";
+        assert_eq!(rows(engine.as_ref(), query).await.unwrap(), 2);
+
+        switch(&mut engine, &builder, Engine::DuckDB).await;
+        assert_eq!(rows(engine.as_ref(), query).await.unwrap(), 2);
+        switch(&mut engine, &builder, Engine::DataFusion).await;
+        assert_eq!(rows(engine.as_ref(), query).await.unwrap(), 2);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn switched_engines_keep_the_path_policy() {
+        let builder = Engine::DataFusion.builder().path_policy(crate::PathPolicy {
+            allow: Vec::new(),
+            deny: vec!["/denied".to_string()],
+        });
+        let mut engine = builder.clone().build().unwrap();
+        for kind in [Engine::DuckDB, Engine::Polars, Engine::DataFusion] {
+            switch(&mut engine, &builder, kind).await;
+            let error = rows(engine.as_ref(), "SELECT * FROM '/denied/t.parquet'")
+                .await
+                .unwrap_err();
+            assert!(
+                matches!(
+                    crate::CallistoError::of(&error),
+                    Some(crate::CallistoError::PathNotAllowed { .. })
+                ),
+                "{:?} loaded a denied path: {:?}",
+                kind,
+                error
+            );
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn switched_engines_without_a_configuration_take_their_defaults() {
+        let mut engine = Engine::DataFusion.builder().row_limit(2).build().unwrap();
+        MetaCommand::Engine(Engine::DuckDB)
+            .execute(
+                &mut engine,
+                &SchemaCache::default(),
+                &mut ReplOptions::default(),
+            )
+            .await
+            .unwrap();
+        let query = "SELECT * FROM (VALUES (1), (2), (3), (4)) AS t (n)";
+        assert_eq!(rows(engine.as_ref(), query).await.unwrap(), 4);
+    }
+}
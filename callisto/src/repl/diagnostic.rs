@@ -0,0 +1,305 @@
+use crate::SourceSpan;
+
+/// An error located within the SQL that caused it, displayed as the offending line with a caret
+/// under the problem token, e.g.
+///
+/// ```text
+/// Error at line 1, column 10:
+///   SELECT * FORM t
+///            ^^^^
+/// Expected end of statement, found: FORM
+/// ```
+#[derive(Debug)]
+pub(crate) struct Diagnostic {
+    /// The line of SQL the error points into
+    source_line: String,
+    /// 1-based line and column of the problem token
+    line: usize,
+    column: usize,
+    /// Width of the problem token, in characters
+    width: usize,
+    hint: String,
+}
+
+impl Diagnostic {
+    /// Locate `error` within `sql`, if the parser or engine reported where it went wrong.
+    ///
+    /// An error from executing `sql` carries the [`SourceSpan`] of the statement at fault, within
+    /// which a token DuckDB reports as `at or near "<token>"` is looked for, the start of the span
+    /// being pointed at otherwise. Failing that, positions are recognized as sqlparser's
+    /// `at Line: <n>, Column <m>` suffix, as that DuckDB phrasing, in which case the first
+    /// occurrence of the token is assumed, or as sqlparser finding the end of input.
+    pub(crate) fn locate(sql: &str, error: &anyhow::Error) -> Option<Diagnostic> {
+        if let Some(span) = SourceSpan::of(error) {
+            return Diagnostic::within(sql, span, error);
+        }
+        error.chain().find_map(|cause| {
+            let message = cause.to_string();
+            let message = message.lines().next().unwrap_or_default();
+            let (line, column) = parse_location(message)
+                .or_else(|| find_near_token(sql, message))
+                .or_else(|| end_of_input(sql, message))?;
+            let source_line = sql.lines().nth(line.checked_sub(1)?)?.to_string();
+            let width = token_width(source_line.chars().skip(column.checked_sub(1)?));
+            Some(Diagnostic {
+                source_line,
+                line,
+                column,
+                width,
+                hint: hint(message),
+            })
+        })
+    }
+
+    /// Locate `error` within the `span` of `sql` it carries
+    fn within(sql: &str, span: &SourceSpan, error: &anyhow::Error) -> Option<Diagnostic> {
+        let message = error.root_cause().to_string();
+        let message = message.lines().next().unwrap_or_default();
+        let statement = sql.get(span.range.clone())?;
+        let (start, width) = match find_near_token(statement, message) {
+            Some((line, column)) => {
+                let line_start = statement
+                    .split_inclusive('\n')
+                    .take(line - 1)
+                    .map(str::len)
+                    .sum::<usize>();
+                let offset = statement[line_start..]
+                    .char_indices()
+                    .nth(column - 1)
+                    .map_or(statement.len(), |(index, _)| line_start + index);
+                let start = span.range.start + offset;
+                (start, token_width(sql[start..].chars()))
+            }
+            // The span's first line, or a single caret where the span is empty
+            None => {
+                let width = statement.lines().next().unwrap_or_default().chars().count();
+                (span.range.start, width.max(1))
+            }
+        };
+        let line_start = sql[..start].rfind('\n').map_or(0, |index| index + 1);
+        let source_line = sql[line_start..].lines().next().unwrap_or_default();
+        let column = sql[line_start..start].chars().count() + 1;
+        Some(Diagnostic {
+            source_line: source_line.to_string(),
+            line: sql[..start].matches('\n').count() + 1,
+            column,
+            width,
+            hint: hint(message),
+        })
+    }
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Error at line {}, column {}:", self.line, self.column)?;
+        writeln!(f, "  {}", self.source_line)?;
+        writeln!(
+            f,
+            "  {}{}",
+            " ".repeat(self.column - 1),
+            "^".repeat(self.width)
+        )?;
+        write!(f, "{}", self.hint)
+    }
+}
+
+impl std::error::Error for Diagnostic {}
+
+/// An error accompanied by guesses at what the user meant, e.g. a similarly named table
+#[derive(Debug)]
+pub(crate) struct Suggested {
+    pub error: anyhow::Error,
+    pub suggestions: Vec<String>,
+}
+
+impl std::fmt::Display for Suggested {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", report(&self.error))?;
+        for suggestion in &self.suggestions {
+            write!(f, "\n{}", suggestion)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Suggested {}
+
+/// The text with which an error is reported to the user, with any diagnostic or suggestions shown
+/// as-is and other errors as their full chain of causes
+pub(crate) fn report(error: &anyhow::Error) -> String {
+    if error.is::<Diagnostic>() || error.is::<Suggested>() {
+        format!("{}", error)
+    } else {
+        format!("Error: {:?}", error)
+    }
+}
+
+/// The line and column from sqlparser's `at Line: <n>, Column <m>` suffix
+fn parse_location(message: &str) -> Option<(usize, usize)> {
+    let (_, location) = message.rsplit_once("at Line: ")?;
+    let (line, column) = location.split_once(", Column")?;
+    let column = column
+        .trim_start_matches(':')
+        .trim_start()
+        .split(|c: char| !c.is_ascii_digit())
+        .next()?;
+    Some((line.trim().parse().ok()?, column.parse().ok()?))
+}
+
+/// The position of the first occurrence in `sql` of the token named by `at or near "<token>"`
+fn find_near_token(sql: &str, message: &str) -> Option<(usize, usize)> {
+    let (_, near) = message.split_once("at or near \"")?;
+    let (token, _) = near.split_once('"')?;
+    let token = token.to_lowercase();
+    if token.is_empty() {
+        return None;
+    }
+    sql.lines().enumerate().find_map(|(index, line)| {
+        let offset = line.to_lowercase().find(&token)?;
+        Some((index + 1, line[..offset].chars().count() + 1))
+    })
+}
+
+/// The position just past the end of `sql`, for errors about input ending too soon
+fn end_of_input(sql: &str, message: &str) -> Option<(usize, usize)> {
+    if !message.ends_with("found: EOF") {
+        return None;
+    }
+    let sql = sql.trim_end();
+    let last = sql.lines().last()?;
+    Some((sql.lines().count(), last.chars().count() + 1))
+}
+
+/// Width of the token at the start of `chars`, taking a single character for punctuation
+fn token_width(chars: impl Iterator<Item = char>) -> usize {
+    let mut chars = chars.peekable();
+    match chars.peek() {
+        Some(c) if c.is_alphanumeric() || *c == '_' => chars
+            .take_while(|c| c.is_alphanumeric() || *c == '_')
+            .count(),
+        _ => 1,
+    }
+}
+
+/// The error's message without the parser's prefix and location, which the caret already shows
+fn hint(message: &str) -> String {
+    let message = message
+        .trim_start_matches("SQL error: ")
+        .trim_start_matches("ParserError(\"")
+        .trim_start_matches("sql parser error: ");
+    let message = match message.rsplit_once(" at Line: ") {
+        Some((message, _)) => message,
+        None => message.trim_end_matches("\")"),
+    };
+    message.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The error sqlparser fails to parse `sql` with
+    fn parse_error(sql: &str) -> anyhow::Error {
+        sqlparser::parser::Parser::parse_sql(&sqlparser::dialect::GenericDialect, sql)
+            .unwrap_err()
+            .into()
+    }
+
+    fn locate(sql: &str, error: &anyhow::Error) -> String {
+        Diagnostic::locate(sql, error).unwrap().to_string()
+    }
+
+    #[test]
+    fn parse_errors_point_at_their_token() {
+        let sql = "SELECT * FORM t";
+        assert_eq!(
+            locate(sql, &parse_error(sql)),
+            "Error at line 1, column 10:\n  SELECT * FORM t\n           ^^^^\n\
+             Expected end of statement, found: FORM"
+        );
+    }
+
+    #[test]
+    fn columns_count_characters() {
+        let sql = "SELECT 'é', * FORM t";
+        assert_eq!(
+            locate(sql, &parse_error(sql)),
+            "Error at line 1, column 15:\n  SELECT 'é', * FORM t\n                ^^^^\n\
+             Expected end of statement, found: FORM"
+        );
+    }
+
+    #[test]
+    fn input_ending_too_soon_is_pointed_past() {
+        let sql = "SELECT *\nFROM  \n";
+        assert_eq!(
+            locate(sql, &parse_error(sql)),
+            "Error at line 2, column 5:\n  FROM  \n      ^\nExpected identifier, found: EOF"
+        );
+    }
+
+    #[test]
+    fn tokens_duckdb_is_near_are_found() {
+        let sql = "SELECT a\nFROM t WHERE a = = 1";
+        let error = anyhow::anyhow!("Parser Error: syntax error at or near \"=\"");
+        assert_eq!(
+            locate(sql, &error),
+            "Error at line 2, column 16:\n  FROM t WHERE a = = 1\n                 ^\n\
+             Parser Error: syntax error at or near \"=\""
+        );
+    }
+
+    #[test]
+    fn source_spans_narrow_the_search_to_their_statement() {
+        // FROM appears in the first statement too, which doesn't fail
+        let sql = "SELECT a FROM t;\nSELECT a, FROM t";
+        let error =
+            anyhow::anyhow!("Parser Error: syntax error at or near \"FROM\"").context(SourceSpan {
+                statement: 1,
+                range: 17..sql.len(),
+            });
+        assert_eq!(
+            locate(sql, &error),
+            "Error at line 2, column 11:\n  SELECT a, FROM t\n            ^^^^\n\
+             Parser Error: syntax error at or near \"FROM\""
+        );
+    }
+
+    #[test]
+    fn source_spans_are_pointed_at_whole() {
+        let sql = "SELECT 1;\nSELECT * FROM missing";
+        let start = sql.find("missing").unwrap();
+        let error = anyhow::anyhow!("Table missing does not exist").context(SourceSpan {
+            statement: 1,
+            range: start..sql.len(),
+        });
+        assert_eq!(
+            locate(sql, &error),
+            "Error at line 2, column 15:\n  SELECT * FROM missing\n                ^^^^^^^\n\
+             Table missing does not exist"
+        );
+    }
+
+    #[test]
+    fn errors_without_locations_are_reported_whole() {
+        let error = anyhow::anyhow!("Disk full").context("Failed to write results");
+        assert!(Diagnostic::locate("SELECT 1", &error).is_none());
+        // Followed by a backtrace, if they're enabled
+        assert!(report(&error)
+            .starts_with("Error: Failed to write results\n\nCaused by:\n    Disk full"));
+    }
+
+    #[test]
+    fn suggestions_follow_their_error() {
+        let sql = "SELECT * FORM t";
+        let located = Diagnostic::locate(sql, &parse_error(sql)).unwrap();
+        let error = anyhow::Error::from(Suggested {
+            error: located.into(),
+            suggestions: vec!["Did you mean FROM?".to_string()],
+        });
+        assert_eq!(
+            report(&error),
+            format!("{}\nDid you mean FROM?", locate(sql, &parse_error(sql)))
+        );
+    }
+}
@@ -0,0 +1,30 @@
+const DEFAULT_EDITOR: &str = "vi";
+
+/// Let the user edit `text` in `$VISUAL` or `$EDITOR`, falling back to `vi`, returning what they
+/// saved
+pub(crate) fn edit(text: &str) -> anyhow::Result<String> {
+    use anyhow::Context as _;
+
+    let command = ["VISUAL", "EDITOR"]
+        .into_iter()
+        .filter_map(|variable| std::env::var(variable).ok())
+        .find(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_EDITOR.to_string());
+    let path = std::env::temp_dir().join(format!("callisto-edit-{}.sql", std::process::id()));
+    std::fs::write(&path, text).with_context(|| format!("Failed to write {}", path.display()))?;
+
+    let mut words = command.split_whitespace();
+    let program = words.next().context("Empty editor command")?;
+    let status = std::process::Command::new(program)
+        .args(words)
+        .arg(&path)
+        .status()
+        .with_context(|| format!("Failed to start editor '{}'", command));
+    let edited = std::fs::read_to_string(&path);
+    let _ = std::fs::remove_file(&path);
+    let status = status?;
+    if !status.success() {
+        anyhow::bail!("Editor '{}' exited with {}", command, status);
+    }
+    edited.with_context(|| format!("Failed to read back {}", path.display()))
+}
@@ -0,0 +1,931 @@
+use std::borrow::Cow;
+use std::path::PathBuf;
+
+use crate::completion::{Completer, CompletionKind};
+use crate::highlight::{Highlighter, TokenClass};
+use arrow::record_batch::RecordBatch;
+
+use crate::datafusion::physical_plan::SendableRecordBatchStream;
+use crate::output_format::Renderer;
+use crate::schema_cache::SchemaCache;
+use crate::{
+    EngineBuilder, EngineInterface, Language, MaskPolicy, OutputFormat, QueryExecution, ResultSet,
+};
+use diagnostic::{Diagnostic, Suggested};
+use result_history::ResultHistory;
+use spinner::Spinner;
+
+mod commands;
+mod diagnostic;
+mod editor;
+mod pager;
+mod result_history;
+mod script;
+mod spinner;
+mod suggest;
+mod variables;
+
+pub use commands::MetaCommand;
+pub use variables::Variables;
+
+const COMPLETION_MENU: &str = "completion_menu";
+
+/// Prompt used unless configured otherwise
+pub const DEFAULT_PROMPT: &str = "{engine}{transaction} ▶ ";
+
+/// Settings controlling REPL input and how it reports on executed statements
+#[derive(Clone, Debug)]
+pub struct ReplOptions {
+    /// Prompt template, see [`DEFAULT_PROMPT`]; `{engine}` expands to the active engine's name,
+    /// `{catalog}` to the catalog and schema it resolves table names in, and `{transaction}` to `*`
+    /// while a transaction is open
+    pub prompt: String,
+    /// Print timing and row counts after each statement, toggled during a session with `.timing`
+    pub timing: bool,
+    /// How results are rendered, changed during a session with `.format`
+    pub format: OutputFormat,
+    /// Language queries are written in, changed during a session with `.lang`
+    pub language: Language,
+    /// Show results taller than the terminal through `$PAGER`, toggled with `.pager`
+    pub pager: bool,
+    /// Most rows displayed per result, changed during a session with `.maxrows`
+    pub max_rows: Option<usize>,
+    /// Number of recent results kept for `.show` and `.export`
+    pub result_history: usize,
+    /// Variables substituted into statements, changed during a session with `\set` and `\unset`
+    pub variables: Variables,
+    /// Script of meta-commands and SQL run before the first prompt, skipped if it doesn't exist
+    pub rc_file: Option<PathBuf>,
+    /// File in which to persist command history across sessions, if any
+    pub history_file: Option<PathBuf>,
+    /// Maximum number of history entries kept
+    pub history_size: usize,
+    /// Read input through a line editor on the terminal, with prompts, history, and completion,
+    /// rather than from the input given; e.g. when stdin is a terminal
+    pub interactive: bool,
+    /// When input is piped, report errors and carry on rather than stopping at the first one
+    pub continue_on_error: bool,
+    /// Batches of a result fetched ahead while the one before them is rendered, so that the
+    /// engine runs on as the terminal is written to; none if zero
+    pub prefetch_batches: usize,
+    /// Masks of columns of results, applied before they're displayed or kept, changed during a
+    /// session with `.mask`
+    pub masks: MaskPolicy,
+    /// Configuration the engines switched to with `.engine` are built from, e.g. that of the
+    /// session's first engine, so that they keep its row limit and path policy; each engine's
+    /// defaults if unset
+    pub engine: Option<EngineBuilder>,
+}
+
+impl Default for ReplOptions {
+    fn default() -> ReplOptions {
+        ReplOptions {
+            prompt: DEFAULT_PROMPT.to_string(),
+            timing: false,
+            format: OutputFormat::default(),
+            language: Language::default(),
+            pager: true,
+            max_rows: None,
+            result_history: 20,
+            variables: Variables::default(),
+            rc_file: None,
+            history_file: None,
+            history_size: 10_000,
+            interactive: false,
+            continue_on_error: false,
+            prefetch_batches: 4,
+            masks: MaskPolicy::default(),
+            engine: None,
+        }
+    }
+}
+
+fn create_editor(
+    options: &ReplOptions,
+    schema_cache: &SchemaCache,
+) -> anyhow::Result<reedline::Reedline> {
+    let mut keybindings = reedline::default_emacs_keybindings();
+    keybindings.add_binding(
+        reedline::KeyModifiers::NONE,
+        reedline::KeyCode::Tab,
+        reedline::ReedlineEvent::UntilFound(vec![
+            reedline::ReedlineEvent::Menu(COMPLETION_MENU.to_string()),
+            reedline::ReedlineEvent::MenuNext,
+        ]),
+    );
+    let completion_menu = reedline::ColumnarMenu::default().with_name(COMPLETION_MENU);
+
+    let mut editor = reedline::Reedline::create()
+        .with_edit_mode(Box::new(reedline::Emacs::new(keybindings)))
+        .with_completer(Box::new(ReplCompleter(Completer::new(
+            schema_cache.clone(),
+        ))))
+        .with_highlighter(Box::new(ReplHighlighter(Highlighter::new(
+            schema_cache.clone(),
+        ))))
+        .with_menu(reedline::ReedlineMenu::EngineCompleter(Box::new(
+            completion_menu,
+        )))
+        .with_validator(Box::new(ReplValidator))
+        // Pasted text arrives as one edit, so its newlines don't submit it piecemeal
+        .use_bracketed_paste(true);
+    if let Some(path) = &options.history_file {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        // Consecutive duplicate entries are skipped by the history itself.
+        let history = reedline::FileBackedHistory::with_file(options.history_size, path.clone())?;
+        editor = editor.with_history(Box::new(history));
+    }
+    Ok(editor)
+}
+
+/// Source of REPL input lines: a line editor when attached to a terminal, otherwise the raw input
+enum LineReader<Input> {
+    Editor(Box<reedline::Reedline>),
+    Lines(tokio::io::Lines<tokio::io::BufReader<Input>>),
+}
+
+impl<Input> LineReader<Input>
+where
+    Input: tokio::io::AsyncRead + Unpin,
+{
+    /// Read the next input, returning `None` at end of input.
+    ///
+    /// Piped input is read without prompting, a line at a time until it holds a complete
+    /// statement or meta-command.
+    async fn next_input(&mut self, prompt: &ReplPrompt) -> anyhow::Result<Option<String>> {
+        match self {
+            LineReader::Editor(editor) => loop {
+                let signal = tokio::task::block_in_place(|| editor.read_line(prompt))?;
+                match signal {
+                    reedline::Signal::Success(line) => return Ok(Some(line)),
+                    // Ctrl-C abandons the line being edited rather than leaving the REPL
+                    reedline::Signal::CtrlC => continue,
+                    reedline::Signal::CtrlD => return Ok(None),
+                }
+            },
+            LineReader::Lines(lines) => {
+                let mut input = String::new();
+                loop {
+                    // Once a query has installed the Ctrl-C handler, interrupting ends piped input
+                    let line = tokio::select! {
+                        line = lines.next_line() => line?,
+                        _ = tokio::signal::ctrl_c() => None,
+                    };
+                    let Some(line) = line else {
+                        return Ok(Some(input).filter(|input| !input.trim().is_empty()));
+                    };
+                    input.push_str(&line);
+                    input.push('\n');
+                    if is_exit(&input) || script::is_complete(&input) {
+                        return Ok(Some(input));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Keeps Enter inserting newlines until the input ends in a complete statement or meta-command
+struct ReplValidator;
+
+impl reedline::Validator for ReplValidator {
+    fn validate(&self, line: &str) -> reedline::ValidationResult {
+        if is_exit(line) || script::is_complete(line) {
+            reedline::ValidationResult::Complete
+        } else {
+            reedline::ValidationResult::Incomplete
+        }
+    }
+}
+
+struct ReplCompleter(Completer);
+
+impl reedline::Completer for ReplCompleter {
+    fn complete(&mut self, line: &str, pos: usize) -> Vec<reedline::Suggestion> {
+        self.0
+            .complete(line, pos)
+            .into_iter()
+            .map(|completion| reedline::Suggestion {
+                value: completion.value,
+                description: completion.description,
+                style: None,
+                extra: None,
+                span: reedline::Span::new(completion.span.start, completion.span.end),
+                append_whitespace: completion.kind == CompletionKind::Keyword,
+            })
+            .collect()
+    }
+}
+
+struct ReplHighlighter(Highlighter);
+
+impl reedline::Highlighter for ReplHighlighter {
+    fn highlight(&self, line: &str, _cursor: usize) -> reedline::StyledText {
+        use nu_ansi_term::{Color, Style};
+
+        let mut styled = reedline::StyledText::new();
+        for (class, span) in self.0.classify(line) {
+            let style = match class {
+                TokenClass::Plain => Style::new(),
+                TokenClass::Keyword => Color::Blue.bold(),
+                TokenClass::String => Color::Green.normal(),
+                TokenClass::Number => Color::Purple.normal(),
+                TokenClass::Comment => Color::DarkGray.normal(),
+                TokenClass::Table => Color::Cyan.normal(),
+                TokenClass::Error => Color::Red.bold().underline(),
+            };
+            styled.push((style, line[span].to_string()));
+        }
+        styled
+    }
+}
+
+/// Prompt rendered from a template, e.g. `datafusion ▶ `
+struct ReplPrompt {
+    text: String,
+}
+
+impl ReplPrompt {
+    /// Expand `{engine}` in `template` to the active engine's name, `{catalog}` to its current
+    /// catalog, if it has catalogs, and `{transaction}` to `*` while a transaction is open
+    fn new(template: &str, engine: &dyn EngineInterface, in_transaction: bool) -> ReplPrompt {
+        let mut text = template
+            .replace("{engine}", engine.kind().name())
+            .replace("{transaction}", if in_transaction { "*" } else { "" });
+        // Only asked of the engine if the template shows it
+        if text.contains("{catalog}") {
+            text = text.replace("{catalog}", &engine.current_catalog().unwrap_or_default());
+        }
+        ReplPrompt { text }
+    }
+}
+
+impl reedline::Prompt for ReplPrompt {
+    fn render_prompt_left(&self) -> Cow<str> {
+        Cow::Borrowed(&self.text)
+    }
+
+    fn render_prompt_right(&self) -> Cow<str> {
+        Cow::Borrowed("")
+    }
+
+    fn render_prompt_indicator(&self, _prompt_mode: reedline::PromptEditMode) -> Cow<str> {
+        Cow::Borrowed("")
+    }
+
+    fn render_prompt_multiline_indicator(&self) -> Cow<str> {
+        Cow::Borrowed("... ")
+    }
+
+    fn render_prompt_history_search_indicator(
+        &self,
+        history_search: reedline::PromptHistorySearch,
+    ) -> Cow<str> {
+        let prefix = match history_search.status {
+            reedline::PromptHistorySearchStatus::Passing => "",
+            reedline::PromptHistorySearchStatus::Failing => "failing ",
+        };
+        Cow::Owned(format!(
+            "({}reverse-search: {}) ",
+            prefix, history_search.term
+        ))
+    }
+}
+
+/// Error marking a query interrupted with Ctrl-C
+#[derive(Debug)]
+struct Cancelled;
+
+impl std::fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Query cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// Where a statement's rendered results are written
+enum ResultSink {
+    /// Straight to the REPL's output
+    Direct,
+    /// Held back until it is clear whether they fit within `rows` terminal lines
+    Buffering { text: String, rows: usize },
+    /// Into a pager, having outgrown the terminal
+    Paging(pager::Pager),
+}
+
+/// What the REPL should do after handling a line
+enum Flow {
+    Continue,
+    Exit,
+}
+
+pub struct Repl<Output> {
+    output: Output,
+    schema_cache: SchemaCache,
+    options: ReplOptions,
+    /// Results of the most recently executed statements
+    results: ResultHistory,
+    /// Whether a transaction has been started and not yet committed or rolled back
+    in_transaction: bool,
+    /// The SQL most recently submitted, before variable substitution, for `.edit`
+    last_statement: Option<String>,
+}
+
+impl<Output> Repl<Output>
+where
+    Output: tokio::io::AsyncWriteExt + Unpin,
+{
+    async fn print(&mut self, text: &str) -> tokio::io::Result<()> {
+        self.output.write_all(text.as_bytes()).await
+    }
+
+    async fn println(&mut self, text: &str) -> tokio::io::Result<()> {
+        self.print(text).await?;
+        self.print("\n").await
+    }
+
+    /// Handle one line of input, either a meta-command or SQL
+    async fn handle(
+        &mut self,
+        engine: &mut Box<dyn EngineInterface>,
+        line: &str,
+    ) -> anyhow::Result<Flow> {
+        let command = line.trim();
+        if command.is_empty() {
+            return Ok(Flow::Continue);
+        }
+        if is_exit(command) {
+            return Ok(Flow::Exit);
+        }
+        if let Some(parsed) = MetaCommand::parse(command) {
+            match parsed? {
+                MetaCommand::Exit => return Ok(Flow::Exit),
+                MetaCommand::Read { path, force } => {
+                    return self.read(engine, &path, force, true).await
+                }
+                MetaCommand::Edit => return self.edit(engine).await,
+                MetaCommand::RunBookmark(name) => return self.run_bookmark(engine, &name).await,
+                MetaCommand::SaveBookmark(name) => {
+                    let statement = self
+                        .last_statement
+                        .as_deref()
+                        .ok_or_else(|| anyhow::anyhow!("No statement to bookmark yet"))?;
+                    crate::Bookmarks::load()?.save(&name, statement)?;
+                    self.println(&format!("Bookmarked the last statement as {}", name))
+                        .await?;
+                    return Ok(Flow::Continue);
+                }
+                MetaCommand::Show(id) => {
+                    self.show(id).await?;
+                    return Ok(Flow::Continue);
+                }
+                MetaCommand::Results => {
+                    let text = self.list_results();
+                    self.println(&text).await?;
+                    return Ok(Flow::Continue);
+                }
+                MetaCommand::Export(path) => {
+                    let result = self
+                        .results
+                        .last()
+                        .map(|entry| &entry.result)
+                        .ok_or_else(|| anyhow::anyhow!("No result to export yet"))?;
+                    result.export(std::path::Path::new(&path))?;
+                    let text = format!("Exported {} rows to {}", result.num_rows(), path);
+                    self.println(&text).await?;
+                    return Ok(Flow::Continue);
+                }
+                meta_command => {
+                    let text = meta_command
+                        .execute(engine, &self.schema_cache, &mut self.options)
+                        .await?;
+                    if !text.is_empty() {
+                        self.println(&text).await?;
+                    }
+                    return Ok(Flow::Continue);
+                }
+            }
+        }
+
+        self.last_statement = Some(command.to_string());
+        let command = self.options.variables.substitute(command)?;
+        let command = crate::prql::to_sql(&command, self.options.language, engine.kind())?;
+        for (name, entry) in self.results.referenced(&command) {
+            engine
+                .register_batches(
+                    &name,
+                    entry.result.schema.clone(),
+                    entry.result.batches.clone(),
+                )
+                .await?;
+        }
+        // Ctrl-C is watched for on a task of its own, as an engine blocking this one while it
+        // works would leave it unread. It interrupts what the engine is running, and the
+        // execution is dropped, dropping its result streams, for engines which stop once dropped.
+        let (interrupted, mut cancelled) = tokio::sync::oneshot::channel();
+        let interrupter = engine.interrupter();
+        let watcher = tokio::spawn(async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                let _ = interrupted.send(());
+                interrupter.interrupt();
+            }
+        });
+        let outcome = tokio::select! {
+            biased;
+            Ok(()) = &mut cancelled => None,
+            result = self.execute(engine, &command) => Some(result),
+        };
+        watcher.abort();
+        // A statement the interrupt stopped fails with the engine's own error, which is the
+        // cancel's
+        let outcome = match outcome {
+            Some(result) if cancelled.try_recv().is_err() => result,
+            _ => Err(Cancelled.into()),
+        };
+        // Statements before a failure may still have registered or created tables
+        if let Err(error) = self.schema_cache.refresh(engine).await {
+            tracing::warn!("Failed to refresh table schemas: {}", error);
+        }
+        outcome.map(|()| Flow::Continue).map_err(|error| {
+            if error.is::<Cancelled>() {
+                return error;
+            }
+            let error = match Diagnostic::locate(&command, &error) {
+                Some(diagnostic) => diagnostic.into(),
+                None => error,
+            };
+            let suggestions = suggest::did_you_mean(&command, &self.schema_cache);
+            if suggestions.is_empty() {
+                error
+            } else {
+                Suggested { error, suggestions }.into()
+            }
+        })
+    }
+
+    /// Open the last statement in the user's editor, then run whatever they save
+    async fn edit(&mut self, engine: &mut Box<dyn EngineInterface>) -> anyhow::Result<Flow> {
+        let text = self.last_statement.clone().unwrap_or_default();
+        self.output.flush().await?;
+        let edited = tokio::task::block_in_place(|| editor::edit(&text))?;
+        for entry in script::split(&edited) {
+            self.println(&entry).await?;
+            if let Flow::Exit = Box::pin(self.handle(engine, &entry)).await? {
+                return Ok(Flow::Exit);
+            }
+        }
+        Ok(Flow::Continue)
+    }
+
+    /// Run the statements of the bookmark called `name`, echoing each as it goes
+    async fn run_bookmark(
+        &mut self,
+        engine: &mut Box<dyn EngineInterface>,
+        name: &str,
+    ) -> anyhow::Result<Flow> {
+        let bookmarks = crate::Bookmarks::load()?;
+        let sql = bookmarks
+            .get(name)
+            .ok_or_else(|| anyhow::anyhow!("No bookmark named '{}', see .bookmark", name))?;
+        for entry in script::split(sql) {
+            self.println(&entry).await?;
+            if let Flow::Exit = Box::pin(self.handle(engine, &entry)).await? {
+                return Ok(Flow::Exit);
+            }
+        }
+        Ok(Flow::Continue)
+    }
+
+    /// Run each statement and meta-command in the script at `path`, reporting progress as it goes
+    /// if `progress` is set.
+    ///
+    /// The script stops at the first error unless `force` is set, in which case errors are
+    /// reported and counted while the rest of the script runs.  Cancelling a query always stops
+    /// the script.
+    async fn read(
+        &mut self,
+        engine: &mut Box<dyn EngineInterface>,
+        path: &str,
+        force: bool,
+        progress: bool,
+    ) -> anyhow::Result<Flow> {
+        use anyhow::Context as _;
+
+        let script = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read script {}", path))?;
+        // A PRQL script is a single query, without the semicolons that would split it
+        let entries = match Language::of_path(std::path::Path::new(path)) {
+            Language::Prql => vec![format!("{}{}", crate::prql::PRQL_PREFIX, script)],
+            Language::Sql => script::split(&script),
+        };
+        let mut failures = 0;
+        for (index, entry) in entries.iter().enumerate() {
+            if progress {
+                self.println(&format!("-- {} [{}/{}]", path, index + 1, entries.len()))
+                    .await?;
+            }
+            match Box::pin(self.handle(engine, entry)).await {
+                Ok(Flow::Continue) => {}
+                Ok(Flow::Exit) => return Ok(Flow::Exit),
+                Err(error) if force && !error.is::<Cancelled>() => {
+                    failures += 1;
+                    self.println(&diagnostic::report(&error)).await?;
+                }
+                Err(error) => {
+                    return Err(error.context(format!(
+                        "Stopped at entry {} of {}",
+                        index + 1,
+                        path
+                    )))
+                }
+            }
+        }
+        if failures > 0 {
+            anyhow::bail!(
+                "{} of {} entries in {} failed",
+                failures,
+                entries.len(),
+                path
+            );
+        }
+        Ok(Flow::Continue)
+    }
+
+    /// Write part of a statement's results to `sink`, switching to a pager once a buffered result
+    /// outgrows the terminal
+    async fn emit(&mut self, sink: &mut ResultSink, text: &str) -> anyhow::Result<()> {
+        match sink {
+            ResultSink::Direct => {
+                self.print(text).await?;
+                self.output.flush().await?;
+            }
+            ResultSink::Buffering { text: buffer, rows } => {
+                buffer.push_str(text);
+                // Leave a line for the prompt which follows
+                if buffer.lines().count() >= *rows {
+                    let buffer = std::mem::take(buffer);
+                    self.output.flush().await?;
+                    let paged = tokio::task::block_in_place(|| {
+                        let mut pager = pager::Pager::spawn()?;
+                        pager.write(&buffer)?;
+                        anyhow::Ok(pager)
+                    });
+                    *sink = match paged {
+                        Ok(pager) => ResultSink::Paging(pager),
+                        Err(error) => {
+                            tracing::warn!("{:#}", error);
+                            self.print(&buffer).await?;
+                            ResultSink::Direct
+                        }
+                    };
+                }
+            }
+            ResultSink::Paging(pager) => tokio::task::block_in_place(|| pager.write(text))?,
+        }
+        Ok(())
+    }
+
+    /// Finish writing a statement's results, waiting for the user to leave any pager
+    async fn close(&mut self, sink: ResultSink) -> anyhow::Result<()> {
+        match sink {
+            ResultSink::Direct => {}
+            ResultSink::Buffering { text, .. } => self.print(&text).await?,
+            ResultSink::Paging(pager) => tokio::task::block_in_place(|| pager.wait())?,
+        }
+        Ok(())
+    }
+
+    /// The part of `batch` to display given the rows `renderer` has already shown
+    fn visible(&self, renderer: &Renderer, batch: &RecordBatch) -> RecordBatch {
+        match self.options.max_rows {
+            Some(max_rows) => batch.slice(0, batch.num_rows().min(max_rows - renderer.rows())),
+            None => batch.clone(),
+        }
+    }
+
+    /// Display a remembered result again, the most recent one if `id` isn't given
+    async fn show(&mut self, id: Option<usize>) -> anyhow::Result<()> {
+        let entry = match id {
+            Some(id) => self.results.get(id).ok_or_else(|| {
+                anyhow::anyhow!("No result {} in history, see .results for those kept", id)
+            })?,
+            None => self
+                .results
+                .last()
+                .ok_or_else(|| anyhow::anyhow!("No results yet"))?,
+        };
+        let header = format!("\n$ [{}] {}", entry.id, entry.statement);
+        let timing = timing(entry.wall_time, &entry.metrics);
+        let result = entry.result.clone();
+
+        self.println(&header).await?;
+        let mut sink = self.sink();
+        let mut renderer = Renderer::new(self.options.format, result.schema.clone());
+        self.emit(&mut sink, "Results:\n").await?;
+        for batch in &result.batches {
+            let text = renderer.push(&self.visible(&renderer, batch))?;
+            self.emit(&mut sink, &text).await?;
+        }
+        let text = renderer.finish()?;
+        self.emit(&mut sink, &text).await?;
+        if renderer.rows() < result.num_rows() {
+            self.emit(&mut sink, &truncation_note(&renderer, &result))
+                .await?;
+        }
+        self.close(sink).await?;
+        self.println(&timing).await?;
+        Ok(())
+    }
+
+    /// One line per remembered result, oldest first
+    fn list_results(&self) -> String {
+        let lines = self
+            .results
+            .iter()
+            .map(|entry| {
+                format!(
+                    "[{}] {} ({} rows, queryable as {})",
+                    entry.id,
+                    entry.statement,
+                    entry.result.num_rows(),
+                    entry.table_name()
+                )
+            })
+            .collect::<Vec<_>>();
+        if lines.is_empty() {
+            "No results yet".to_string()
+        } else {
+            lines.join("\n")
+        }
+    }
+
+    /// Where to write the results about to be displayed, buffering them to decide on paging if
+    /// the pager is enabled and stdout is a terminal
+    fn sink(&self) -> ResultSink {
+        match pager::terminal_rows() {
+            Some(rows) if self.options.pager => ResultSink::Buffering {
+                text: String::new(),
+                rows,
+            },
+            _ => ResultSink::Direct,
+        }
+    }
+
+    /// Execute the statements in `command` one at a time, printing the results of each as they
+    /// arrive before the next is run
+    async fn execute(
+        &mut self,
+        engine: &mut Box<dyn EngineInterface>,
+        command: &str,
+    ) -> anyhow::Result<()> {
+        use futures::stream::StreamExt as _;
+
+        // Wall time runs from submission for the first statement, then from the previous one
+        let mut started = std::time::Instant::now();
+        let mut executions = engine.execute_statements(command);
+        loop {
+            let spinner = Spinner::start(None);
+            let execution = executions.next().await;
+            spinner.stop().await;
+            let Some(execution) = execution else {
+                break;
+            };
+            let QueryExecution {
+                statement,
+                stream,
+                metrics,
+                ..
+            } = execution?;
+            // Masked before anything's rendered or kept, so neither shows the values hidden
+            let stream = self.options.masks.masked(stream);
+            let schema = stream.schema();
+            if let Some(in_transaction) = transaction_state(&statement) {
+                self.in_transaction = in_transaction;
+            }
+            // Piped input in a machine-readable format gets nothing but the results themselves
+            let annotate = self.options.interactive || self.options.format.is_human_readable();
+            if annotate {
+                self.println(&format!("\n$ {}", statement)).await?;
+                self.output.flush().await?;
+            }
+            let mut sink = self.sink();
+            let mut renderer = Renderer::new(self.options.format, schema.clone());
+            if annotate {
+                self.emit(&mut sink, "Results:\n").await?;
+            }
+
+            // Rows are shown as each batch arrives, up to the row limit, while the full result is
+            // kept for later use.
+            let mut stream = prefetched(stream, self.options.prefetch_batches);
+            let mut batches = Vec::new();
+            let mut spinner = Some(Spinner::start(Some(metrics.clone())));
+            while let Some(batch) = stream.next().await {
+                if let Some(spinner) = spinner.take() {
+                    spinner.stop().await;
+                }
+                let batch = batch?;
+                let text = renderer.push(&self.visible(&renderer, &batch))?;
+                self.emit(&mut sink, &text).await?;
+                batches.push(batch);
+            }
+            if let Some(spinner) = spinner.take() {
+                spinner.stop().await;
+            }
+            let text = renderer.finish()?;
+            self.emit(&mut sink, &text).await?;
+            let result = ResultSet { schema, batches };
+            if renderer.rows() < result.num_rows() {
+                self.emit(&mut sink, &truncation_note(&renderer, &result))
+                    .await?;
+            }
+            self.close(sink).await?;
+
+            let wall_time = started.elapsed();
+            if self.options.timing && annotate {
+                self.println(&timing(wall_time, &metrics)).await?;
+            } else if self.options.timing {
+                eprintln!("{}", timing(wall_time, &metrics));
+            }
+            self.results
+                .push(statement.to_string(), wall_time, metrics, result);
+            started = std::time::Instant::now();
+        }
+        Ok(())
+    }
+
+    /// Run the REPL until input is exhausted or the user exits.
+    ///
+    /// When [`ReplOptions::interactive`] is set, lines are read through a line editor with history,
+    /// search, and the usual Emacs-style editing bindings; otherwise they are read from `input`,
+    /// without prompts, stopping with an error at the first failure unless
+    /// [`ReplOptions::continue_on_error`] is set.  Ctrl-C while a query runs cancels it and
+    /// returns to the prompt.
+    pub async fn run<Input>(
+        engine: &mut Box<dyn EngineInterface>,
+        input: Input,
+        output: Output,
+        options: ReplOptions,
+    ) -> anyhow::Result<()>
+    where
+        Input: tokio::io::AsyncRead + Unpin,
+    {
+        use tokio::io::AsyncBufReadExt as _;
+
+        let interactive = options.interactive;
+        let mut repl = Repl {
+            output,
+            schema_cache: SchemaCache::default(),
+            results: ResultHistory::new(options.result_history),
+            options,
+            in_transaction: false,
+            last_statement: None,
+        };
+
+        let mut reader = if interactive {
+            LineReader::Editor(Box::new(create_editor(&repl.options, &repl.schema_cache)?))
+        } else {
+            LineReader::Lines(tokio::io::BufReader::new(input).lines())
+        };
+
+        if let Some(path) = repl.options.rc_file.clone().filter(|path| path.exists()) {
+            match repl
+                .read(engine, &path.to_string_lossy(), true, false)
+                .await
+            {
+                Ok(Flow::Continue) => {}
+                Ok(Flow::Exit) => return Ok(()),
+                Err(error) => repl.println(&diagnostic::report(&error)).await?,
+            }
+            repl.output.flush().await?;
+        }
+
+        let mut failures = 0;
+        'input: loop {
+            let prompt = ReplPrompt::new(&repl.options.prompt, &**engine, repl.in_transaction);
+            let Some(input) = reader.next_input(&prompt).await? else {
+                break;
+            };
+            // Input may hold several statements and meta-commands, e.g. when pasted, which run
+            // in turn until one fails
+            for entry in script::split(&input) {
+                let error = match repl.handle(engine, &entry).await {
+                    Ok(Flow::Continue) => continue,
+                    Ok(Flow::Exit) => break 'input,
+                    Err(error) => error,
+                };
+                repl.output.flush().await?;
+                if !interactive && !repl.options.continue_on_error {
+                    return Err(error);
+                } else if !interactive {
+                    failures += 1;
+                    eprintln!("{}", diagnostic::report(&error));
+                } else if error.is::<Cancelled>() {
+                    repl.println("\nQuery cancelled").await?;
+                } else {
+                    repl.println(&diagnostic::report(&error)).await?;
+                }
+                break;
+            }
+            repl.output.flush().await?;
+        }
+        if interactive {
+            repl.println("\nGoodbye!").await?;
+        }
+        repl.output.flush().await?;
+        if failures > 0 {
+            anyhow::bail!("{} statement(s) failed", failures);
+        }
+        Ok(())
+    }
+}
+
+/// `stream`, up to `batches` of its batches read ahead on a task of their own as those before
+/// them are taken, so that it's read while they're rendered. The task stops once the returned
+/// stream is dropped.
+fn prefetched(stream: SendableRecordBatchStream, batches: usize) -> SendableRecordBatchStream {
+    use crate::datafusion::physical_plan::stream::RecordBatchReceiverStream;
+    use futures::stream::StreamExt as _;
+
+    if batches == 0 {
+        return stream;
+    }
+    let mut builder = RecordBatchReceiverStream::builder(stream.schema(), batches);
+    let sender = builder.tx();
+    builder.spawn(async move {
+        let mut stream = stream;
+        while let Some(batch) = stream.next().await {
+            if sender.send(batch).await.is_err() {
+                break;
+            }
+        }
+        Ok(())
+    });
+    builder.build()
+}
+
+/// Whether `input` is one of the words that leave the REPL
+fn is_exit(input: &str) -> bool {
+    ["exit", "bye", "q", "quit"].contains(&input.trim().to_lowercase().as_str())
+}
+
+/// Whether a transaction is open after `statement` runs, or `None` if it doesn't start or end one
+fn transaction_state(statement: &sqlparser::ast::Statement) -> Option<bool> {
+    use sqlparser::ast::Statement;
+
+    match statement {
+        Statement::StartTransaction { .. } => Some(true),
+        Statement::Rollback {
+            savepoint: Some(_), ..
+        } => None,
+        Statement::Commit { .. } | Statement::Rollback { .. } => Some(false),
+        _ => None,
+    }
+}
+
+fn timing(wall_time: std::time::Duration, metrics: &crate::ExecutionMetrics) -> String {
+    format!(
+        "Time: {:?} wall, {} rows ({})",
+        wall_time,
+        metrics.rows_returned(),
+        metrics
+    )
+}
+
+fn truncation_note(renderer: &Renderer, result: &ResultSet) -> String {
+    format!(
+        "({} of {} rows shown, see .maxrows)\n",
+        renderer.rows(),
+        result.num_rows()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run `script` as piped input, with the default options
+    async fn run(script: &str) -> anyhow::Result<()> {
+        let mut engine = crate::Engine::DataFusion.new()?;
+        Repl::run(
+            &mut engine,
+            script.as_bytes(),
+            tokio::io::sink(),
+            ReplOptions::default(),
+        )
+        .await
+    }
+
+    // Whether or not the tests are run from a terminal, input given to a session that isn't
+    // interactive is read as a script
+    #[tokio::test(flavor = "multi_thread")]
+    async fn scripts_stop_at_their_first_failure() {
+        run("SELECT 1;\nSELECT 2;\n").await.unwrap();
+        let error = run("SELECT 1;\nSELECT * FROM missing;\nSELECT 2;\n")
+            .await
+            .unwrap_err();
+        assert!(format!("{:#}", error).contains("missing"), "{:?}", error);
+    }
+}
@@ -0,0 +1,65 @@
+use std::io::IsTerminal as _;
+
+const DEFAULT_PAGER: &str = "less -S";
+
+/// Height of the terminal stdout is attached to, or `None` when stdout isn't a terminal so that
+/// piped and redirected output is never paged
+pub(crate) fn terminal_rows() -> Option<usize> {
+    if !std::io::stdout().is_terminal() {
+        return None;
+    }
+    let (_, rows) = ratatui::crossterm::terminal::size().ok()?;
+    Some(usize::from(rows))
+}
+
+/// A running `$PAGER`, falling back to `less -S`, which is fed text as it becomes available
+pub(crate) struct Pager {
+    child: std::process::Child,
+    /// Input to the pager, dropped once it stops reading, e.g. because the user quit it
+    stdin: Option<std::process::ChildStdin>,
+}
+
+impl Pager {
+    pub(crate) fn spawn() -> anyhow::Result<Pager> {
+        use anyhow::Context as _;
+
+        let command = std::env::var("PAGER")
+            .ok()
+            .filter(|pager| !pager.trim().is_empty())
+            .unwrap_or_else(|| DEFAULT_PAGER.to_string());
+        let mut words = command.split_whitespace();
+        let program = words.next().context("Empty pager command")?;
+        let mut child = std::process::Command::new(program)
+            .args(words)
+            .stdin(std::process::Stdio::piped())
+            .spawn()
+            .with_context(|| format!("Failed to start pager '{}'", command))?;
+        let stdin = child.stdin.take();
+        Ok(Pager { child, stdin })
+    }
+
+    /// Send `text` to the pager, blocking while it isn't reading
+    pub(crate) fn write(&mut self, text: &str) -> anyhow::Result<()> {
+        use anyhow::Context as _;
+        use std::io::Write as _;
+
+        if let Some(stdin) = &mut self.stdin {
+            match stdin.write_all(text.as_bytes()) {
+                Ok(()) => {}
+                // The pager closing its input early, e.g. on `q`, is not an error
+                Err(error) if error.kind() == std::io::ErrorKind::BrokenPipe => self.stdin = None,
+                Err(error) => return Err(error).context("Failed to write to pager"),
+            }
+        }
+        Ok(())
+    }
+
+    /// Signal the end of input and wait for the user to leave the pager
+    pub(crate) fn wait(mut self) -> anyhow::Result<()> {
+        use anyhow::Context as _;
+
+        drop(self.stdin.take());
+        self.child.wait().context("Failed waiting for pager")?;
+        Ok(())
+    }
+}
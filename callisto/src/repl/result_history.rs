@@ -0,0 +1,98 @@
+use std::collections::{BTreeSet, VecDeque};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::{ExecutionMetrics, ResultSet};
+
+/// Table name by which queries can refer to the most recent result
+const LAST_TABLE: &str = "_last";
+
+/// A statement's result as remembered by the REPL
+pub(crate) struct HistoricalResult {
+    /// Number of the result within the session, counting from 1
+    pub id: usize,
+    pub statement: String,
+    pub wall_time: Duration,
+    pub metrics: Arc<ExecutionMetrics>,
+    pub result: ResultSet,
+}
+
+impl HistoricalResult {
+    /// Name by which queries can refer to this result as a table
+    pub fn table_name(&self) -> String {
+        format!("_r{}", self.id)
+    }
+}
+
+/// The most recent results of a session, numbered in the order their statements ran.
+///
+/// Results can be queried as tables, being registered with the engine when first referenced.
+pub(crate) struct ResultHistory {
+    entries: VecDeque<HistoricalResult>,
+    capacity: usize,
+    next_id: usize,
+}
+
+impl ResultHistory {
+    pub fn new(capacity: usize) -> ResultHistory {
+        ResultHistory {
+            entries: VecDeque::new(),
+            capacity,
+            next_id: 1,
+        }
+    }
+
+    /// Remember a result, forgetting the oldest if already at capacity
+    pub fn push(
+        &mut self,
+        statement: String,
+        wall_time: Duration,
+        metrics: Arc<ExecutionMetrics>,
+        result: ResultSet,
+    ) {
+        if self.capacity == 0 {
+            return;
+        }
+        while self.entries.len() >= self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(HistoricalResult {
+            id: self.next_id,
+            statement,
+            wall_time,
+            metrics,
+            result,
+        });
+        self.next_id += 1;
+    }
+
+    pub fn last(&self) -> Option<&HistoricalResult> {
+        self.entries.back()
+    }
+
+    pub fn get(&self, id: usize) -> Option<&HistoricalResult> {
+        self.entries.iter().find(|entry| entry.id == id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &HistoricalResult> {
+        self.entries.iter()
+    }
+
+    /// The results referred to as tables in `sql`, as `_last` or `_r<id>`, with the name used
+    pub fn referenced(&self, sql: &str) -> Vec<(String, &HistoricalResult)> {
+        let words = sql
+            .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .map(|word| word.to_lowercase())
+            .collect::<BTreeSet<_>>();
+        let mut referenced = Vec::new();
+        if let Some(last) = self.last().filter(|_| words.contains(LAST_TABLE)) {
+            referenced.push((LAST_TABLE.to_string(), last));
+        }
+        for entry in &self.entries {
+            if words.contains(&entry.table_name()) {
+                referenced.push((entry.table_name(), entry));
+            }
+        }
+        referenced
+    }
+}
@@ -0,0 +1,75 @@
+/// Split a script into the entries the REPL would run had they been typed in.
+///
+/// A line beginning with `.` or `\` where a statement would start is a meta-command on its own,
+/// while SQL statements run through to a semicolon outside of any quotes or comments.  Entries
+/// holding nothing but comments are dropped.
+pub(crate) fn split(script: &str) -> Vec<String> {
+    let (mut entries, pending) = split_pending(script);
+    entries.extend(pending);
+    entries
+}
+
+/// Whether `input` ends with a complete entry, rather than a statement still missing its
+/// semicolon or left inside an unterminated quote or comment
+pub(crate) fn is_complete(input: &str) -> bool {
+    split_pending(input).1.is_none()
+}
+
+/// The complete entries of `script`, followed by any statement left unterminated at its end
+fn split_pending(script: &str) -> (Vec<String>, Option<String>) {
+    let mut entries = Vec::new();
+    let mut current = String::new();
+    let mut has_code = false;
+    let mut chars = script.chars().peekable();
+    while let Some(c) = chars.next() {
+        let starts_line = current.rsplit('\n').next().unwrap_or("").trim().is_empty();
+        if !has_code && starts_line && (c == '.' || c == '\\') {
+            let mut command = String::from(c);
+            while let Some(c) = chars.next_if(|c| *c != '\n') {
+                command.push(c);
+            }
+            entries.push(command.trim().to_string());
+            current.clear();
+            continue;
+        }
+        current.push(c);
+        match c {
+            '\'' | '"' => {
+                has_code = true;
+                // Doubled quotes are escapes, which simply reopen the literal
+                for inner in chars.by_ref() {
+                    current.push(inner);
+                    if inner == c {
+                        break;
+                    }
+                }
+            }
+            '-' if chars.peek() == Some(&'-') => {
+                while let Some(c) = chars.next_if(|c| *c != '\n') {
+                    current.push(c);
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                let mut previous = ' ';
+                for inner in chars.by_ref() {
+                    current.push(inner);
+                    if previous == '*' && inner == '/' {
+                        break;
+                    }
+                    previous = inner;
+                }
+            }
+            ';' => {
+                if has_code {
+                    entries.push(current.trim().to_string());
+                }
+                current.clear();
+                has_code = false;
+            }
+            c if !c.is_whitespace() => has_code = true,
+            _ => {}
+        }
+    }
+    let pending = has_code.then(|| current.trim().to_string());
+    (entries, pending)
+}
@@ -0,0 +1,81 @@
+use std::io::IsTerminal as _;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::ExecutionMetrics;
+
+const FRAMES: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+/// How long to wait before showing the spinner, so quick queries don't flicker
+const DELAY: Duration = Duration::from_millis(250);
+const INTERVAL: Duration = Duration::from_millis(100);
+const CLEAR_LINE: &str = "\r\x1b[2K";
+
+/// Progress indicator drawn on stderr while waiting on an engine, showing elapsed time and, once
+/// a statement is streaming, what its metrics report so far.
+///
+/// It does nothing when stdout isn't a terminal.
+pub(crate) struct Spinner {
+    stop: Option<tokio::sync::oneshot::Sender<()>>,
+    task: Option<tokio::task::JoinHandle<()>>,
+}
+
+impl Spinner {
+    pub(crate) fn start(metrics: Option<Arc<ExecutionMetrics>>) -> Spinner {
+        if !std::io::stdout().is_terminal() || !std::io::stderr().is_terminal() {
+            return Spinner {
+                stop: None,
+                task: None,
+            };
+        }
+        let (stop, mut stopped) = tokio::sync::oneshot::channel();
+        let task = tokio::spawn(async move {
+            let started = Instant::now();
+            tokio::select! {
+                _ = tokio::time::sleep(DELAY) => {}
+                _ = &mut stopped => return,
+            }
+            let mut interval = tokio::time::interval(INTERVAL);
+            for frame in FRAMES.iter().cycle() {
+                let mut status = format!("{} {:.1}s", frame, started.elapsed().as_secs_f64());
+                if let Some(metrics) = &metrics {
+                    if metrics.rows_returned() > 0 {
+                        status.push_str(&format!(", {} rows", metrics.rows_returned()));
+                    }
+                    if let Some(bytes) = metrics.bytes_scanned() {
+                        status.push_str(&format!(", {} bytes scanned", bytes));
+                    }
+                }
+                eprint!("{}{}", CLEAR_LINE, status);
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    _ = &mut stopped => break,
+                }
+            }
+            eprint!("{}", CLEAR_LINE);
+        });
+        Spinner {
+            stop: Some(stop),
+            task: Some(task),
+        }
+    }
+
+    /// Stop the spinner, waiting for it to clear its line
+    pub(crate) async fn stop(mut self) {
+        if let Some(stop) = self.stop.take() {
+            let _ = stop.send(());
+        }
+        if let Some(task) = self.task.take() {
+            let _ = task.await;
+        }
+    }
+}
+
+impl Drop for Spinner {
+    fn drop(&mut self) {
+        // Dropped without being stopped, e.g. because the query was cancelled
+        if let Some(task) = self.task.take() {
+            task.abort();
+            eprint!("{}", CLEAR_LINE);
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use crate::completion::{referenced_tables, tokenize};
+use crate::schema_cache::SchemaCache;
+
+/// Suggestions for the tables and file paths named in `sql` that aren't registered with the
+/// engine or present on disk, drawn from registered tables, their sources, and nearby files
+pub(crate) fn did_you_mean(sql: &str, schema_cache: &SchemaCache) -> Vec<String> {
+    let tables = schema_cache.tables();
+    let mut suggestions = Vec::new();
+    for (name, _) in referenced_tables(&tokenize(sql)) {
+        if schema_cache.table(&name).is_some() || std::path::Path::new(&name).exists() {
+            continue;
+        }
+        let mut candidates = tables
+            .iter()
+            .flat_map(|table| std::iter::once(table.name.clone()).chain(table.source.clone()))
+            .collect::<Vec<_>>();
+        candidates.extend(files_near(&name));
+        if let Some(closest) = closest(&name, &candidates) {
+            suggestions.push(format!("Did you mean '{}' instead of '{}'?", closest, name));
+        }
+    }
+    suggestions
+}
+
+/// Files in the directory `path` points into, named as `path` names them
+fn files_near(path: &str) -> Vec<String> {
+    let (directory, prefix) = match path.rfind('/') {
+        Some(index) => (&path[..index + 1], &path[..index + 1]),
+        None => (".", ""),
+    };
+    let Ok(entries) = std::fs::read_dir(directory) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_ok_and(|file_type| file_type.is_file()))
+        .map(|entry| format!("{}{}", prefix, entry.file_name().to_string_lossy()))
+        .collect()
+}
+
+/// The candidate nearest to `name` by edit distance, if any is close enough to be a likely typo
+fn closest<'a>(name: &str, candidates: &'a [String]) -> Option<&'a str> {
+    let name = name.to_lowercase();
+    let threshold = (name.chars().count() / 3).max(1);
+    candidates
+        .iter()
+        .map(|candidate| (edit_distance(&name, &candidate.to_lowercase()), candidate))
+        .filter(|(distance, _)| *distance <= threshold)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate.as_str())
+}
+
+/// Levenshtein distance between `a` and `b`, counted in characters
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
@@ -0,0 +1,348 @@
+use std::collections::BTreeMap;
+
+/// Session variables set with `\set` and substituted into statements as `${name}`
+#[derive(Clone, Debug, Default)]
+pub struct Variables {
+    values: BTreeMap<String, Value>,
+}
+
+/// A variable's value, remembering whether it must be quoted to be used as a SQL literal
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Value {
+    /// Text, substituted as a quoted string literal
+    Text(String),
+    /// A number, boolean, or NULL, substituted verbatim
+    Literal(String),
+}
+
+impl Value {
+    /// Interpret `raw` as written after `\set name`.
+    ///
+    /// Quoted values are always text, while numbers, booleans, and `null` are kept as literals and
+    /// anything else is treated as unquoted text. That includes `nan` and `inf`, which parse as
+    /// numbers but aren't SQL literals.
+    fn parse(raw: &str) -> Value {
+        for quote in ['\'', '"'] {
+            if let Some(inner) = raw
+                .strip_prefix(quote)
+                .and_then(|rest| rest.strip_suffix(quote))
+            {
+                let doubled = format!("{}{}", quote, quote);
+                return Value::Text(inner.replace(&doubled, &quote.to_string()));
+            }
+        }
+        let is_literal = raw.parse::<f64>().is_ok_and(f64::is_finite)
+            || ["true", "false", "null"]
+                .iter()
+                .any(|word| raw.eq_ignore_ascii_case(word));
+        if is_literal {
+            Value::Literal(raw.to_string())
+        } else {
+            Value::Text(raw.to_string())
+        }
+    }
+
+    fn text(&self) -> &str {
+        match self {
+            Value::Text(text) | Value::Literal(text) => text,
+        }
+    }
+
+    /// The value as it appears in a statement outside of any quotes
+    fn sql(&self) -> String {
+        match self {
+            Value::Text(text) => format!("'{}'", text.replace('\'', "''")),
+            Value::Literal(literal) => literal.clone(),
+        }
+    }
+}
+
+impl Variables {
+    pub fn set(&mut self, name: &str, raw: &str) -> anyhow::Result<()> {
+        if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+            anyhow::bail!(
+                "Invalid variable name '{}', use letters, digits, and underscores",
+                name
+            );
+        }
+        self.values.insert(name.to_string(), Value::parse(raw));
+        Ok(())
+    }
+
+    /// Remove `name`, returning whether it was set
+    pub fn unset(&mut self, name: &str) -> bool {
+        self.values.remove(name).is_some()
+    }
+
+    /// Replace each `${name}` in `statement` with the variable's value.
+    ///
+    /// Outside of quotes a text value becomes a string literal, while inside a quoted string or
+    /// identifier its text is spliced in with that quote character escaped. References within
+    /// `--` and `/* */` comments are left as they are, as are quotes.
+    pub fn substitute(&self, statement: &str) -> anyhow::Result<String> {
+        let mut substituted = String::with_capacity(statement.len());
+        let mut quote = None;
+        let mut rest = statement;
+        while let Some(c) = rest.chars().next() {
+            if quote.is_none() {
+                let comment = if rest.starts_with("--") {
+                    rest.find('\n').map_or(rest.len(), |end| end + 1)
+                } else if let Some(body) = rest.strip_prefix("/*") {
+                    body.find("*/").map_or(rest.len(), |end| end + 4)
+                } else {
+                    0
+                };
+                if comment > 0 {
+                    substituted.push_str(&rest[..comment]);
+                    rest = &rest[comment..];
+                    continue;
+                }
+            }
+            if let Some(reference) = rest.strip_prefix("${") {
+                let end = reference
+                    .find('}')
+                    .ok_or_else(|| anyhow::anyhow!("Unterminated variable reference '${{'"))?;
+                let name = &reference[..end];
+                let value = self
+                    .values
+                    .get(name)
+                    .ok_or_else(|| anyhow::anyhow!("Undefined variable '{}'", name))?;
+                match quote {
+                    Some(quote) => substituted
+                        .push_str(&value.text().replace(quote, &format!("{}{}", quote, quote))),
+                    None => substituted.push_str(&value.sql()),
+                }
+                rest = &reference[end + 1..];
+                continue;
+            }
+            match quote {
+                // A doubled quote is an escape and toggles twice, leaving the state unchanged
+                Some(open) if c == open => quote = None,
+                None if c == '\'' || c == '"' => quote = Some(c),
+                _ => {}
+            }
+            substituted.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+        Ok(substituted)
+    }
+}
+
+impl std::fmt::Display for Variables {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.values.is_empty() {
+            return write!(f, "No variables set");
+        }
+        let lines = self
+            .values
+            .iter()
+            .map(|(name, value)| format!("{} = {}", name, value.sql()))
+            .collect::<Vec<_>>();
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variables(values: &[(&str, &str)]) -> Variables {
+        let mut variables = Variables::default();
+        for (name, raw) in values {
+            variables.set(name, raw).unwrap();
+        }
+        variables
+    }
+
+    #[test]
+    fn values_are_substituted_as_literals() {
+        let variables = variables(&[
+            ("name", "ada"),
+            ("quoted", "'42'"),
+            ("limit", "10"),
+            ("ratio", "-0.5"),
+            ("flag", "TRUE"),
+            ("missing", "null"),
+        ]);
+        assert_eq!(
+            variables
+                .substitute(
+                    "SELECT ${name}, ${quoted}, ${ratio}, ${flag}, ${missing} LIMIT ${limit}"
+                )
+                .unwrap(),
+            "SELECT 'ada', '42', -0.5, TRUE, null LIMIT 10"
+        );
+        assert_eq!(
+            variables.substitute("SELECT ${name}${name}").unwrap(),
+            "SELECT 'ada''ada'"
+        );
+    }
+
+    #[test]
+    fn statements_without_references_are_unchanged() {
+        let variables = Variables::default();
+        for statement in ["", "SELECT 1", "SELECT '$', '{}', '$ {x}', 'é'"] {
+            assert_eq!(variables.substitute(statement).unwrap(), statement);
+        }
+    }
+
+    #[test]
+    fn text_is_spliced_into_string_literals_and_identifiers() {
+        let variables = variables(&[("name", "ada"), ("limit", "10")]);
+        assert_eq!(
+            variables
+                .substitute("SELECT 'hello ${name}', \"${name}_id\" FROM t")
+                .unwrap(),
+            "SELECT 'hello ada', \"ada_id\" FROM t"
+        );
+        // Literals are spliced in as their text, rather than quoted again
+        assert_eq!(
+            variables.substitute("SELECT '${limit} rows'").unwrap(),
+            "SELECT '10 rows'"
+        );
+        // References after a string literal ends are outside it again
+        assert_eq!(
+            variables.substitute("SELECT '${name}', ${name}").unwrap(),
+            "SELECT 'ada', 'ada'"
+        );
+    }
+
+    #[test]
+    fn quotes_in_values_are_escaped() {
+        let variables = variables(&[("name", "o'brien"), ("column", "say \"hi\"")]);
+        assert_eq!(
+            variables.substitute("SELECT ${name}").unwrap(),
+            "SELECT 'o''brien'"
+        );
+        assert_eq!(
+            variables.substitute("SELECT 'dear ${name}'").unwrap(),
+            "SELECT 'dear o''brien'"
+        );
+        assert_eq!(
+            variables.substitute("SELECT \"${column}\"").unwrap(),
+            "SELECT \"say \"\"hi\"\"\""
+        );
+        // Each quote is escaped only within strings of its own kind
+        assert_eq!(
+            variables
+                .substitute("SELECT \"${name}\", '${column}'")
+                .unwrap(),
+            "SELECT \"o'brien\", 'say \"hi\"'"
+        );
+    }
+
+    #[test]
+    fn escaped_quotes_leave_strings_open() {
+        let variables = variables(&[("name", "ada")]);
+        assert_eq!(
+            variables.substitute("SELECT 'it''s ${name}'").unwrap(),
+            "SELECT 'it''s ada'"
+        );
+        assert_eq!(
+            variables.substitute("SELECT '''', ${name}").unwrap(),
+            "SELECT '''', 'ada'"
+        );
+        // A quote of the other kind doesn't end a string
+        assert_eq!(
+            variables.substitute("SELECT '\"${name}', ${name}").unwrap(),
+            "SELECT '\"ada', 'ada'"
+        );
+    }
+
+    #[test]
+    fn comments_are_left_as_they_are() {
+        let variables = variables(&[("name", "ada")]);
+        assert_eq!(
+            variables
+                .substitute("SELECT ${name} -- by ${name}'s\nFROM t -- ${other}")
+                .unwrap(),
+            "SELECT 'ada' -- by ${name}'s\nFROM t -- ${other}"
+        );
+        assert_eq!(
+            variables
+                .substitute("SELECT /* ${other} \n ' */ ${name} /* ${name}")
+                .unwrap(),
+            "SELECT /* ${other} \n ' */ 'ada' /* ${name}"
+        );
+        // Comment markers within quotes don't begin comments
+        assert_eq!(
+            variables
+                .substitute("SELECT '-- ${name}', \"/* ${name}\", ${name}")
+                .unwrap(),
+            "SELECT '-- ada', \"/* ada\", 'ada'"
+        );
+        // Nor do single dashes and slashes
+        assert_eq!(
+            variables.substitute("SELECT -${name} / 2").unwrap(),
+            "SELECT -'ada' / 2"
+        );
+    }
+
+    #[test]
+    fn non_finite_numbers_are_text() {
+        let variables = variables(&[
+            ("nan", "nan"),
+            ("infinity", "inf"),
+            ("negative", "-Infinity"),
+            ("large", "1e999"),
+            ("exponent", "1e9"),
+        ]);
+        assert_eq!(
+            variables
+                .substitute("SELECT ${nan}, ${infinity}, ${negative}, ${large}, ${exponent}")
+                .unwrap(),
+            "SELECT 'nan', 'inf', '-Infinity', '1e999', 1e9"
+        );
+    }
+
+    #[test]
+    fn quoted_values_are_unescaped() {
+        let variables = variables(&[("single", "'it''s'"), ("double", "\"say \"\"hi\"\"\"")]);
+        assert_eq!(
+            variables.substitute("SELECT ${single}, ${double}").unwrap(),
+            "SELECT 'it''s', 'say \"hi\"'"
+        );
+        assert_eq!(
+            variables.to_string(),
+            "double = 'say \"hi\"'\nsingle = 'it''s'"
+        );
+    }
+
+    #[test]
+    fn unknown_variables_are_errors() {
+        let variables = variables(&[("name", "ada")]);
+        let error = variables.substitute("SELECT ${nmae}").unwrap_err();
+        assert_eq!(error.to_string(), "Undefined variable 'nmae'");
+        assert!(variables.substitute("SELECT '${other}'").is_err());
+        assert!(variables.substitute("SELECT ${}").is_err());
+    }
+
+    #[test]
+    fn unterminated_references_are_errors() {
+        let variables = variables(&[("name", "ada")]);
+        for statement in ["SELECT ${name", "SELECT ${", "SELECT '${name'"] {
+            let error = variables.substitute(statement).unwrap_err();
+            assert_eq!(error.to_string(), "Unterminated variable reference '${'");
+        }
+    }
+
+    #[test]
+    fn unset_variables_are_unknown() {
+        let mut variables = variables(&[("name", "ada")]);
+        assert!(variables.unset("name"));
+        assert!(!variables.unset("name"));
+        assert!(variables.substitute("SELECT ${name}").is_err());
+        assert_eq!(variables.to_string(), "No variables set");
+    }
+
+    #[test]
+    fn invalid_names_are_rejected() {
+        let mut variables = Variables::default();
+        for name in ["", "a-b", "a b", "${a}", "a}"] {
+            assert!(variables.set(name, "1").is_err(), "{:?}", name);
+        }
+        variables.set("row_count_2", "1").unwrap();
+        variables.set("row_count_2", "2").unwrap();
+        assert_eq!(variables.to_string(), "row_count_2 = 2");
+    }
+}
@@ -0,0 +1,57 @@
+use std::path::Path;
+
+use arrow::datatypes::SchemaRef;
+use arrow::record_batch::RecordBatch;
+
+/// The complete result of a statement, kept so it can be revisited without re-running the query
+#[derive(Clone, Debug)]
+pub struct ResultSet {
+    pub schema: SchemaRef,
+    pub batches: Vec<RecordBatch>,
+}
+
+impl ResultSet {
+    pub fn num_rows(&self) -> usize {
+        self.batches.iter().map(|batch| batch.num_rows()).sum()
+    }
+
+    /// Bytes of memory the result's batches take up
+    pub fn memory_size(&self) -> usize {
+        self.batches
+            .iter()
+            .map(|batch| batch.get_array_memory_size())
+            .sum()
+    }
+
+    /// Write the result to `path`, in a format chosen by its extension: `.parquet`, `.csv`, or
+    /// `.json`
+    pub fn export(&self, path: &Path) -> anyhow::Result<()> {
+        use anyhow::Context as _;
+
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_lowercase());
+        let file = || {
+            std::fs::File::create(path)
+                .with_context(|| format!("Failed to create {}", path.display()))
+        };
+        match extension.as_deref() {
+            Some("parquet") => {
+                let mut writer =
+                    parquet::arrow::ArrowWriter::try_new(file()?, self.schema.clone(), None)?;
+                for batch in &self.batches {
+                    writer.write(batch)?;
+                }
+                writer.close()?;
+            }
+            Some("csv") => crate::serialize::write_csv(file()?, &self.schema, &self.batches, true)?,
+            Some("json") => crate::serialize::write_ndjson(file()?, &self.batches)?,
+            _ => anyhow::bail!(
+                "Cannot tell what format to export {} as, use a .parquet, .csv, or .json extension",
+                path.display()
+            ),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,261 @@
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::Context as _;
+use arrow::array::{ArrayRef, TimestampMicrosecondArray};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use arrow::record_batch::RecordBatch;
+use chrono::{DateTime, Datelike as _, Local, NaiveDate, TimeZone as _};
+
+use crate::EngineInterface;
+
+/// Column each run's rows are stamped with the time of the run in
+pub const RUN_AT_COLUMN: &str = "run_at";
+
+/// When a query is run, as a cron expression of five fields: minute, hour, day of the month,
+/// month, and day of the week, in local time. Each field is `*`, a number, a range `a-b`, any of
+/// those with a step, e.g. `*/15`, or a list of them, e.g. `1,15`; `@hourly`, `@daily`,
+/// `@weekly`, `@monthly`, and `@yearly` stand for the usual expressions.
+///
+/// As with cron, a day matches if either its day of the month or of the week does, when both are
+/// restricted.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CronSchedule {
+    /// As it was written
+    expression: String,
+    minutes: BTreeSet<u32>,
+    hours: BTreeSet<u32>,
+    days: BTreeSet<u32>,
+    months: BTreeSet<u32>,
+    /// Sunday being 0
+    weekdays: BTreeSet<u32>,
+    /// Whether the day of the month is `*`
+    any_day: bool,
+    /// Whether the day of the week is `*`
+    any_weekday: bool,
+}
+
+impl std::str::FromStr for CronSchedule {
+    type Err = anyhow::Error;
+
+    fn from_str(written: &str) -> anyhow::Result<CronSchedule> {
+        let expression = match written.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            expression => expression,
+        };
+        let fields = expression.split_whitespace().collect::<Vec<_>>();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            anyhow::bail!(
+                "A schedule has five fields, minute, hour, day of month, month, and day of week, \
+                 not {}: {}",
+                fields.len(),
+                expression
+            );
+        };
+        let mut weekdays = field(weekdays, "day of week", 0, 7)?;
+        if weekdays.remove(&7) {
+            weekdays.insert(0);
+        }
+        Ok(CronSchedule {
+            expression: written.trim().to_string(),
+            minutes: field(minutes, "minute", 0, 59)?,
+            hours: field(hours, "hour", 0, 23)?,
+            days: field(days, "day of month", 1, 31)?,
+            months: field(months, "month", 1, 12)?,
+            weekdays,
+            any_day: fields[2] == "*",
+            any_weekday: fields[4] == "*",
+        })
+    }
+}
+
+impl std::fmt::Display for CronSchedule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.expression)
+    }
+}
+
+/// The values of a field of a cron expression, each between `min` and `max`
+fn field(text: &str, name: &str, min: u32, max: u32) -> anyhow::Result<BTreeSet<u32>> {
+    let number = |text: &str| {
+        text.parse::<u32>()
+            .ok()
+            .filter(|value| (min..=max).contains(value))
+            .with_context(|| format!("Invalid {} {}, not from {} to {}", name, text, min, max))
+    };
+    let mut values = BTreeSet::new();
+    for part in text.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step = step
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|step| *step > 0)
+                    .with_context(|| format!("Invalid step {} of the {}", step, name))?;
+                (range, step)
+            }
+            None => (part, 1),
+        };
+        let (low, high) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((low, high)) => (number(low)?, number(high)?),
+            // A single value with a step runs from it to the end, e.g. 5/15 for 5, 20, 35, 50
+            None if step > 1 => (number(range)?, max),
+            None => (number(range)?, number(range)?),
+        };
+        if low > high {
+            anyhow::bail!("Invalid {} range {}, its start after its end", name, range);
+        }
+        values.extend((low..=high).step_by(step));
+    }
+    Ok(values)
+}
+
+impl CronSchedule {
+    /// The first time after `after` the schedule runs at, if it ever does
+    pub fn next_after(&self, after: DateTime<Local>) -> Option<DateTime<Local>> {
+        let after = after.naive_local();
+        let mut date = after.date();
+        // Long enough to reach a 29th of February falling on any day of the week
+        for _ in 0..366 * 28 {
+            if self.runs_on(date) {
+                for hour in &self.hours {
+                    for minute in &self.minutes {
+                        let time = date.and_hms_opt(*hour, *minute, 0)?;
+                        if time <= after {
+                            continue;
+                        }
+                        // Times skipped by a change to daylight saving time are skipped
+                        if let Some(time) = Local.from_local_datetime(&time).earliest() {
+                            return Some(time);
+                        }
+                    }
+                }
+            }
+            date = date.succ_opt()?;
+        }
+        None
+    }
+
+    fn runs_on(&self, date: NaiveDate) -> bool {
+        if !self.months.contains(&date.month()) {
+            return false;
+        }
+        let day = self.days.contains(&date.day());
+        let weekday = self
+            .weekdays
+            .contains(&date.weekday().num_days_from_sunday());
+        match (self.any_day, self.any_weekday) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        }
+    }
+}
+
+/// Run `query` on `engine` at each time of `schedule`, appending the rows of its last statement
+/// to the Parquet dataset in the directory `out`, stamped with the time of the run in a
+/// [`RUN_AT_COLUMN`]. Each run writes a file of its own, so the dataset can be queried as a
+/// whole, e.g. as `out/*.parquet`, while runs go on.
+///
+/// A failing run is logged and the schedule carried on with. Runs go on until `runs` of them have
+/// been made, if given, or else forever.
+pub async fn run(
+    engine: &dyn EngineInterface,
+    query: &str,
+    schedule: &CronSchedule,
+    out: &Path,
+    runs: Option<usize>,
+) -> anyhow::Result<()> {
+    std::fs::create_dir_all(out).with_context(|| format!("Failed to create {}", out.display()))?;
+    let mut run = 0;
+    while runs.map_or(true, |runs| run < runs) {
+        let at = schedule
+            .next_after(Local::now())
+            .context("The schedule never runs again")?;
+        tracing::info!(at = %at, "Waiting for the next run");
+        tokio::time::sleep((at - Local::now()).to_std().unwrap_or_default()).await;
+        match run_once(engine, query, out, at).await {
+            Ok((path, rows)) => {
+                tracing::info!(at = %at, rows, path = %path.display(), "Appended a run's results")
+            }
+            Err(error) => tracing::warn!(at = %at, "Scheduled run failed: {:#}", error),
+        }
+        run += 1;
+    }
+    Ok(())
+}
+
+/// Run `query` as scheduled `at`, writing the rows of its last statement to a new file in `out`,
+/// returning the file and how many rows it has
+async fn run_once(
+    engine: &dyn EngineInterface,
+    query: &str,
+    out: &Path,
+    at: DateTime<Local>,
+) -> anyhow::Result<(PathBuf, usize)> {
+    use futures::stream::TryStreamExt as _;
+
+    let mut executions = engine.execute(query).await?;
+    let Some(last) = executions.pop() else {
+        anyhow::bail!("No statements to run");
+    };
+    for execution in executions {
+        execution.stream.try_for_each(|_| async { Ok(()) }).await?;
+    }
+    let batches = last.stream.try_collect::<Vec<_>>().await?;
+    let (schema, batches) = stamped(&last.schema, batches, at)?;
+    let rows = batches.iter().map(RecordBatch::num_rows).sum();
+
+    let name = format!("run-{}.parquet", at.format("%Y%m%dT%H%M%S%z"));
+    // Written under a hidden name first, so a reader of the dataset never sees half a file
+    let partial = out.join(format!(".{}", name));
+    let path = out.join(name);
+    let file = std::fs::File::create(&partial)
+        .with_context(|| format!("Failed to create {}", partial.display()))?;
+    let mut writer = parquet::arrow::ArrowWriter::try_new(file, schema, None)?;
+    for batch in &batches {
+        writer.write(batch)?;
+    }
+    writer.close()?;
+    std::fs::rename(&partial, &path)
+        .with_context(|| format!("Failed to move {} into place", partial.display()))?;
+    Ok((path, rows))
+}
+
+/// `batches`, of `schema`, each with a [`RUN_AT_COLUMN`] of `at` appended
+fn stamped(
+    schema: &SchemaRef,
+    batches: Vec<RecordBatch>,
+    at: DateTime<Local>,
+) -> anyhow::Result<(SchemaRef, Vec<RecordBatch>)> {
+    if schema.column_with_name(RUN_AT_COLUMN).is_some() {
+        anyhow::bail!(
+            "The results already have a {} column, which each run's time is written to",
+            RUN_AT_COLUMN
+        );
+    }
+    let mut fields = schema.fields().iter().cloned().collect::<Vec<_>>();
+    fields.push(Arc::new(Field::new(
+        RUN_AT_COLUMN,
+        DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())),
+        false,
+    )));
+    let stamped_schema = Arc::new(Schema::new_with_metadata(fields, schema.metadata().clone()));
+    let batches = batches
+        .into_iter()
+        .map(|batch| {
+            let run_at =
+                TimestampMicrosecondArray::from_value(at.timestamp_micros(), batch.num_rows())
+                    .with_timezone("UTC");
+            let mut columns = batch.columns().to_vec();
+            columns.push(Arc::new(run_at) as ArrayRef);
+            RecordBatch::try_new(stamped_schema.clone(), columns)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((stamped_schema, batches))
+}
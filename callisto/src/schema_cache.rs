@@ -0,0 +1,43 @@
+use std::sync::{Arc, RwLock};
+
+use crate::{EngineInterface, TableInfo};
+
+/// Snapshot of the tables registered with the active engine.
+///
+/// Line editing happens synchronously while the engine sits idle, so completion and highlighting
+/// consult this cache rather than the engine itself.  Clones share the same underlying snapshot.
+#[derive(Clone, Default)]
+pub struct SchemaCache {
+    tables: Arc<RwLock<Vec<TableInfo>>>,
+}
+
+impl SchemaCache {
+    /// Replace the snapshot with the engine's current tables
+    pub async fn refresh(&self, engine: &dyn EngineInterface) -> anyhow::Result<()> {
+        let tables = engine.tables().await?;
+        *self.tables.write().unwrap() = tables;
+        Ok(())
+    }
+
+    /// Replace the snapshot with `tables`, as listed by the engine elsewhere
+    pub fn set(&self, tables: Vec<TableInfo>) {
+        *self.tables.write().unwrap() = tables;
+    }
+
+    pub fn tables(&self) -> Vec<TableInfo> {
+        self.tables.read().unwrap().clone()
+    }
+
+    /// Look up a table by its registered name or by the path it was loaded from
+    pub fn table(&self, name_or_source: &str) -> Option<TableInfo> {
+        self.tables
+            .read()
+            .unwrap()
+            .iter()
+            .find(|table| {
+                table.name.eq_ignore_ascii_case(name_or_source)
+                    || table.source.as_deref() == Some(name_or_source)
+            })
+            .cloned()
+    }
+}
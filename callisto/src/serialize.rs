@@ -0,0 +1,264 @@
+//! Text encodings of record batches: JSON arrays, newline-delimited JSON, and CSV.
+//!
+//! Values are written as Arrow writes them, timestamps as RFC 3339 and decimals to their full
+//! scale, except where a format can't hold them: nested values (lists, structs, and maps) are
+//! written to CSV as their display text, as are binary, duration, and interval values to JSON.
+//! JSON objects carry every column, `null` where a value is missing.
+
+use std::io::Write;
+use std::sync::Arc;
+
+use arrow::array::{Array as _, ArrayRef, StringArray};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use arrow::record_batch::RecordBatch;
+use futures::stream::{Stream, TryStreamExt as _};
+
+/// Write `batches` to `writer` as a single JSON array of objects, one per row
+pub fn write_json<W: Write>(writer: W, batches: &[RecordBatch]) -> anyhow::Result<()> {
+    let mut writer = arrow::json::WriterBuilder::new()
+        .with_explicit_nulls(true)
+        .build::<_, arrow::json::writer::JsonArray>(writer);
+    for batch in batches {
+        writer.write(&json_compatible(batch)?)?;
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+/// Write `batches` to `writer` as newline-delimited JSON, an object a line
+pub fn write_ndjson<W: Write>(writer: W, batches: &[RecordBatch]) -> anyhow::Result<()> {
+    let mut writer = arrow::json::WriterBuilder::new()
+        .with_explicit_nulls(true)
+        .build::<_, arrow::json::writer::LineDelimited>(writer);
+    for batch in batches {
+        writer.write(&json_compatible(batch)?)?;
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+/// Write `batches` with the given `schema` to `writer` as CSV, preceded by a header row if
+/// `header`, which is written even if there are no rows
+pub fn write_csv<W: Write>(
+    writer: W,
+    schema: &SchemaRef,
+    batches: &[RecordBatch],
+    header: bool,
+) -> anyhow::Result<()> {
+    let mut writer = arrow::csv::WriterBuilder::new()
+        .with_header(header)
+        .build(writer);
+    if batches.is_empty() {
+        writer.write(&csv_compatible(&RecordBatch::new_empty(schema.clone()))?)?;
+    }
+    for batch in batches {
+        writer.write(&csv_compatible(batch)?)?;
+    }
+    Ok(())
+}
+
+/// `batches` as a JSON array of objects, one per row
+pub fn to_json(batches: &[RecordBatch]) -> anyhow::Result<String> {
+    let mut buffer = Vec::new();
+    write_json(&mut buffer, batches)?;
+    Ok(String::from_utf8(buffer)?)
+}
+
+/// `batches` as newline-delimited JSON, an object a line
+pub fn to_ndjson(batches: &[RecordBatch]) -> anyhow::Result<String> {
+    let mut buffer = Vec::new();
+    write_ndjson(&mut buffer, batches)?;
+    Ok(String::from_utf8(buffer)?)
+}
+
+/// `batches` with the given `schema` as CSV, preceded by a header row if `header`
+pub fn to_csv(schema: &SchemaRef, batches: &[RecordBatch], header: bool) -> anyhow::Result<String> {
+    let mut buffer = Vec::new();
+    write_csv(&mut buffer, schema, batches, header)?;
+    Ok(String::from_utf8(buffer)?)
+}
+
+/// The rows of `batch` as comma-separated JSON objects, without the brackets of an array, for
+/// splicing into an array spanning several batches
+pub fn json_objects(batch: &RecordBatch) -> anyhow::Result<String> {
+    let array = to_json(std::slice::from_ref(batch))?;
+    Ok(array
+        .trim()
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .to_string())
+}
+
+/// Read `stream`, e.g. that of a [`crate::QueryExecution`], to its end, as a JSON array of objects
+pub async fn stream_to_json<S, E>(stream: S) -> anyhow::Result<String>
+where
+    S: Stream<Item = Result<RecordBatch, E>>,
+    E: std::error::Error + Send + Sync + 'static,
+{
+    to_json(&stream.try_collect::<Vec<_>>().await?)
+}
+
+/// Read `stream` to its end, as newline-delimited JSON
+pub async fn stream_to_ndjson<S, E>(stream: S) -> anyhow::Result<String>
+where
+    S: Stream<Item = Result<RecordBatch, E>>,
+    E: std::error::Error + Send + Sync + 'static,
+{
+    to_ndjson(&stream.try_collect::<Vec<_>>().await?)
+}
+
+/// Read `stream`, whose batches have the given `schema`, to its end, as CSV with a header row
+pub async fn stream_to_csv<S, E>(schema: &SchemaRef, stream: S) -> anyhow::Result<String>
+where
+    S: Stream<Item = Result<RecordBatch, E>>,
+    E: std::error::Error + Send + Sync + 'static,
+{
+    to_csv(schema, &stream.try_collect::<Vec<_>>().await?, true)
+}
+
+fn json_compatible(batch: &RecordBatch) -> anyhow::Result<RecordBatch> {
+    as_text_where(batch, |data_type| {
+        matches!(
+            data_type,
+            DataType::Binary
+                | DataType::LargeBinary
+                | DataType::FixedSizeBinary(_)
+                | DataType::Duration(_)
+                | DataType::Interval(_)
+        )
+    })
+}
+
+fn csv_compatible(batch: &RecordBatch) -> anyhow::Result<RecordBatch> {
+    as_text_where(batch, |data_type| data_type.is_nested())
+}
+
+/// `batch` with the columns whose type is `unwritable` replaced by their display text
+fn as_text_where(
+    batch: &RecordBatch,
+    unwritable: fn(&DataType) -> bool,
+) -> anyhow::Result<RecordBatch> {
+    let schema = batch.schema();
+    if !schema
+        .fields()
+        .iter()
+        .any(|field| unwritable(field.data_type()))
+    {
+        return Ok(batch.clone());
+    }
+    let options = arrow::util::display::FormatOptions::default();
+    let mut fields = Vec::new();
+    let mut columns = Vec::new();
+    for (field, column) in schema.fields().iter().zip(batch.columns()) {
+        if !unwritable(field.data_type()) {
+            fields.push(field.as_ref().clone());
+            columns.push(column.clone());
+            continue;
+        }
+        let formatter = arrow::util::display::ArrayFormatter::try_new(column.as_ref(), &options)?;
+        let text: StringArray = (0..column.len())
+            .map(|row| {
+                column
+                    .is_valid(row)
+                    .then(|| formatter.value(row).to_string())
+            })
+            .collect();
+        fields.push(Field::new(field.name(), DataType::Utf8, true));
+        columns.push(Arc::new(text) as ArrayRef);
+    }
+    Ok(RecordBatch::try_new(
+        Arc::new(Schema::new(fields)),
+        columns,
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::{BinaryArray, Int64Array, ListArray};
+    use arrow::datatypes::Int64Type;
+
+    use super::*;
+
+    /// Numbers and their names, the second row null in each
+    fn numbers() -> RecordBatch {
+        RecordBatch::try_from_iter([
+            (
+                "n",
+                Arc::new(Int64Array::from(vec![Some(1), None])) as ArrayRef,
+            ),
+            ("name", Arc::new(StringArray::from(vec![Some("one"), None]))),
+        ])
+        .unwrap()
+    }
+
+    #[test]
+    fn json_objects_carry_every_column() {
+        let batches = [numbers(), numbers()];
+        assert_eq!(
+            to_json(&batches[..1]).unwrap(),
+            r#"[{"n":1,"name":"one"},{"n":null,"name":null}]"#
+        );
+        assert_eq!(
+            to_ndjson(&batches).unwrap(),
+            "{\"n\":1,\"name\":\"one\"}\n{\"n\":null,\"name\":null}\n".repeat(2)
+        );
+        assert_eq!(
+            json_objects(&numbers()).unwrap(),
+            r#"{"n":1,"name":"one"},{"n":null,"name":null}"#
+        );
+        assert_eq!(to_json(&[]).unwrap(), "[]");
+    }
+
+    #[test]
+    fn csv_headers_are_written_even_without_rows() {
+        let schema = numbers().schema();
+        assert_eq!(
+            to_csv(&schema, &[numbers()], true).unwrap(),
+            "n,name\n1,one\n,\n"
+        );
+        assert_eq!(to_csv(&schema, &[numbers()], false).unwrap(), "1,one\n,\n");
+        assert_eq!(to_csv(&schema, &[], true).unwrap(), "n,name\n");
+    }
+
+    #[test]
+    fn values_a_format_cant_hold_are_written_as_text() {
+        let lists =
+            ListArray::from_iter_primitive::<Int64Type, _, _>([Some(vec![Some(1), Some(2)]), None]);
+        let binary = BinaryArray::from(vec![Some(&b"\x01\xff"[..]), None]);
+        let batch = RecordBatch::try_from_iter([
+            ("list", Arc::new(lists) as ArrayRef),
+            ("binary", Arc::new(binary) as ArrayRef),
+        ])
+        .unwrap();
+        assert_eq!(
+            to_json(std::slice::from_ref(&batch)).unwrap(),
+            r#"[{"list":[1,2],"binary":"01ff"},{"list":null,"binary":null}]"#
+        );
+        assert_eq!(
+            to_csv(&batch.schema(), &[batch], true).unwrap(),
+            "list,binary\n\"[1, 2]\",01ff\n,\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn streams_are_read_to_their_end() {
+        let schema = numbers().schema();
+        let stream =
+            || futures::stream::iter([numbers(), numbers()].map(Ok::<_, arrow::error::ArrowError>));
+        assert_eq!(
+            stream_to_json(stream()).await.unwrap(),
+            to_json(&[numbers(), numbers()]).unwrap()
+        );
+        assert_eq!(stream_to_ndjson(stream()).await.unwrap().lines().count(), 4);
+        assert_eq!(
+            stream_to_csv(&schema, stream()).await.unwrap(),
+            "n,name\n1,one\n,\n1,one\n,\n"
+        );
+
+        let failing = futures::stream::iter([
+            Ok(numbers()),
+            Err(arrow::error::ArrowError::ComputeError("Failed".to_string())),
+        ]);
+        assert!(stream_to_json(failing).await.is_err());
+    }
+}
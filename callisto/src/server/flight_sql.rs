@@ -0,0 +1,556 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, Mutex,
+};
+use std::time::{Duration, Instant};
+
+use anyhow::Context as _;
+use arrow::{
+    array::{Array as _, Int64Array},
+    datatypes::{DataType, SchemaRef},
+    ipc::writer::IpcWriteOptions,
+    record_batch::RecordBatch,
+};
+use arrow_flight::{
+    encode::FlightDataEncoderBuilder,
+    error::FlightError,
+    flight_service_server::{FlightService, FlightServiceServer},
+    sql::{
+        metadata::{SqlInfoData, SqlInfoDataBuilder},
+        server::{FlightSqlService, PeekableFlightDataStream},
+        ActionClosePreparedStatementRequest, ActionCreatePreparedStatementRequest,
+        ActionCreatePreparedStatementResult, Any, CommandGetCatalogs, CommandGetDbSchemas,
+        CommandGetSqlInfo, CommandGetTables, CommandPreparedStatementQuery, CommandStatementQuery,
+        CommandStatementUpdate, ProstMessageExt as _, SqlInfo, TicketStatementQuery,
+    },
+    Action, FlightDescriptor, FlightEndpoint, FlightInfo, HandshakeRequest, HandshakeResponse,
+    IpcMessage, SchemaAsIpc, Ticket,
+};
+use futures::stream::{BoxStream, Stream, StreamExt as _, TryStreamExt as _};
+use prost::Message as _;
+use tonic::{metadata::MetadataValue, Request, Response, Status, Streaming};
+
+use crate::{EngineBuilder, EngineInterface, MaskPolicy, PreparedStatement, QueryExecution};
+
+/// How long a session may go unused before its engine is dropped
+const SESSION_IDLE: Duration = Duration::from_secs(30 * 60);
+/// Names the engines' tables are listed under, as they have no catalogs or schemas of their own
+const CATALOG: &str = "callisto";
+const SCHEMA: &str = "public";
+
+type DoGetStream = <FlightSqlServer as FlightService>::DoGetStream;
+
+/// The engine layer served over Arrow Flight SQL, for BI tools and other Arrow-native clients.
+///
+/// Each client gets a session of its own, with an engine of its own: clients that handshake are
+/// given a bearer token naming theirs, and those that don't are told apart by their connection.
+/// Sessions unused for half an hour are dropped. Nothing is authenticated, so the server is best
+/// listening only where its clients are trusted.
+pub struct FlightSqlServer {
+    engine: EngineBuilder,
+    /// SQL run on each new session's engine before its first query, to register tables
+    init: Option<String>,
+    /// Masks applied to every result sent
+    masks: MaskPolicy,
+    sessions: Mutex<HashMap<String, Arc<Session>>>,
+    next_handle: AtomicU64,
+    sql_info: SqlInfoData,
+}
+
+/// A client's engine and the statements it has in flight
+struct Session {
+    /// Shared by the session's statements, which run concurrently
+    engine: Box<dyn EngineInterface>,
+    masks: MaskPolicy,
+    /// Results planned by `GetFlightInfo` and waiting to be fetched by `DoGet`, by handle
+    results: Mutex<HashMap<String, QueryResult>>,
+    /// Statements prepared, but not yet run, by handle
+    prepared: Mutex<HashMap<String, PreparedStatement>>,
+    last_used: Mutex<Instant>,
+}
+
+/// The result of a query's last statement, its batches read as they're sent
+struct QueryResult {
+    schema: SchemaRef,
+    batches: BoxStream<'static, Result<RecordBatch, FlightError>>,
+}
+
+impl From<QueryExecution> for QueryResult {
+    fn from(execution: QueryExecution) -> QueryResult {
+        QueryResult {
+            schema: execution.schema,
+            batches: execution
+                .stream
+                .map_err(|error| FlightError::ExternalError(Box::new(error)))
+                .boxed(),
+        }
+    }
+}
+
+impl FlightSqlServer {
+    /// A server giving each session a new engine built by `engine`, on which `init` is run first,
+    /// and masking every result it sends with `masks`
+    pub fn new(
+        engine: EngineBuilder,
+        init: Option<String>,
+        masks: MaskPolicy,
+    ) -> anyhow::Result<FlightSqlServer> {
+        let mut sql_info = SqlInfoDataBuilder::new();
+        sql_info.append(SqlInfo::FlightSqlServerName, "callisto");
+        sql_info.append(SqlInfo::FlightSqlServerVersion, env!("CARGO_PKG_VERSION"));
+        sql_info.append(SqlInfo::FlightSqlServerArrowVersion, "1.3");
+        sql_info.append(SqlInfo::FlightSqlServerReadOnly, false);
+        Ok(FlightSqlServer {
+            engine,
+            init,
+            masks,
+            sessions: Mutex::new(HashMap::new()),
+            next_handle: AtomicU64::new(0),
+            sql_info: sql_info
+                .build()
+                .context("Failed to describe the Flight SQL server")?,
+        })
+    }
+
+    /// Serve clients connecting to `address` until the process ends
+    pub async fn serve(self, address: SocketAddr) -> anyhow::Result<()> {
+        tracing::info!(%address, engine = self.engine.kind().name(), "Serving Flight SQL");
+        tonic::transport::Server::builder()
+            .add_service(FlightServiceServer::new(self))
+            .serve(address)
+            .await
+            .with_context(|| format!("Failed to serve Flight SQL on {}", address))
+    }
+
+    /// A new session, with the initial SQL run on its engine
+    async fn open(&self) -> anyhow::Result<Session> {
+        Ok(Session {
+            engine: super::open_engine(&self.engine, self.init.as_deref()).await?,
+            masks: self.masks.clone(),
+            results: Mutex::new(HashMap::new()),
+            prepared: Mutex::new(HashMap::new()),
+            last_used: Mutex::new(Instant::now()),
+        })
+    }
+
+    /// The session `request` belongs to, opened if it's the first of its client's, dropping any
+    /// sessions gone unused
+    async fn session<T>(&self, request: &Request<T>) -> Result<Arc<Session>, Status> {
+        let key = session_key(request)?;
+        let existing = {
+            let mut sessions = self.sessions.lock().unwrap();
+            sessions.retain(|key, session| {
+                let idle = session.last_used.lock().unwrap().elapsed() > SESSION_IDLE;
+                if idle {
+                    tracing::info!(session = %key, "Dropped idle Flight SQL session");
+                }
+                !idle
+            });
+            sessions.get(&key).cloned()
+        };
+        let session = match existing {
+            Some(session) => session,
+            None => {
+                let session = Arc::new(self.open().await.map_err(status)?);
+                tracing::info!(session = %key, "Opened Flight SQL session");
+                self.sessions
+                    .lock()
+                    .unwrap()
+                    .entry(key)
+                    .or_insert(session)
+                    .clone()
+            }
+        };
+        *session.last_used.lock().unwrap() = Instant::now();
+        Ok(session)
+    }
+
+    /// A new name for a statement or result, unique to the server
+    fn handle(&self) -> String {
+        format!("{:x}", self.next_handle.fetch_add(1, Ordering::Relaxed))
+    }
+
+    /// A new session token: 128 bits from the operating system's source of randomness, in hex.
+    ///
+    /// The handshake takes any credentials, so a token authenticates no one. It's the only thing
+    /// keeping one client out of another's session, its tables, prepared statements, and pending
+    /// results, so it can't be guessable or derived from anything another client sees, as a
+    /// counter or the time would be. Whoever holds it has the session, so it must travel only
+    /// over connections others can't read.
+    fn token() -> String {
+        let mut token = [0; 16];
+        getrandom::getrandom(&mut token).expect("No source of randomness to make a token from");
+        token.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+}
+
+impl Session {
+    /// Run `sql` on the session's engine, returning the result of its last statement once the
+    /// statements before it have run
+    async fn query(&self, sql: &str) -> anyhow::Result<QueryResult> {
+        tracing::info!(sql, "Running Flight SQL query");
+        let mut statements = self.engine.execute(sql).await?;
+        let Some(last) = statements.pop() else {
+            anyhow::bail!("No statements to run");
+        };
+        for mut execution in statements {
+            while let Some(batch) = execution.stream.next().await {
+                batch?;
+            }
+        }
+        Ok(self.result(last))
+    }
+
+    /// Run the prepared statement `handle`
+    async fn execute_prepared(&self, handle: &str) -> anyhow::Result<QueryResult> {
+        let prepared = self.prepared.lock().unwrap().get(handle).cloned();
+        let prepared = prepared.with_context(|| format!("No prepared statement {}", handle))?;
+        tracing::info!(sql = %prepared.sql(), "Running Flight SQL prepared statement");
+        let execution = prepared.execute(self.engine.as_ref()).await?;
+        Ok(self.result(execution))
+    }
+
+    /// The result of `execution`, masked
+    fn result(&self, execution: QueryExecution) -> QueryResult {
+        QueryResult::from(QueryExecution {
+            schema: self.masks.schema(&execution.schema),
+            stream: self.masks.masked(execution.stream),
+            ..execution
+        })
+    }
+
+    /// The result of the prepared statement `handle`: the one waiting to be fetched, or else
+    /// that of running it again
+    async fn run_prepared(&self, handle: &str) -> anyhow::Result<QueryResult> {
+        let waiting = self.results.lock().unwrap().remove(handle);
+        match waiting {
+            Some(result) => Ok(result),
+            None => self.execute_prepared(handle).await,
+        }
+    }
+}
+
+/// What names the session of `request`: the bearer token it carries, or else the address of
+/// the connection it came over
+fn session_key<T>(request: &Request<T>) -> Result<String, Status> {
+    if let Some(value) = request.metadata().get("authorization") {
+        let value = value
+            .to_str()
+            .map_err(|_| Status::unauthenticated("Malformed authorization header"))?;
+        if let Some(token) = value.strip_prefix("Bearer ") {
+            return Ok(token.to_string());
+        }
+    }
+    request
+        .remote_addr()
+        .map(|address| address.to_string())
+        .ok_or_else(|| Status::unauthenticated("No session: handshake first"))
+}
+
+fn status(error: anyhow::Error) -> Status {
+    Status::internal(format!("{:#}", error))
+}
+
+/// How many rows a statement changed, as DataFusion and DuckDB report it: a single row of a
+/// single `count` column
+fn update_count(batches: &[RecordBatch]) -> Option<i64> {
+    let mut batches = batches.iter().filter(|batch| batch.num_rows() > 0);
+    let (Some(batch), None) = (batches.next(), batches.next()) else {
+        return None;
+    };
+    let schema = batch.schema();
+    if batch.num_rows() != 1
+        || batch.num_columns() != 1
+        || !schema.field(0).name().eq_ignore_ascii_case("count")
+    {
+        return None;
+    }
+    let count = arrow::compute::cast(batch.column(0), &DataType::Int64).ok()?;
+    let count = count.as_any().downcast_ref::<Int64Array>()?;
+    count.is_valid(0).then(|| count.value(0))
+}
+
+/// A description of `schema`'s rows, fetched with a ticket holding `command`
+fn flight_info(
+    schema: &SchemaRef,
+    command: Any,
+    descriptor: FlightDescriptor,
+) -> Result<Response<FlightInfo>, Status> {
+    let info = FlightInfo::new()
+        .try_with_schema(schema)
+        .map_err(|error| Status::internal(error.to_string()))?
+        .with_endpoint(FlightEndpoint::new().with_ticket(Ticket::new(command.encode_to_vec())))
+        .with_descriptor(descriptor);
+    Ok(Response::new(info))
+}
+
+/// `result` encoded for sending, its schema first even if it has no rows
+fn send(result: QueryResult) -> Response<DoGetStream> {
+    Response::new(
+        FlightDataEncoderBuilder::new()
+            .with_schema(result.schema)
+            .build(result.batches)
+            .map_err(Status::from)
+            .boxed(),
+    )
+}
+
+/// `batch` alone encoded for sending, as the answer to a request for metadata
+fn send_batch(batch: arrow::error::Result<RecordBatch>) -> Result<Response<DoGetStream>, Status> {
+    let batch = batch.map_err(|error| Status::internal(error.to_string()))?;
+    Ok(send(QueryResult {
+        schema: batch.schema(),
+        batches: futures::stream::iter([Ok(batch)]).boxed(),
+    }))
+}
+
+#[tonic::async_trait]
+impl FlightSqlService for FlightSqlServer {
+    type FlightService = FlightSqlServer;
+
+    /// Open a session whatever the credentials, giving its token as the payload and as a
+    /// bearer token
+    async fn do_handshake(
+        &self,
+        request: Request<Streaming<HandshakeRequest>>,
+    ) -> Result<
+        Response<Pin<Box<dyn Stream<Item = Result<HandshakeResponse, Status>> + Send>>>,
+        Status,
+    > {
+        let token = FlightSqlServer::token();
+        let session = Arc::new(self.open().await.map_err(status)?);
+        self.sessions.lock().unwrap().insert(token.clone(), session);
+        tracing::info!(peer = ?request.remote_addr(), "Opened Flight SQL session by handshake");
+        let bearer = MetadataValue::try_from(format!("Bearer {}", token))
+            .map_err(|error| Status::internal(error.to_string()))?;
+        let handshake = HandshakeResponse {
+            protocol_version: 0,
+            payload: token.into(),
+        };
+        let mut response = Response::new(futures::stream::iter([Ok(handshake)]).boxed());
+        response.metadata_mut().insert("authorization", bearer);
+        Ok(response)
+    }
+
+    async fn get_flight_info_statement(
+        &self,
+        query: CommandStatementQuery,
+        request: Request<FlightDescriptor>,
+    ) -> Result<Response<FlightInfo>, Status> {
+        let session = self.session(&request).await?;
+        let result = session.query(&query.query).await.map_err(status)?;
+        let schema = result.schema.clone();
+        let handle = self.handle();
+        session
+            .results
+            .lock()
+            .unwrap()
+            .insert(handle.clone(), result);
+        let ticket = TicketStatementQuery {
+            statement_handle: handle.into(),
+        };
+        flight_info(&schema, ticket.as_any(), request.into_inner())
+    }
+
+    async fn do_get_statement(
+        &self,
+        ticket: TicketStatementQuery,
+        request: Request<Ticket>,
+    ) -> Result<Response<DoGetStream>, Status> {
+        let session = self.session(&request).await?;
+        let handle = String::from_utf8_lossy(&ticket.statement_handle).into_owned();
+        let result = session.results.lock().unwrap().remove(&handle);
+        let result = result.ok_or_else(|| Status::not_found(format!("No result {}", handle)))?;
+        Ok(send(result))
+    }
+
+    async fn do_put_statement_update(
+        &self,
+        command: CommandStatementUpdate,
+        request: Request<PeekableFlightDataStream>,
+    ) -> Result<i64, Status> {
+        let session = self.session(&request).await?;
+        let result = session.query(&command.query).await.map_err(status)?;
+        let batches = result
+            .batches
+            .try_collect::<Vec<_>>()
+            .await
+            .map_err(Status::from)?;
+        // Polars doesn't say how many rows a statement changed, which leaves it unknown
+        Ok(update_count(&batches).unwrap_or(-1))
+    }
+
+    async fn do_action_create_prepared_statement(
+        &self,
+        query: ActionCreatePreparedStatementRequest,
+        request: Request<Action>,
+    ) -> Result<ActionCreatePreparedStatementResult, Status> {
+        let session = self.session(&request).await?;
+        // Planned rather than run, so that it runs only once its result is asked for. Engines that
+        // don't plan ahead give the schema of its result only then.
+        let prepared = session.engine.prepare(&query.query).await.map_err(status)?;
+        let dataset_schema = match prepared.schema() {
+            Some(schema) => {
+                let schema = session.masks.schema(&schema);
+                let IpcMessage(schema) = SchemaAsIpc::new(&schema, &IpcWriteOptions::default())
+                    .try_into()
+                    .map_err(|error: arrow::error::ArrowError| {
+                        Status::internal(error.to_string())
+                    })?;
+                schema
+            }
+            None => Default::default(),
+        };
+        let handle = self.handle();
+        session
+            .prepared
+            .lock()
+            .unwrap()
+            .insert(handle.clone(), prepared);
+        Ok(ActionCreatePreparedStatementResult {
+            prepared_statement_handle: handle.into(),
+            dataset_schema,
+            ..Default::default()
+        })
+    }
+
+    async fn do_action_close_prepared_statement(
+        &self,
+        query: ActionClosePreparedStatementRequest,
+        request: Request<Action>,
+    ) -> Result<(), Status> {
+        let session = self.session(&request).await?;
+        let handle = String::from_utf8_lossy(&query.prepared_statement_handle).into_owned();
+        session.prepared.lock().unwrap().remove(&handle);
+        session.results.lock().unwrap().remove(&handle);
+        Ok(())
+    }
+
+    async fn get_flight_info_prepared_statement(
+        &self,
+        command: CommandPreparedStatementQuery,
+        request: Request<FlightDescriptor>,
+    ) -> Result<Response<FlightInfo>, Status> {
+        let session = self.session(&request).await?;
+        let handle = String::from_utf8_lossy(&command.prepared_statement_handle).into_owned();
+        // Run afresh, its result kept for the first time it's fetched
+        let result = session.execute_prepared(&handle).await.map_err(status)?;
+        let schema = result.schema.clone();
+        session.results.lock().unwrap().insert(handle, result);
+        flight_info(&schema, command.as_any(), request.into_inner())
+    }
+
+    async fn do_get_prepared_statement(
+        &self,
+        command: CommandPreparedStatementQuery,
+        request: Request<Ticket>,
+    ) -> Result<Response<DoGetStream>, Status> {
+        let session = self.session(&request).await?;
+        let handle = String::from_utf8_lossy(&command.prepared_statement_handle).into_owned();
+        Ok(send(session.run_prepared(&handle).await.map_err(status)?))
+    }
+
+    async fn get_flight_info_catalogs(
+        &self,
+        query: CommandGetCatalogs,
+        request: Request<FlightDescriptor>,
+    ) -> Result<Response<FlightInfo>, Status> {
+        let schema = query.clone().into_builder().schema();
+        flight_info(&schema, query.as_any(), request.into_inner())
+    }
+
+    async fn do_get_catalogs(
+        &self,
+        query: CommandGetCatalogs,
+        _request: Request<Ticket>,
+    ) -> Result<Response<DoGetStream>, Status> {
+        let mut builder = query.into_builder();
+        builder.append(CATALOG);
+        send_batch(builder.build())
+    }
+
+    async fn get_flight_info_schemas(
+        &self,
+        query: CommandGetDbSchemas,
+        request: Request<FlightDescriptor>,
+    ) -> Result<Response<FlightInfo>, Status> {
+        let schema = query.clone().into_builder().schema();
+        flight_info(&schema, query.as_any(), request.into_inner())
+    }
+
+    async fn do_get_schemas(
+        &self,
+        query: CommandGetDbSchemas,
+        _request: Request<Ticket>,
+    ) -> Result<Response<DoGetStream>, Status> {
+        let mut builder = query.into_builder();
+        builder.append(CATALOG, SCHEMA);
+        send_batch(builder.build())
+    }
+
+    async fn get_flight_info_tables(
+        &self,
+        query: CommandGetTables,
+        request: Request<FlightDescriptor>,
+    ) -> Result<Response<FlightInfo>, Status> {
+        let schema = query.clone().into_builder().schema();
+        flight_info(&schema, query.as_any(), request.into_inner())
+    }
+
+    async fn do_get_tables(
+        &self,
+        query: CommandGetTables,
+        request: Request<Ticket>,
+    ) -> Result<Response<DoGetStream>, Status> {
+        let session = self.session(&request).await?;
+        let tables = session.engine.tables().await.map_err(status)?;
+        let mut builder = query.into_builder();
+        let unknown = arrow::datatypes::Schema::empty();
+        for table in tables {
+            let schema = table.schema.as_deref().unwrap_or(&unknown);
+            builder
+                .append(CATALOG, SCHEMA, &table.name, "TABLE", schema)
+                .map_err(|error| Status::internal(error.to_string()))?;
+        }
+        send_batch(builder.build())
+    }
+
+    async fn get_flight_info_sql_info(
+        &self,
+        query: CommandGetSqlInfo,
+        request: Request<FlightDescriptor>,
+    ) -> Result<Response<FlightInfo>, Status> {
+        let schema = query.clone().into_builder(&self.sql_info).schema();
+        flight_info(&schema, query.as_any(), request.into_inner())
+    }
+
+    async fn do_get_sql_info(
+        &self,
+        query: CommandGetSqlInfo,
+        _request: Request<Ticket>,
+    ) -> Result<Response<DoGetStream>, Status> {
+        send_batch(query.into_builder(&self.sql_info).build())
+    }
+
+    async fn register_sql_info(&self, _id: i32, _result: &SqlInfo) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens_are_random_128_bit_hex() {
+        let tokens = (0..100)
+            .map(|_| FlightSqlServer::token())
+            .collect::<std::collections::HashSet<_>>();
+        assert_eq!(tokens.len(), 100);
+        for token in &tokens {
+            assert_eq!(token.len(), 32);
+            assert!(token.chars().all(|c| c.is_ascii_hexdigit()));
+        }
+    }
+}
@@ -0,0 +1,296 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use anyhow::Context as _;
+use arrow::{
+    datatypes::{Schema, SchemaRef},
+    ipc::writer::StreamWriter,
+    record_batch::RecordBatch,
+};
+use axum::{
+    body::{Body, Bytes},
+    extract::{FromRef, Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use futures::stream::StreamExt as _;
+use serde_json::json;
+
+use crate::{CallistoError, EngineBuilder, EngineInterface, MaskPolicy, OutputFormat, Renderer};
+
+/// Media type of Arrow's IPC streaming format
+const ARROW_STREAM: &str = "application/vnd.apache.arrow.stream";
+/// Chunks of a response encoded ahead of the client reading them
+const CHUNKS_AHEAD: usize = 4;
+
+/// The engine the server's requests share, running them concurrently
+pub(super) type SharedEngine = Arc<dyn EngineInterface>;
+
+/// What the server's requests share: the engine, and the masks applied to every result
+#[derive(Clone)]
+struct Shared {
+    engine: SharedEngine,
+    masks: Arc<MaskPolicy>,
+}
+
+impl FromRef<Shared> for SharedEngine {
+    fn from_ref(shared: &Shared) -> SharedEngine {
+        shared.engine.clone()
+    }
+}
+
+impl FromRef<Shared> for Arc<MaskPolicy> {
+    fn from_ref(shared: &Shared) -> Arc<MaskPolicy> {
+        shared.masks.clone()
+    }
+}
+
+/// The engine layer served over HTTP.
+///
+/// SQL posted to `/query` is answered with the rows of its last statement, as JSON, CSV, or Arrow
+/// IPC by the request's `Accept` header, sent a batch at a time as the engine produces them.
+/// `/ws` takes queries over a WebSocket instead, sending rows as JSON or Arrow IPC frames until a
+/// query finishes or is cancelled. `/tables` lists the tables registered and `/schema/<table>`
+/// describes one. Requests share an engine, so tables one registers are seen by the rest. Nothing
+/// is authenticated, so the server is best listening only where its clients are trusted.
+pub struct HttpServer {
+    engine: SharedEngine,
+    /// Masks applied to every result sent, over HTTP or a WebSocket
+    masks: MaskPolicy,
+}
+
+/// A request that couldn't be answered, with why as plain text
+struct Failure(StatusCode, String);
+
+impl IntoResponse for Failure {
+    fn into_response(self) -> Response {
+        (self.0, format!("{}\n", self.1)).into_response()
+    }
+}
+
+fn bad_request(error: anyhow::Error) -> Failure {
+    // A file the path policy refused is forbidden rather than asked for wrongly
+    let status = match CallistoError::of(&error) {
+        Some(CallistoError::PathNotAllowed { .. }) => StatusCode::FORBIDDEN,
+        _ => StatusCode::BAD_REQUEST,
+    };
+    Failure(status, format!("{:#}", error))
+}
+
+fn internal(error: anyhow::Error) -> Failure {
+    Failure(StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", error))
+}
+
+/// How a result is written out, chosen by the request's `Accept` header
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Encoding {
+    Json,
+    Csv,
+    Arrow,
+}
+
+impl Encoding {
+    /// The first encoding `headers` accept, JSON if they don't say
+    fn accepted(headers: &HeaderMap) -> Result<Encoding, Failure> {
+        let accept = headers
+            .get(header::ACCEPT)
+            .and_then(|accept| accept.to_str().ok())
+            .unwrap_or("*/*");
+        for media in accept.split(',') {
+            let media = media.split(';').next().unwrap_or_default().trim();
+            match media {
+                "application/json" | "application/*" | "*/*" => return Ok(Encoding::Json),
+                "text/csv" | "text/*" => return Ok(Encoding::Csv),
+                ARROW_STREAM => return Ok(Encoding::Arrow),
+                _ => {}
+            }
+        }
+        Err(Failure(
+            StatusCode::NOT_ACCEPTABLE,
+            format!(
+                "Can't answer with {}: accept application/json, text/csv, or {}",
+                accept, ARROW_STREAM
+            ),
+        ))
+    }
+
+    fn content_type(&self) -> &'static str {
+        match self {
+            Encoding::Json => "application/json",
+            Encoding::Csv => "text/csv",
+            Encoding::Arrow => ARROW_STREAM,
+        }
+    }
+}
+
+/// Writes a result out in an [`Encoding`], a chunk a batch
+enum Writer {
+    Text(Renderer),
+    Arrow(StreamWriter<Vec<u8>>),
+}
+
+impl Writer {
+    fn new(encoding: Encoding, schema: SchemaRef) -> anyhow::Result<Writer> {
+        Ok(match encoding {
+            Encoding::Json => Writer::Text(Renderer::new(OutputFormat::Json, schema)),
+            Encoding::Csv => Writer::Text(Renderer::new(OutputFormat::Csv, schema)),
+            Encoding::Arrow => Writer::Arrow(StreamWriter::try_new(Vec::new(), &schema)?),
+        })
+    }
+
+    /// The encoding of `batch`, preceded by whatever comes before the first
+    fn push(&mut self, batch: &RecordBatch) -> anyhow::Result<Bytes> {
+        Ok(match self {
+            Writer::Text(renderer) => Bytes::from(renderer.push(batch)?),
+            Writer::Arrow(writer) => {
+                writer.write(batch)?;
+                Bytes::from(std::mem::take(writer.get_mut()))
+            }
+        })
+    }
+
+    /// Whatever follows the last batch, or the whole encoding of an empty result
+    fn finish(&mut self) -> anyhow::Result<Bytes> {
+        Ok(match self {
+            Writer::Text(renderer) => Bytes::from(renderer.finish()?),
+            Writer::Arrow(writer) => {
+                writer.finish()?;
+                Bytes::from(std::mem::take(writer.get_mut()))
+            }
+        })
+    }
+}
+
+impl HttpServer {
+    /// A server whose requests share a new engine built by `engine`, on which `init` is run
+    /// first, and whose results are masked with `masks`
+    pub async fn new(
+        engine: EngineBuilder,
+        init: Option<&str>,
+        masks: MaskPolicy,
+    ) -> anyhow::Result<HttpServer> {
+        let engine = super::open_engine(&engine, init).await?;
+        Ok(HttpServer {
+            engine: Arc::from(engine),
+            masks,
+        })
+    }
+
+    /// Serve requests to `address` until the process ends
+    pub async fn serve(self, address: SocketAddr) -> anyhow::Result<()> {
+        let router = Router::new()
+            .route("/query", post(query))
+            .route("/tables", get(tables))
+            .route("/schema/:table", get(schema))
+            .route("/ws", get(super::websocket::upgrade))
+            .with_state(Shared {
+                engine: self.engine,
+                masks: Arc::new(self.masks),
+            });
+        let listener = tokio::net::TcpListener::bind(address)
+            .await
+            .with_context(|| format!("Failed to listen on {}", address))?;
+        tracing::info!(%address, "Serving HTTP");
+        axum::serve(listener, router)
+            .await
+            .with_context(|| format!("Failed to serve HTTP on {}", address))
+    }
+}
+
+/// Run the SQL that is the request's body, answering with the rows of its last statement once
+/// the statements before it have run
+async fn query(
+    State(engine): State<SharedEngine>,
+    State(masks): State<Arc<MaskPolicy>>,
+    headers: HeaderMap,
+    sql: String,
+) -> Result<Response, Failure> {
+    let encoding = Encoding::accepted(&headers)?;
+    tracing::info!(sql, "Running HTTP query");
+    let mut statements = engine.execute(&sql).await.map_err(bad_request)?;
+    let Some(last) = statements.pop() else {
+        return Err(bad_request(anyhow::anyhow!("No statements to run")));
+    };
+    for mut execution in statements {
+        while let Some(batch) = execution.stream.next().await {
+            batch.map_err(|error| bad_request(error.into()))?;
+        }
+    }
+
+    let mut stream = masks.masked(last.stream);
+    let mut writer = Writer::new(encoding, masks.schema(&last.schema)).map_err(internal)?;
+    let (chunks, body) = tokio::sync::mpsc::channel(CHUNKS_AHEAD);
+    tokio::spawn(async move {
+        let written: anyhow::Result<()> = async {
+            while let Some(batch) = stream.next().await {
+                let chunk = writer.push(&batch?)?;
+                // A client gone away leaves the rest of the query unread
+                if !chunk.is_empty() && chunks.send(Ok(chunk)).await.is_err() {
+                    return Ok(());
+                }
+            }
+            let _ = chunks.send(Ok(writer.finish()?)).await;
+            Ok(())
+        }
+        .await;
+        // The status has already been sent, so all that can be done is to cut the body short
+        if let Err(error) = written {
+            tracing::warn!("HTTP query failed while streaming: {:#}", error);
+            let _ = chunks
+                .send(Err(std::io::Error::other(format!("{:#}", error))))
+                .await;
+        }
+    });
+    Ok((
+        [(header::CONTENT_TYPE, encoding.content_type())],
+        Body::from_stream(tokio_stream::wrappers::ReceiverStream::new(body)),
+    )
+        .into_response())
+}
+
+/// The names of the tables registered, with the paths of those read from files
+async fn tables(State(engine): State<SharedEngine>) -> Result<Json<serde_json::Value>, Failure> {
+    let tables = engine.tables().await.map_err(internal)?;
+    Ok(Json(
+        tables
+            .into_iter()
+            .map(|table| json!({ "name": table.name, "source": table.source }))
+            .collect(),
+    ))
+}
+
+/// The columns of `table`, with their types and whether they may be null
+async fn schema(
+    State(engine): State<SharedEngine>,
+    Path(table): Path<String>,
+) -> Result<Json<serde_json::Value>, Failure> {
+    let tables = engine.tables().await.map_err(internal)?;
+    let not_found = |message: String| Failure(StatusCode::NOT_FOUND, message);
+    let info = tables
+        .into_iter()
+        .find(|info| info.name == table)
+        .ok_or_else(|| not_found(format!("No table {}", table)))?;
+    let schema = info
+        .schema
+        .ok_or_else(|| not_found(format!("The schema of {} isn't known", table)))?;
+    Ok(Json(
+        json!({ "name": info.name, "columns": columns(&schema) }),
+    ))
+}
+
+/// The columns of `schema`, with their types and whether they may be null
+pub(super) fn columns(schema: &Schema) -> serde_json::Value {
+    schema
+        .fields()
+        .iter()
+        .map(|field| {
+            json!({
+                "name": field.name(),
+                "type": field.data_type().to_string(),
+                "nullable": field.is_nullable(),
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,519 @@
+use core::ops::ControlFlow;
+use std::collections::{BTreeSet, HashMap};
+use std::ops::Range;
+use std::sync::Mutex;
+
+use arrow::datatypes::SchemaRef;
+use sqlparser::{
+    ast::{self, Visit as _},
+    dialect::GenericDialect,
+    parser::{Parser, ParserError, ParserOptions},
+    tokenizer::{Location, Token, TokenWithLocation, Tokenizer},
+};
+use tower_lsp::{jsonrpc, lsp_types as lsp, Client, LspService};
+
+use crate::completion::{Completer, CompletionKind};
+use crate::schema_cache::SchemaCache;
+use crate::Engine;
+
+/// SQL intelligence for editors over the Language Server Protocol, spoken on stdin and stdout.
+///
+/// Open documents get diagnostics for statements that don't parse and for tables and columns
+/// that aren't known, completions of keywords, tables, columns, and paths, and hovers describing
+/// the table or column under the cursor. Tables are known from the catalog of an engine the
+/// server opens, and from the Parquet files the documents name. Nothing is ever executed.
+pub struct LspServer {
+    cache: SchemaCache,
+}
+
+impl LspServer {
+    /// A server whose catalog is that of a new engine of kind `engine`, on which `init` is run
+    /// first, e.g. to register the tables the documents query
+    pub async fn new(engine: Engine, init: Option<&str>) -> anyhow::Result<LspServer> {
+        let engine = super::open_engine(&engine.builder(), init).await?;
+        let cache = SchemaCache::default();
+        cache.refresh(&engine).await?;
+        Ok(LspServer { cache })
+    }
+
+    /// Serve the editor on the other end of stdin and stdout until it shuts the server down
+    pub async fn serve_stdio(self) {
+        let (service, socket) = LspService::new(|client| Backend {
+            client,
+            completer: Completer::new(self.cache.clone()),
+            cache: self.cache,
+            documents: Mutex::default(),
+        });
+        tower_lsp::Server::new(tokio::io::stdin(), tokio::io::stdout(), socket)
+            .serve(service)
+            .await;
+    }
+}
+
+struct Backend {
+    client: Client,
+    cache: SchemaCache,
+    completer: Completer,
+    /// Text of each open document, by URI
+    documents: Mutex<HashMap<lsp::Url, String>>,
+}
+
+impl Backend {
+    fn text(&self, uri: &lsp::Url) -> Option<String> {
+        self.documents.lock().unwrap().get(uri).cloned()
+    }
+
+    /// Replace the text of the document at `uri`, publishing its diagnostics anew
+    async fn update(&self, uri: lsp::Url, text: String, version: i32) {
+        let diagnostics = diagnose(&text, &self.cache);
+        self.documents.lock().unwrap().insert(uri.clone(), text);
+        self.client
+            .publish_diagnostics(uri, diagnostics, Some(version))
+            .await;
+    }
+}
+
+#[tower_lsp::async_trait]
+impl tower_lsp::LanguageServer for Backend {
+    async fn initialize(&self, _: lsp::InitializeParams) -> jsonrpc::Result<lsp::InitializeResult> {
+        Ok(lsp::InitializeResult {
+            capabilities: lsp::ServerCapabilities {
+                text_document_sync: Some(lsp::TextDocumentSyncCapability::Kind(
+                    lsp::TextDocumentSyncKind::FULL,
+                )),
+                completion_provider: Some(lsp::CompletionOptions {
+                    trigger_characters: Some(vec![".".into(), "'".into(), "\"".into()]),
+                    ..Default::default()
+                }),
+                hover_provider: Some(lsp::HoverProviderCapability::Simple(true)),
+                ..Default::default()
+            },
+            server_info: Some(lsp::ServerInfo {
+                name: "callisto".into(),
+                version: Some(env!("CARGO_PKG_VERSION").into()),
+            }),
+        })
+    }
+
+    async fn shutdown(&self) -> jsonrpc::Result<()> {
+        Ok(())
+    }
+
+    async fn did_open(&self, params: lsp::DidOpenTextDocumentParams) {
+        let document = params.text_document;
+        self.update(document.uri, document.text, document.version)
+            .await;
+    }
+
+    async fn did_change(&self, mut params: lsp::DidChangeTextDocumentParams) {
+        // Changes are whole documents, as asked for on initialization
+        if let Some(change) = params.content_changes.pop() {
+            let document = params.text_document;
+            self.update(document.uri, change.text, document.version)
+                .await;
+        }
+    }
+
+    async fn did_close(&self, params: lsp::DidCloseTextDocumentParams) {
+        let uri = params.text_document.uri;
+        self.documents.lock().unwrap().remove(&uri);
+        self.client.publish_diagnostics(uri, Vec::new(), None).await;
+    }
+
+    async fn completion(
+        &self,
+        params: lsp::CompletionParams,
+    ) -> jsonrpc::Result<Option<lsp::CompletionResponse>> {
+        let position = params.text_document_position;
+        let Some(text) = self.text(&position.text_document.uri) else {
+            return Ok(None);
+        };
+        let offset = offset_of(&text, position.position);
+        let items = self
+            .completer
+            .complete(&text, offset)
+            .into_iter()
+            .map(|completion| lsp::CompletionItem {
+                label: completion.value.clone(),
+                kind: Some(match completion.kind {
+                    CompletionKind::Keyword => lsp::CompletionItemKind::KEYWORD,
+                    CompletionKind::Table => lsp::CompletionItemKind::STRUCT,
+                    CompletionKind::Column => lsp::CompletionItemKind::FIELD,
+                    CompletionKind::Path => lsp::CompletionItemKind::FILE,
+                }),
+                detail: completion.description,
+                text_edit: Some(lsp::CompletionTextEdit::Edit(lsp::TextEdit {
+                    range: range_of(&text, completion.span),
+                    new_text: completion.value,
+                })),
+                ..Default::default()
+            })
+            .collect();
+        Ok(Some(lsp::CompletionResponse::Array(items)))
+    }
+
+    async fn hover(&self, params: lsp::HoverParams) -> jsonrpc::Result<Option<lsp::Hover>> {
+        let position = params.text_document_position_params;
+        let Some(text) = self.text(&position.text_document.uri) else {
+            return Ok(None);
+        };
+        let offset = offset_of(&text, position.position);
+        let Ok(tokens) = Tokenizer::new(&GenericDialect, &text).tokenize_with_location() else {
+            return Ok(None);
+        };
+        let Some((name, span)) = tokens.iter().find_map(|token| {
+            let span = span_of(&text, token);
+            let name = match &token.token {
+                Token::Word(word) => &word.value,
+                Token::SingleQuotedString(string) | Token::DoubleQuotedString(string) => string,
+                _ => return None,
+            };
+            (span.start..=span.end)
+                .contains(&offset)
+                .then(|| (name.clone(), span))
+        }) else {
+            return Ok(None);
+        };
+        Ok(describe(&self.cache, &name).map(|value| lsp::Hover {
+            contents: lsp::HoverContents::Markup(lsp::MarkupContent {
+                kind: lsp::MarkupKind::Markdown,
+                value,
+            }),
+            range: Some(range_of(&text, span)),
+        }))
+    }
+}
+
+/// Problems with each statement of `text`: failures to parse, then tables and columns that
+/// aren't known
+fn diagnose(text: &str, cache: &SchemaCache) -> Vec<lsp::Diagnostic> {
+    let tokens = match Tokenizer::new(&GenericDialect, text).tokenize_with_location() {
+        Ok(tokens) => tokens,
+        Err(error) => {
+            let start = location_offset(text, error.location);
+            return vec![diagnostic(
+                range_of(text, start..start),
+                lsp::DiagnosticSeverity::ERROR,
+                error.message,
+            )];
+        }
+    };
+
+    let mut diagnostics = Vec::new();
+    // Tables created earlier in the document are known to the statements after
+    let mut created = BTreeSet::new();
+    for statement in tokens.split(|token| token.token == Token::SemiColon) {
+        if statement
+            .iter()
+            .all(|token| matches!(token.token, Token::Whitespace(_) | Token::EOF))
+        {
+            continue;
+        }
+        let parsed = Parser::new(&GenericDialect)
+            .with_options(ParserOptions {
+                trailing_commas: true,
+                ..Default::default()
+            })
+            .with_tokens_with_locations(statement.to_vec())
+            .parse_statements();
+        let statements = match parsed {
+            Ok(statements) => statements,
+            Err(error) => {
+                let message = match error {
+                    ParserError::ParserError(message) | ParserError::TokenizerError(message) => {
+                        message
+                    }
+                    error => error.to_string(),
+                };
+                // Errors only say where they are in their message
+                let span = error_location(&message)
+                    .map(|location| location_offset(text, location))
+                    .and_then(|offset| {
+                        statement
+                            .iter()
+                            .map(|token| span_of(text, token))
+                            .find(|span| span.contains(&offset))
+                    })
+                    .unwrap_or_else(|| span_of(text, &statement[0]));
+                diagnostics.push(diagnostic(
+                    range_of(text, span),
+                    lsp::DiagnosticSeverity::ERROR,
+                    message,
+                ));
+                continue;
+            }
+        };
+        for parsed in statements {
+            let mut problems = Vec::new();
+            check(&parsed, cache, &created, &mut problems);
+            for (name, message) in problems {
+                let span = statement
+                    .iter()
+                    .find(|token| matches!(&token.token, Token::Word(word) if word.value == name))
+                    .map_or_else(
+                        || span_of(text, &statement[0]),
+                        |token| span_of(text, token),
+                    );
+                diagnostics.push(diagnostic(
+                    range_of(text, span),
+                    lsp::DiagnosticSeverity::WARNING,
+                    message,
+                ));
+            }
+            if let ast::Statement::CreateTable { name, .. }
+            | ast::Statement::CreateView { name, .. } = &parsed
+            {
+                created.insert(name.0[0].value.clone());
+            }
+        }
+    }
+    diagnostics
+}
+
+/// Push the names in `statement` of the tables and columns that aren't known onto `problems`,
+/// each with what's wrong with it.
+///
+/// Columns are only checked where every table the statement reads has a known schema.
+fn check(
+    statement: &ast::Statement,
+    cache: &SchemaCache,
+    created: &BTreeSet<String>,
+    problems: &mut Vec<(String, String)>,
+) {
+    let mut references = References::default();
+    let _ = statement.visit(&mut references);
+    let mut columns = BTreeSet::new();
+    let mut fully_known = true;
+    for relation in &references.relations {
+        if references.ctes.contains(relation) || created.contains(relation) {
+            fully_known = false;
+        } else if let Some(schema) = schema_of(cache, relation) {
+            columns.extend(schema.fields().iter().map(|f| f.name().to_lowercase()));
+        } else if cache.table(relation).is_some()
+            || relation.contains(['*', '?', '['])
+            || std::path::Path::new(relation).exists()
+        {
+            fully_known = false;
+        } else {
+            fully_known = false;
+            problems.push((relation.clone(), format!("Unknown table {}", relation)));
+        }
+    }
+    if !fully_known || references.relations.is_empty() {
+        return;
+    }
+
+    let known = |ident: &ast::Ident| {
+        let name = ident.value.to_lowercase();
+        columns.contains(&name) || references.aliases.contains(&name)
+    };
+    let mut unknown = BTreeSet::new();
+    let _ = ast::visit_expressions(statement, |expr| {
+        match expr {
+            ast::Expr::Identifier(ident) if !known(ident) => {
+                unknown.insert(ident.value.clone());
+            }
+            ast::Expr::CompoundIdentifier(idents)
+                if idents.len() == 2
+                    && references
+                        .qualifiers
+                        .contains(&idents[0].value.to_lowercase())
+                    && !known(&idents[1]) =>
+            {
+                unknown.insert(idents[1].value.clone());
+            }
+            _ => {}
+        }
+        ControlFlow::<()>::Continue(())
+    });
+    for column in unknown {
+        let message = format!("Unknown column {}", column);
+        problems.push((column, message));
+    }
+}
+
+/// The names a statement defines and references
+#[derive(Default)]
+struct References {
+    relations: BTreeSet<String>,
+    /// Names of common table expressions
+    ctes: BTreeSet<String>,
+    /// Lowercased projection aliases, usable as columns in e.g. ORDER BY
+    aliases: BTreeSet<String>,
+    /// Lowercased relation names and aliases which may qualify a column
+    qualifiers: BTreeSet<String>,
+}
+
+impl ast::Visitor for References {
+    type Break = ();
+
+    fn pre_visit_query(&mut self, query: &ast::Query) -> ControlFlow<()> {
+        if let Some(with) = &query.with {
+            for cte in &with.cte_tables {
+                self.ctes.insert(cte.alias.name.value.clone());
+            }
+        }
+        if let ast::SetExpr::Select(select) = query.body.as_ref() {
+            for item in &select.projection {
+                if let ast::SelectItem::ExprWithAlias { alias, .. } = item {
+                    self.aliases.insert(alias.value.to_lowercase());
+                }
+            }
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn pre_visit_relation(&mut self, relation: &ast::ObjectName) -> ControlFlow<()> {
+        self.relations.insert(relation.0[0].value.clone());
+        self.qualifiers.insert(relation.0[0].value.to_lowercase());
+        ControlFlow::Continue(())
+    }
+
+    fn pre_visit_table_factor(&mut self, table_factor: &ast::TableFactor) -> ControlFlow<()> {
+        if let ast::TableFactor::Table {
+            alias: Some(alias), ..
+        }
+        | ast::TableFactor::Derived {
+            alias: Some(alias), ..
+        } = table_factor
+        {
+            self.qualifiers.insert(alias.name.value.to_lowercase());
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+/// The schema of `name`, a table in the catalog or the path of a Parquet file, if known
+fn schema_of(cache: &SchemaCache, name: &str) -> Option<SchemaRef> {
+    if let Some(table) = cache.table(name) {
+        return table.schema;
+    }
+    let file = std::fs::File::open(name).ok()?;
+    let builder =
+        parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder::try_new(file).ok()?;
+    Some(builder.schema().clone())
+}
+
+/// Markdown describing `name`: the columns of a table of that name, or else the tables in the
+/// catalog with a column of that name
+fn describe(cache: &SchemaCache, name: &str) -> Option<String> {
+    if let Some(schema) = schema_of(cache, name) {
+        let mut description = format!("**{}**", name);
+        if let Some(source) = cache.table(name).and_then(|table| table.source) {
+            description.push_str(&format!(" from `{}`", source));
+        }
+        description.push_str("\n\n| Column | Type |\n| --- | --- |\n");
+        for field in schema.fields() {
+            let nullable = if field.is_nullable() { "" } else { " not null" };
+            description.push_str(&format!(
+                "| {} | {}{} |\n",
+                field.name(),
+                field.data_type(),
+                nullable
+            ));
+        }
+        return Some(description);
+    }
+
+    let columns = cache
+        .tables()
+        .into_iter()
+        .filter_map(|table| {
+            let schema = table.schema?;
+            let field = schema
+                .fields()
+                .iter()
+                .find(|field| field.name().eq_ignore_ascii_case(name))?;
+            Some(format!(
+                "`{}.{}`: {}",
+                table.name,
+                field.name(),
+                field.data_type()
+            ))
+        })
+        .collect::<Vec<_>>();
+    (!columns.is_empty()).then(|| columns.join("\n\n"))
+}
+
+fn diagnostic(
+    range: lsp::Range,
+    severity: lsp::DiagnosticSeverity,
+    message: String,
+) -> lsp::Diagnostic {
+    lsp::Diagnostic {
+        range,
+        severity: Some(severity),
+        source: Some("callisto".into()),
+        message,
+        ..Default::default()
+    }
+}
+
+/// The line and column an error message from sqlparser ends by naming, if it names one
+fn error_location(message: &str) -> Option<Location> {
+    let (_, at) = message.rsplit_once("Line: ")?;
+    let (line, rest) = at.split_once(',')?;
+    let column = rest
+        .trim_start_matches(|c: char| !c.is_ascii_digit())
+        .split(|c: char| !c.is_ascii_digit())
+        .next()?;
+    Some(Location {
+        line: line.trim().parse().ok()?,
+        column: column.parse().ok()?,
+    })
+}
+
+/// Byte offset in `text` of a sqlparser `location`, whose line and column count characters from 1
+fn location_offset(text: &str, location: Location) -> usize {
+    let line_start = text
+        .split_inclusive('\n')
+        .take((location.line as usize).saturating_sub(1))
+        .map(str::len)
+        .sum::<usize>();
+    text[line_start..]
+        .char_indices()
+        .nth((location.column as usize).saturating_sub(1))
+        .map_or(text.len(), |(index, _)| line_start + index)
+}
+
+/// Byte range of `text` spanned by `token`
+fn span_of(text: &str, token: &TokenWithLocation) -> Range<usize> {
+    let start = location_offset(text, token.location);
+    start..(start + token.token.to_string().len()).min(text.len())
+}
+
+/// Byte offset in `text` of an LSP `position`, whose character counts UTF-16 code units
+fn offset_of(text: &str, position: lsp::Position) -> usize {
+    let line_start = text
+        .split_inclusive('\n')
+        .take(position.line as usize)
+        .map(str::len)
+        .sum::<usize>();
+    let line = text[line_start..].split('\n').next().unwrap_or_default();
+    let mut units = 0;
+    for (index, c) in line.char_indices() {
+        if units >= position.character as usize {
+            return line_start + index;
+        }
+        units += c.len_utf16();
+    }
+    line_start + line.len()
+}
+
+/// LSP position of the byte `offset` in `text`
+fn position_of(text: &str, offset: usize) -> lsp::Position {
+    let mut offset = offset.min(text.len());
+    while !text.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+    lsp::Position::new(
+        before.matches('\n').count() as u32,
+        before[line_start..].encode_utf16().count() as u32,
+    )
+}
+
+fn range_of(text: &str, span: Range<usize>) -> lsp::Range {
+    lsp::Range::new(position_of(text, span.start), position_of(text, span.end))
+}
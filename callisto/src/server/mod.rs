@@ -0,0 +1,34 @@
+use futures::stream::StreamExt as _;
+
+use crate::{EngineBuilder, EngineInterface};
+
+mod flight_sql;
+mod http;
+mod lsp;
+mod websocket;
+
+pub use flight_sql::FlightSqlServer;
+pub use http::HttpServer;
+pub use lsp::LspServer;
+
+/// A new engine built by `engine`, with `init` run on it first, e.g. to register tables
+async fn open_engine(
+    engine: &EngineBuilder,
+    init: Option<&str>,
+) -> anyhow::Result<Box<dyn EngineInterface>> {
+    use anyhow::Context as _;
+
+    let engine = engine.clone().build()?;
+    if let Some(init) = init {
+        let statements = engine
+            .execute(init)
+            .await
+            .context("Failed to run the initial SQL")?;
+        for mut execution in statements {
+            while let Some(batch) = execution.stream.next().await {
+                batch.context("Failed to run the initial SQL")?;
+            }
+        }
+    }
+    Ok(engine)
+}
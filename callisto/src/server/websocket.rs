@@ -0,0 +1,215 @@
+use std::sync::Arc;
+
+use anyhow::Context as _;
+use arrow::ipc::writer::StreamWriter;
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        State,
+    },
+    response::Response,
+};
+use futures::stream::StreamExt as _;
+use serde::Deserialize;
+use serde_json::json;
+
+use super::http::{columns, SharedEngine};
+use crate::MaskPolicy;
+
+/// What a client asks of the WebSocket endpoint, as a JSON text frame
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum ClientMessage {
+    /// Run `sql`, sending the rows of its last statement in `format` as they're produced
+    Query {
+        sql: String,
+        #[serde(default)]
+        format: Frames,
+    },
+    /// Stop the query running
+    Cancel,
+}
+
+/// How rows are sent: as JSON text frames of row objects, or as binary frames that together make
+/// up an Arrow IPC stream
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum Frames {
+    #[default]
+    Json,
+    Arrow,
+}
+
+/// Answer a request to `/ws` by taking queries over a WebSocket, one at a time.
+///
+/// Each query is answered with a `schema` frame describing its columns if JSON was asked for,
+/// then its rows a batch a frame, then a `done` frame with the number of rows sent. A `cancel`
+/// message while the query runs or its rows are being sent stops it, answered with a `cancelled`
+/// frame, and a failure is answered with an `error` frame with its message.
+pub(super) async fn upgrade(
+    State(engine): State<SharedEngine>,
+    State(masks): State<Arc<MaskPolicy>>,
+    upgrade: WebSocketUpgrade,
+) -> Response {
+    upgrade.on_upgrade(move |socket| serve(socket, engine, masks))
+}
+
+async fn serve(mut socket: WebSocket, engine: SharedEngine, masks: Arc<MaskPolicy>) {
+    while let Some(Ok(message)) = socket.recv().await {
+        let text = match message {
+            Message::Text(text) => text,
+            Message::Close(_) => break,
+            _ => continue,
+        };
+        let outcome = match serde_json::from_str(&text) {
+            Ok(ClientMessage::Query { sql, format }) => {
+                run(&mut socket, &engine, &masks, &sql, format).await
+            }
+            Ok(ClientMessage::Cancel) => Err(anyhow::anyhow!("No query running to cancel")),
+            Err(error) => Err(anyhow::anyhow!("Invalid message: {}", error)),
+        };
+        if let Err(error) = outcome {
+            let error = json!({ "type": "error", "message": format!("{:#}", error) });
+            if send(&mut socket, error).await.is_err() {
+                break;
+            }
+        }
+    }
+}
+
+/// Run `sql`, sending the rows of its last statement masked with `masks` as they're produced
+/// until they run out or the client cancels
+async fn run(
+    socket: &mut WebSocket,
+    engine: &SharedEngine,
+    masks: &MaskPolicy,
+    sql: &str,
+    format: Frames,
+) -> anyhow::Result<()> {
+    tracing::info!(sql, "Running WebSocket query");
+    // Run as a task of its own, so that the socket is read for a cancel meanwhile
+    let mut execution = tokio::spawn({
+        let (engine, sql) = (engine.clone(), sql.to_string());
+        async move {
+            let mut statements = engine.execute(&sql).await?;
+            let last = statements.pop().context("No statements to run")?;
+            for mut execution in statements {
+                while let Some(batch) = execution.stream.next().await {
+                    batch?;
+                }
+            }
+            anyhow::Ok(last)
+        }
+    });
+    let last = loop {
+        tokio::select! {
+            last = &mut execution => break last??,
+            message = socket.recv() => {
+                let stop = interruption(socket, message).await;
+                if !matches!(stop, Ok(None)) {
+                    execution.abort();
+                }
+                match stop? {
+                    Some(Stop::Cancelled) => return cancelled(socket, 0).await,
+                    Some(Stop::Closed) => return Ok(()),
+                    None => {}
+                }
+            }
+        }
+    };
+
+    let schema = masks.schema(&last.schema);
+    let mut stream = masks.masked(last.stream);
+    let mut ipc = match format {
+        Frames::Json => {
+            send(
+                socket,
+                json!({ "type": "schema", "columns": columns(&schema) }),
+            )
+            .await?;
+            None
+        }
+        Frames::Arrow => {
+            let mut writer = StreamWriter::try_new(Vec::new(), &schema)?;
+            socket
+                .send(Message::Binary(std::mem::take(writer.get_mut())))
+                .await?;
+            Some(writer)
+        }
+    };
+    let mut rows = 0;
+    loop {
+        tokio::select! {
+            batch = stream.next() => {
+                let Some(batch) = batch else {
+                    break;
+                };
+                let batch = batch?;
+                if batch.num_rows() == 0 {
+                    continue;
+                }
+                rows += batch.num_rows();
+                let frame = match &mut ipc {
+                    Some(writer) => {
+                        writer.write(&batch)?;
+                        Message::Binary(std::mem::take(writer.get_mut()))
+                    }
+                    None => {
+                        let objects = crate::serialize::json_objects(&batch)?;
+                        Message::Text(format!(r#"{{"type":"rows","rows":[{}]}}"#, objects))
+                    }
+                };
+                socket.send(frame).await?;
+            }
+            // Dropping the stream on returning abandons whatever work it has outstanding
+            message = socket.recv() => match interruption(socket, message).await? {
+                Some(Stop::Cancelled) => return cancelled(socket, rows).await,
+                Some(Stop::Closed) => return Ok(()),
+                None => {}
+            },
+        }
+    }
+    if let Some(mut writer) = ipc {
+        writer.finish()?;
+        socket
+            .send(Message::Binary(std::mem::take(writer.get_mut())))
+            .await?;
+    }
+    send(socket, json!({ "type": "done", "rows": rows })).await
+}
+
+/// Why a query stopped before its rows ran out
+enum Stop {
+    Cancelled,
+    /// The client went away
+    Closed,
+}
+
+/// Answer `message`, received while a query runs, returning whether it stops the query
+async fn interruption(
+    socket: &mut WebSocket,
+    message: Option<Result<Message, axum::Error>>,
+) -> anyhow::Result<Option<Stop>> {
+    match message {
+        Some(Ok(Message::Text(text))) => match serde_json::from_str(&text) {
+            Ok(ClientMessage::Cancel) => Ok(Some(Stop::Cancelled)),
+            _ => {
+                let busy = "A query is already running: cancel it or wait for it to finish";
+                send(socket, json!({ "type": "error", "message": busy })).await?;
+                Ok(None)
+            }
+        },
+        Some(Ok(Message::Close(_)) | Err(_)) | None => Ok(Some(Stop::Closed)),
+        Some(Ok(_)) => Ok(None),
+    }
+}
+
+/// Tell the client its query was cancelled after `rows` rows were sent
+async fn cancelled(socket: &mut WebSocket, rows: usize) -> anyhow::Result<()> {
+    tracing::info!(rows, "WebSocket query cancelled");
+    send(socket, json!({ "type": "cancelled", "rows": rows })).await
+}
+
+async fn send(socket: &mut WebSocket, frame: serde_json::Value) -> anyhow::Result<()> {
+    Ok(socket.send(Message::Text(frame.to_string())).await?)
+}
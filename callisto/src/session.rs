@@ -0,0 +1,207 @@
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context as _;
+use arrow::datatypes::SchemaRef;
+use arrow::record_batch::RecordBatch;
+use futures::stream::StreamExt as _;
+
+use crate::datafusion::datasource::TableProvider;
+use crate::{
+    Engine, EngineInterface, Language, ParquetOptions, PreparedStatement, QueryExecution,
+    ResultSet, StatementReferences, TableInfo,
+};
+
+/// An engine for embedding in other programs, its tables registered explicitly.
+///
+/// ```no_run
+/// # async fn example() -> anyhow::Result<()> {
+/// let session = callisto::Session::new(callisto::Engine::DataFusion)?;
+/// session
+///     .register_parquet("events", "data/events.parquet", Default::default())
+///     .await?;
+/// let result = session.sql("SELECT count(*) FROM events").await?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Queries may still name Parquet files by path, as they can in the REPL, but registering them
+/// gives the tables stable names and surfaces a file that can't be read as an error rather than
+/// an unknown table.
+///
+/// A session can be shared between tasks in an `Arc`, its queries then running concurrently.
+pub struct Session {
+    engine: Box<dyn EngineInterface>,
+    language: Language,
+}
+
+impl Session {
+    pub fn new(engine: Engine) -> anyhow::Result<Session> {
+        Ok(Session::from_engine(engine.new()?))
+    }
+
+    /// A session over an engine already made, e.g. one with tables registered
+    pub fn from_engine(engine: Box<dyn EngineInterface>) -> Session {
+        Session {
+            engine,
+            language: Language::default(),
+        }
+    }
+
+    /// Write queries in `language` from here on
+    pub fn with_language(mut self, language: Language) -> Session {
+        self.language = language;
+        self
+    }
+
+    /// Which engine the session runs on
+    pub fn kind(&self) -> Engine {
+        self.engine.kind()
+    }
+
+    /// The engine itself, for anything the session doesn't cover
+    pub fn engine(&self) -> &dyn EngineInterface {
+        self.engine.as_ref()
+    }
+
+    /// Register the Parquet file (or glob of files) at `path` as a table called `name`,
+    /// replacing any table of that name
+    pub async fn register_parquet(
+        &self,
+        name: &str,
+        path: impl AsRef<Path>,
+        options: ParquetOptions,
+    ) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        let fs_name = path
+            .to_str()
+            .with_context(|| format!("Path {} isn't valid UTF-8", path.display()))?;
+        self.engine
+            .register_parquet(name, fs_name, &options)
+            .await
+            .with_context(|| format!("Failed to register {} as {}", path.display(), name))
+    }
+
+    /// Register `batches`, data the embedding program already has in memory, as a table called
+    /// `name` with the columns of `schema`, replacing any table of that name
+    pub async fn register_batches(
+        &self,
+        name: &str,
+        schema: SchemaRef,
+        batches: Vec<RecordBatch>,
+    ) -> anyhow::Result<()> {
+        self.engine
+            .register_batches(name, schema, batches)
+            .await
+            .with_context(|| format!("Failed to register {}", name))
+    }
+
+    /// Register `result` as a table called `name`, replacing any table of that name
+    pub async fn register_result(&self, name: &str, result: ResultSet) -> anyhow::Result<()> {
+        self.register_batches(name, result.schema, result.batches)
+            .await
+    }
+
+    /// Register the Polars `frame` as a table called `name`, replacing any table of that name
+    #[cfg(feature = "polars-engine")]
+    pub async fn register_lazy_frame(
+        &self,
+        name: &str,
+        frame: crate::polars::prelude::LazyFrame,
+    ) -> anyhow::Result<()> {
+        self.engine
+            .register_lazy_frame(name, frame)
+            .await
+            .with_context(|| format!("Failed to register {}", name))
+    }
+
+    /// Register `provider` as a table called `name`, replacing any table of that name. Only a
+    /// DataFusion session can, see [`EngineInterface::register_table_provider`].
+    pub async fn register_table_provider(
+        &self,
+        name: &str,
+        provider: Arc<dyn TableProvider>,
+    ) -> anyhow::Result<()> {
+        self.engine
+            .register_table_provider(name, provider)
+            .await
+            .with_context(|| format!("Failed to register {}", name))
+    }
+
+    /// The tables registered, sorted by name
+    pub async fn tables(&self) -> anyhow::Result<Vec<TableInfo>> {
+        self.engine.tables().await
+    }
+
+    /// Run `query`, returning the rows of its last statement once the statements before it have
+    /// run
+    pub async fn sql(&self, query: &str) -> anyhow::Result<ResultSet> {
+        let mut statements = self.execute(query).await?;
+        let Some(mut last) = statements.pop() else {
+            anyhow::bail!("No statements to run");
+        };
+        for mut execution in statements {
+            while let Some(batch) = execution.stream.next().await {
+                batch?;
+            }
+        }
+        let mut batches = Vec::new();
+        while let Some(batch) = last.stream.next().await {
+            batches.push(batch?);
+        }
+        Ok(ResultSet {
+            schema: last.schema,
+            batches,
+        })
+    }
+
+    /// Execute each statement of `query` in turn, returning their executions with rows yet to be
+    /// read
+    pub async fn execute(&self, query: &str) -> anyhow::Result<Vec<QueryExecution>> {
+        let sql = crate::prql::to_sql(query, self.language, self.engine.kind())?;
+        self.engine.execute(&sql).await
+    }
+
+    /// [`execute`](Session::execute) `query`, cancelling each statement still running after
+    /// `timeout` in place of the engine's own timeout
+    pub async fn execute_with_timeout(
+        &self,
+        query: &str,
+        timeout: Option<Duration>,
+    ) -> anyhow::Result<Vec<QueryExecution>> {
+        let sql = crate::prql::to_sql(query, self.language, self.engine.kind())?;
+        self.engine.execute_with_timeout(&sql, timeout).await
+    }
+
+    /// Find what each statement of `query` reads, without running it: the tables its relations
+    /// resolve to, the files those are loaded from, and the columns it projects and filters on
+    pub async fn references(&self, query: &str) -> anyhow::Result<Vec<StatementReferences>> {
+        let sql = crate::prql::to_sql(query, self.language, self.engine.kind())?;
+        self.engine.references(&sql).await
+    }
+
+    /// Prepare the single statement `query`, its parameters written `$1`, `$2`, ... or `?`, for
+    /// [`Session::execute_prepared`] with values bound to them
+    pub async fn prepare(&self, query: &str) -> anyhow::Result<PreparedStatement> {
+        let sql = crate::prql::to_sql(query, self.language, self.engine.kind())?;
+        self.engine.prepare(&sql).await
+    }
+
+    /// Execute `statement`, prepared by this session, with the values bound to its parameters
+    pub async fn execute_prepared(
+        &self,
+        statement: &PreparedStatement,
+    ) -> anyhow::Result<QueryExecution> {
+        statement.execute(self.engine.as_ref()).await
+    }
+}
+
+impl std::fmt::Debug for Session {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Session")
+            .field("engine", &self.engine.kind())
+            .field("language", &self.language)
+            .finish()
+    }
+}
@@ -0,0 +1,286 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::observer::{ExecutionObserver, Observed, Observers};
+use crate::rewrite::{Rewriters, RowLimit, StatementRewriter};
+use crate::{BatchSizing, Engine, EngineInterface, PathPolicy};
+
+/// Receives warnings that don't fail the query they arise in, e.g. a file named in it that
+/// couldn't be loaded
+pub type DiagnosticSink = Arc<dyn Fn(&str) + Send + Sync>;
+
+/// How Polars reads the Parquet files queries name, and hands its results over.
+///
+/// Results are written by Polars as an Arrow IPC stream through a pipe, read from it as record
+/// batches on another thread, and queued to be streamed out. Polars waits while the pipe is full,
+/// and reading while the queue is, so results are produced no faster than they're consumed.
+#[derive(Clone, Copy, Debug)]
+pub struct PolarsOptions {
+    /// Read files a row group at a time, trading speed for memory
+    pub low_memory: bool,
+    /// Gather each table into contiguous memory once read
+    pub rechunk: bool,
+    /// Bytes the pipe results are written through holds
+    pub bridge_buffer_bytes: usize,
+    /// Record batches read from the pipe which may be queued ahead of being streamed out, at
+    /// least one
+    pub bridge_batches: usize,
+}
+
+impl Default for PolarsOptions {
+    fn default() -> PolarsOptions {
+        PolarsOptions {
+            low_memory: false,
+            rechunk: false,
+            bridge_buffer_bytes: 1024 * 1024,
+            bridge_batches: 16,
+        }
+    }
+}
+
+/// Configuration of an engine to be built, see [`Engine::builder`].
+///
+/// Options an engine has no equivalent of are ignored by it: Polars takes neither a memory limit
+/// nor a thread count, its thread pool being shared by the whole process (see
+/// `POLARS_MAX_THREADS`).
+#[derive(Clone)]
+pub struct EngineBuilder {
+    pub(crate) engine: Engine,
+    pub(crate) memory_limit: Option<usize>,
+    pub(crate) threads: Option<usize>,
+    pub(crate) duckdb_database: Option<PathBuf>,
+    pub(crate) datafusion_options: Vec<(String, String)>,
+    pub(crate) polars_options: PolarsOptions,
+    pub(crate) diagnostics: Option<DiagnosticSink>,
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) row_limit: Option<usize>,
+    pub(crate) batching: Option<BatchSizing>,
+    pub(crate) path_policy: PathPolicy,
+    pub(crate) plan_cache: usize,
+    pub(crate) footer_cache: usize,
+    pub(crate) load_concurrency: usize,
+    pub(crate) mmap_parquet: bool,
+    pub(crate) observers: Observers,
+    pub(crate) rewriters: Rewriters,
+}
+
+impl EngineBuilder {
+    pub fn new(engine: Engine) -> EngineBuilder {
+        EngineBuilder {
+            engine,
+            memory_limit: None,
+            threads: None,
+            duckdb_database: None,
+            datafusion_options: Vec::new(),
+            polars_options: PolarsOptions::default(),
+            diagnostics: None,
+            timeout: None,
+            row_limit: None,
+            batching: None,
+            path_policy: PathPolicy::default(),
+            plan_cache: 128,
+            footer_cache: 1024,
+            load_concurrency: 8,
+            mmap_parquet: false,
+            observers: Vec::new(),
+            rewriters: Vec::new(),
+        }
+    }
+
+    /// Most bytes of memory queries may use, beyond which they spill to disk or fail
+    pub fn memory_limit(mut self, bytes: usize) -> EngineBuilder {
+        self.memory_limit = Some(bytes);
+        self
+    }
+
+    /// Most threads a query runs on at once
+    pub fn threads(mut self, threads: usize) -> EngineBuilder {
+        self.threads = Some(threads);
+        self
+    }
+
+    /// Keep DuckDB's tables in the database file at `path` rather than in memory
+    pub fn duckdb_database(mut self, path: impl Into<PathBuf>) -> EngineBuilder {
+        self.duckdb_database = Some(path.into());
+        self
+    }
+
+    /// Set the DataFusion configuration option `key`, e.g. `datafusion.execution.batch_size`
+    pub fn datafusion_option(
+        mut self,
+        key: impl Into<String>,
+        value: impl Into<String>,
+    ) -> EngineBuilder {
+        self.datafusion_options.push((key.into(), value.into()));
+        self
+    }
+
+    pub fn polars_options(mut self, options: PolarsOptions) -> EngineBuilder {
+        self.polars_options = options;
+        self
+    }
+
+    /// Pass warnings to `sink` as well as logging them
+    pub fn diagnostics(mut self, sink: impl Fn(&str) + Send + Sync + 'static) -> EngineBuilder {
+        self.diagnostics = Some(Arc::new(sink));
+        self
+    }
+
+    /// Cancel statements still running `timeout` after being executed, failing them with
+    /// [`CallistoError::Timeout`](crate::CallistoError::Timeout). Each statement of a query is
+    /// timed on its own, and a single call can set its own timeout with
+    /// [`EngineInterface::execute_with_timeout`].
+    ///
+    /// DuckDB is interrupted where it is. Polars can't be, so its statements are given up on,
+    /// left to finish on a thread of their own with their results discarded.
+    pub fn timeout(mut self, timeout: Duration) -> EngineBuilder {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Return at most `rows` rows from each statement, protecting interactive sessions and servers
+    /// from dumping whole tables by accident. Queries are given a LIMIT just past it where they
+    /// have no lower one, and results which still go past it are cut short, marked
+    /// [`truncated`](crate::ExecutionStats::truncated) in their stats.
+    pub fn row_limit(mut self, rows: usize) -> EngineBuilder {
+        self.row_limit = Some(rows);
+        self
+    }
+
+    /// Re-batch every statement's results to the sizes of `sizing`, rather than passing them on
+    /// in whatever batches the engine produces, so that they're rendered or sent steadily
+    pub fn batch_sizing(mut self, sizing: BatchSizing) -> EngineBuilder {
+        self.batching = Some(sizing);
+        self
+    }
+
+    /// Load tables only from the files `policy` allows, failing statements naming any others
+    /// with [`CallistoError::PathNotAllowed`](crate::CallistoError::PathNotAllowed)
+    pub fn path_policy(mut self, policy: PathPolicy) -> EngineBuilder {
+        self.path_policy = policy;
+        self
+    }
+
+    /// Keep the logical plans of up to `plans` queries, 128 by default, so that running one again,
+    /// e.g. over fresh data, skips planning it. They're dropped whenever a table is registered or
+    /// a statement other than a query is run. Only DataFusion's plans are kept; zero keeps none.
+    pub fn plan_cache(mut self, plans: usize) -> EngineBuilder {
+        self.plan_cache = plans;
+        self
+    }
+
+    /// Keep the footers, schemas and row group statistics, of up to `files` Parquet files, 1024 by
+    /// default, so that queries over the same files don't fetch and parse them again. Each is
+    /// kept with the modification time, ETag, and size of its file, and read again once those
+    /// change. DuckDB keeps any number in its object cache instead, and Polars none. Zero keeps
+    /// none.
+    pub fn footer_cache(mut self, files: usize) -> EngineBuilder {
+        self.footer_cache = files;
+        self
+    }
+
+    /// Load up to `files` of the new files a statement names at once, 8 by default, so that
+    /// reading their schemas from a remote object store waits on one request's latency rather
+    /// than each's in turn
+    pub fn load_concurrency(mut self, files: usize) -> EngineBuilder {
+        self.load_concurrency = files;
+        self
+    }
+
+    /// Read local Parquet files from memory maps of them, the 256 most recently read kept mapped,
+    /// so that scanning a large file again reads it from the page cache without a system call or
+    /// copy for each read. Only DataFusion takes this; Polars maps local files already, and DuckDB
+    /// reads them once, as they're loaded. Files mustn't be written in place while mapped.
+    pub fn mmap_parquet(mut self, enabled: bool) -> EngineBuilder {
+        self.mmap_parquet = enabled;
+        self
+    }
+
+    /// Tell `observer` of each statement, batch, and table registration as it happens
+    pub fn observer(mut self, observer: impl ExecutionObserver + 'static) -> EngineBuilder {
+        self.observers.push(Arc::new(observer));
+        self
+    }
+
+    /// Rewrite each statement with `rewriter` before running it, after the rewriters added before
+    /// it. Statements are rewritten as they're executed or prepared.
+    pub fn rewriter(mut self, rewriter: impl StatementRewriter + 'static) -> EngineBuilder {
+        self.rewriters.push(Arc::new(rewriter));
+        self
+    }
+
+    /// Build `engine` instead, configured the same, e.g. to switch a session's engine keeping its
+    /// row limit and path policy
+    pub fn engine(mut self, engine: Engine) -> EngineBuilder {
+        self.engine = engine;
+        self
+    }
+
+    /// Which engine is to be built
+    pub fn kind(&self) -> Engine {
+        self.engine
+    }
+
+    pub fn build(mut self) -> anyhow::Result<Box<dyn EngineInterface>> {
+        // Runs after the rewriters added, so none can lift the limit
+        if let Some(rows) = self.row_limit {
+            self.rewriters.push(Arc::new(RowLimit(rows)));
+        }
+        let engine: Box<dyn EngineInterface> = match self.engine {
+            #[cfg(feature = "polars-engine")]
+            Engine::Polars => Box::new(crate::polars_engine::build(&self)),
+            #[cfg(feature = "duckdb-engine")]
+            Engine::DuckDB => Box::new(crate::duckdb_engine::build(&self)?),
+            #[cfg(feature = "datafusion-engine")]
+            Engine::DataFusion => Box::new(crate::datafusion_engine::build(&self)?),
+            #[allow(unreachable_patterns)]
+            engine => anyhow::bail!(
+                "Built without the {} engine, see the `{}-engine` feature",
+                engine.name(),
+                engine.name()
+            ),
+        };
+        Ok(if self.observers.is_empty() {
+            engine
+        } else {
+            Box::new(Observed {
+                engine,
+                observers: self.observers,
+            })
+        })
+    }
+}
+
+impl std::fmt::Debug for EngineBuilder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EngineBuilder")
+            .field("engine", &self.engine)
+            .field("memory_limit", &self.memory_limit)
+            .field("threads", &self.threads)
+            .field("duckdb_database", &self.duckdb_database)
+            .field("datafusion_options", &self.datafusion_options)
+            .field("polars_options", &self.polars_options)
+            .field("diagnostics", &self.diagnostics.is_some())
+            .field("timeout", &self.timeout)
+            .field("row_limit", &self.row_limit)
+            .field("batching", &self.batching)
+            .field("path_policy", &self.path_policy)
+            .field("plan_cache", &self.plan_cache)
+            .field("footer_cache", &self.footer_cache)
+            .field("load_concurrency", &self.load_concurrency)
+            .field("mmap_parquet", &self.mmap_parquet)
+            .field("observers", &self.observers.len())
+            .field("rewriters", &self.rewriters.len())
+            .finish()
+    }
+}
+
+/// Log `message` as a warning, passing it to `diagnostics` too if there is a sink
+#[cfg(any(feature = "polars-engine", feature = "datafusion-engine"))]
+pub(crate) fn warn(diagnostics: &Option<DiagnosticSink>, message: String) {
+    tracing::warn!("{}", message);
+    if let Some(sink) = diagnostics {
+        sink(&message);
+    }
+}
@@ -0,0 +1,60 @@
+use datafusion::datasource::listing::ListingTableUrl;
+use futures::stream::TryStreamExt as _;
+use sqlparser::ast;
+
+use crate::StatementReferences;
+
+/// What executing a statement would do, found without reading its data; see
+/// [`EngineInterface::dry_run`](crate::EngineInterface::dry_run)
+#[derive(Clone, Debug)]
+pub struct DryRun {
+    pub statement: ast::Statement,
+    /// The plan the engine would run the statement with, as the engine describes it, if it could
+    /// plan it without running it
+    pub plan: Option<String>,
+    /// The files the statement would read, those of a glob or directory each listed
+    pub files: Vec<ScannedFile>,
+}
+
+/// A file a statement would read
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScannedFile {
+    pub path: String,
+    /// Size of the whole file, which is the most the statement could read of it
+    pub bytes: u64,
+}
+
+impl DryRun {
+    /// Bytes the statement would read at most, were it to read every file in full
+    pub fn bytes(&self) -> u64 {
+        self.files.iter().map(|file| file.bytes).sum()
+    }
+}
+
+/// The files the relations of `references` are loaded from, or would be, listed from their paths,
+/// globs, or directories
+pub(crate) async fn files(references: &StatementReferences) -> anyhow::Result<Vec<ScannedFile>> {
+    use anyhow::Context as _;
+
+    let state = datafusion::execution::context::SessionContext::new().state();
+    let mut files = Vec::new();
+    for source in references
+        .relations
+        .values()
+        .filter_map(|relation| relation.source.as_ref())
+    {
+        let url = ListingTableUrl::parse(source)?;
+        let store = state.runtime_env().object_store(url.object_store())?;
+        let listed = url
+            .list_all_files(&state, store.as_ref(), "")
+            .await?
+            .try_collect::<Vec<_>>()
+            .await
+            .with_context(|| format!("Failed to list {}", source))?;
+        files.extend(listed.into_iter().map(|meta| ScannedFile {
+            path: meta.location.to_string(),
+            bytes: meta.size as u64,
+        }));
+    }
+    Ok(files)
+}
@@ -0,0 +1,35 @@
+use std::time::Duration;
+
+/// Failures a caller may want to tell apart from the rest, found in an `anyhow::Error` by
+/// [`CallistoError::of`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CallistoError {
+    /// A statement was still running once the timeout it was given had passed, and was cancelled
+    Timeout(Duration),
+    /// A file was to be loaded from where the engine's path policy doesn't allow, see
+    /// [`PathPolicy`](crate::PathPolicy)
+    PathNotAllowed { path: String, reason: String },
+}
+
+impl CallistoError {
+    /// The `CallistoError` that caused `error`, if one did
+    pub fn of(error: &anyhow::Error) -> Option<&CallistoError> {
+        error
+            .chain()
+            .find_map(|cause| cause.downcast_ref::<CallistoError>())
+    }
+}
+
+impl std::fmt::Display for CallistoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CallistoError::Timeout(timeout) => write!(f, "Timed out after {:?}", timeout),
+            CallistoError::PathNotAllowed { path, reason } => {
+                write!(f, "{} may not be read, as {}", path, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CallistoError {}
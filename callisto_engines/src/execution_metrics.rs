@@ -0,0 +1,414 @@
+use core::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures::Stream;
+
+use arrow::record_batch::RecordBatch;
+use datafusion::physical_plan::{ExecutionPlan, SendableRecordBatchStream};
+use web_time::Instant;
+
+use crate::{lineage, rebatch, telemetry, BatchSizing, Engine, FileRead, PlanNode};
+
+/// Timing and volume measurements for a single executed statement.
+///
+/// The up-front phases (parsing, table loading, and execution up to the point a stream is
+/// available) are filled in by the engine, while rows returned, streaming time, bytes scanned,
+/// and the plan's per-operator metrics are recorded as the statement's result stream is consumed.
+#[derive(Debug, Default)]
+pub struct ExecutionMetrics {
+    /// Time spent parsing the query text the statement was part of
+    pub parse_time: Duration,
+    /// Time spent resolving and registering tables referenced by the statement
+    pub load_time: Duration,
+    /// Time spent executing the statement before its result stream was returned
+    pub execution_time: Duration,
+    rows_returned: AtomicUsize,
+    stream_time: Mutex<Option<Duration>>,
+    bytes_scanned: Mutex<Option<usize>>,
+    reads: Mutex<Option<Vec<FileRead>>>,
+    plan: Mutex<Option<PlanNode>>,
+    /// Whether the result stream was dropped before being exhausted
+    abandoned: AtomicBool,
+    /// Whether the result stream was cut short at the engine's row limit
+    truncated: AtomicBool,
+    /// Notified once the result stream is exhausted or dropped
+    ended: tokio::sync::Notify,
+}
+
+/// The final measurements of a statement, once its result stream has ended
+#[derive(Clone, Debug)]
+pub struct ExecutionStats {
+    pub parse_time: Duration,
+    pub load_time: Duration,
+    pub execution_time: Duration,
+    /// Time from the result stream being returned to it being exhausted, or `None` if it was
+    /// dropped first
+    pub stream_time: Option<Duration>,
+    /// Rows read from the result stream
+    pub rows: usize,
+    /// Bytes read from storage, if the engine reports it
+    pub bytes_scanned: Option<usize>,
+    /// Whether the results went past the engine's row limit and were cut short at it, see
+    /// [`EngineBuilder::row_limit`](crate::EngineBuilder::row_limit)
+    pub truncated: bool,
+}
+
+impl ExecutionStats {
+    /// Whether the result stream was read to its end, rather than dropped part way
+    pub fn exhausted(&self) -> bool {
+        self.stream_time.is_some()
+    }
+
+    /// Time from the statement being parsed to its last row being read, if it was
+    pub fn total_time(&self) -> Option<Duration> {
+        self.stream_time
+            .map(|stream_time| self.parse_time + self.load_time + self.execution_time + stream_time)
+    }
+}
+
+impl ExecutionMetrics {
+    /// Number of rows yielded by the result stream so far
+    pub fn rows_returned(&self) -> usize {
+        self.rows_returned.load(Ordering::Relaxed)
+    }
+
+    /// Time from the result stream being returned to it being exhausted, if it has been
+    pub fn stream_time(&self) -> Option<Duration> {
+        *self.stream_time.lock().unwrap()
+    }
+
+    /// Whether the result stream has been cut short at the engine's row limit
+    pub fn truncated(&self) -> bool {
+        self.truncated.load(Ordering::Relaxed)
+    }
+
+    /// Bytes read from storage, if the engine reports it and the stream has been exhausted
+    pub fn bytes_scanned(&self) -> Option<usize> {
+        *self.bytes_scanned.lock().unwrap()
+    }
+
+    /// The ranges of files the statement's scans read, if the engine reports them and the stream
+    /// has been exhausted
+    pub fn reads(&self) -> Option<Vec<FileRead>> {
+        self.reads.lock().unwrap().clone()
+    }
+
+    /// The plan the statement ran, with what each operator measured, if the engine reports it and
+    /// the stream has been exhausted
+    pub fn plan(&self) -> Option<PlanNode> {
+        self.plan.lock().unwrap().clone()
+    }
+
+    /// The final measurements, if the result stream has been exhausted or dropped
+    pub fn stats(&self) -> Option<ExecutionStats> {
+        let stream_time = self.stream_time();
+        if stream_time.is_none() && !self.abandoned.load(Ordering::Relaxed) {
+            return None;
+        }
+        Some(ExecutionStats {
+            parse_time: self.parse_time,
+            load_time: self.load_time,
+            execution_time: self.execution_time,
+            stream_time,
+            rows: self.rows_returned(),
+            bytes_scanned: self.bytes_scanned(),
+            truncated: self.truncated(),
+        })
+    }
+
+    /// The final measurements, once the result stream has been exhausted or dropped
+    pub async fn finished(&self) -> ExecutionStats {
+        loop {
+            // Registered before checking, so an ending in between isn't missed
+            let ended = self.ended.notified();
+            if let Some(stats) = self.stats() {
+                return stats;
+            }
+            ended.await;
+        }
+    }
+}
+
+impl std::fmt::Display for ExecutionMetrics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "parse {:?}, load {:?}, execution {:?}",
+            self.parse_time, self.load_time, self.execution_time
+        )?;
+        if let Some(stream_time) = self.stream_time() {
+            write!(f, ", streaming {:?}", stream_time)?;
+        }
+        write!(f, ", {} rows", self.rows_returned())?;
+        if self.truncated() {
+            write!(f, " (truncated)")?;
+        }
+        if let Some(bytes) = self.bytes_scanned() {
+            write!(f, ", {} bytes scanned", bytes)?;
+        }
+        Ok(())
+    }
+}
+
+/// Wrap `stream`, from a statement on `engine`, so that consuming it records into `metrics`.
+///
+/// When a physical `plan` is provided, its `bytes_scanned` metrics are summed, and its operators'
+/// metrics and the ranges of files it scanned kept, once the stream is exhausted. Given a
+/// `row_limit`, the stream ends once that many rows have been read, marked truncated if there were
+/// more. Given a `batching`, its batches are first re-batched to those sizes.
+pub(crate) fn metered(
+    engine: Engine,
+    stream: SendableRecordBatchStream,
+    metrics: Arc<ExecutionMetrics>,
+    plan: Option<Arc<dyn ExecutionPlan>>,
+    row_limit: Option<usize>,
+    batching: Option<BatchSizing>,
+) -> SendableRecordBatchStream {
+    let stream = match batching {
+        Some(sizing) => rebatch::rebatched(stream, sizing),
+        None => stream,
+    };
+    Box::pin(MeteredStream {
+        engine,
+        stream,
+        metrics,
+        plan,
+        row_limit,
+        started: Instant::now(),
+        span: tracing::info_span!("stream", rows = tracing::field::Empty),
+    })
+}
+
+fn bytes_scanned(plan: &dyn ExecutionPlan) -> usize {
+    let own = plan
+        .metrics()
+        .and_then(|metrics| metrics.sum_by_name("bytes_scanned"))
+        .map(|value| value.as_usize())
+        .unwrap_or(0);
+    own + plan
+        .children()
+        .iter()
+        .map(|child| bytes_scanned(child.as_ref()))
+        .sum::<usize>()
+}
+
+#[pin_project::pin_project(PinnedDrop)]
+struct MeteredStream {
+    engine: Engine,
+    #[pin]
+    stream: SendableRecordBatchStream,
+    metrics: Arc<ExecutionMetrics>,
+    plan: Option<Arc<dyn ExecutionPlan>>,
+    row_limit: Option<usize>,
+    started: Instant,
+    span: tracing::Span,
+}
+
+impl datafusion::physical_plan::RecordBatchStream for MeteredStream {
+    fn schema(&self) -> Arc<arrow::datatypes::Schema> {
+        self.stream.schema()
+    }
+}
+
+impl Stream for MeteredStream {
+    type Item = Result<RecordBatch, datafusion::common::DataFusionError>;
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut futures::task::Context<'_>,
+    ) -> futures::task::Poll<Option<Self::Item>> {
+        let this = self.project();
+        let _entered = this.span.enter();
+        let mut poll = if this.metrics.truncated() {
+            futures::task::Poll::Ready(None)
+        } else {
+            this.stream.poll_next(cx)
+        };
+        if let (Some(limit), futures::task::Poll::Ready(Some(Ok(batch)))) = (*this.row_limit, &poll)
+        {
+            let remaining = limit.saturating_sub(this.metrics.rows_returned());
+            if batch.num_rows() > remaining {
+                tracing::debug!(limit, "Truncated results at the row limit");
+                this.metrics.truncated.store(true, Ordering::Relaxed);
+                // Nothing more is read, the stream ending with the rows up to the limit
+                poll = futures::task::Poll::Ready(
+                    (remaining > 0).then(|| Ok(batch.slice(0, remaining))),
+                );
+            }
+        }
+        match &poll {
+            futures::task::Poll::Ready(Some(Ok(batch))) => {
+                this.metrics
+                    .rows_returned
+                    .fetch_add(batch.num_rows(), Ordering::Relaxed);
+                telemetry::rows(*this.engine, batch.num_rows());
+            }
+            futures::task::Poll::Ready(None) => {
+                let mut stream_time = this.metrics.stream_time.lock().unwrap();
+                if stream_time.is_none() {
+                    tracing::debug!(rows = this.metrics.rows_returned(), "Stream exhausted");
+                    this.span.record("rows", this.metrics.rows_returned());
+                    *stream_time = Some(this.started.elapsed());
+                    if let Some(plan) = this.plan {
+                        *this.metrics.bytes_scanned.lock().unwrap() =
+                            Some(bytes_scanned(plan.as_ref()));
+                        *this.metrics.reads.lock().unwrap() =
+                            Some(lineage::file_reads(plan.as_ref()));
+                        *this.metrics.plan.lock().unwrap() =
+                            Some(PlanNode::from_execution_plan(plan.as_ref()));
+                    }
+                    drop(stream_time);
+                    telemetry::finished(*this.engine, &this.metrics.stats().unwrap());
+                    this.metrics.ended.notify_waiters();
+                }
+            }
+            _ => {}
+        }
+        poll
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.stream.size_hint()
+    }
+}
+
+#[pin_project::pinned_drop]
+impl PinnedDrop for MeteredStream {
+    fn drop(self: Pin<&mut Self>) {
+        if self.metrics.stream_time().is_none() {
+            self.metrics.abandoned.store(true, Ordering::Relaxed);
+            telemetry::finished(self.engine, &self.metrics.stats().unwrap());
+            self.metrics.ended.notify_waiters();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use arrow::array::{AsArray as _, Int64Array};
+    use arrow::datatypes::{DataType, Field, Int64Type, Schema};
+    use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+    use futures::TryStreamExt as _;
+
+    use super::*;
+
+    /// A stream of batches of the given sizes, numbering their rows from 0
+    fn batches(sizes: &[usize]) -> SendableRecordBatchStream {
+        let schema = Arc::new(Schema::new(vec![Field::new("n", DataType::Int64, false)]));
+        let mut start = 0;
+        let batches = sizes
+            .iter()
+            .map(|&size| {
+                let values = (start..start + size as i64).collect::<Vec<_>>();
+                start += size as i64;
+                let column = Arc::new(Int64Array::from(values));
+                RecordBatch::try_new(schema.clone(), vec![column]).unwrap()
+            })
+            .collect::<Vec<_>>();
+        let batches = futures::stream::iter(batches.into_iter().map(Ok));
+        Box::pin(RecordBatchStreamAdapter::new(schema, batches))
+    }
+
+    /// The sizes of the batches read from `sizes` limited to `row_limit`, with the metrics
+    /// recorded, checking the rows read are the first of the stream's
+    async fn read(
+        sizes: &[usize],
+        row_limit: Option<usize>,
+        batching: Option<BatchSizing>,
+    ) -> (Vec<usize>, Arc<ExecutionMetrics>) {
+        let metrics = Arc::new(ExecutionMetrics::default());
+        let stream = metered(
+            Engine::DataFusion,
+            batches(sizes),
+            metrics.clone(),
+            None,
+            row_limit,
+            batching,
+        );
+        let batches = stream.try_collect::<Vec<_>>().await.unwrap();
+        let values = batches
+            .iter()
+            .flat_map(|batch| {
+                batch
+                    .column(0)
+                    .as_primitive::<Int64Type>()
+                    .values()
+                    .to_vec()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(values, (0..values.len() as i64).collect::<Vec<_>>());
+        assert_eq!(metrics.rows_returned(), values.len());
+        let sizes = batches.iter().map(RecordBatch::num_rows).collect();
+        (sizes, metrics)
+    }
+
+    #[tokio::test]
+    async fn streams_are_read_whole_without_a_limit() {
+        let (sizes, metrics) = read(&[3, 3, 3], None, None).await;
+        assert_eq!(sizes, [3, 3, 3]);
+        assert!(!metrics.truncated());
+        assert!(metrics.stream_time().is_some());
+    }
+
+    #[tokio::test]
+    async fn limits_within_a_batch_slice_it() {
+        let (sizes, metrics) = read(&[3, 3, 3], Some(4), None).await;
+        assert_eq!(sizes, [3, 1]);
+        assert!(metrics.truncated());
+
+        let (sizes, metrics) = read(&[3, 3, 3], Some(2), None).await;
+        assert_eq!(sizes, [2]);
+        assert!(metrics.truncated());
+    }
+
+    #[tokio::test]
+    async fn limits_at_a_batch_boundary_end_before_the_next() {
+        let (sizes, metrics) = read(&[3, 3, 3], Some(6), None).await;
+        assert_eq!(sizes, [3, 3]);
+        // The batch after the limit shows there were more rows than it
+        assert!(metrics.truncated());
+
+        let (sizes, metrics) = read(&[3, 3, 3], Some(3), None).await;
+        assert_eq!(sizes, [3]);
+        assert!(metrics.truncated());
+    }
+
+    #[tokio::test]
+    async fn limits_of_every_row_are_not_truncation() {
+        let (sizes, metrics) = read(&[3, 3, 3], Some(9), None).await;
+        assert_eq!(sizes, [3, 3, 3]);
+        assert!(!metrics.truncated());
+
+        let (sizes, metrics) = read(&[3, 3, 3], Some(100), None).await;
+        assert_eq!(sizes, [3, 3, 3]);
+        assert!(!metrics.truncated());
+    }
+
+    #[tokio::test]
+    async fn limits_of_nothing_read_nothing() {
+        let (sizes, metrics) = read(&[3], Some(0), None).await;
+        assert!(sizes.is_empty());
+        assert!(metrics.truncated());
+
+        let (sizes, metrics) = read(&[], Some(0), None).await;
+        assert!(sizes.is_empty());
+        assert!(!metrics.truncated());
+    }
+
+    #[tokio::test]
+    async fn limits_apply_to_rebatched_streams() {
+        let batching = BatchSizing {
+            rows: 4,
+            ..BatchSizing::default()
+        };
+        let (sizes, metrics) = read(&[3, 3, 3], Some(6), Some(batching)).await;
+        assert_eq!(sizes, [4, 2]);
+        assert!(metrics.truncated());
+
+        let (sizes, metrics) = read(&[3, 3, 3], Some(8), Some(batching)).await;
+        assert_eq!(sizes, [4, 4]);
+        assert!(metrics.truncated());
+    }
+}
@@ -0,0 +1,177 @@
+use std::ops::Range;
+use std::sync::Arc;
+
+use bytes::Bytes;
+use datafusion::common::tree_node::{Transformed, TransformedResult as _, TreeNode as _};
+use datafusion::config::ConfigOptions;
+use datafusion::datasource::object_store::ObjectStoreUrl;
+use datafusion::datasource::physical_plan::{
+    DefaultParquetFileReaderFactory, FileMeta, ParquetExec, ParquetFileReaderFactory,
+};
+use datafusion::error::Result;
+use datafusion::execution::runtime_env::RuntimeEnv;
+use datafusion::parquet::arrow::async_reader::AsyncFileReader;
+use datafusion::parquet::file::metadata::ParquetMetaData;
+use datafusion::physical_optimizer::PhysicalOptimizerRule;
+use datafusion::physical_plan::metrics::ExecutionPlanMetricsSet;
+use datafusion::physical_plan::ExecutionPlan;
+use futures::future::{BoxFuture, FutureExt as _};
+
+use crate::plan_cache::PlanCache;
+
+/// Parquet footers, the schema and row group statistics of each file, by the object store, path,
+/// modification time, ETag, and size of the file they were read from, so that one changed since is
+/// read again, and files of the same path in different stores aren't taken for one another
+pub(crate) type Footers = PlanCache<Arc<ParquetMetaData>>;
+
+/// Makes the Parquet scans of each plan read files' footers through `footers`, so that queries
+/// over the same files, large remote ones especially, fetch and parse each footer once, and read
+/// local files from their maps in `mapped` if there are any
+pub(crate) struct ParquetReaders {
+    /// What the scans' object stores are found in
+    pub(crate) runtime: Arc<RuntimeEnv>,
+    pub(crate) footers: Arc<Footers>,
+    #[cfg(feature = "native")]
+    pub(crate) mapped: Option<Arc<crate::mmap::MappedFiles>>,
+}
+
+impl ParquetReaders {
+    /// How the files of the object store at `url` are read, but for their footers
+    fn readers(&self, url: &ObjectStoreUrl) -> Result<Arc<dyn ParquetFileReaderFactory>> {
+        #[cfg(feature = "native")]
+        if let Some(mapped) = &self.mapped {
+            if *url == ObjectStoreUrl::local_filesystem() {
+                return Ok(Arc::new(crate::mmap::MmapReaders {
+                    files: mapped.clone(),
+                }));
+            }
+        }
+        let store = self.runtime.object_store(url)?;
+        Ok(Arc::new(DefaultParquetFileReaderFactory::new(store)))
+    }
+}
+
+impl PhysicalOptimizerRule for ParquetReaders {
+    fn optimize(
+        &self,
+        plan: Arc<dyn ExecutionPlan>,
+        _config: &ConfigOptions,
+    ) -> Result<Arc<dyn ExecutionPlan>> {
+        plan.transform_up(|plan| {
+            let Some(scan) = plan.as_any().downcast_ref::<ParquetExec>() else {
+                return Ok(Transformed::no(plan));
+            };
+            // Scans given their own way of reading files by their table are left to it
+            if scan.parquet_file_reader_factory().is_some() {
+                return Ok(Transformed::no(plan));
+            }
+            let store = &scan.base_config().object_store_url;
+            let readers = FooterCachingReaders {
+                readers: self.readers(store)?,
+                store: store.clone(),
+                footers: self.footers.clone(),
+            };
+            let scan = scan
+                .clone()
+                .with_parquet_file_reader_factory(Arc::new(readers));
+            Ok(Transformed::yes(Arc::new(scan) as Arc<dyn ExecutionPlan>))
+        })
+        .data()
+    }
+
+    fn name(&self) -> &str {
+        "parquet_readers"
+    }
+
+    fn schema_check(&self) -> bool {
+        true
+    }
+}
+
+impl std::fmt::Debug for ParquetReaders {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ParquetReaders").finish_non_exhaustive()
+    }
+}
+
+/// Readers of Parquet files, but for their footers being read through `footers`
+struct FooterCachingReaders {
+    readers: Arc<dyn ParquetFileReaderFactory>,
+    /// The object store the files are read from
+    store: ObjectStoreUrl,
+    footers: Arc<Footers>,
+}
+
+impl ParquetFileReaderFactory for FooterCachingReaders {
+    fn create_reader(
+        &self,
+        partition_index: usize,
+        file_meta: FileMeta,
+        metadata_size_hint: Option<usize>,
+        metrics: &ExecutionPlanMetricsSet,
+    ) -> Result<Box<dyn AsyncFileReader + Send>> {
+        let file = &file_meta.object_meta;
+        let key = format!(
+            "{}{}@{}:{}:{}",
+            self.store.as_str(),
+            file.location,
+            file.last_modified.to_rfc3339(),
+            file.e_tag.as_deref().unwrap_or_default(),
+            file.size
+        );
+        let reader =
+            self.readers
+                .create_reader(partition_index, file_meta, metadata_size_hint, metrics)?;
+        Ok(Box::new(FooterCachingReader {
+            reader,
+            key,
+            footers: self.footers.clone(),
+        }))
+    }
+}
+
+impl std::fmt::Debug for FooterCachingReaders {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FooterCachingReaders")
+            .field("readers", &self.readers)
+            .field("store", &self.store)
+            .finish_non_exhaustive()
+    }
+}
+
+struct FooterCachingReader {
+    reader: Box<dyn AsyncFileReader + Send>,
+    /// The file's entry in `footers`
+    key: String,
+    footers: Arc<Footers>,
+}
+
+impl AsyncFileReader for FooterCachingReader {
+    fn get_bytes(
+        &mut self,
+        range: Range<usize>,
+    ) -> BoxFuture<'_, datafusion::parquet::errors::Result<Bytes>> {
+        self.reader.get_bytes(range)
+    }
+
+    fn get_byte_ranges(
+        &mut self,
+        ranges: Vec<Range<usize>>,
+    ) -> BoxFuture<'_, datafusion::parquet::errors::Result<Vec<Bytes>>> {
+        self.reader.get_byte_ranges(ranges)
+    }
+
+    fn get_metadata(
+        &mut self,
+    ) -> BoxFuture<'_, datafusion::parquet::errors::Result<Arc<ParquetMetaData>>> {
+        if let Some(footer) = self.footers.get(&self.key) {
+            return futures::future::ready(Ok(footer)).boxed();
+        }
+        async move {
+            let footer = self.reader.get_metadata().await?;
+            self.footers.insert(self.key.clone(), footer.clone());
+            Ok(footer)
+        }
+        .boxed()
+    }
+}
@@ -0,0 +1,132 @@
+use core::ops::ControlFlow;
+use std::sync::Arc;
+
+use arrow::array::{BooleanArray, StringArray};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use arrow::record_batch::RecordBatch;
+use sqlparser::ast;
+
+use crate::Engine;
+
+/// Name of the virtual table listing the engines, written `callisto_engines()`
+pub(crate) const ENGINES_TABLE: &str = "callisto_engines";
+
+/// What an engine is built on and how it's configured, see [`crate::EngineInterface::info`]
+#[derive(Clone, Debug)]
+pub struct EngineInfo {
+    pub engine: Engine,
+    /// Version of the library the engine runs queries with, e.g. DuckDB's
+    pub library_version: String,
+    /// Cargo features callisto-engines was built with
+    pub features: Vec<&'static str>,
+    /// Names and values of the engine's settings, whether set through the
+    /// [`EngineBuilder`](crate::EngineBuilder) or left at their defaults
+    pub settings: Vec<(String, String)>,
+}
+
+impl EngineInfo {
+    pub(crate) fn new(engine: Engine, settings: Vec<(String, String)>) -> EngineInfo {
+        EngineInfo {
+            engine,
+            library_version: engine.library_version().unwrap_or_default(),
+            features: features(),
+            settings,
+        }
+    }
+}
+
+/// The Cargo features callisto-engines was built with
+fn features() -> Vec<&'static str> {
+    [
+        ("datafusion-engine", cfg!(feature = "datafusion-engine")),
+        ("duckdb-engine", cfg!(feature = "duckdb-engine")),
+        ("native", cfg!(feature = "native")),
+        ("polars-engine", cfg!(feature = "polars-engine")),
+    ]
+    .into_iter()
+    .filter_map(|(feature, enabled)| enabled.then_some(feature))
+    .collect()
+}
+
+/// A setting's value as [`EngineInfo`] lists it, or `default` if it wasn't set
+pub(crate) fn setting<T: std::fmt::Debug>(name: &str, value: Option<T>) -> (String, String) {
+    let value = value.map_or_else(|| "default".to_string(), |value| format!("{:?}", value));
+    (name.to_string(), value)
+}
+
+/// Rewrite the relations `callisto_engines()` in `statement` as the table [`ENGINES_TABLE`],
+/// returning whether there were any
+pub(crate) fn resolve_engines_table(statement: &mut ast::Statement) -> bool {
+    struct Resolver(bool);
+
+    impl ast::VisitorMut for Resolver {
+        type Break = ();
+
+        fn pre_visit_table_factor(
+            &mut self,
+            table_factor: &mut ast::TableFactor,
+        ) -> ControlFlow<Self::Break> {
+            if let ast::TableFactor::Table { name, args, .. } = table_factor {
+                if name.0.len() == 1 && name.0[0].value == ENGINES_TABLE && args.is_some() {
+                    *args = None;
+                    self.0 = true;
+                }
+            }
+            ControlFlow::Continue(())
+        }
+    }
+
+    let mut resolver = Resolver(false);
+    let _ = ast::VisitMut::visit(statement, &mut resolver);
+    resolver.0
+}
+
+/// The rows of the virtual table [`ENGINES_TABLE`]: one per engine, whether it was built in, and
+/// whether it's `active`'s, with the version and settings of the active one
+pub(crate) fn engines_table(active: &EngineInfo) -> anyhow::Result<(SchemaRef, RecordBatch)> {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("engine", DataType::Utf8, false),
+        Field::new("available", DataType::Boolean, false),
+        Field::new("active", DataType::Boolean, false),
+        Field::new("library_version", DataType::Utf8, true),
+        Field::new("features", DataType::Utf8, false),
+        Field::new("settings", DataType::Utf8, true),
+    ]));
+    let settings = active
+        .settings
+        .iter()
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let is_active = |engine: &Engine| *engine == active.engine;
+    let batch = RecordBatch::try_new(
+        schema.clone(),
+        vec![
+            Arc::new(StringArray::from_iter_values(
+                Engine::ALL.iter().map(Engine::name),
+            )),
+            Arc::new(BooleanArray::from_iter(
+                Engine::ALL.iter().map(|engine| Some(engine.is_available())),
+            )),
+            Arc::new(BooleanArray::from_iter(
+                Engine::ALL.iter().map(|engine| Some(is_active(engine))),
+            )),
+            Arc::new(StringArray::from_iter(Engine::ALL.iter().map(|engine| {
+                if is_active(engine) {
+                    Some(active.library_version.clone())
+                } else {
+                    engine.library_version()
+                }
+            }))),
+            Arc::new(StringArray::from_iter_values(
+                Engine::ALL.iter().map(|_| active.features.join(", ")),
+            )),
+            Arc::new(StringArray::from_iter(
+                Engine::ALL
+                    .iter()
+                    .map(|engine| is_active(engine).then(|| settings.clone())),
+            )),
+        ],
+    )?;
+    Ok((schema, batch))
+}
@@ -0,0 +1,77 @@
+use std::sync::Arc;
+
+/// Cancels the statements an engine is running, from any task or thread, e.g. when the user
+/// presses Ctrl-C; see [`EngineInterface::interrupter`](crate::EngineInterface::interrupter)
+#[derive(Clone)]
+pub struct Interrupter(Arc<dyn Fn() + Send + Sync>);
+
+impl Interrupter {
+    pub(crate) fn new(interrupt: impl Fn() + Send + Sync + 'static) -> Interrupter {
+        Interrupter(Arc::new(interrupt))
+    }
+
+    /// An interrupter of nothing, for engines whose work stops once their executions are dropped
+    pub(crate) fn none() -> Interrupter {
+        Interrupter::new(|| {})
+    }
+
+    /// Interrupt every statement the engine is running, each then failing
+    pub fn interrupt(&self) {
+        (self.0)()
+    }
+}
+
+impl std::fmt::Debug for Interrupter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Interrupter").finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "duckdb-engine")]
+type Interrupt = Arc<dyn Fn() + Send + Sync>;
+
+/// The interrupts of the work an engine is running, which its [`Interrupter`] calls
+#[cfg(feature = "duckdb-engine")]
+#[derive(Clone, Default)]
+pub(crate) struct Running(Arc<std::sync::Mutex<Vec<Interrupt>>>);
+
+#[cfg(feature = "duckdb-engine")]
+impl Running {
+    /// Have `interrupt` called by the interrupter until the registration returned is dropped
+    pub(crate) fn register(&self, interrupt: impl Fn() + Send + Sync + 'static) -> Registration {
+        let interrupt: Interrupt = Arc::new(interrupt);
+        self.0.lock().unwrap().push(interrupt.clone());
+        Registration {
+            running: self.clone(),
+            interrupt,
+        }
+    }
+
+    pub(crate) fn interrupter(&self) -> Interrupter {
+        let running = self.clone();
+        Interrupter::new(move || {
+            running
+                .0
+                .lock()
+                .unwrap()
+                .iter()
+                .for_each(|interrupt| interrupt())
+        })
+    }
+}
+
+/// Keeps an interrupt registered with [`Running`] while the work it interrupts runs
+#[cfg(feature = "duckdb-engine")]
+pub(crate) struct Registration {
+    running: Running,
+    interrupt: Interrupt,
+}
+
+#[cfg(feature = "duckdb-engine")]
+impl Drop for Registration {
+    fn drop(&mut self) {
+        self.running.0.lock().unwrap().retain(|interrupt| {
+            Arc::as_ptr(interrupt) as *const () != Arc::as_ptr(&self.interrupt) as *const ()
+        });
+    }
+}
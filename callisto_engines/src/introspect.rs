@@ -0,0 +1,338 @@
+use core::ops::ControlFlow;
+use std::collections::{BTreeMap, BTreeSet};
+
+use sqlparser::ast::{self, Visit as _};
+
+use crate::TableInfo;
+
+/// What a statement reads, as far as can be told from its text
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StatementReferences {
+    /// Relations read, by the name they're written with, excluding common table expressions and
+    /// tables created earlier in the query
+    pub relations: BTreeMap<String, RelationReference>,
+    /// Columns the statement's projections refer to
+    pub projected: BTreeSet<ColumnReference>,
+    /// Columns the statement filters or joins on, in `WHERE`, `HAVING`, and `JOIN ... ON`
+    pub filtered: BTreeSet<ColumnReference>,
+}
+
+/// A relation a statement reads
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RelationReference {
+    /// Name of the table the engine reads, which a path is rewritten to
+    pub table: String,
+    /// File the table is (or would be) loaded from, if it isn't held in memory
+    pub source: Option<String>,
+}
+
+/// A column a statement refers to
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ColumnReference {
+    /// Name of the table the column belongs to, when its qualifier or the statement reading only
+    /// one relation says which
+    pub table: Option<String>,
+    pub column: String,
+}
+
+/// Find what each statement of `query` reads, resolving relations against `tables`, those
+/// registered with an engine.
+///
+/// A relation that's neither a registered table nor the source of one is taken to be a path to
+/// be loaded, as engines take it.
+pub fn query_references(
+    query: &str,
+    tables: &[TableInfo],
+) -> anyhow::Result<Vec<StatementReferences>> {
+    let mut created = BTreeSet::new();
+    let mut references = Vec::new();
+    for statement in crate::parse(query)? {
+        let mut collector = ReferenceCollector::default();
+        let _ = statement.visit(&mut collector);
+        if let ast::Statement::CreateTable { name, .. } | ast::Statement::CreateView { name, .. } =
+            &statement
+        {
+            created.insert(name.0[0].value.clone());
+        }
+
+        let relations: BTreeMap<String, RelationReference> = collector
+            .relations
+            .iter()
+            .filter(|name| !collector.ctes.contains(*name) && !created.contains(*name))
+            .map(|name| (name.clone(), resolve(name, tables)))
+            .collect();
+        let only = match relations.values().collect::<Vec<_>>()[..] {
+            [relation] => Some(relation.table.clone()),
+            _ => None,
+        };
+        let column = |(qualifier, column): (Option<String>, String)| ColumnReference {
+            table: match qualifier {
+                Some(qualifier) => {
+                    let name = collector.aliases.get(&qualifier).unwrap_or(&qualifier);
+                    Some(
+                        relations
+                            .get(name)
+                            .map(|relation| relation.table.clone())
+                            .unwrap_or_else(|| name.clone()),
+                    )
+                }
+                None => only.clone(),
+            },
+            column,
+        };
+        references.push(StatementReferences {
+            projected: collector
+                .projected
+                .clone()
+                .into_iter()
+                .map(column)
+                .collect(),
+            filtered: collector.filtered.clone().into_iter().map(column).collect(),
+            relations,
+        });
+    }
+    Ok(references)
+}
+
+/// The table `name` is read as, and where it's loaded from
+fn resolve(name: &str, tables: &[TableInfo]) -> RelationReference {
+    let table = tables
+        .iter()
+        .find(|table| table.source.as_deref() == Some(name))
+        .or_else(|| tables.iter().find(|table| table.name == name));
+    match table {
+        Some(table) => RelationReference {
+            table: table.name.clone(),
+            source: table.source.clone(),
+        },
+        None => RelationReference {
+            table: crate::derive_table_from_fs_name(name),
+            source: Some(name.to_string()),
+        },
+    }
+}
+
+/// Collects the relations a statement reads and the columns it refers to
+#[derive(Default)]
+struct ReferenceCollector {
+    relations: BTreeSet<String>,
+    /// Names of common table expressions
+    ctes: BTreeSet<String>,
+    /// Relation names by the aliases they're given
+    aliases: BTreeMap<String, String>,
+    /// Columns as (qualifier, name)
+    projected: BTreeSet<(Option<String>, String)>,
+    filtered: BTreeSet<(Option<String>, String)>,
+}
+
+impl ReferenceCollector {
+    fn collect_select(&mut self, body: &ast::SetExpr) {
+        match body {
+            ast::SetExpr::Select(select) => {
+                for item in &select.projection {
+                    match item {
+                        ast::SelectItem::UnnamedExpr(expr)
+                        | ast::SelectItem::ExprWithAlias { expr, .. } => {
+                            columns(expr, &mut self.projected)
+                        }
+                        ast::SelectItem::QualifiedWildcard(qualifier, _) => {
+                            self.projected
+                                .insert((Some(qualifier.0[0].value.clone()), "*".to_string()));
+                        }
+                        ast::SelectItem::Wildcard(_) => {
+                            self.projected.insert((None, "*".to_string()));
+                        }
+                    }
+                }
+                for expr in select.selection.iter().chain(&select.having) {
+                    columns(expr, &mut self.filtered);
+                }
+                for table in &select.from {
+                    for join in &table.joins {
+                        if let ast::JoinOperator::Inner(ast::JoinConstraint::On(expr))
+                        | ast::JoinOperator::LeftOuter(ast::JoinConstraint::On(expr))
+                        | ast::JoinOperator::RightOuter(ast::JoinConstraint::On(expr))
+                        | ast::JoinOperator::FullOuter(ast::JoinConstraint::On(expr)) =
+                            &join.join_operator
+                        {
+                            columns(expr, &mut self.filtered);
+                        }
+                    }
+                }
+            }
+            ast::SetExpr::SetOperation { left, right, .. } => {
+                self.collect_select(left);
+                self.collect_select(right);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl ast::Visitor for ReferenceCollector {
+    type Break = ();
+
+    fn pre_visit_query(&mut self, query: &ast::Query) -> ControlFlow<Self::Break> {
+        if let Some(with) = &query.with {
+            for cte in &with.cte_tables {
+                self.ctes.insert(cte.alias.name.value.clone());
+            }
+        }
+        self.collect_select(&query.body);
+        ControlFlow::Continue(())
+    }
+
+    fn pre_visit_table_factor(
+        &mut self,
+        table_factor: &ast::TableFactor,
+    ) -> ControlFlow<Self::Break> {
+        // Table functions, e.g. DuckDB's read_parquet, are left out
+        if let ast::TableFactor::Table {
+            name,
+            alias,
+            args: None,
+            ..
+        } = table_factor
+        {
+            let name = name.0[0].value.clone();
+            if let Some(alias) = alias {
+                self.aliases.insert(alias.name.value.clone(), name.clone());
+            }
+            self.relations.insert(name);
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+/// Add the columns `expr` refers to, as (qualifier, name), to `found`
+fn columns(expr: &ast::Expr, found: &mut BTreeSet<(Option<String>, String)>) {
+    let _ = ast::visit_expressions(expr, |expr| {
+        match expr {
+            ast::Expr::Identifier(ident) => {
+                found.insert((None, ident.value.clone()));
+            }
+            ast::Expr::CompoundIdentifier(idents) if idents.len() >= 2 => {
+                let qualifier = &idents[idents.len() - 2];
+                let column = &idents[idents.len() - 1];
+                found.insert((Some(qualifier.value.clone()), column.value.clone()));
+            }
+            _ => {}
+        }
+        ControlFlow::<()>::Continue(())
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(name: &str, source: Option<&str>) -> TableInfo {
+        TableInfo {
+            name: name.to_string(),
+            source: source.map(str::to_string),
+            schema: None,
+        }
+    }
+
+    fn column(table: Option<&str>, column: &str) -> ColumnReference {
+        ColumnReference {
+            table: table.map(str::to_string),
+            column: column.to_string(),
+        }
+    }
+
+    /// The names of the relations `references` reads, with the table and source of each
+    fn relations(references: &StatementReferences) -> Vec<(&str, &str, Option<&str>)> {
+        let relations = references.relations.iter();
+        relations
+            .map(|(name, relation)| {
+                (
+                    name.as_str(),
+                    relation.table.as_str(),
+                    relation.source.as_deref(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn columns_of_a_lone_relation_belong_to_it() {
+        let references =
+            query_references("SELECT a, b + 1 FROM t WHERE c > 1", &[table("t", None)]).unwrap();
+        assert_eq!(relations(&references[0]), [("t", "t", None)]);
+        assert_eq!(
+            references[0].projected,
+            [column(Some("t"), "a"), column(Some("t"), "b")].into()
+        );
+        assert_eq!(references[0].filtered, [column(Some("t"), "c")].into());
+    }
+
+    #[test]
+    fn paths_are_resolved_to_the_tables_loaded_from_them() {
+        let tables = [table("tbl_a_parquet", Some("data/a.parquet"))];
+        let query = r#"SELECT * FROM "data/a.parquet"; SELECT * FROM "data/b.parquet""#;
+        let references = query_references(query, &tables).unwrap();
+        assert_eq!(
+            relations(&references[0]),
+            [("data/a.parquet", "tbl_a_parquet", Some("data/a.parquet"))]
+        );
+        // Or those they would be
+        assert_eq!(
+            relations(&references[1]),
+            [("data/b.parquet", "tbl_b_parquet", Some("data/b.parquet"))]
+        );
+        assert_eq!(
+            references[1].projected,
+            [column(Some("tbl_b_parquet"), "*")].into()
+        );
+    }
+
+    #[test]
+    fn qualifiers_and_aliases_name_the_tables_of_columns() {
+        let query = "SELECT o.id, c.*, total FROM orders o JOIN customers AS c \
+                     ON o.customer = c.id WHERE c.region = 'EU'";
+        let tables = [table("orders", None), table("customers", None)];
+        let references = query_references(query, &tables).unwrap();
+        assert_eq!(
+            references[0].projected,
+            [
+                column(None, "total"),
+                column(Some("customers"), "*"),
+                column(Some("orders"), "id"),
+            ]
+            .into()
+        );
+        assert_eq!(
+            references[0].filtered,
+            [
+                column(Some("customers"), "id"),
+                column(Some("customers"), "region"),
+                column(Some("orders"), "customer"),
+            ]
+            .into()
+        );
+    }
+
+    #[test]
+    fn tables_the_query_makes_itself_are_not_read() {
+        let query = "WITH recent AS (SELECT * FROM events) SELECT * FROM recent; \
+                     CREATE TABLE totals AS SELECT 1 AS n; \
+                     SELECT * FROM totals JOIN read_parquet('x.parquet') ON true";
+        let references = query_references(query, &[table("events", None)]).unwrap();
+        assert_eq!(references.len(), 3);
+        assert_eq!(relations(&references[0]), [("events", "events", None)]);
+        assert!(references[1].relations.is_empty());
+        // Nor are table functions
+        assert!(references[2].relations.is_empty());
+    }
+
+    #[test]
+    fn both_sides_of_set_operations_are_read() {
+        let references = query_references("SELECT a FROM t UNION SELECT b FROM u", &[]).unwrap();
+        assert_eq!(references[0].relations.len(), 2);
+        assert_eq!(
+            references[0].projected,
+            [column(None, "a"), column(None, "b")].into()
+        );
+    }
+}
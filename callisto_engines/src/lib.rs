@@ -1,20 +1,73 @@
-use core::pin::Pin;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::sync::Arc;
-
-use futures::Stream;
+use std::time::Duration;
 
 use sqlparser::ast;
 use sqlparser::dialect::GenericDialect;
 use sqlparser::parser::{Parser, ParserOptions};
 
 use arrow::record_batch::RecordBatch;
-use datafusion::datasource::file_format::options::ParquetReadOptions;
 use datafusion::physical_plan::SendableRecordBatchStream;
-use polars_lazy::frame::LazyFrame;
+use futures::stream::{BoxStream, StreamExt as _, TryStreamExt as _};
+use web_time::Instant;
 
+mod builder;
+mod dry_run;
+mod error;
+mod execution_metrics;
+#[cfg(feature = "datafusion-engine")]
+mod footer_cache;
+mod info;
+mod interrupt;
+mod introspect;
+mod lineage;
+#[cfg(all(feature = "datafusion-engine", feature = "native"))]
+mod mmap;
+mod observer;
+mod path_policy;
+mod plan;
+mod plan_cache;
+#[cfg(feature = "polars-engine")]
 mod polars_to_arrow;
+mod prepared;
+mod query_execution;
+mod rebatch;
+mod rewrite;
+mod source_span;
+mod sql_format;
+mod telemetry;
+mod timeout;
+mod validate;
+
+/// The DataFusion the engines are built against, whose types results are passed as and custom
+/// table providers implement
+pub use datafusion;
+/// The Polars the Polars engine is built against, whose frames can be registered as tables
+#[cfg(feature = "polars-engine")]
+pub use polars;
+
+pub use builder::{DiagnosticSink, EngineBuilder, PolarsOptions};
+pub use dry_run::{DryRun, ScannedFile};
+pub use error::CallistoError;
+pub use execution_metrics::{ExecutionMetrics, ExecutionStats};
+pub use info::EngineInfo;
+pub use interrupt::Interrupter;
+pub use introspect::{query_references, ColumnReference, RelationReference, StatementReferences};
+pub use lineage::{FileRead, Lineage, LineageInput};
+pub use observer::ExecutionObserver;
+pub use path_policy::PathPolicy;
+pub use plan::PlanNode;
+pub use plan_cache::CacheStats;
+pub use prepared::PreparedStatement;
+pub use query_execution::QueryExecution;
+pub use rebatch::BatchSizing;
+pub use rewrite::StatementRewriter;
+pub use source_span::SourceSpan;
+pub use sql_format::{format_sql, FormatOptions, KeywordCase};
+pub use telemetry::describe_metrics;
+pub use validate::{validate_query, ValidationIssue};
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Engine {
     Polars,
     DuckDB,
@@ -22,66 +75,471 @@ pub enum Engine {
 }
 
 impl Engine {
+    pub const ALL: [Engine; 3] = [Engine::Polars, Engine::DuckDB, Engine::DataFusion];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Engine::Polars => "polars",
+            Engine::DuckDB => "duckdb",
+            Engine::DataFusion => "datafusion",
+        }
+    }
+
+    /// Whether the engine was compiled in, by its feature: `polars-engine`, `duckdb-engine`, or
+    /// `datafusion-engine`. Building one that wasn't fails.
+    pub fn is_available(&self) -> bool {
+        match self {
+            Engine::Polars => cfg!(feature = "polars-engine"),
+            Engine::DuckDB => cfg!(feature = "duckdb-engine"),
+            Engine::DataFusion => cfg!(feature = "datafusion-engine"),
+        }
+    }
+
+    /// Version of the library the engine runs queries with, if it was compiled in
+    pub fn library_version(&self) -> Option<String> {
+        match self {
+            #[cfg(feature = "polars-engine")]
+            Engine::Polars => Some(polars::VERSION.to_string()),
+            #[cfg(feature = "duckdb-engine")]
+            Engine::DuckDB => duckdb::Connection::open_in_memory()
+                .and_then(|connection| {
+                    connection.query_row(
+                        "SELECT library_version FROM pragma_version()",
+                        [],
+                        |row| row.get(0),
+                    )
+                })
+                .ok(),
+            #[cfg(feature = "datafusion-engine")]
+            Engine::DataFusion => Some(datafusion::DATAFUSION_VERSION.to_string()),
+            #[allow(unreachable_patterns)]
+            _ => None,
+        }
+    }
+
+    /// A new instance of the engine, configured as it is by default
     pub fn new(&self) -> anyhow::Result<Box<dyn EngineInterface>> {
-        Ok(match self {
-            Engine::Polars => Box::new(polars_engine::default()),
-            Engine::DuckDB => Box::new(duckdb_engine::default()),
-            Engine::DataFusion => Box::new(datafusion_engine::default()),
-        })
+        self.builder().build()
+    }
+
+    /// Configuration of a new instance of the engine, to be built with [`EngineBuilder::build`]
+    pub fn builder(&self) -> EngineBuilder {
+        EngineBuilder::new(*self)
     }
 }
 
+/// A table registered with an engine
+#[derive(Clone, Debug)]
+pub struct TableInfo {
+    /// Name under which the table is registered
+    pub name: String,
+    /// Path the table was loaded from, if it was loaded from the filesystem
+    pub source: Option<String>,
+    /// Schema of the table, if it could be determined
+    pub schema: Option<arrow::datatypes::SchemaRef>,
+}
+
+/// How a Parquet file is read when registered as a table, see
+/// [`EngineInterface::register_parquet`]
+#[derive(Clone, Debug, Default)]
+pub struct ParquetOptions {
+    /// Columns to read, or all of them if `None`
+    pub columns: Option<Vec<String>>,
+    /// Most rows to read, or all of them if `None`
+    pub limit: Option<usize>,
+}
+
+/// A query engine. Its methods take `&self`, so one engine shared as an
+/// `Arc<dyn EngineInterface>` can run queries from many tasks at once.
 #[async_trait::async_trait]
-pub trait EngineInterface {
-    async fn execute(
-        &mut self,
+pub trait EngineInterface: Send + Sync {
+    /// Execute each statement of `query` in turn, returning their executions with rows yet to be
+    /// read
+    async fn execute(&self, query: &str) -> anyhow::Result<Vec<QueryExecution>> {
+        let timeout = self.timeout();
+        self.execute_with_timeout(query, timeout).await
+    }
+
+    /// [`execute`](EngineInterface::execute) `query`, cancelling each statement still running
+    /// after `timeout` in place of the engine's own timeout
+    async fn execute_with_timeout(
+        &self,
         query: &str,
-    ) -> anyhow::Result<Vec<(sqlparser::ast::Statement, SendableRecordBatchStream)>>;
+        timeout: Option<Duration>,
+    ) -> anyhow::Result<Vec<QueryExecution>> {
+        self.execute_statements_with_timeout(query, timeout)
+            .try_collect()
+            .await
+    }
+
+    /// Execute the statements of `query` one at a time, each as the stream is polled for it, so
+    /// the results of one can be read before the next has run.
+    ///
+    /// The stream ends after the first statement to fail, its error carrying the
+    /// [`SourceSpan`] of the statement, or of the part of it at fault.
+    fn execute_statements<'a>(
+        &'a self,
+        query: &'a str,
+    ) -> BoxStream<'a, anyhow::Result<QueryExecution>> {
+        let timeout = self.timeout();
+        self.execute_statements_with_timeout(query, timeout)
+    }
+
+    /// [`execute_statements`](EngineInterface::execute_statements) of `query`, cancelling each
+    /// still running after `timeout` in place of the engine's own timeout
+    fn execute_statements_with_timeout<'a>(
+        &'a self,
+        query: &'a str,
+        timeout: Option<Duration>,
+    ) -> BoxStream<'a, anyhow::Result<QueryExecution>> {
+        use tracing::Instrument as _;
+
+        let parse_start = Instant::now();
+        let parse_span = tracing::info_span!("parse", bytes = query.len());
+        let statements = match parse_span.in_scope(|| plan_cache::parse(query)) {
+            Ok(statements) => statements,
+            Err(error) => {
+                let error = source_span::locate_parse_error(query, error);
+                return futures::stream::once(async { Err(error) }).boxed();
+            }
+        };
+        let parse_time = parse_start.elapsed();
+        // Statements the tokenizer split differently from the parser go without spans
+        let ranges = source_span::statement_ranges(query);
+        let ranges = if ranges.len() == statements.len() {
+            ranges.into_iter().map(Some).collect()
+        } else {
+            vec![None; statements.len()]
+        };
+        let statements = statements.into_iter().zip(ranges).enumerate();
+        futures::stream::unfold(
+            (self, statements),
+            move |(engine, mut statements)| async move {
+                let (index, (mut statement, range)) = statements.next()?;
+                let span = tracing::info_span!(
+                    "statement",
+                    id = next_statement_id(),
+                    index,
+                    engine = engine.kind().name()
+                );
+                let execution = async {
+                    if plan_cache::is_show_cache(&statement) {
+                        return plan_cache::show_cache(
+                            engine.kind(),
+                            statement,
+                            &engine.cache_stats(),
+                        );
+                    }
+                    if info::resolve_engines_table(&mut statement) {
+                        let (schema, batch) = info::engines_table(&engine.info())?;
+                        engine
+                            .register_batches(info::ENGINES_TABLE, schema, vec![batch])
+                            .await?;
+                    }
+                    engine
+                        .execute_statement(statement, parse_time, timeout)
+                        .await
+                }
+                .instrument(span)
+                .await;
+                telemetry::statement(engine.kind(), execution.is_ok());
+                let execution = execution.map_err(|error| match range {
+                    Some(range) => source_span::locate(query, index, range, error),
+                    None => error,
+                });
+                if execution.is_err() {
+                    statements.by_ref().for_each(drop);
+                }
+                Some((execution, (engine, statements)))
+            },
+        )
+        .boxed()
+    }
+
+    /// Execute `statement`, one of a query which took `parse_time` to parse, cancelling it if
+    /// it's still running after `timeout`
+    async fn execute_statement(
+        &self,
+        statement: ast::Statement,
+        parse_time: Duration,
+        timeout: Option<Duration>,
+    ) -> anyhow::Result<QueryExecution>;
+
+    /// How long statements may run before they're cancelled, unless a call says otherwise, see
+    /// [`EngineBuilder::timeout`]
+    fn timeout(&self) -> Option<Duration> {
+        None
+    }
+
+    /// What interrupts the statements the engine is running from another task or thread, e.g.
+    /// when the user presses Ctrl-C, as an engine which blocks the task running a statement
+    /// leaves it unable to notice.
+    ///
+    /// Engines which don't block stop once their executions are dropped, so interrupt nothing.
+    fn interrupter(&self) -> Interrupter {
+        Interrupter::none()
+    }
+
+    /// Prepare the single statement `sql`, its parameters written `$1`, `$2`, ... or `?`, to be
+    /// executed with values bound to them
+    async fn prepare(&self, sql: &str) -> anyhow::Result<PreparedStatement>;
+
+    /// Execute `statement`, prepared by this engine, with the values bound to its parameters,
+    /// subject to the engine's timeout
+    async fn execute_prepared(
+        &self,
+        statement: &PreparedStatement,
+    ) -> anyhow::Result<QueryExecution>;
+
+    /// Which engine this is
+    fn kind(&self) -> Engine;
+
+    /// The engine's library version, the features it was built with, and its settings. It's
+    /// listed among the other engines by the virtual table `callisto_engines()`.
+    fn info(&self) -> EngineInfo;
+
+    /// The catalog and schema tables named without them are found in, e.g. `memory.main`, if the
+    /// engine has catalogs
+    fn current_catalog(&self) -> Option<String> {
+        None
+    }
+
+    /// List the tables currently registered with the engine, sorted by name
+    async fn tables(&self) -> anyhow::Result<Vec<TableInfo>>;
+
+    /// Register `batches` as a table called `name`, replacing any table of that name, to be
+    /// queried and joined against files like any other.
+    ///
+    /// Every batch must have the columns of `schema`, which there may be none of.
+    async fn register_batches(
+        &self,
+        name: &str,
+        schema: arrow::datatypes::SchemaRef,
+        batches: Vec<RecordBatch>,
+    ) -> anyhow::Result<()>;
+
+    /// Register the Polars `frame` as a table called `name`, replacing any table of that name, so
+    /// frames built by other code can be queried. A `DataFrame` is registered by way of its
+    /// `lazy()`.
+    ///
+    /// The Polars engine takes the frame as it is, evaluating it as part of each query that reads
+    /// it. The others collect it once, then register its rows.
+    #[cfg(feature = "polars-engine")]
+    async fn register_lazy_frame(
+        &self,
+        name: &str,
+        frame: polars::prelude::LazyFrame,
+    ) -> anyhow::Result<()> {
+        let df = tokio::task::block_in_place(|| frame.collect())?;
+        let (schema, batches) = polars_to_arrow::convert_frame(df)?;
+        self.register_batches(name, schema, batches).await
+    }
+
+    /// Register the Parquet file (or glob of files) at `path` as a table called `name`, replacing
+    /// any table of that name.
+    ///
+    /// Unlike a path named in a query, a file that can't be read is an error here. A file the
+    /// engine's [`PathPolicy`] doesn't allow is refused either way.
+    async fn register_parquet(
+        &self,
+        name: &str,
+        path: &str,
+        options: &ParquetOptions,
+    ) -> anyhow::Result<()>;
+
+    /// Register `provider` as a table called `name`, replacing any table of that name, for
+    /// sources callisto has no support for of its own, e.g. an in-house service.
+    ///
+    /// Only the DataFusion engine, which table providers are written for, can do this.
+    async fn register_table_provider(
+        &self,
+        name: &str,
+        provider: Arc<dyn datafusion::datasource::TableProvider>,
+    ) -> anyhow::Result<()> {
+        let _ = (name, provider);
+        anyhow::bail!(
+            "The {} engine can't register DataFusion table providers",
+            self.kind().name()
+        )
+    }
+
+    /// Execute the serialized Substrait `plan`, e.g. one produced by another tool
+    async fn execute_substrait(
+        &self,
+        plan: &[u8],
+    ) -> anyhow::Result<(SendableRecordBatchStream, Arc<ExecutionMetrics>)> {
+        let _ = plan;
+        anyhow::bail!(
+            "The {} engine can't run Substrait plans",
+            self.kind().name()
+        )
+    }
+
+    /// Find what each statement of `query` reads, its relations resolved to the tables they're
+    /// read as and the files those are loaded from
+    async fn references(&self, query: &str) -> anyhow::Result<Vec<StatementReferences>> {
+        let tables = self.tables().await?;
+        query_references(query, &tables)
+    }
+
+    /// What `statement` read and wrote, its relations resolved to the tables they're read as and
+    /// the files those are loaded from, and the ranges of files scanned as measured in `metrics`,
+    /// so should be asked once its results have been read
+    async fn lineage(
+        &self,
+        statement: &ast::Statement,
+        metrics: &ExecutionMetrics,
+    ) -> anyhow::Result<Lineage> {
+        let references = self
+            .references(&statement.to_string())
+            .await?
+            .pop()
+            .unwrap_or_default();
+        Ok(lineage::lineage(statement, references, metrics))
+    }
+
+    /// Check `query` for problems without executing it, returning every issue found
+    async fn validate(&self, query: &str) -> anyhow::Result<Vec<ValidationIssue>> {
+        Ok(validate_query(query))
+    }
+
+    /// Plan each statement of `query` and list the files it would read, without executing it
+    /// or reading any data, e.g. to see what a query would scan before running it over a large
+    /// prefix. Tables may be registered for the files named, their schemas read.
+    async fn dry_run(&self, query: &str) -> anyhow::Result<Vec<DryRun>> {
+        let statements = parse(query)?;
+        let references = self.references(query).await?;
+        let mut runs = Vec::new();
+        for (statement, references) in statements.into_iter().zip(references) {
+            // Planned first, so that files the path policy doesn't allow fail it unlisted
+            let plan = self.plan_statement(&statement).await?;
+            let files = dry_run::files(&references).await?;
+            runs.push(DryRun {
+                statement,
+                plan,
+                files,
+            });
+        }
+        Ok(runs)
+    }
+
+    /// How the caches have fared of the statements parsed from queries, which all engines share,
+    /// and of the engine's plans, if it keeps them. `SHOW CACHE` answers with these.
+    fn cache_stats(&self) -> Vec<CacheStats> {
+        vec![plan_cache::statements().stats()]
+    }
+
+    /// Empty the caches of [`cache_stats`](EngineInterface::cache_stats), e.g. to time a query
+    /// planned afresh
+    fn clear_caches(&self) {
+        plan_cache::statements().clear();
+    }
+
+    /// How the engine would run `statement`, as it describes it, if it can tell without running it
+    /// or reading any data, see [`dry_run`](EngineInterface::dry_run)
+    async fn plan_statement(&self, statement: &ast::Statement) -> anyhow::Result<Option<String>> {
+        let _ = statement;
+        Ok(None)
+    }
 }
 
+#[cfg(feature = "polars-engine")]
 mod polars_engine {
     use super::*;
+    use core::pin::Pin;
+    use futures::Stream;
+    use polars_lazy::frame::LazyFrame;
 
-    pub fn default() -> PolarsImpl {
-        PolarsImpl::default()
+    pub fn build(builder: &EngineBuilder) -> PolarsImpl {
+        PolarsImpl {
+            scan_args: polars_lazy::prelude::ScanArgsParquet {
+                low_memory: builder.polars_options.low_memory,
+                rechunk: builder.polars_options.rechunk,
+                ..Default::default()
+            },
+            diagnostics: builder.diagnostics.clone(),
+            observers: builder.observers.clone(),
+            rewriters: builder.rewriters.clone(),
+            timeout: builder.timeout,
+            row_limit: builder.row_limit,
+            batching: builder.batching,
+            path_policy: builder.path_policy.clone(),
+            load_concurrency: builder.load_concurrency,
+            options: builder.polars_options,
+            ..Default::default()
+        }
     }
 
     #[derive(Default)]
     pub struct PolarsImpl {
+        /// Locked while statements are planned, the plans then collected concurrently
+        state: std::sync::Mutex<State>,
+        /// How the Parquet files queries name are read
+        scan_args: polars_lazy::prelude::ScanArgsParquet,
+        /// Of which the bridge sizes are used here, the rest being in `scan_args`
+        options: PolarsOptions,
+        diagnostics: Option<DiagnosticSink>,
+        observers: observer::Observers,
+        rewriters: rewrite::Rewriters,
+        timeout: Option<Duration>,
+        row_limit: Option<usize>,
+        batching: Option<BatchSizing>,
+        path_policy: PathPolicy,
+        /// Most files a statement names which are scanned at once
+        load_concurrency: usize,
+    }
+
+    #[derive(Default)]
+    struct State {
         fs_name_to_table_name: BTreeMap<String, String>,
+        /// Tables registered from record batches rather than loaded from files
+        memory_tables: BTreeSet<String>,
         context: polars::sql::SQLContext,
     }
 
     impl PolarsImpl {
-        fn load_tables(&mut self, query: &ast::Statement) -> anyhow::Result<ast::Statement> {
-            let mut rewritten = query.clone();
-            let mut new_tables = Vec::new();
-            ast::visit_relations_mut(&mut rewritten, |table| {
-                let symbol_or_file: &str = &table.0[0].value;
-                let table_name =
-                    if let Some(table_name) = self.fs_name_to_table_name.get(symbol_or_file) {
-                        table_name.to_string()
-                    } else {
-                        let table_name = derive_table_from_fs_name(symbol_or_file);
-                        new_tables.push((symbol_or_file.to_string(), table_name.clone()));
-                        table_name
-                    };
-                table.0[0].value = table.0[0].value.replace(symbol_or_file, &table_name);
-                core::ops::ControlFlow::<()>::Continue(())
-            });
+        fn load_tables(
+            &self,
+            state: &mut State,
+            query: &ast::Statement,
+        ) -> anyhow::Result<ast::Statement> {
+            let tables = rewrite::TableNames {
+                engine: Engine::Polars,
+                fs_name_to_table_name: &state.fs_name_to_table_name,
+                memory_tables: &state.memory_tables,
+                path_policy: &self.path_policy,
+            };
+            let (rewritten, new_tables) = rewrite::rewrite(&self.rewriters, &tables, query)?;
 
-            for (fs_name, table_name) in new_tables {
-                let frame = LazyFrame::scan_parquet(&fs_name, Default::default());
+            // Scanning a file reads its schema, so they're scanned at once
+            let scanned = load_concurrently(new_tables, self.load_concurrency, |names| {
+                let (fs_name, table_name) = &names;
+                let _span =
+                    tracing::info_span!("register", table = %table_name, path = %fs_name).entered();
+                let frame = LazyFrame::scan_parquet(fs_name, self.scan_args.clone());
+                (names, frame)
+            });
+            for ((fs_name, table_name), frame) in scanned {
                 match frame {
                     Ok(frame) => {
-                        self.fs_name_to_table_name
+                        tracing::debug!(path = %fs_name, table = %table_name, "Registered table");
+                        telemetry::table_loaded(Engine::Polars);
+                        observer::registered(&self.observers, &table_name, Some(&fs_name));
+                        state
+                            .fs_name_to_table_name
                             .insert(fs_name.to_string(), table_name.clone());
-                        self.context.register(&table_name, frame);
+                        state.context.register(&table_name, frame);
+                    }
+                    Err(error) => {
+                        telemetry::table_load_failed(Engine::Polars);
+                        builder::warn(
+                            &self.diagnostics,
+                            format!(
+                                "Loading referenced parquet path ({}) failed with error: {}",
+                                fs_name, error
+                            ),
+                        )
                     }
-                    Err(error) => println!(
-                        "Warning -- loading referenced parquet path ({}) failed with error: {}",
-                        fs_name, error
-                    ),
                 }
             }
             Ok(rewritten)
@@ -90,70 +548,353 @@ mod polars_engine {
 
     #[async_trait::async_trait]
     impl EngineInterface for PolarsImpl {
-        async fn execute(
-            &mut self,
-            query: &str,
-        ) -> anyhow::Result<Vec<(sqlparser::ast::Statement, SendableRecordBatchStream)>> {
-            use polars::prelude::SerWriter as _;
-            let mut parser = Parser::new(&GenericDialect);
-            parser = parser.with_options(ParserOptions {
-                trailing_commas: true,
+        fn kind(&self) -> Engine {
+            Engine::Polars
+        }
+
+        fn info(&self) -> EngineInfo {
+            EngineInfo::new(
+                Engine::Polars,
+                vec![
+                    info::setting("low_memory", Some(self.scan_args.low_memory)),
+                    info::setting("rechunk", Some(self.scan_args.rechunk)),
+                    info::setting(
+                        "bridge_buffer_bytes",
+                        Some(self.options.bridge_buffer_bytes),
+                    ),
+                    info::setting("bridge_batches", Some(self.options.bridge_batches)),
+                    info::setting("timeout", self.timeout),
+                    info::setting("row_limit", self.row_limit),
+                    info::setting("batch_sizing", self.batching),
+                ],
+            )
+        }
+
+        async fn execute_statement(
+            &self,
+            statement: ast::Statement,
+            parse_time: Duration,
+            timeout: Option<Duration>,
+        ) -> anyhow::Result<QueryExecution> {
+            // TODO(alex): Table loading should be column aware so we don't load unnecessary
+            // columns here.
+            let mut metrics = ExecutionMetrics {
+                parse_time,
+                ..Default::default()
+            };
+            let deadline = timeout::Deadline::after(timeout);
+            let (span, frame, execution_start) = tokio::task::block_in_place(|| {
+                let mut state = self.state.lock().unwrap();
+                let load_start = Instant::now();
+                let transformed_stmt = tracing::info_span!("load_tables")
+                    .in_scope(|| self.load_tables(&mut state, &statement))?;
+                metrics.load_time = load_start.elapsed();
+
+                let execution_start = Instant::now();
+                let span = tracing::info_span!("execute", statement = %transformed_stmt);
+                let frame =
+                    span.in_scope(|| state.context.execute(&transformed_stmt.to_string()))?;
+                anyhow::Ok((span, frame, execution_start))
+            })?;
+            let df =
+                timeout::blocking(deadline, move || Ok(span.in_scope(|| frame.collect())?)).await?;
+            metrics.execution_time = execution_start.elapsed();
+            let stream = timeout::limit_stream(stream_frame(df, &self.options)?, deadline);
+            let metrics = Arc::new(metrics);
+            let stream = execution_metrics::metered(
+                Engine::Polars,
+                stream,
+                metrics.clone(),
+                None,
+                self.row_limit,
+                self.batching,
+            );
+            // TODO(alex): Figure out how to push this streamification down into the execution
+            // instead of post-collection.
+            Ok(QueryExecution::new(statement, stream, metrics))
+        }
+
+        fn timeout(&self) -> Option<Duration> {
+            self.timeout
+        }
+
+        async fn prepare(&self, sql: &str) -> anyhow::Result<PreparedStatement> {
+            let statement = prepared::parse(sql)?;
+            let mut statement = tokio::task::block_in_place(|| {
+                self.load_tables(&mut self.state.lock().unwrap(), &statement)
+            })?;
+            let parameter_count = prepared::number_parameters(&mut statement)?;
+            // Polars has no parameters of its own, so values are bound as literals on execution
+            Ok(PreparedStatement::new(
+                Engine::Polars,
+                statement,
+                parameter_count,
+                None,
+            ))
+        }
+
+        async fn execute_prepared(
+            &self,
+            statement: &PreparedStatement,
+        ) -> anyhow::Result<QueryExecution> {
+            let bound = statement.with_literals()?;
+            let deadline = timeout::Deadline::after(self.timeout);
+            let span = tracing::info_span!("execute", statement = %bound);
+            let execution_start = Instant::now();
+            let frame = tokio::task::block_in_place(|| {
+                let mut state = self.state.lock().unwrap();
+                span.in_scope(|| state.context.execute(&bound.to_string()))
+            })?;
+            let df =
+                timeout::blocking(deadline, move || Ok(span.in_scope(|| frame.collect())?)).await?;
+            let metrics = Arc::new(ExecutionMetrics {
+                execution_time: execution_start.elapsed(),
                 ..Default::default()
             });
+            let stream = timeout::limit_stream(stream_frame(df, &self.options)?, deadline);
+            let stream = execution_metrics::metered(
+                Engine::Polars,
+                stream,
+                metrics.clone(),
+                None,
+                self.row_limit,
+                self.batching,
+            );
+            Ok(QueryExecution::new(
+                statement.statement.clone(),
+                stream,
+                metrics,
+            ))
+        }
 
-            let ast = parser.try_with_sql(query)?.parse_statements()?;
-
-            let mut executions = Vec::new();
-            for statement in ast {
-                // TODO(alex): Table loading should be column aware so we don't load unnecessary
-                // columns here.
-                let mut df: polars::frame::DataFrame = tokio::task::block_in_place(|| {
-                    self.load_tables(&statement).and_then(|transformed_stmt| {
-                        let lazy_frame = self
-                            .context
-                            .execute(&transformed_stmt.to_string())
-                            .map_err(|error| error.into());
-                        lazy_frame.and_then(|frame| frame.collect().map_err(|error| error.into()))
-                    })
-                })?;
-                let schema = Arc::new(polars_to_arrow::convert_schema(
-                    df.schema().to_arrow(false),
-                )?);
-                let (arrow_client, mut polars_server) = tokio::io::duplex(1024);
-                // TODO(alex): Figure out how to refactor this so it performs fewer (preferably no)
-                // copies.  Perhaps convert the Polars arrays in memory, returning a an object
-                // implmenting the stream which holds the dataframe memory?
-                let polars_writer_handle = tokio::task::spawn_blocking(move || {
-                    polars_io::ipc::IpcStreamWriter::new(tokio_util::io::SyncIoBridge::new(
-                        &mut polars_server,
-                    ))
-                    .finish(&mut df)
-                });
-                let (datafusion_tx, datafusion_rx) = tokio::sync::mpsc::channel(100);
-                // TODO(alex): Handle this join
-                let _join_handle = tokio::task::spawn_blocking(move || -> anyhow::Result<_> {
-                    let arrow_stream =
-                        datafusion::common::arrow::ipc::reader::StreamReader::try_new(
-                            tokio_util::io::SyncIoBridge::new(arrow_client),
-                            None,
-                        )?;
-                    for record_batch in arrow_stream {
-                        datafusion_tx.blocking_send(record_batch.map_err(|error| {
-                            datafusion::error::DataFusionError::ArrowError(error, None)
-                        }))?;
-                    }
-                    Ok(polars_writer_handle)
-                });
-                let stream: SendableRecordBatchStream = Box::pin(StreamFromPolars {
-                    stream: tokio_stream::wrappers::ReceiverStream::new(datafusion_rx),
+        #[tracing::instrument(name = "register", skip_all, fields(table = %name))]
+        async fn register_batches(
+            &self,
+            name: &str,
+            schema: arrow::datatypes::SchemaRef,
+            batches: Vec<RecordBatch>,
+        ) -> anyhow::Result<()> {
+            use polars::prelude::{IntoLazy as _, SerReader as _};
+
+            check_batches(name, &schema, &batches)?;
+            // Batches cross into Polars through the Arrow IPC format, as results do coming out.
+            let frame = tokio::task::block_in_place(|| {
+                let mut buffer = Vec::new();
+                let mut writer = arrow::ipc::writer::StreamWriter::try_new(&mut buffer, &schema)?;
+                for batch in &batches {
+                    writer.write(batch)?;
+                }
+                writer.finish()?;
+                drop(writer);
+                let frame =
+                    polars_io::ipc::IpcStreamReader::new(std::io::Cursor::new(buffer)).finish()?;
+                anyhow::Ok(frame)
+            })?;
+            {
+                let mut state = self.state.lock().unwrap();
+                state.context.register(name, frame.lazy());
+                record_in_memory(
+                    &mut state.fs_name_to_table_name,
+                    &mut state.memory_tables,
+                    name,
+                );
+            }
+            tracing::debug!(table = %name, "Registered record batches");
+            observer::registered(&self.observers, name, None);
+            Ok(())
+        }
+
+        #[tracing::instrument(name = "register", skip_all, fields(table = %name))]
+        async fn register_lazy_frame(
+            &self,
+            name: &str,
+            frame: polars::prelude::LazyFrame,
+        ) -> anyhow::Result<()> {
+            {
+                let mut state = self.state.lock().unwrap();
+                state.context.register(name, frame);
+                record_in_memory(
+                    &mut state.fs_name_to_table_name,
+                    &mut state.memory_tables,
+                    name,
+                );
+            }
+            tracing::debug!(table = %name, "Registered lazy frame");
+            observer::registered(&self.observers, name, None);
+            Ok(())
+        }
+
+        #[tracing::instrument(name = "register", skip_all, fields(table = %name, path = %path))]
+        async fn register_parquet(
+            &self,
+            name: &str,
+            path: &str,
+            options: &ParquetOptions,
+        ) -> anyhow::Result<()> {
+            use anyhow::Context as _;
+
+            self.path_policy.check(path)?;
+            let args = polars_lazy::prelude::ScanArgsParquet {
+                n_rows: options.limit,
+                ..self.scan_args.clone()
+            };
+            let mut frame = LazyFrame::scan_parquet(path, args)
+                .with_context(|| format!("Failed to read {}", path))?;
+            if let Some(columns) = &options.columns {
+                frame = frame.select(
+                    columns
+                        .iter()
+                        .map(|column| polars_lazy::dsl::col(column))
+                        .collect::<Vec<_>>(),
+                );
+            }
+            {
+                let mut state = self.state.lock().unwrap();
+                let state = &mut *state;
+                state.context.register(name, frame);
+                record_source(
+                    &mut state.fs_name_to_table_name,
+                    &mut state.memory_tables,
+                    path,
+                    name,
+                );
+            }
+            tracing::debug!(%path, table = %name, "Registered table");
+            observer::registered(&self.observers, name, Some(path));
+            Ok(())
+        }
+
+        async fn tables(&self) -> anyhow::Result<Vec<TableInfo>> {
+            let (frames, fs_name_to_table_name) = {
+                let state = self.state.lock().unwrap();
+                (
+                    state.context.get_table_map(),
+                    state.fs_name_to_table_name.clone(),
+                )
+            };
+            let mut tables = Vec::new();
+            for (name, frame) in frames {
+                let schema = tokio::task::block_in_place(|| frame.schema())
+                    .map_err(anyhow::Error::from)
+                    .and_then(|schema| polars_to_arrow::convert_schema(schema.to_arrow(false)))
+                    .map(Arc::new)
+                    .ok();
+                tables.push(TableInfo {
+                    source: source_of(&fs_name_to_table_name, &name),
+                    name,
                     schema,
                 });
-                // TODO(alex): Figure out how to push this streamification down into the execution
-                // instead of post-collection.
-                executions.push((statement, stream));
             }
-            Ok(executions)
+            tables.sort_by(|a, b| a.name.cmp(&b.name));
+            Ok(tables)
+        }
+
+        async fn validate(&self, query: &str) -> anyhow::Result<Vec<ValidationIssue>> {
+            let validation = validate::check_statically(query);
+            let mut issues = validation.issues;
+            // Scans are lazy, so resolving the output schema plans the query without reading data.
+            for (index, statement) in validation.plannable {
+                let result = tokio::task::block_in_place(|| {
+                    let mut state = self.state.lock().unwrap();
+                    let transformed_stmt = self.load_tables(&mut state, &statement)?;
+                    let schema = state
+                        .context
+                        .execute(&transformed_stmt.to_string())
+                        .and_then(|frame| frame.schema())?;
+                    anyhow::Ok(schema)
+                });
+                if let Err(error) = result {
+                    issues.push(ValidationIssue {
+                        statement: Some(index),
+                        message: error.to_string(),
+                    });
+                }
+            }
+            Ok(issues)
+        }
+
+        async fn plan_statement(
+            &self,
+            statement: &ast::Statement,
+        ) -> anyhow::Result<Option<String>> {
+            // Other statements take effect as they're planned
+            if !matches!(statement, ast::Statement::Query(_)) {
+                return Ok(None);
+            }
+            tokio::task::block_in_place(|| {
+                let mut state = self.state.lock().unwrap();
+                let transformed_stmt = self.load_tables(&mut state, statement)?;
+                let frame = state.context.execute(&transformed_stmt.to_string())?;
+                Ok(Some(frame.describe_optimized_plan()?))
+            })
+        }
+    }
+
+    /// Stream `df` out of Polars as record batches, through a bridge sized by `options`
+    fn stream_frame(
+        mut df: polars::frame::DataFrame,
+        options: &PolarsOptions,
+    ) -> anyhow::Result<SendableRecordBatchStream> {
+        use polars::prelude::SerWriter as _;
+
+        let schema = Arc::new(polars_to_arrow::convert_schema(
+            df.schema().to_arrow(false),
+        )?);
+        let (arrow_client, mut polars_server) = tokio::io::duplex(options.bridge_buffer_bytes);
+        // TODO(alex): Figure out how to refactor this so it performs fewer (preferably no)
+        // copies.  Perhaps convert the Polars arrays in memory, returning a an object
+        // implmenting the stream which holds the dataframe memory?
+        let polars_writer_handle = tokio::task::spawn_blocking(move || {
+            polars_io::ipc::IpcStreamWriter::new(tokio_util::io::SyncIoBridge::new(
+                &mut polars_server,
+            ))
+            .finish(&mut df)
+        });
+        let (datafusion_tx, datafusion_rx) =
+            tokio::sync::mpsc::channel(options.bridge_batches.max(1));
+        tokio::task::spawn_blocking(move || {
+            let forwarded = forward_batches(arrow_client, &datafusion_tx);
+            if let Ok(false) = forwarded {
+                // The results were dropped, so Polars is left to fail writing to the closed pipe
+                return;
+            }
+            let written = tokio::runtime::Handle::current().block_on(polars_writer_handle);
+            // Polars failing leaves the stream cut short, which reads as its end or as a
+            // truncated message, so its error is the one to pass on
+            let error = match (forwarded, written) {
+                (_, Ok(Err(error))) => {
+                    datafusion::error::DataFusionError::External(Box::new(error))
+                }
+                (_, Err(error)) => datafusion::error::DataFusionError::External(Box::new(error)),
+                (Err(error), Ok(Ok(()))) => error,
+                (Ok(_), Ok(Ok(()))) => return,
+            };
+            let _ = datafusion_tx.blocking_send(Err(error));
+        });
+        Ok(Box::pin(StreamFromPolars {
+            stream: tokio_stream::wrappers::ReceiverStream::new(datafusion_rx),
+            schema,
+        }))
+    }
+
+    /// Read the Arrow IPC stream from `client` as record batches, sending each to `batches`.
+    /// Returns whether it was read to the end, rather than given up on with `batches` closed.
+    fn forward_batches(
+        client: tokio::io::DuplexStream,
+        batches: &tokio::sync::mpsc::Sender<
+            Result<RecordBatch, datafusion::common::DataFusionError>,
+        >,
+    ) -> Result<bool, datafusion::common::DataFusionError> {
+        let stream = datafusion::common::arrow::ipc::reader::StreamReader::try_new(
+            tokio_util::io::SyncIoBridge::new(client),
+            None,
+        )?;
+        for record_batch in stream {
+            if batches.blocking_send(Ok(record_batch?)).is_err() {
+                return Ok(false);
+            }
         }
+        Ok(true)
     }
 
     #[pin_project::pin_project]
@@ -191,184 +932,1255 @@ mod polars_engine {
     }
 }
 
+#[cfg(feature = "duckdb-engine")]
 mod duckdb_engine {
     use super::*;
 
-    pub fn default() -> DuckDbImpl {
-        DuckDbImpl::default()
+    pub fn build(builder: &EngineBuilder) -> anyhow::Result<DuckDbImpl> {
+        use anyhow::Context as _;
+
+        let mut config = duckdb::Config::default();
+        if let Some(threads) = builder.threads {
+            config = config.threads(threads as i64)?;
+        }
+        if let Some(limit) = builder.memory_limit {
+            config = config.max_memory(&format!("{}B", limit))?;
+        }
+        let connection = match &builder.duckdb_database {
+            Some(path) => duckdb::Connection::open_with_flags(path, config)
+                .with_context(|| format!("Failed to open {}", path.display()))?,
+            None => duckdb::Connection::open_in_memory_with_flags(config)?,
+        };
+        if builder.footer_cache > 0 {
+            // Keeps the footers of the Parquet files read, checked against their modification
+            // times, for as long as the database is open
+            connection.execute_batch("SET enable_object_cache = true;")?;
+        }
+        // Lets record batches be read as a table function, see `register_batches`
+        connection.register_table_function::<duckdb::vtab::arrow::ArrowVTab>("arrow")?;
+        Ok(DuckDbImpl {
+            connection: std::sync::Mutex::new(connection),
+            state: Default::default(),
+            observers: builder.observers.clone(),
+            rewriters: builder.rewriters.clone(),
+            timeout: builder.timeout,
+            row_limit: builder.row_limit,
+            batching: builder.batching,
+            path_policy: builder.path_policy.clone(),
+            load_concurrency: builder.load_concurrency,
+            running: Default::default(),
+        })
     }
 
     pub struct DuckDbImpl {
-        fs_name_to_table_name: BTreeMap<String, String>,
-        connection: duckdb::Connection,
+        state: std::sync::Mutex<State>,
+        /// The connection each statement's own is cloned from, see `connect`
+        connection: std::sync::Mutex<duckdb::Connection>,
+        observers: observer::Observers,
+        rewriters: rewrite::Rewriters,
+        timeout: Option<Duration>,
+        row_limit: Option<usize>,
+        batching: Option<BatchSizing>,
+        path_policy: PathPolicy,
+        /// Most files a statement names which are loaded at once
+        load_concurrency: usize,
+        /// Interrupts of the connections running statements, for the engine's interrupter
+        running: interrupt::Running,
     }
 
-    impl Default for DuckDbImpl {
-        fn default() -> DuckDbImpl {
-            DuckDbImpl {
-                connection: duckdb::Connection::open_in_memory().unwrap(),
-                fs_name_to_table_name: Default::default(),
-            }
-        }
+    #[derive(Default)]
+    struct State {
+        fs_name_to_table_name: BTreeMap<String, String>,
+        /// Tables registered from record batches rather than loaded from files
+        memory_tables: BTreeSet<String>,
     }
 
     impl DuckDbImpl {
-        fn load_tables(&mut self, query: &ast::Statement) -> anyhow::Result<ast::Statement> {
-            let mut rewritten = query.clone();
-            let mut new_tables = Vec::new();
-            ast::visit_relations_mut(&mut rewritten, |table| {
-                let symbol_or_file: &str = &table.0[0].value;
-                let table_name =
-                    if let Some(table_name) = self.fs_name_to_table_name.get(symbol_or_file) {
-                        table_name.to_string()
-                    } else {
-                        let table_name = derive_table_from_fs_name(symbol_or_file);
-                        new_tables.push((symbol_or_file.to_string(), table_name.clone()));
-                        table_name
-                    };
-                table.0[0].value = table.0[0].value.replace(symbol_or_file, &table_name);
-                core::ops::ControlFlow::<()>::Continue(())
+        /// A new connection to the database, so that a statement run on it can run alongside
+        /// those of other connections
+        fn connect(&self) -> anyhow::Result<duckdb::Connection> {
+            Ok(self.connection.lock().unwrap().try_clone()?)
+        }
+
+        /// Have the engine's interrupter interrupt `connection` until the registration returned
+        /// is dropped
+        fn interruptible(&self, connection: &duckdb::Connection) -> interrupt::Registration {
+            let handle = connection.interrupt_handle();
+            self.running.register(move || handle.interrupt())
+        }
+
+        /// Load the files `query` names which aren't yet, each on a clone of `connection` so that
+        /// they're loaded at once, interrupting them at `deadline`
+        fn load_tables(
+            &self,
+            connection: &duckdb::Connection,
+            query: &ast::Statement,
+            deadline: Option<timeout::Deadline>,
+        ) -> anyhow::Result<ast::Statement> {
+            use anyhow::Context as _;
+
+            let (rewritten, new_tables) = {
+                // Not held while the new tables are loaded, so another statement naming the same
+                // file at once may load it too, harmlessly replacing it
+                let state = self.state.lock().unwrap();
+                let tables = rewrite::TableNames {
+                    engine: Engine::DuckDB,
+                    fs_name_to_table_name: &state.fs_name_to_table_name,
+                    memory_tables: &state.memory_tables,
+                    path_policy: &self.path_policy,
+                };
+                rewrite::rewrite(&self.rewriters, &tables, query)?
+            };
+
+            let loads = new_tables
+                .into_iter()
+                .map(|names| Ok((names, connection.try_clone()?)))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            let handles = loads
+                .iter()
+                .map(|(_, connection)| connection.interrupt_handle())
+                .collect::<Vec<_>>();
+            let _running = loads
+                .iter()
+                .map(|(_, connection)| self.interruptible(connection))
+                .collect::<Vec<_>>();
+            let _alarm = timeout::Alarm::set(deadline, move || {
+                handles.iter().for_each(|handle| handle.interrupt())
             });
+            let loaded = load_concurrently(
+                loads,
+                self.load_concurrency,
+                |((fs_name, table_name), connection)| {
+                    let _span =
+                        tracing::info_span!("register", table = %table_name, path = %fs_name)
+                            .entered();
+                    let result = connection
+                        .execute(
+                            &format!(
+                                "CREATE OR REPLACE TABLE \"{}\" AS \
+                                 SELECT * FROM READ_PARQUET('{}', union_by_name=true);",
+                                table_name.replace('"', "\"\""),
+                                fs_name.replace('\'', "''")
+                            ),
+                            duckdb::params![],
+                        )
+                        .with_context(|| source_span::LoadFailure(fs_name.clone()));
+                    (fs_name, table_name, result)
+                },
+            );
 
-            for (fs_name, table_name) in new_tables {
-                self.connection.execute(
-                    &format!(
-                        "CREATE TABLE {} AS SELECT * FROM READ_PARQUET('{}', union_by_name=true);",
-                        table_name, fs_name
-                    ),
-                    duckdb::params![],
-                )?;
-                self.fs_name_to_table_name
+            // Those loaded are recorded even if others failed, their tables having been created
+            let mut failure = None;
+            for (fs_name, table_name, result) in loaded {
+                if let Err(error) = result {
+                    telemetry::table_load_failed(Engine::DuckDB);
+                    failure.get_or_insert(error);
+                    continue;
+                }
+                tracing::debug!(path = %fs_name, table = %table_name, "Registered table");
+                telemetry::table_loaded(Engine::DuckDB);
+                observer::registered(&self.observers, &table_name, Some(&fs_name));
+                self.state
+                    .lock()
+                    .unwrap()
+                    .fs_name_to_table_name
                     .insert(fs_name.to_string(), table_name.clone());
             }
-            Ok(rewritten)
+            match failure {
+                Some(error) => Err(error),
+                None => Ok(rewritten),
+            }
         }
     }
 
+    /// An alarm interrupting whatever `connection` is running once `deadline` passes
+    fn interrupt_at(
+        connection: &duckdb::Connection,
+        deadline: Option<timeout::Deadline>,
+    ) -> Option<timeout::Alarm> {
+        let handle = connection.interrupt_handle();
+        timeout::Alarm::set(deadline, move || handle.interrupt())
+    }
+
     #[async_trait::async_trait]
     impl EngineInterface for DuckDbImpl {
-        async fn execute(
-            &mut self,
-            query: &str,
-        ) -> anyhow::Result<Vec<(sqlparser::ast::Statement, SendableRecordBatchStream)>> {
-            let mut parser = Parser::new(&GenericDialect);
-            parser = parser.with_options(ParserOptions {
-                trailing_commas: true,
+        fn kind(&self) -> Engine {
+            Engine::DuckDB
+        }
+
+        fn current_catalog(&self) -> Option<String> {
+            self.connection
+                .lock()
+                .unwrap()
+                .query_row(
+                    "SELECT current_database() || '.' || current_schema()",
+                    [],
+                    |row| row.get(0),
+                )
+                .ok()
+        }
+
+        fn info(&self) -> EngineInfo {
+            let mut settings = self
+                .connection
+                .lock()
+                .unwrap()
+                .prepare(
+                    "SELECT name, value FROM duckdb_settings() \
+                     WHERE name IN ('threads', 'memory_limit', 'enable_object_cache') \
+                     ORDER BY name",
+                )
+                .and_then(|mut statement| {
+                    statement
+                        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+                        .collect::<Result<Vec<_>, _>>()
+                })
+                .unwrap_or_default();
+            settings.push(info::setting("timeout", self.timeout));
+            settings.push(info::setting("row_limit", self.row_limit));
+            settings.push(info::setting("batch_sizing", self.batching));
+            EngineInfo::new(Engine::DuckDB, settings)
+        }
+
+        async fn execute_statement(
+            &self,
+            statement: ast::Statement,
+            parse_time: Duration,
+            timeout: Option<Duration>,
+        ) -> anyhow::Result<QueryExecution> {
+            // TODO(alex): Table loading should be column aware so we don't load unnecessary
+            // columns here.
+            let mut metrics = ExecutionMetrics {
+                parse_time,
                 ..Default::default()
-            });
+            };
+            let connection = self.connect()?;
+            let deadline = timeout::Deadline::after(timeout);
+            let alarm = interrupt_at(&connection, deadline);
+            let running = self.interruptible(&connection);
+            let (schema, res) = tokio::task::block_in_place(|| {
+                let load_start = Instant::now();
+                let transformed_stmt = tracing::info_span!("load_tables")
+                    .in_scope(|| self.load_tables(&connection, &statement, deadline))?;
+                metrics.load_time = load_start.elapsed();
 
-            let ast = parser.try_with_sql(query)?.parse_statements()?;
-
-            let mut executions = Vec::new();
-            for statement in ast {
-                // TODO(alex): Table loading should be column aware so we don't load unnecessary
-                // columns here.
-                let res: Vec<duckdb::arrow::record_batch::RecordBatch> =
-                    tokio::task::block_in_place(|| {
-                        self.load_tables(&statement).and_then(|transformed_stmt| {
-                            let stmt = self
-                                .connection
-                                .prepare(&transformed_stmt.to_string())
-                                .map_err(|error| error.into());
-                            stmt.and_then(|mut stmt| {
-                                stmt.query_arrow([]).map(|query| query.collect())
-                            })
-                            .map_err(|error| error.into())
-                        })
+                let execution_start = Instant::now();
+                let res = tracing::info_span!("execute", statement = %transformed_stmt).in_scope(
+                    || -> anyhow::Result<_> {
+                        let mut stmt = connection.prepare(&transformed_stmt.to_string())?;
+                        // Taken from the query rather than its first batch, as a result
+                        // without rows has no batches
+                        let arrow = stmt.query_arrow([])?;
+                        Ok((arrow.get_schema(), arrow.collect::<Vec<_>>()))
+                    },
+                )?;
+                metrics.execution_time = execution_start.elapsed();
+                anyhow::Ok(res)
+            })
+            .map_err(|error| timeout::Deadline::explain(deadline, error))?;
+            drop((alarm, running));
+            let mem_stream =
+                datafusion::physical_plan::memory::MemoryStream::try_new(res, schema, None)?;
+            let metrics = Arc::new(metrics);
+            let stream = timeout::limit_stream(Box::pin(mem_stream), deadline);
+            let stream = execution_metrics::metered(
+                Engine::DuckDB,
+                stream,
+                metrics.clone(),
+                None,
+                self.row_limit,
+                self.batching,
+            );
+            // TODO(alex): Figure out how to push this streamification down into the execution
+            // instead of post-collection.
+            Ok(QueryExecution::new(statement, stream, metrics))
+        }
+
+        fn timeout(&self) -> Option<Duration> {
+            self.timeout
+        }
+
+        fn interrupter(&self) -> Interrupter {
+            self.running.interrupter()
+        }
+
+        async fn prepare(&self, sql: &str) -> anyhow::Result<PreparedStatement> {
+            let statement = prepared::parse(sql)?;
+            let connection = self.connect()?;
+            tokio::task::block_in_place(|| {
+                let mut statement = self.load_tables(&connection, &statement, None)?;
+                prepared::number_parameters(&mut statement)?;
+                // Prepared now to surface any error, and again by each execution on a connection
+                // of its own
+                let parameter_count = connection
+                    .prepare(&statement.to_string())?
+                    .parameter_count();
+                Ok(PreparedStatement::new(
+                    Engine::DuckDB,
+                    statement,
+                    parameter_count,
+                    None,
+                ))
+            })
+        }
+
+        async fn execute_prepared(
+            &self,
+            statement: &PreparedStatement,
+        ) -> anyhow::Result<QueryExecution> {
+            let values = statement
+                .values()?
+                .iter()
+                .map(to_duckdb)
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            let mut metrics = ExecutionMetrics::default();
+            let connection = self.connect()?;
+            let deadline = timeout::Deadline::after(self.timeout);
+            let alarm = interrupt_at(&connection, deadline);
+            let running = self.interruptible(&connection);
+            let (schema, res) = tokio::task::block_in_place(|| {
+                let execution_start = Instant::now();
+                let res = tracing::info_span!("execute", statement = %statement.statement)
+                    .in_scope(|| -> anyhow::Result<_> {
+                        let mut stmt = connection.prepare(&statement.sql())?;
+                        let arrow = stmt.query_arrow(duckdb::params_from_iter(values))?;
+                        Ok((arrow.get_schema(), arrow.collect::<Vec<_>>()))
                     })?;
-                let schema = res[0].schema().clone();
-                let mem_stream =
-                    datafusion::physical_plan::memory::MemoryStream::try_new(res, schema, None)?;
-                let stream: SendableRecordBatchStream = Box::pin(mem_stream);
-                // TODO(alex): Figure out how to push this streamification down into the execution
-                // instead of post-collection.
-                executions.push((statement, stream));
+                metrics.execution_time = execution_start.elapsed();
+                anyhow::Ok(res)
+            })
+            .map_err(|error| timeout::Deadline::explain(deadline, error))?;
+            drop((alarm, running));
+            let mem_stream =
+                datafusion::physical_plan::memory::MemoryStream::try_new(res, schema, None)?;
+            let metrics = Arc::new(metrics);
+            let stream = timeout::limit_stream(Box::pin(mem_stream), deadline);
+            let stream = execution_metrics::metered(
+                Engine::DuckDB,
+                stream,
+                metrics.clone(),
+                None,
+                self.row_limit,
+                self.batching,
+            );
+            Ok(QueryExecution::new(
+                statement.statement.clone(),
+                stream,
+                metrics,
+            ))
+        }
+
+        async fn execute_substrait(
+            &self,
+            plan: &[u8],
+        ) -> anyhow::Result<(SendableRecordBatchStream, Arc<ExecutionMetrics>)> {
+            use anyhow::Context as _;
+
+            let mut metrics = ExecutionMetrics::default();
+            let connection = self.connect()?;
+            let deadline = timeout::Deadline::after(self.timeout);
+            let alarm = interrupt_at(&connection, deadline);
+            let running = self.interruptible(&connection);
+            let (schema, res) = tokio::task::block_in_place(|| {
+                // Substrait is a DuckDB extension, fetched from DuckDB's repository on first use
+                connection
+                    .execute_batch("INSTALL substrait; LOAD substrait;")
+                    .context("Failed to load DuckDB's substrait extension")?;
+                let execution_start = Instant::now();
+                let res = tracing::info_span!("execute_substrait").in_scope(
+                    || -> anyhow::Result<_> {
+                        let mut stmt = connection.prepare("CALL from_substrait(?)")?;
+                        let arrow = stmt.query_arrow(duckdb::params![plan])?;
+                        Ok((arrow.get_schema(), arrow.collect::<Vec<_>>()))
+                    },
+                )?;
+                metrics.execution_time = execution_start.elapsed();
+                anyhow::Ok(res)
+            })
+            .map_err(|error| timeout::Deadline::explain(deadline, error))?;
+            drop((alarm, running));
+            let mem_stream =
+                datafusion::physical_plan::memory::MemoryStream::try_new(res, schema, None)?;
+            let metrics = Arc::new(metrics);
+            let stream = timeout::limit_stream(Box::pin(mem_stream), deadline);
+            let stream = execution_metrics::metered(
+                Engine::DuckDB,
+                stream,
+                metrics.clone(),
+                None,
+                self.row_limit,
+                self.batching,
+            );
+            Ok((stream, metrics))
+        }
+
+        #[tracing::instrument(name = "register", skip_all, fields(table = %name))]
+        async fn register_batches(
+            &self,
+            name: &str,
+            schema: arrow::datatypes::SchemaRef,
+            batches: Vec<RecordBatch>,
+        ) -> anyhow::Result<()> {
+            use duckdb::vtab::arrow::arrow_recordbatch_to_query_params;
+
+            check_batches(name, &schema, &batches)?;
+            let quoted = name.replace('"', "\"\"");
+            let connection = self.connect()?;
+            tokio::task::block_in_place(|| {
+                // The table is created from the first batch, or an empty one to fix its columns
+                // for an empty result, and the rest are appended.
+                let mut batches = batches.into_iter();
+                let first = batches
+                    .next()
+                    .unwrap_or_else(|| RecordBatch::new_empty(schema.clone()));
+                connection.execute_batch(&format!("DROP TABLE IF EXISTS \"{}\"", quoted))?;
+                connection
+                    .prepare(&format!(
+                        "CREATE TABLE \"{}\" AS SELECT * FROM arrow(?, ?)",
+                        quoted
+                    ))?
+                    .execute(arrow_recordbatch_to_query_params(first))?;
+                let mut insert = connection.prepare(&format!(
+                    "INSERT INTO \"{}\" SELECT * FROM arrow(?, ?)",
+                    quoted
+                ))?;
+                for batch in batches {
+                    insert.execute(arrow_recordbatch_to_query_params(batch))?;
+                }
+                anyhow::Ok(())
+            })?;
+            {
+                let mut state = self.state.lock().unwrap();
+                record_in_memory(
+                    &mut state.fs_name_to_table_name,
+                    &mut state.memory_tables,
+                    name,
+                );
+            }
+            tracing::debug!(table = %name, "Registered record batches");
+            observer::registered(&self.observers, name, None);
+            Ok(())
+        }
+
+        #[tracing::instrument(name = "register", skip_all, fields(table = %name, path = %path))]
+        async fn register_parquet(
+            &self,
+            name: &str,
+            path: &str,
+            options: &ParquetOptions,
+        ) -> anyhow::Result<()> {
+            use anyhow::Context as _;
+
+            self.path_policy.check(path)?;
+            let columns = match &options.columns {
+                Some(columns) => columns
+                    .iter()
+                    .map(|column| format!("\"{}\"", column.replace('"', "\"\"")))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                None => "*".to_string(),
+            };
+            let limit = options
+                .limit
+                .map(|limit| format!(" LIMIT {}", limit))
+                .unwrap_or_default();
+            let create = format!(
+                "CREATE OR REPLACE TABLE \"{}\" AS \
+                 SELECT {} FROM READ_PARQUET('{}', union_by_name=true){}",
+                name.replace('"', "\"\""),
+                columns,
+                path.replace('\'', "''"),
+                limit
+            );
+            let connection = self.connect()?;
+            tokio::task::block_in_place(|| connection.execute_batch(&create))
+                .with_context(|| format!("Failed to read {}", path))?;
+            {
+                let mut state = self.state.lock().unwrap();
+                let state = &mut *state;
+                record_source(
+                    &mut state.fs_name_to_table_name,
+                    &mut state.memory_tables,
+                    path,
+                    name,
+                );
             }
-            Ok(executions)
+            tracing::debug!(%path, table = %name, "Registered table");
+            observer::registered(&self.observers, name, Some(path));
+            Ok(())
         }
+
+        async fn tables(&self) -> anyhow::Result<Vec<TableInfo>> {
+            let connection = self.connect()?;
+            tokio::task::block_in_place(|| {
+                let names: Vec<String> = connection
+                    .prepare(
+                        "SELECT table_name FROM information_schema.tables \
+                         WHERE table_schema = current_schema() ORDER BY table_name",
+                    )?
+                    .query_map([], |row| row.get(0))?
+                    .collect::<Result<_, _>>()?;
+
+                let fs_name_to_table_name =
+                    self.state.lock().unwrap().fs_name_to_table_name.clone();
+                let mut tables = Vec::new();
+                for name in names {
+                    let schema = connection
+                        .prepare(&format!("SELECT * FROM \"{}\" LIMIT 0", name))
+                        .and_then(|mut stmt| {
+                            let schema = stmt.query_arrow([])?.get_schema();
+                            Ok(schema)
+                        })
+                        .ok();
+                    tables.push(TableInfo {
+                        source: source_of(&fs_name_to_table_name, &name),
+                        name,
+                        schema,
+                    });
+                }
+                Ok(tables)
+            })
+        }
+
+        async fn plan_statement(
+            &self,
+            statement: &ast::Statement,
+        ) -> anyhow::Result<Option<String>> {
+            if !matches!(statement, ast::Statement::Query(_)) {
+                return Ok(None);
+            }
+            let (rewritten, new_tables) = {
+                let state = self.state.lock().unwrap();
+                let tables = rewrite::TableNames {
+                    engine: Engine::DuckDB,
+                    fs_name_to_table_name: &state.fs_name_to_table_name,
+                    memory_tables: &state.memory_tables,
+                    path_policy: &self.path_policy,
+                };
+                rewrite::rewrite(&self.rewriters, &tables, statement)?
+            };
+            let connection = self.connect()?;
+            tokio::task::block_in_place(|| {
+                // Files not loaded yet are put behind views, which read only their schemas, and
+                // dropped with the transaction once the statement is planned
+                connection.execute_batch("BEGIN TRANSACTION")?;
+                let plan = (|| {
+                    for (fs_name, table_name) in &new_tables {
+                        connection.execute_batch(&format!(
+                            "CREATE VIEW \"{}\" AS \
+                             SELECT * FROM READ_PARQUET('{}', union_by_name=true);",
+                            table_name.replace('"', "\"\""),
+                            fs_name.replace('\'', "''")
+                        ))?;
+                    }
+                    let lines = connection
+                        .prepare(&format!("EXPLAIN {}", rewritten))?
+                        .query_map([], |row| row.get::<_, String>(1))?
+                        .collect::<Result<Vec<_>, _>>()?;
+                    anyhow::Ok(lines.join("\n"))
+                })();
+                connection.execute_batch("ROLLBACK")?;
+                Ok(Some(plan?))
+            })
+        }
+    }
+
+    /// `value` as a DuckDB parameter value
+    fn to_duckdb(value: &datafusion::common::ScalarValue) -> anyhow::Result<duckdb::types::Value> {
+        use datafusion::common::ScalarValue;
+        use duckdb::types::Value;
+
+        Ok(match value.clone() {
+            value if value.is_null() => Value::Null,
+            ScalarValue::Boolean(Some(value)) => Value::Boolean(value),
+            ScalarValue::Int8(Some(value)) => Value::TinyInt(value),
+            ScalarValue::Int16(Some(value)) => Value::SmallInt(value),
+            ScalarValue::Int32(Some(value)) => Value::Int(value),
+            ScalarValue::Int64(Some(value)) => Value::BigInt(value),
+            ScalarValue::UInt8(Some(value)) => Value::UTinyInt(value),
+            ScalarValue::UInt16(Some(value)) => Value::USmallInt(value),
+            ScalarValue::UInt32(Some(value)) => Value::UInt(value),
+            ScalarValue::UInt64(Some(value)) => Value::UBigInt(value),
+            ScalarValue::Float32(Some(value)) => Value::Float(value),
+            ScalarValue::Float64(Some(value)) => Value::Double(value),
+            ScalarValue::Utf8(Some(value)) | ScalarValue::LargeUtf8(Some(value)) => {
+                Value::Text(value)
+            }
+            ScalarValue::Binary(Some(value)) | ScalarValue::LargeBinary(Some(value)) => {
+                Value::Blob(value)
+            }
+            ScalarValue::Date32(Some(days)) => Value::Date32(days),
+            value => anyhow::bail!(
+                "Can't bind a {} value to a DuckDB parameter",
+                value.data_type()
+            ),
+        })
     }
 }
 
+#[cfg(feature = "datafusion-engine")]
 mod datafusion_engine {
     use super::*;
+    use datafusion::datasource::file_format::options::ParquetReadOptions;
+    use tracing::Instrument as _;
+
+    pub fn build(builder: &EngineBuilder) -> anyhow::Result<DataFusionImpl> {
+        use anyhow::Context as _;
+        use datafusion::execution::context::SessionState;
+        use datafusion::execution::runtime_env::{RuntimeConfig, RuntimeEnv};
 
-    pub fn default() -> DataFusionImpl {
-        DataFusionImpl::default()
+        let mut config = datafusion::execution::context::SessionConfig::new();
+        if let Some(threads) = builder.threads {
+            config = config.with_target_partitions(threads);
+        }
+        for (key, value) in &builder.datafusion_options {
+            config
+                .options_mut()
+                .set(key, value)
+                .with_context(|| format!("Invalid DataFusion option {}", key))?;
+        }
+        let mut runtime = RuntimeConfig::new();
+        if let Some(limit) = builder.memory_limit {
+            runtime = runtime.with_memory_limit(limit, 1.0);
+        }
+        let runtime = Arc::new(RuntimeEnv::new(runtime)?);
+        let footers = Arc::new(plan_cache::PlanCache::new("footers", builder.footer_cache));
+        let mut state = SessionState::new_with_config_rt(config, runtime.clone());
+        if builder.footer_cache > 0 || builder.mmap_parquet {
+            state = state.add_physical_optimizer_rule(Arc::new(footer_cache::ParquetReaders {
+                runtime,
+                footers: footers.clone(),
+                #[cfg(feature = "native")]
+                mapped: builder.mmap_parquet.then(Default::default),
+            }));
+        }
+        let context = datafusion::execution::context::SessionContext::new_with_state(state);
+        Ok(DataFusionImpl {
+            context,
+            diagnostics: builder.diagnostics.clone(),
+            observers: builder.observers.clone(),
+            rewriters: builder.rewriters.clone(),
+            timeout: builder.timeout,
+            row_limit: builder.row_limit,
+            batching: builder.batching,
+            path_policy: builder.path_policy.clone(),
+            load_concurrency: builder.load_concurrency,
+            mmap_parquet: builder.mmap_parquet,
+            plans: plan_cache::PlanCache::new("plans", builder.plan_cache),
+            footers,
+            state: Default::default(),
+        })
     }
 
-    #[derive(Default)]
     pub struct DataFusionImpl {
-        fs_name_to_table_name: BTreeMap<String, String>,
+        state: std::sync::Mutex<State>,
         context: datafusion::execution::context::SessionContext,
+        diagnostics: Option<DiagnosticSink>,
+        observers: observer::Observers,
+        rewriters: rewrite::Rewriters,
+        timeout: Option<Duration>,
+        row_limit: Option<usize>,
+        batching: Option<BatchSizing>,
+        path_policy: PathPolicy,
+        /// Most files a statement names which are registered at once
+        load_concurrency: usize,
+        /// Whether local Parquet files are read from memory maps of them
+        mmap_parquet: bool,
+        /// Logical plans of queries, by their text once rewritten
+        plans: plan_cache::PlanCache<datafusion::logical_expr::LogicalPlan>,
+        /// Footers of the Parquet files scanned, shared with the optimizer rule reading them
+        footers: Arc<footer_cache::Footers>,
+    }
+
+    #[derive(Default)]
+    struct State {
+        fs_name_to_table_name: BTreeMap<String, String>,
+        /// Tables registered from record batches rather than loaded from files
+        memory_tables: BTreeSet<String>,
     }
 
     impl DataFusionImpl {
-        async fn load_tables(&mut self, query: &ast::Statement) -> anyhow::Result<ast::Statement> {
-            let mut rewritten = query.clone();
-            let mut new_tables = Vec::new();
-            ast::visit_relations_mut(&mut rewritten, |table| {
-                let symbol_or_file: &str = &table.0[0].value;
-                let table_name =
-                    if let Some(table_name) = self.fs_name_to_table_name.get(symbol_or_file) {
-                        table_name.to_string()
-                    } else {
-                        let table_name = derive_table_from_fs_name(symbol_or_file);
-                        new_tables.push((symbol_or_file.to_string(), table_name.clone()));
-                        table_name
-                    };
-                table.0[0].value = table.0[0].value.replace(symbol_or_file, &table_name);
-                core::ops::ControlFlow::<()>::Continue(())
-            });
+        async fn load_tables(&self, query: &ast::Statement) -> anyhow::Result<ast::Statement> {
+            let (rewritten, new_tables) = {
+                // Not held while the new tables are registered, so another statement naming the
+                // same file at once may register it too, harmlessly replacing it
+                let state = self.state.lock().unwrap();
+                let tables = rewrite::TableNames {
+                    engine: Engine::DataFusion,
+                    fs_name_to_table_name: &state.fs_name_to_table_name,
+                    memory_tables: &state.memory_tables,
+                    path_policy: &self.path_policy,
+                };
+                rewrite::rewrite(&self.rewriters, &tables, query)?
+            };
 
-            for (fs_name, table_name) in new_tables {
-                let res = self
-                    .context
-                    .register_parquet(&table_name, &fs_name, ParquetReadOptions::default())
-                    .await;
+            // Registering a file reads its schema, so they're registered at once
+            let registered = futures::stream::iter(new_tables)
+                .map(|(fs_name, table_name)| async move {
+                    let res = self
+                        .context
+                        .register_parquet(&table_name, &fs_name, ParquetReadOptions::default())
+                        .instrument(
+                            tracing::info_span!("register", table = %table_name, path = %fs_name),
+                        )
+                        .await;
+                    (fs_name, table_name, res)
+                })
+                .buffer_unordered(self.load_concurrency.max(1))
+                .collect::<Vec<_>>()
+                .await;
+            for (fs_name, table_name, res) in registered {
                 match res {
                     Ok(()) => {
-                        self.fs_name_to_table_name
+                        tracing::debug!(path = %fs_name, table = %table_name, "Registered table");
+                        telemetry::table_loaded(Engine::DataFusion);
+                        observer::registered(&self.observers, &table_name, Some(&fs_name));
+                        self.state
+                            .lock()
+                            .unwrap()
+                            .fs_name_to_table_name
                             .insert(fs_name.to_string(), table_name.clone());
                     }
-                    Err(error) => println!(
-                        "Warning -- loading referenced parquet path ({}) failed with error: {}",
-                        fs_name, error
-                    ),
+                    Err(error) => {
+                        telemetry::table_load_failed(Engine::DataFusion);
+                        builder::warn(
+                            &self.diagnostics,
+                            format!(
+                                "Loading referenced parquet path ({}) failed with error: {}",
+                                fs_name, error
+                            ),
+                        )
+                    }
                 }
             }
             Ok(rewritten)
         }
+
+        /// `statement` planned, its logical plan that of the last time it was if it's a query.
+        /// Any other statement is run as it's planned, and may change what queries read, so the
+        /// plans kept are dropped.
+        async fn dataframe(
+            &self,
+            statement: &ast::Statement,
+        ) -> anyhow::Result<datafusion::dataframe::DataFrame> {
+            let sql = statement.to_string();
+            if !matches!(statement, ast::Statement::Query(_)) {
+                let frame = self.context.sql(&sql).await?;
+                self.plans.clear();
+                return Ok(frame);
+            }
+            let plan = match self.plans.get(&sql) {
+                Some(plan) => plan,
+                None => {
+                    let plan = self.context.state().create_logical_plan(&sql).await?;
+                    self.plans.insert(sql, plan.clone());
+                    plan
+                }
+            };
+            Ok(self.context.execute_logical_plan(plan).await?)
+        }
     }
 
     #[async_trait::async_trait]
     impl EngineInterface for DataFusionImpl {
-        async fn execute(
-            &mut self,
-            query: &str,
-        ) -> anyhow::Result<Vec<(sqlparser::ast::Statement, SendableRecordBatchStream)>> {
-            let parser = Parser::new(&GenericDialect).with_options(ParserOptions {
-                trailing_commas: true,
+        fn kind(&self) -> Engine {
+            Engine::DataFusion
+        }
+
+        fn info(&self) -> EngineInfo {
+            let config = self.context.copied_config();
+            EngineInfo::new(
+                Engine::DataFusion,
+                vec![
+                    info::setting("target_partitions", Some(config.target_partitions())),
+                    info::setting("batch_size", Some(config.batch_size())),
+                    info::setting("timeout", self.timeout),
+                    info::setting("row_limit", self.row_limit),
+                    info::setting("batch_sizing", self.batching),
+                    info::setting("mmap_parquet", Some(self.mmap_parquet)),
+                ],
+            )
+        }
+
+        fn current_catalog(&self) -> Option<String> {
+            let state = self.context.state();
+            let defaults = &state.config_options().catalog;
+            Some(format!(
+                "{}.{}",
+                defaults.default_catalog, defaults.default_schema
+            ))
+        }
+
+        async fn execute_statement(
+            &self,
+            statement: ast::Statement,
+            parse_time: Duration,
+            timeout: Option<Duration>,
+        ) -> anyhow::Result<QueryExecution> {
+            // TODO(alex): Table loading should be column aware so we don't load unnecessary
+            // columns here.
+            let deadline = timeout::Deadline::after(timeout);
+            let load_start = Instant::now();
+            let load = self
+                .load_tables(&statement)
+                .instrument(tracing::info_span!("load_tables"));
+            let transformed_stmt = timeout::run(deadline, load).await?;
+            let load_time = load_start.elapsed();
+
+            let execution_start = Instant::now();
+            let plan = async {
+                let plan = self
+                    .dataframe(&transformed_stmt)
+                    .await?
+                    .create_physical_plan()
+                    .await?;
+                anyhow::Ok(plan)
+            }
+            .instrument(tracing::info_span!("execute", statement = %transformed_stmt));
+            let plan = timeout::run(deadline, plan).await?;
+            let stream =
+                datafusion::physical_plan::execute_stream(plan.clone(), self.context.task_ctx())?;
+            let stream = timeout::limit_stream(stream, deadline);
+            let metrics = Arc::new(ExecutionMetrics {
+                parse_time,
+                load_time,
+                execution_time: execution_start.elapsed(),
                 ..Default::default()
             });
+            let stream = execution_metrics::metered(
+                Engine::DataFusion,
+                stream,
+                metrics.clone(),
+                Some(plan),
+                self.row_limit,
+                self.batching,
+            );
+            Ok(QueryExecution::new(statement, stream, metrics))
+        }
 
-            let ast = parser.try_with_sql(query)?.parse_statements()?;
+        fn timeout(&self) -> Option<Duration> {
+            self.timeout
+        }
 
-            let mut executions = Vec::new();
-            for statement in ast {
-                // TODO(alex): Table loading should be column aware so we don't load unnecessary
-                // columns here.
-                let transformed_stmt = self.load_tables(&statement).await?;
-                let stream = self
+        fn cache_stats(&self) -> Vec<CacheStats> {
+            vec![
+                plan_cache::statements().stats(),
+                self.plans.stats(),
+                self.footers.stats(),
+            ]
+        }
+
+        fn clear_caches(&self) {
+            plan_cache::statements().clear();
+            self.plans.clear();
+            self.footers.clear();
+        }
+
+        async fn prepare(&self, sql: &str) -> anyhow::Result<PreparedStatement> {
+            let statement = prepared::parse(sql)?;
+            let mut statement = self.load_tables(&statement).await?;
+            let parameter_count = prepared::number_parameters(&mut statement)?;
+            let plan = self
+                .context
+                .state()
+                .create_logical_plan(&statement.to_string())
+                .await?;
+            Ok(PreparedStatement::new(
+                Engine::DataFusion,
+                statement,
+                parameter_count,
+                Some(plan),
+            ))
+        }
+
+        async fn execute_prepared(
+            &self,
+            statement: &PreparedStatement,
+        ) -> anyhow::Result<QueryExecution> {
+            let Some(plan) = statement.plan.clone() else {
+                anyhow::bail!("The statement wasn't planned by DataFusion");
+            };
+            // Values are cast to the types inferred for their parameters, so that e.g. an i64 can
+            // be compared with an Int32 column
+            let types = plan.get_parameter_types()?;
+            let values = statement
+                .values()?
+                .into_iter()
+                .enumerate()
+                .map(
+                    |(index, value)| match types.get(&format!("${}", index + 1)) {
+                        Some(Some(data_type)) if *data_type != value.data_type() => {
+                            value.cast_to(data_type)
+                        }
+                        _ => Ok(value),
+                    },
+                )
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let deadline = timeout::Deadline::after(self.timeout);
+            let execution_start = Instant::now();
+            let plan = async {
+                let plan = plan.with_param_values(values)?;
+                let plan = self
                     .context
-                    .sql(&transformed_stmt.to_string())
+                    .execute_logical_plan(plan)
                     .await?
-                    .execute_stream()
+                    .create_physical_plan()
                     .await?;
-                executions.push((statement, stream))
+                anyhow::Ok(plan)
             }
-            Ok(executions)
+            .instrument(tracing::info_span!("execute", statement = %statement.statement));
+            let plan = timeout::run(deadline, plan).await?;
+            let stream =
+                datafusion::physical_plan::execute_stream(plan.clone(), self.context.task_ctx())?;
+            let stream = timeout::limit_stream(stream, deadline);
+            let metrics = Arc::new(ExecutionMetrics {
+                execution_time: execution_start.elapsed(),
+                ..Default::default()
+            });
+            let stream = execution_metrics::metered(
+                Engine::DataFusion,
+                stream,
+                metrics.clone(),
+                Some(plan),
+                self.row_limit,
+                self.batching,
+            );
+            Ok(QueryExecution::new(
+                statement.statement.clone(),
+                stream,
+                metrics,
+            ))
         }
+
+        #[cfg(feature = "native")]
+        async fn execute_substrait(
+            &self,
+            plan: &[u8],
+        ) -> anyhow::Result<(SendableRecordBatchStream, Arc<ExecutionMetrics>)> {
+            let parse_start = Instant::now();
+            let plan = datafusion_substrait::serializer::deserialize_bytes(plan.to_vec()).await?;
+            let parse_time = parse_start.elapsed();
+
+            let deadline = timeout::Deadline::after(self.timeout);
+            let execution_start = Instant::now();
+            let plan = async {
+                let plan = datafusion_substrait::logical_plan::consumer::from_substrait_plan(
+                    &self.context,
+                    &plan,
+                )
+                .await?;
+                let plan = self
+                    .context
+                    .execute_logical_plan(plan)
+                    .await?
+                    .create_physical_plan()
+                    .await?;
+                anyhow::Ok(plan)
+            }
+            .instrument(tracing::info_span!("execute_substrait"));
+            let plan = timeout::run(deadline, plan).await?;
+            let stream =
+                datafusion::physical_plan::execute_stream(plan.clone(), self.context.task_ctx())?;
+            let stream = timeout::limit_stream(stream, deadline);
+            let metrics = Arc::new(ExecutionMetrics {
+                parse_time,
+                execution_time: execution_start.elapsed(),
+                ..Default::default()
+            });
+            let stream = execution_metrics::metered(
+                Engine::DataFusion,
+                stream,
+                metrics.clone(),
+                Some(plan),
+                self.row_limit,
+                self.batching,
+            );
+            Ok((stream, metrics))
+        }
+
+        #[tracing::instrument(name = "register", skip_all, fields(table = %name))]
+        async fn register_batches(
+            &self,
+            name: &str,
+            schema: arrow::datatypes::SchemaRef,
+            batches: Vec<RecordBatch>,
+        ) -> anyhow::Result<()> {
+            let table = datafusion::datasource::MemTable::try_new(schema, vec![batches])?;
+            self.context.deregister_table(name)?;
+            self.context.register_table(name, Arc::new(table))?;
+            {
+                let mut state = self.state.lock().unwrap();
+                record_in_memory(
+                    &mut state.fs_name_to_table_name,
+                    &mut state.memory_tables,
+                    name,
+                );
+            }
+            // Plans may have read a table of the same name
+            self.plans.clear();
+            tracing::debug!(table = %name, "Registered record batches");
+            observer::registered(&self.observers, name, None);
+            Ok(())
+        }
+
+        #[tracing::instrument(name = "register", skip_all, fields(table = %name))]
+        async fn register_table_provider(
+            &self,
+            name: &str,
+            provider: Arc<dyn datafusion::datasource::TableProvider>,
+        ) -> anyhow::Result<()> {
+            self.context.deregister_table(name)?;
+            self.context.register_table(name, provider)?;
+            {
+                // Not read from a file callisto knows of, so treated as one registered in memory
+                let mut state = self.state.lock().unwrap();
+                record_in_memory(
+                    &mut state.fs_name_to_table_name,
+                    &mut state.memory_tables,
+                    name,
+                );
+            }
+            // Plans may have read a table of the same name
+            self.plans.clear();
+            tracing::debug!(table = %name, "Registered table provider");
+            observer::registered(&self.observers, name, None);
+            Ok(())
+        }
+
+        #[tracing::instrument(name = "register", skip_all, fields(table = %name, path = %path))]
+        async fn register_parquet(
+            &self,
+            name: &str,
+            path: &str,
+            options: &ParquetOptions,
+        ) -> anyhow::Result<()> {
+            use anyhow::Context as _;
+
+            self.path_policy.check(path)?;
+            let mut frame = self
+                .context
+                .read_parquet(path, ParquetReadOptions::default())
+                .await
+                .with_context(|| format!("Failed to read {}", path))?;
+            if let Some(columns) = &options.columns {
+                let columns = columns.iter().map(String::as_str).collect::<Vec<_>>();
+                frame = frame.select_columns(&columns)?;
+            }
+            if options.limit.is_some() {
+                frame = frame.limit(0, options.limit)?;
+            }
+            self.context.deregister_table(name)?;
+            self.context.register_table(name, frame.into_view())?;
+            {
+                let mut state = self.state.lock().unwrap();
+                let state = &mut *state;
+                record_source(
+                    &mut state.fs_name_to_table_name,
+                    &mut state.memory_tables,
+                    path,
+                    name,
+                );
+            }
+            // Plans may have read a table of the same name
+            self.plans.clear();
+            tracing::debug!(%path, table = %name, "Registered table");
+            observer::registered(&self.observers, name, Some(path));
+            Ok(())
+        }
+
+        async fn tables(&self) -> anyhow::Result<Vec<TableInfo>> {
+            let state = self.context.state();
+            let defaults = &state.config_options().catalog;
+            let mut names = self
+                .context
+                .catalog(&defaults.default_catalog)
+                .and_then(|catalog| catalog.schema(&defaults.default_schema))
+                .map(|schema| schema.table_names())
+                .unwrap_or_default();
+            names.sort();
+
+            let fs_name_to_table_name = self.state.lock().unwrap().fs_name_to_table_name.clone();
+            let mut tables = Vec::new();
+            for name in names {
+                let schema = self
+                    .context
+                    .table_provider(datafusion::sql::TableReference::bare(name.clone()))
+                    .await
+                    .ok()
+                    .map(|provider| provider.schema());
+                tables.push(TableInfo {
+                    source: source_of(&fs_name_to_table_name, &name),
+                    name,
+                    schema,
+                });
+            }
+            Ok(tables)
+        }
+
+        async fn validate(&self, query: &str) -> anyhow::Result<Vec<ValidationIssue>> {
+            let validation = validate::check_statically(query);
+            let mut issues = validation.issues;
+            // Registering Parquet files only reads their schemas, so DataFusion can cheaply plan
+            // any query whose sources all resolved, catching type errors the static checks miss.
+            for (index, statement) in validation.plannable {
+                let transformed_stmt = self.load_tables(&statement).await?;
+                if let Err(error) = self
+                    .context
+                    .state()
+                    .create_logical_plan(&transformed_stmt.to_string())
+                    .await
+                {
+                    issues.push(ValidationIssue {
+                        statement: Some(index),
+                        message: error.to_string(),
+                    });
+                }
+            }
+            Ok(issues)
+        }
+
+        async fn plan_statement(
+            &self,
+            statement: &ast::Statement,
+        ) -> anyhow::Result<Option<String>> {
+            // Other statements, e.g. CREATE TABLE ... AS, are run as they're planned
+            if !matches!(statement, ast::Statement::Query(_)) {
+                return Ok(None);
+            }
+            let transformed_stmt = self.load_tables(statement).await?;
+            let plan = self
+                .context
+                .sql(&transformed_stmt.to_string())
+                .await?
+                .create_physical_plan()
+                .await?;
+            Ok(Some(
+                datafusion::physical_plan::displayable(plan.as_ref())
+                    .indent(true)
+                    .to_string(),
+            ))
+        }
+    }
+}
+
+/// A new ID for a statement about to run, telling its tracing spans apart from those of the
+/// statements running alongside it
+fn next_statement_id() -> u64 {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    static NEXT: AtomicU64 = AtomicU64::new(1);
+    NEXT.fetch_add(1, Ordering::Relaxed)
+}
+
+fn parse(query: &str) -> Result<Vec<ast::Statement>, sqlparser::parser::ParserError> {
+    Parser::new(&GenericDialect)
+        .with_options(ParserOptions {
+            trailing_commas: true,
+            ..Default::default()
+        })
+        .try_with_sql(query)?
+        .parse_statements()
+}
+
+/// Whether `name` is a table name previously derived for a loaded file
+fn is_registered_name(fs_name_to_table_name: &BTreeMap<String, String>, name: &str) -> bool {
+    fs_name_to_table_name
+        .values()
+        .any(|table_name| table_name == name)
+}
+
+/// The file from which the table `table_name` was loaded, if any
+fn source_of(fs_name_to_table_name: &BTreeMap<String, String>, table_name: &str) -> Option<String> {
+    fs_name_to_table_name
+        .iter()
+        .find(|(_, name)| name.as_str() == table_name)
+        .map(|(fs_name, _)| fs_name.clone())
+}
+
+/// Note `table_name` as having been loaded from `fs_name`, replacing whatever it was before
+fn record_source(
+    fs_name_to_table_name: &mut BTreeMap<String, String>,
+    memory_tables: &mut BTreeSet<String>,
+    fs_name: &str,
+    table_name: &str,
+) {
+    fs_name_to_table_name.retain(|_, name| name != table_name);
+    fs_name_to_table_name.insert(fs_name.to_string(), table_name.to_string());
+    memory_tables.remove(table_name);
+}
+
+/// Note `table_name` as having been registered in memory, replacing whatever file it was loaded
+/// from before
+fn record_in_memory(
+    fs_name_to_table_name: &mut BTreeMap<String, String>,
+    memory_tables: &mut BTreeSet<String>,
+    table_name: &str,
+) {
+    fs_name_to_table_name.retain(|_, name| name != table_name);
+    memory_tables.insert(table_name.to_string());
+}
+
+/// Fail unless each of `batches` has the columns of `schema`, as the batches of a table called
+/// `name` must
+#[cfg(any(feature = "polars-engine", feature = "duckdb-engine"))]
+fn check_batches(
+    name: &str,
+    schema: &arrow::datatypes::Schema,
+    batches: &[RecordBatch],
+) -> anyhow::Result<()> {
+    for batch in batches {
+        if !schema.contains(&batch.schema()) {
+            anyhow::bail!(
+                "A batch registered as {} doesn't have the table's columns: {} rather than {}",
+                name,
+                batch.schema(),
+                schema
+            );
+        }
+    }
+    Ok(())
+}
+
+/// `load` applied to each of `items` on up to `concurrency` threads at once, e.g. to read the
+/// schemas of the files a statement names, their results in the order of the items
+#[cfg(any(feature = "polars-engine", feature = "duckdb-engine"))]
+fn load_concurrently<T: Send, R: Send>(
+    items: Vec<T>,
+    concurrency: usize,
+    load: impl Fn(T) -> R + Sync,
+) -> Vec<R> {
+    let threads = concurrency.min(items.len());
+    if threads <= 1 {
+        return items.into_iter().map(load).collect();
     }
+    let items = std::sync::Mutex::new(items.into_iter().enumerate());
+    let next = || items.lock().unwrap().next();
+    let parent = tracing::Span::current();
+    let mut results = std::thread::scope(|scope| {
+        let workers = (0..threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let _entered = parent.enter();
+                    let mut results = Vec::new();
+                    while let Some((index, item)) = next() {
+                        results.push((index, load(item)));
+                    }
+                    results
+                })
+            })
+            .collect::<Vec<_>>();
+        workers
+            .into_iter()
+            .flat_map(|worker| {
+                worker
+                    .join()
+                    .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+            })
+            .collect::<Vec<_>>()
+    });
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
 }
 
 fn derive_table_from_fs_name(fs_name: &str) -> String {
@@ -383,3 +2195,148 @@ fn derive_table_from_fs_name(fs_name: &str) -> String {
             .replace("*", "_")
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use futures::stream::TryStreamExt as _;
+
+    use super::*;
+
+    /// The rows of the last statement of `query` on `engine`, with their schema
+    async fn query(
+        engine: &dyn EngineInterface,
+        query: &str,
+    ) -> anyhow::Result<(arrow::datatypes::SchemaRef, Vec<RecordBatch>)> {
+        let execution = engine.execute(query).await?.pop().unwrap();
+        let batches = execution.stream.try_collect().await?;
+        Ok((execution.schema, batches))
+    }
+
+    fn rows(batches: &[RecordBatch]) -> usize {
+        batches.iter().map(RecordBatch::num_rows).sum()
+    }
+
+    /// A batch of the numbers 1 and 2 in a column `n`
+    fn numbers() -> RecordBatch {
+        let schema = Arc::new(arrow::datatypes::Schema::new(vec![
+            arrow::datatypes::Field::new("n", arrow::datatypes::DataType::Int64, false),
+        ]));
+        RecordBatch::try_new(
+            schema,
+            vec![Arc::new(arrow::array::Int64Array::from(vec![1, 2]))],
+        )
+        .unwrap()
+    }
+
+    #[cfg(feature = "native")]
+    fn write_parquet(path: &std::path::Path, batch: &RecordBatch) {
+        let file = std::fs::File::create(path).unwrap();
+        let mut writer = parquet::arrow::ArrowWriter::try_new(file, batch.schema(), None).unwrap();
+        writer.write(batch).unwrap();
+        writer.close().unwrap();
+    }
+
+    #[cfg(all(
+        feature = "datafusion-engine",
+        feature = "duckdb-engine",
+        feature = "polars-engine",
+        feature = "native"
+    ))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn registered_batches_replace_tables_loaded_from_files() {
+        let batch = numbers();
+        let schema = batch.schema();
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("numbers.parquet");
+        write_parquet(&path, &batch);
+        let path = path.to_str().unwrap();
+
+        for kind in [Engine::Polars, Engine::DuckDB, Engine::DataFusion] {
+            let engine = kind.new().unwrap();
+            engine
+                .register_parquet("numbers", path, &ParquetOptions::default())
+                .await
+                .unwrap();
+            engine
+                .register_batches("numbers", schema.clone(), vec![batch.clone()])
+                .await
+                .unwrap();
+            let tables = engine.tables().await.unwrap();
+            let numbers = tables.iter().find(|table| table.name == "numbers").unwrap();
+            assert_eq!(numbers.source, None, "{:?} kept the file's source", kind);
+        }
+    }
+
+    #[cfg(feature = "duckdb-engine")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn duckdb_results_without_rows() {
+        let engine = Engine::DuckDB.new().unwrap();
+        let (schema, batches) = query(engine.as_ref(), "SELECT 1 AS n WHERE false")
+            .await
+            .unwrap();
+        assert_eq!(schema.field(0).name(), "n");
+        assert_eq!(rows(&batches), 0);
+
+        // As `.open` checks a table it registered
+        let batch = numbers();
+        engine
+            .register_batches("numbers", batch.schema(), vec![batch])
+            .await
+            .unwrap();
+        let (schema, batches) = query(engine.as_ref(), "SELECT * FROM \"numbers\" LIMIT 0")
+            .await
+            .unwrap();
+        assert_eq!(schema.field(0).name(), "n");
+        assert_eq!(rows(&batches), 0);
+    }
+
+    #[cfg(all(feature = "duckdb-engine", feature = "native"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn duckdb_loads_files_whose_names_need_quoting() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("o'brien.parquet");
+        write_parquet(&path, &numbers());
+        let sql = format!("SELECT * FROM \"{}\"", path.display());
+
+        // Planned behind a view, then loaded as a table
+        let engine = Engine::DuckDB.new().unwrap();
+        let runs = engine.dry_run(&sql).await.unwrap();
+        assert!(runs[0].plan.is_some());
+        let (_, batches) = query(engine.as_ref(), &sql).await.unwrap();
+        assert_eq!(rows(&batches), 2);
+    }
+
+    #[cfg(feature = "duckdb-engine")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn duckdb_statements_are_interrupted() {
+        let engine: Arc<dyn EngineInterface> = Arc::from(Engine::DuckDB.new().unwrap());
+        let started = Instant::now();
+        let running = tokio::spawn({
+            let engine = engine.clone();
+            async move {
+                // Far more rows than can be joined in the time the test takes
+                let sql = "SELECT count(*) FROM range(1000000000) a, range(1000000000) b \
+                           WHERE a.range + b.range = 7";
+                engine.execute(sql).await.map(drop)
+            }
+        });
+        // Interrupted until it stops, as it may not have started running on the first
+        let interrupter = engine.interrupter();
+        let outcome = loop {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            interrupter.interrupt();
+            if running.is_finished() {
+                break running.await.unwrap();
+            }
+            assert!(
+                started.elapsed() < Duration::from_secs(30),
+                "The statement wasn't interrupted"
+            );
+        };
+        assert!(outcome.is_err());
+
+        // The engine runs statements as before once one has been interrupted
+        let (_, batches) = query(engine.as_ref(), "SELECT 1").await.unwrap();
+        assert_eq!(rows(&batches), 1);
+    }
+}
@@ -0,0 +1,146 @@
+use datafusion::datasource::physical_plan::ParquetExec;
+use datafusion::physical_plan::ExecutionPlan;
+use sqlparser::ast;
+
+use crate::{ExecutionMetrics, StatementReferences};
+
+/// What a statement read and wrote, for tools tracking which datasets depend on which; see
+/// [`EngineInterface::lineage`](crate::EngineInterface::lineage)
+#[derive(Clone, Debug, Default, PartialEq, serde::Serialize)]
+pub struct Lineage {
+    pub statement: String,
+    /// Relations read, excluding common table expressions
+    pub inputs: Vec<LineageInput>,
+    /// Columns the statement refers to, as `table.column` where the table can be told
+    pub columns: Vec<String>,
+    /// The parts of files the engine scanned, if it reports them
+    pub reads: Vec<FileRead>,
+    /// Bytes read from storage, if the engine reports it
+    pub bytes_scanned: Option<usize>,
+    /// Tables and files written, by `COPY ... TO`, `CREATE TABLE`, `CREATE VIEW`, or `INSERT`
+    pub outputs: Vec<String>,
+}
+
+/// A relation a statement read
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize)]
+pub struct LineageInput {
+    /// The relation as the statement names it, a table or a path
+    pub name: String,
+    /// Name of the table the engine read it as
+    pub table: String,
+    /// File, glob, or directory the table was loaded from, if it wasn't held in memory
+    pub source: Option<String>,
+}
+
+/// A range of a file scanned for a statement, and the columns read of it
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize)]
+pub struct FileRead {
+    /// URI of the file, e.g. `file:///data/users.parquet`
+    pub path: String,
+    /// Offset of the first byte of the range
+    pub start: u64,
+    /// Offset just past its last byte, the size of the file if the whole of it was scanned
+    pub end: u64,
+    pub columns: Vec<String>,
+}
+
+/// The lineage of `statement`, its relations resolved as `references` and its scans measured in
+/// `metrics`
+pub(crate) fn lineage(
+    statement: &ast::Statement,
+    references: StatementReferences,
+    metrics: &ExecutionMetrics,
+) -> Lineage {
+    let columns = references
+        .projected
+        .iter()
+        .chain(&references.filtered)
+        .map(|column| match &column.table {
+            Some(table) => format!("{}.{}", table, column.column),
+            None => column.column.clone(),
+        })
+        .collect::<std::collections::BTreeSet<_>>();
+    Lineage {
+        statement: statement.to_string(),
+        inputs: references
+            .relations
+            .into_iter()
+            .map(|(name, relation)| LineageInput {
+                name,
+                table: relation.table,
+                source: relation.source,
+            })
+            .collect(),
+        columns: columns.into_iter().collect(),
+        reads: metrics.reads().unwrap_or_default(),
+        bytes_scanned: metrics.bytes_scanned(),
+        outputs: outputs(statement),
+    }
+}
+
+/// The tables and files `statement` writes
+fn outputs(statement: &ast::Statement) -> Vec<String> {
+    match statement {
+        ast::Statement::Copy {
+            to: true,
+            target: ast::CopyTarget::File { filename },
+            ..
+        } => vec![filename.clone()],
+        ast::Statement::CreateTable { name, .. } | ast::Statement::CreateView { name, .. } => {
+            vec![name.to_string()]
+        }
+        ast::Statement::Insert(insert) => vec![insert.table_name.to_string()],
+        _ => Vec::new(),
+    }
+}
+
+/// The ranges of files the Parquet scans of `plan` were given to read, with the columns each
+/// projects
+pub(crate) fn file_reads(plan: &dyn ExecutionPlan) -> Vec<FileRead> {
+    let mut reads = plan
+        .children()
+        .iter()
+        .flat_map(|child| file_reads(child.as_ref()))
+        .collect::<Vec<_>>();
+    let Some(scan) = plan.as_any().downcast_ref::<ParquetExec>() else {
+        return reads;
+    };
+    let config = scan.base_config();
+    // Projections index the file's columns, then those of the table's partitioning
+    let names = config
+        .file_schema
+        .fields()
+        .iter()
+        .map(|field| field.name().clone())
+        .chain(
+            config
+                .table_partition_cols
+                .iter()
+                .map(|field| field.name().clone()),
+        )
+        .collect::<Vec<_>>();
+    let columns = match &config.projection {
+        Some(projection) => projection
+            .iter()
+            .filter_map(|index| names.get(*index).cloned())
+            .collect(),
+        None => names,
+    };
+    for file in config.file_groups.iter().flatten() {
+        let (start, end) = match &file.range {
+            Some(range) => (range.start as u64, range.end as u64),
+            None => (0, file.object_meta.size as u64),
+        };
+        reads.push(FileRead {
+            path: format!(
+                "{}{}",
+                config.object_store_url.as_str(),
+                file.object_meta.location
+            ),
+            start,
+            end,
+            columns: columns.clone(),
+        });
+    }
+    reads
+}
@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use bytes::Bytes;
+use datafusion::datasource::physical_plan::parquet::ParquetFileMetrics;
+use datafusion::datasource::physical_plan::{FileMeta, ParquetFileReaderFactory};
+use datafusion::error::Result;
+use datafusion::parquet::arrow::async_reader::AsyncFileReader;
+use datafusion::parquet::errors::ParquetError;
+use datafusion::parquet::file::footer::{decode_footer, decode_metadata};
+use datafusion::parquet::file::metadata::ParquetMetaData;
+use datafusion::parquet::file::FOOTER_SIZE;
+use datafusion::physical_plan::metrics::ExecutionPlanMetricsSet;
+use futures::future::{BoxFuture, FutureExt as _};
+use memmap2::Mmap;
+
+/// Most files kept mapped at once
+const MAPPED_FILES: usize = 256;
+
+/// Local Parquet files mapped into memory, by path, each kept with the size and modification time
+/// it was mapped at and mapped again once they change. Up to [`MAPPED_FILES`] are kept mapped, the
+/// least recently read unmapped first, so that scanning a file again reads it from memory already
+/// mapped. One is only unmapped once the scans reading it are done with it.
+#[derive(Debug, Default)]
+pub(crate) struct MappedFiles {
+    maps: Mutex<Maps>,
+}
+
+#[derive(Debug, Default)]
+struct Maps {
+    files: HashMap<PathBuf, Mapped>,
+    /// Files mapped or read so far, counting each time a file is
+    reads: u64,
+}
+
+#[derive(Debug)]
+struct Mapped {
+    version: String,
+    /// The whole of the file, slices of which share its map
+    map: Bytes,
+    /// When it was last read, in `Maps::reads`
+    read: u64,
+}
+
+impl MappedFiles {
+    /// The map of the file at `path`, as of `version`
+    fn map(&self, path: PathBuf, version: String, size: usize) -> std::io::Result<Bytes> {
+        let mut maps = self.maps.lock().unwrap();
+        maps.reads += 1;
+        let read = maps.reads;
+        if let Some(mapped) = maps.files.get_mut(&path) {
+            if mapped.version == version {
+                mapped.read = read;
+                return Ok(mapped.map.clone());
+            }
+        }
+        let file = std::fs::File::open(&path)?;
+        // Safety: Parquet files aren't written in place, and one replaced since being mapped is
+        // told by its new size or modification time
+        let map = unsafe { Mmap::map(&file)? };
+        if map.len() != size {
+            return Err(std::io::Error::other(format!(
+                "{} changed while being mapped",
+                path.display()
+            )));
+        }
+        let map = Bytes::from_owner(map);
+        maps.files.insert(
+            path,
+            Mapped {
+                version,
+                map: map.clone(),
+                read,
+            },
+        );
+        if maps.files.len() > MAPPED_FILES {
+            let least_recent = maps
+                .files
+                .iter()
+                .min_by_key(|(_, mapped)| mapped.read)
+                .map(|(path, _)| path.clone());
+            if let Some(path) = least_recent {
+                maps.files.remove(&path);
+            }
+        }
+        Ok(map)
+    }
+}
+
+/// Readers of local Parquet files which read them from their maps in `files`
+#[derive(Debug)]
+pub(crate) struct MmapReaders {
+    pub(crate) files: Arc<MappedFiles>,
+}
+
+impl ParquetFileReaderFactory for MmapReaders {
+    fn create_reader(
+        &self,
+        partition_index: usize,
+        file_meta: FileMeta,
+        _metadata_size_hint: Option<usize>,
+        metrics: &ExecutionPlanMetricsSet,
+    ) -> Result<Box<dyn AsyncFileReader + Send>> {
+        let file = &file_meta.object_meta;
+        // Local paths are those of the local file system's object store, made relative to its root
+        let path = PathBuf::from(format!("/{}", file.location));
+        let version = format!("{}:{}", file.last_modified.to_rfc3339(), file.size);
+        let map = self.files.map(path, version, file.size)?;
+        let metrics = ParquetFileMetrics::new(partition_index, file.location.as_ref(), metrics);
+        Ok(Box::new(MmapReader { map, metrics }))
+    }
+}
+
+struct MmapReader {
+    /// The whole of the file
+    map: Bytes,
+    metrics: ParquetFileMetrics,
+}
+
+impl MmapReader {
+    /// The bytes of the file in `range`, a view of its map rather than a copy
+    fn bytes(&self, range: Range<usize>) -> datafusion::parquet::errors::Result<Bytes> {
+        if range.start > range.end || range.end > self.map.len() {
+            return Err(ParquetError::EOF(format!(
+                "Bytes {:?} are past the end of the file, of {} bytes",
+                range,
+                self.map.len()
+            )));
+        }
+        self.metrics.bytes_scanned.add(range.len());
+        Ok(self.map.slice(range))
+    }
+}
+
+impl AsyncFileReader for MmapReader {
+    fn get_bytes(
+        &mut self,
+        range: Range<usize>,
+    ) -> BoxFuture<'_, datafusion::parquet::errors::Result<Bytes>> {
+        futures::future::ready(self.bytes(range)).boxed()
+    }
+
+    fn get_byte_ranges(
+        &mut self,
+        ranges: Vec<Range<usize>>,
+    ) -> BoxFuture<'_, datafusion::parquet::errors::Result<Vec<Bytes>>> {
+        let bytes = ranges.into_iter().map(|range| self.bytes(range)).collect();
+        futures::future::ready(bytes).boxed()
+    }
+
+    fn get_metadata(
+        &mut self,
+    ) -> BoxFuture<'_, datafusion::parquet::errors::Result<Arc<ParquetMetaData>>> {
+        futures::future::ready(footer(&self.map).map(Arc::new)).boxed()
+    }
+}
+
+/// The footer of the Parquet file `file`
+fn footer(file: &[u8]) -> datafusion::parquet::errors::Result<ParquetMetaData> {
+    let too_small = || ParquetError::EOF(format!("A file of {} bytes is too small", file.len()));
+    let end = file.len().checked_sub(FOOTER_SIZE).ok_or_else(too_small)?;
+    let metadata_len = decode_footer(file[end..].try_into().unwrap())?;
+    let start = end.checked_sub(metadata_len).ok_or_else(too_small)?;
+    decode_metadata(&file[start..end])
+}
@@ -0,0 +1,276 @@
+use core::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+use arrow::record_batch::RecordBatch;
+use datafusion::physical_plan::SendableRecordBatchStream;
+use futures::stream::{Stream, StreamExt as _};
+use sqlparser::ast;
+
+use crate::{
+    CacheStats, Engine, EngineInfo, EngineInterface, ExecutionMetrics, ExecutionStats,
+    ParquetOptions, PreparedStatement, QueryExecution, TableInfo, ValidationIssue,
+};
+
+/// Told of what an engine does as it does it, e.g. for auditing, progress reporting, or cost
+/// guards; see [`crate::EngineBuilder::observer`].
+///
+/// Every method does nothing by default, so an observer implements only those it needs. They're
+/// called on the task executing or reading the results, so should be quick.
+pub trait ExecutionObserver: Send + Sync {
+    /// `statement` is about to run. An error refuses to run it, failing it with that error.
+    fn on_statement_start(&self, statement: &ast::Statement) -> anyhow::Result<()> {
+        let _ = statement;
+        Ok(())
+    }
+
+    /// A table called `name` was registered, loaded from the file `source` if it wasn't
+    /// registered from memory
+    fn on_table_registered(&self, name: &str, source: Option<&str>) {
+        let _ = (name, source);
+    }
+
+    /// `statement` produced `batch`. An error stops the statement, its results ending with that
+    /// error.
+    fn on_batch_produced(
+        &self,
+        statement: &ast::Statement,
+        batch: &RecordBatch,
+    ) -> anyhow::Result<()> {
+        let _ = (statement, batch);
+        Ok(())
+    }
+
+    /// `statement` has ended, its results read to the end or dropped part way, or it failed
+    /// before producing any
+    fn on_statement_end(
+        &self,
+        statement: &ast::Statement,
+        outcome: Result<&ExecutionStats, &anyhow::Error>,
+    ) {
+        let _ = (statement, outcome);
+    }
+}
+
+pub(crate) type Observers = Vec<Arc<dyn ExecutionObserver>>;
+
+/// Tell `observers` that the table `name` was registered
+pub(crate) fn registered(observers: &Observers, name: &str, source: Option<&str>) {
+    for observer in observers {
+        observer.on_table_registered(name, source);
+    }
+}
+
+/// An engine whose statements are reported to `observers` as they run
+pub(crate) struct Observed {
+    pub(crate) engine: Box<dyn EngineInterface>,
+    pub(crate) observers: Observers,
+}
+
+#[async_trait::async_trait]
+impl EngineInterface for Observed {
+    async fn execute_statement(
+        &self,
+        statement: ast::Statement,
+        parse_time: Duration,
+        timeout: Option<Duration>,
+    ) -> anyhow::Result<QueryExecution> {
+        for observer in &self.observers {
+            observer.on_statement_start(&statement)?;
+        }
+        match self
+            .engine
+            .execute_statement(statement.clone(), parse_time, timeout)
+            .await
+        {
+            Ok(execution) => Ok(self.observe(execution)),
+            Err(error) => {
+                for observer in &self.observers {
+                    observer.on_statement_end(&statement, Err(&error));
+                }
+                Err(error)
+            }
+        }
+    }
+
+    async fn prepare(&self, sql: &str) -> anyhow::Result<PreparedStatement> {
+        self.engine.prepare(sql).await
+    }
+
+    async fn execute_prepared(
+        &self,
+        statement: &PreparedStatement,
+    ) -> anyhow::Result<QueryExecution> {
+        for observer in &self.observers {
+            observer.on_statement_start(&statement.statement)?;
+        }
+        match self.engine.execute_prepared(statement).await {
+            Ok(execution) => Ok(self.observe(execution)),
+            Err(error) => {
+                for observer in &self.observers {
+                    observer.on_statement_end(&statement.statement, Err(&error));
+                }
+                Err(error)
+            }
+        }
+    }
+
+    fn kind(&self) -> Engine {
+        self.engine.kind()
+    }
+
+    fn info(&self) -> EngineInfo {
+        self.engine.info()
+    }
+
+    fn timeout(&self) -> Option<Duration> {
+        self.engine.timeout()
+    }
+
+    async fn tables(&self) -> anyhow::Result<Vec<TableInfo>> {
+        self.engine.tables().await
+    }
+
+    async fn register_batches(
+        &self,
+        name: &str,
+        schema: arrow::datatypes::SchemaRef,
+        batches: Vec<RecordBatch>,
+    ) -> anyhow::Result<()> {
+        self.engine.register_batches(name, schema, batches).await
+    }
+
+    async fn register_parquet(
+        &self,
+        name: &str,
+        path: &str,
+        options: &ParquetOptions,
+    ) -> anyhow::Result<()> {
+        self.engine.register_parquet(name, path, options).await
+    }
+
+    #[cfg(feature = "polars-engine")]
+    async fn register_lazy_frame(
+        &self,
+        name: &str,
+        frame: polars::prelude::LazyFrame,
+    ) -> anyhow::Result<()> {
+        self.engine.register_lazy_frame(name, frame).await
+    }
+
+    async fn register_table_provider(
+        &self,
+        name: &str,
+        provider: Arc<dyn datafusion::datasource::TableProvider>,
+    ) -> anyhow::Result<()> {
+        self.engine.register_table_provider(name, provider).await
+    }
+
+    async fn execute_substrait(
+        &self,
+        plan: &[u8],
+    ) -> anyhow::Result<(SendableRecordBatchStream, Arc<ExecutionMetrics>)> {
+        self.engine.execute_substrait(plan).await
+    }
+
+    async fn validate(&self, query: &str) -> anyhow::Result<Vec<ValidationIssue>> {
+        self.engine.validate(query).await
+    }
+
+    async fn plan_statement(&self, statement: &ast::Statement) -> anyhow::Result<Option<String>> {
+        self.engine.plan_statement(statement).await
+    }
+
+    fn cache_stats(&self) -> Vec<CacheStats> {
+        self.engine.cache_stats()
+    }
+
+    fn clear_caches(&self) {
+        self.engine.clear_caches()
+    }
+}
+
+impl Observed {
+    /// `execution`, its batches and end reported to the observers as its stream is read
+    fn observe(&self, execution: QueryExecution) -> QueryExecution {
+        let stream = ObservedStream {
+            stream: Some(execution.stream),
+            schema: execution.schema.clone(),
+            statement: execution.statement.clone(),
+            metrics: execution.metrics.clone(),
+            observers: self.observers.clone(),
+        };
+        QueryExecution {
+            stream: Box::pin(stream),
+            ..execution
+        }
+    }
+}
+
+struct ObservedStream {
+    /// The statement's results, until they've ended
+    stream: Option<SendableRecordBatchStream>,
+    schema: arrow::datatypes::SchemaRef,
+    statement: ast::Statement,
+    metrics: Arc<ExecutionMetrics>,
+    observers: Observers,
+}
+
+impl ObservedStream {
+    /// Drop the results, if they haven't been, and report the statement's end
+    fn end(&mut self) {
+        if let Some(stream) = self.stream.take() {
+            // Dropped first so the metrics are final, however far the results were read
+            drop(stream);
+            if let Some(stats) = self.metrics.stats() {
+                for observer in &self.observers {
+                    observer.on_statement_end(&self.statement, Ok(&stats));
+                }
+            }
+        }
+    }
+}
+
+impl datafusion::physical_plan::RecordBatchStream for ObservedStream {
+    fn schema(&self) -> arrow::datatypes::SchemaRef {
+        self.schema.clone()
+    }
+}
+
+impl Stream for ObservedStream {
+    type Item = Result<RecordBatch, datafusion::common::DataFusionError>;
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut futures::task::Context<'_>,
+    ) -> futures::task::Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let Some(stream) = &mut this.stream else {
+            return futures::task::Poll::Ready(None);
+        };
+        let poll = stream.poll_next_unpin(cx);
+        match &poll {
+            futures::task::Poll::Ready(Some(Ok(batch))) => {
+                let refused = this
+                    .observers
+                    .iter()
+                    .find_map(|observer| observer.on_batch_produced(&this.statement, batch).err());
+                if let Some(error) = refused {
+                    this.end();
+                    return futures::task::Poll::Ready(Some(Err(
+                        datafusion::common::DataFusionError::External(error.into()),
+                    )));
+                }
+            }
+            futures::task::Poll::Ready(None) => this.end(),
+            _ => {}
+        }
+        poll
+    }
+}
+
+impl Drop for ObservedStream {
+    fn drop(&mut self) {
+        self.end();
+    }
+}
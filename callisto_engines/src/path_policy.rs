@@ -0,0 +1,364 @@
+use std::path::{Component, Path, PathBuf};
+
+use crate::CallistoError;
+
+/// Which directories and URIs files may be loaded from as tables, so that a served or embedded
+/// engine reads only the datasets it's meant to; see [`crate::EngineBuilder::path_policy`].
+///
+/// Entries are local directories, e.g. `/data`, URI prefixes, e.g. `s3://bucket/public`, or whole
+/// URI schemes, e.g. `https://`. Local paths are compared once made absolute, with `..` and
+/// symbolic links resolved, and globs by the directory they expand within and by each file they
+/// match.
+///
+/// Only the files queries name as tables, and those registered, are checked. Engines' own ways of
+/// reading files, e.g. DuckDB's `read_csv`, aren't.
+#[derive(Clone, Debug, Default)]
+pub struct PathPolicy {
+    /// Where files may be loaded from; anywhere if empty
+    pub allow: Vec<String>,
+    /// Where files may not be loaded from, even within an allowed directory
+    pub deny: Vec<String>,
+}
+
+impl PathPolicy {
+    /// Whether files may be loaded from anywhere
+    pub fn is_unrestricted(&self) -> bool {
+        self.allow.is_empty() && self.deny.is_empty()
+    }
+
+    /// Fail with [`CallistoError::PathNotAllowed`] if the file, glob, or directory at `path` may
+    /// not be loaded.
+    ///
+    /// A name which isn't a path, with neither a directory nor an extension, is let through
+    /// unless a file of that name exists, as it names a table rather than a file, e.g. that of a
+    /// common table expression.
+    pub fn check(&self, path: &str) -> Result<(), CallistoError> {
+        if self.is_unrestricted() || (!looks_like_path(path) && !Path::new(path).exists()) {
+            return Ok(());
+        }
+        self.check_target(path, "it's", &Location::of(path))?;
+        // The files a glob matches may be denied within its directory, or be symbolic links out
+        // of it
+        for matched in glob_matches(path) {
+            let subject = format!("its match {} is", matched.display());
+            self.check_target(path, &subject, &Location::of(&matched.to_string_lossy()))?;
+        }
+        Ok(())
+    }
+
+    /// Fail if `target`, which loading `path` reads and which `subject` describes, may not be
+    /// read
+    fn check_target(
+        &self,
+        path: &str,
+        subject: &str,
+        target: &Location,
+    ) -> Result<(), CallistoError> {
+        let not_allowed = |reason: String| CallistoError::PathNotAllowed {
+            path: path.to_string(),
+            reason,
+        };
+        if let Some(denied) = self
+            .deny
+            .iter()
+            .find(|entry| Location::of(entry).contains(target))
+        {
+            return Err(not_allowed(format!(
+                "{} within the denied {}",
+                subject, denied
+            )));
+        }
+        if !self.allow.is_empty()
+            && !self
+                .allow
+                .iter()
+                .any(|entry| Location::of(entry).contains(target))
+        {
+            return Err(not_allowed(format!(
+                "{} outside every allowed directory and URI",
+                subject
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Whether `name` has a directory, a URI scheme, or an extension, so can only be a path
+fn looks_like_path(name: &str) -> bool {
+    name.contains('/') || name.contains('\\') || Path::new(name).extension().is_some()
+}
+
+/// The files the local glob `path` matches as they are now, none if it isn't one
+fn glob_matches(path: &str) -> Vec<PathBuf> {
+    if path.contains("://") || !path.contains(['*', '?', '[']) {
+        return Vec::new();
+    }
+    glob::glob(path)
+        .map(|matches| matches.flatten().collect())
+        .unwrap_or_default()
+}
+
+/// Where a path or policy entry points, resolved so that it can be compared with others
+#[derive(Debug)]
+enum Location {
+    /// A URI, its scheme lowercased
+    Uri(String),
+    Local(PathBuf),
+}
+
+impl Location {
+    fn of(path: &str) -> Location {
+        match path.split_once("://") {
+            Some((scheme, rest))
+                if !scheme.is_empty()
+                    && scheme
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c)) =>
+            {
+                Location::Uri(format!("{}://{}", scheme.to_ascii_lowercase(), rest))
+            }
+            _ => Location::Local(resolve(path)),
+        }
+    }
+
+    /// Whether `other` lies within this, taken as a directory or URI prefix
+    fn contains(&self, other: &Location) -> bool {
+        match (self, other) {
+            (Location::Uri(prefix), Location::Uri(uri)) => {
+                uri.strip_prefix(prefix.as_str()).is_some_and(|rest| {
+                    rest.is_empty() || rest.starts_with('/') || prefix.ends_with('/')
+                })
+            }
+            (Location::Local(directory), Location::Local(path)) => path.starts_with(directory),
+            _ => false,
+        }
+    }
+}
+
+/// `path` made absolute, its `.` and `..` components resolved, cut before its first glob
+/// component, and with symbolic links among its existing directories followed
+fn resolve(path: &str) -> PathBuf {
+    let absolute = std::env::current_dir().unwrap_or_default().join(path);
+    let mut resolved = PathBuf::new();
+    for component in absolute.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            component => resolved.push(component),
+        }
+    }
+    // Resolved lexically first, so `..` after a glob can't climb out of the directory before it
+    let glob = resolved.components().position(|component| {
+        component
+            .as_os_str()
+            .to_string_lossy()
+            .contains(['*', '?', '['])
+    });
+    if let Some(glob) = glob {
+        resolved = resolved.components().take(glob).collect();
+    }
+    let existing = resolved
+        .ancestors()
+        .find_map(|ancestor| Some((ancestor, ancestor.canonicalize().ok()?)));
+    match existing {
+        Some((ancestor, canonical)) => {
+            canonical.join(resolved.strip_prefix(ancestor).unwrap_or(Path::new("")))
+        }
+        None => resolved,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A directory holding `data/a.csv`, `data/sub/b.csv`, and `secret/s.csv`
+    fn files() -> tempfile::TempDir {
+        let root = tempfile::tempdir().unwrap();
+        for file in ["data/a.csv", "data/sub/b.csv", "secret/s.csv"] {
+            let path = root.path().join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "n\n1\n").unwrap();
+        }
+        root
+    }
+
+    /// A policy of the `allow` and `deny` entries within `root`
+    fn policy(root: &tempfile::TempDir, allow: &[&str], deny: &[&str]) -> PathPolicy {
+        let entries = |entries: &[&str]| {
+            entries
+                .iter()
+                .map(|entry| root.path().join(entry).to_string_lossy().into_owned())
+                .collect()
+        };
+        PathPolicy {
+            allow: entries(allow),
+            deny: entries(deny),
+        }
+    }
+
+    /// Whether `policy` lets `path` within `root` be loaded
+    fn allows(policy: &PathPolicy, root: &tempfile::TempDir, path: &str) -> bool {
+        let path = root.path().join(path);
+        match policy.check(&path.to_string_lossy()) {
+            Ok(()) => true,
+            Err(CallistoError::PathNotAllowed { .. }) => false,
+            Err(error) => panic!("Unexpected error: {}", error),
+        }
+    }
+
+    #[test]
+    fn unrestricted_policies_allow_anything() {
+        let policy = PathPolicy::default();
+        assert!(policy.is_unrestricted());
+        assert!(policy.check("/etc/passwd").is_ok());
+        assert!(policy.check("s3://bucket/key.parquet").is_ok());
+    }
+
+    #[test]
+    fn allow_and_deny_lists() {
+        let root = files();
+        let allowed = policy(&root, &["data"], &[]);
+        assert!(allows(&allowed, &root, "data/a.csv"));
+        assert!(allows(&allowed, &root, "data/sub/b.csv"));
+        assert!(allows(&allowed, &root, "data/missing.csv"));
+        assert!(!allows(&allowed, &root, "secret/s.csv"));
+
+        let denied = policy(&root, &[], &["secret"]);
+        assert!(allows(&denied, &root, "data/a.csv"));
+        assert!(!allows(&denied, &root, "secret/s.csv"));
+        assert!(!allows(&denied, &root, "secret"));
+
+        // Denials win within allowed directories
+        let both = policy(&root, &["data"], &["data/sub"]);
+        assert!(allows(&both, &root, "data/a.csv"));
+        assert!(!allows(&both, &root, "data/sub/b.csv"));
+    }
+
+    #[test]
+    fn directories_are_not_prefixes_of_their_siblings() {
+        let root = files();
+        let allowed = policy(&root, &["data"], &[]);
+        assert!(!allows(&allowed, &root, "database/a.csv"));
+        let denied = policy(&root, &[], &["data/sub"]);
+        assert!(allows(&denied, &root, "data/subway.csv"));
+    }
+
+    #[test]
+    fn parent_directories_are_resolved() {
+        let root = files();
+        let allowed = policy(&root, &["data"], &[]);
+        assert!(allows(&allowed, &root, "data/sub/../a.csv"));
+        assert!(allows(&allowed, &root, "data/./sub/b.csv"));
+        assert!(!allows(&allowed, &root, "data/../secret/s.csv"));
+        assert!(!allows(&allowed, &root, "data/sub/../../secret/s.csv"));
+        assert!(!allows(&allowed, &root, "data/missing/../../secret/s.csv"));
+
+        let denied = policy(&root, &[], &["secret"]);
+        assert!(!allows(&denied, &root, "data/../secret/s.csv"));
+        assert!(allows(&denied, &root, "secret/../data/a.csv"));
+
+        // Entries are resolved too
+        let allowed = policy(&root, &["secret/../data"], &[]);
+        assert!(allows(&allowed, &root, "data/a.csv"));
+        assert!(!allows(&allowed, &root, "secret/s.csv"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symbolic_links_are_followed() {
+        let root = files();
+        let link = |original: &str, link: &str| {
+            std::os::unix::fs::symlink(root.path().join(original), root.path().join(link)).unwrap()
+        };
+        link("secret", "data/linked");
+        link("secret/s.csv", "data/s.csv");
+        link("data", "alias");
+
+        let allowed = policy(&root, &["data"], &[]);
+        assert!(!allows(&allowed, &root, "data/linked/s.csv"));
+        assert!(!allows(&allowed, &root, "data/linked"));
+        assert!(!allows(&allowed, &root, "data/s.csv"));
+        assert!(allows(&allowed, &root, "alias/a.csv"));
+
+        let denied = policy(&root, &[], &["secret"]);
+        assert!(!allows(&denied, &root, "data/linked/s.csv"));
+        assert!(!allows(&denied, &root, "data/s.csv"));
+        assert!(allows(&denied, &root, "alias/a.csv"));
+
+        // Entries are followed too
+        let allowed = policy(&root, &["alias"], &["data/linked"]);
+        assert!(allows(&allowed, &root, "data/a.csv"));
+        assert!(!allows(&allowed, &root, "secret/s.csv"));
+    }
+
+    #[test]
+    fn globs_are_checked_by_their_directory() {
+        let root = files();
+        let allowed = policy(&root, &["data"], &[]);
+        assert!(allows(&allowed, &root, "data/*.csv"));
+        assert!(allows(&allowed, &root, "data/**/*.csv"));
+        assert!(allows(&allowed, &root, "data/sub/?.csv"));
+        assert!(!allows(&allowed, &root, "secret/*.csv"));
+        assert!(!allows(&allowed, &root, "*/*.csv"));
+        // `..` after a glob can't climb out of the directory before it
+        assert!(!allows(&allowed, &root, "data/../secret/*.csv"));
+        assert!(allows(&allowed, &root, "data/*/../../data/a.csv"));
+        assert!(!allows(&allowed, &root, "data/*/../../secret/*.csv"));
+
+        let denied = policy(&root, &[], &["secret"]);
+        assert!(allows(&denied, &root, "data/*.csv"));
+        assert!(!allows(&denied, &root, "secret/*.csv"));
+        assert!(!allows(&denied, &root, "secret/[a-z].csv"));
+    }
+
+    #[test]
+    fn globs_are_checked_by_what_they_match() {
+        let root = files();
+        let denied = policy(&root, &["data"], &["data/sub"]);
+        assert!(allows(&denied, &root, "data/*.csv"));
+        assert!(!allows(&denied, &root, "data/*/b.csv"));
+        assert!(!allows(&denied, &root, "data/**/*.csv"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn globs_are_checked_through_symbolic_links() {
+        let root = files();
+        std::os::unix::fs::symlink(root.path().join("secret"), root.path().join("data/linked"))
+            .unwrap();
+        let allowed = policy(&root, &["data"], &[]);
+        assert!(allows(&allowed, &root, "data/*.csv"));
+        assert!(!allows(&allowed, &root, "data/*/s.csv"));
+        assert!(!allows(&allowed, &root, "data/linked/*.csv"));
+    }
+
+    #[test]
+    fn uris_are_compared_by_prefix() {
+        let policy = PathPolicy {
+            allow: vec!["s3://bucket/public".to_string(), "https://".to_string()],
+            deny: vec!["s3://bucket/public/private/".to_string()],
+        };
+        assert!(policy.check("s3://bucket/public/a.parquet").is_ok());
+        assert!(policy.check("S3://bucket/public/a.parquet").is_ok());
+        assert!(policy.check("https://example.com/a.csv").is_ok());
+        assert!(policy.check("s3://bucket/publicity/a.parquet").is_err());
+        assert!(policy
+            .check("s3://bucket/public/private/a.parquet")
+            .is_err());
+        assert!(policy.check("s3://other/a.parquet").is_err());
+        assert!(policy.check("/tmp/a.csv").is_err());
+    }
+
+    #[test]
+    fn names_of_tables_are_not_paths() {
+        let policy = PathPolicy {
+            allow: vec!["/nowhere".to_string()],
+            deny: Vec::new(),
+        };
+        assert!(policy.check("recent_orders").is_ok());
+        assert!(policy.check("orders.csv").is_err());
+    }
+}
@@ -0,0 +1,134 @@
+use std::time::Duration;
+
+use datafusion::physical_plan::ExecutionPlan;
+
+/// An operator of a query plan with the operators feeding it, and what it measured if the query
+/// ran
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PlanNode {
+    /// Kind of operator, e.g. `FilterExec`
+    pub name: String,
+    /// The operator's arguments, e.g. its predicate
+    pub details: String,
+    /// Rows the operator output, if measured
+    pub rows: Option<usize>,
+    /// Time the operator spent computing, if measured
+    pub elapsed: Option<Duration>,
+    pub children: Vec<PlanNode>,
+}
+
+impl PlanNode {
+    /// The tree of operators of an executed physical plan, with the metrics each recorded
+    pub(crate) fn from_execution_plan(plan: &dyn ExecutionPlan) -> PlanNode {
+        let line = datafusion::physical_plan::displayable(plan)
+            .one_line()
+            .to_string();
+        let (name, details) = split_operator(line.trim());
+        let metrics = plan.metrics();
+        PlanNode {
+            name,
+            details,
+            rows: metrics.as_ref().and_then(|metrics| metrics.output_rows()),
+            elapsed: metrics
+                .as_ref()
+                .and_then(|metrics| metrics.elapsed_compute())
+                .map(|nanos| Duration::from_nanos(nanos as u64)),
+            children: plan
+                .children()
+                .iter()
+                .map(|child| PlanNode::from_execution_plan(child.as_ref()))
+                .collect(),
+        }
+    }
+
+    /// Parse a plan as engines print it for `EXPLAIN`, one operator a line with its inputs
+    /// indented below it.
+    ///
+    /// Metrics that `EXPLAIN ANALYZE` appends as `metrics=[output_rows=…, elapsed_compute=…]`
+    /// are picked out of each line. Plans that aren't indented text, like DuckDB's drawn boxes,
+    /// are kept as one node a line so none of it is lost.
+    pub fn parse(text: &str) -> Vec<PlanNode> {
+        let lines = text.lines().filter(|line| !line.trim().is_empty());
+        if text.contains(['┌', '└', '│']) {
+            return lines
+                .map(|line| PlanNode {
+                    name: line.trim_end().to_string(),
+                    ..PlanNode::default()
+                })
+                .collect();
+        }
+
+        // Nodes still open to children, with their indentation, innermost last
+        let mut open: Vec<(usize, PlanNode)> = Vec::new();
+        let mut roots = Vec::new();
+        let close = |open: &mut Vec<(usize, PlanNode)>, roots: &mut Vec<PlanNode>| {
+            let (_, node) = open.pop().expect("only called with nodes open");
+            match open.last_mut() {
+                Some((_, parent)) => parent.children.push(node),
+                None => roots.push(node),
+            }
+        };
+        for line in lines {
+            let indent = line.len() - line.trim_start().len();
+            while open
+                .last()
+                .is_some_and(|(open_indent, _)| *open_indent >= indent)
+            {
+                close(&mut open, &mut roots);
+            }
+            open.push((indent, parse_line(line.trim())));
+        }
+        while !open.is_empty() {
+            close(&mut open, &mut roots);
+        }
+        roots
+    }
+}
+
+/// An operator from its line in a printed plan, with any metrics appended to it
+fn parse_line(line: &str) -> PlanNode {
+    let (operator, metrics) = match line.rsplit_once("metrics=[") {
+        Some((operator, metrics)) => (
+            operator.trim_end().trim_end_matches(','),
+            metrics.trim_end_matches(']'),
+        ),
+        None => (line, ""),
+    };
+    let (name, details) = split_operator(operator);
+    let mut node = PlanNode {
+        name,
+        details,
+        ..PlanNode::default()
+    };
+    for metric in metrics.split(", ") {
+        match metric.split_once('=') {
+            Some(("output_rows", rows)) => node.rows = rows.parse().ok(),
+            Some(("elapsed_compute", elapsed)) => node.elapsed = parse_duration(elapsed),
+            _ => {}
+        }
+    }
+    node
+}
+
+/// An operator's name and its arguments, as printed like `FilterExec: a@0 > 1`
+fn split_operator(operator: &str) -> (String, String) {
+    match operator.split_once(": ") {
+        Some((name, details)) => (name.to_string(), details.to_string()),
+        None => (operator.to_string(), String::new()),
+    }
+}
+
+/// A duration as DataFusion prints it, e.g. `1.5ms` or `120ns`
+fn parse_duration(text: &str) -> Option<Duration> {
+    let split = text.find(|c: char| !c.is_ascii_digit() && c != '.')?;
+    let (value, unit) = text.split_at(split);
+    let value = value.parse::<f64>().ok()?;
+    let seconds = match unit {
+        "ns" => value / 1e9,
+        "µs" | "us" => value / 1e6,
+        "ms" => value / 1e3,
+        "s" => value,
+        _ => return None,
+    };
+    Some(Duration::from_secs_f64(seconds))
+}
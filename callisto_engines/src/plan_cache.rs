@@ -0,0 +1,315 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use arrow::array::{StringArray, UInt64Array};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use arrow::record_batch::RecordBatch;
+use sqlparser::ast;
+use sqlparser::dialect::GenericDialect;
+use sqlparser::parser::ParserError;
+use sqlparser::tokenizer::{Token, Tokenizer};
+
+use crate::{execution_metrics, Engine, ExecutionMetrics, QueryExecution};
+
+/// Queries whose parsed statements are kept, shared by every engine
+const STATEMENTS_CAPACITY: usize = 256;
+
+/// How a cache of parsed statements or of an engine's plans has fared, see
+/// [`EngineInterface::cache_stats`](crate::EngineInterface::cache_stats)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CacheStats {
+    /// What's cached: `statements`, those of each query parsed, `plans`, the engine's logical
+    /// plans of queries, or `footers`, those of the Parquet files it's read
+    pub cache: &'static str,
+    pub entries: usize,
+    /// Most entries kept, the least recently used being evicted past it
+    pub capacity: usize,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+/// Values kept by a key, e.g. the normalized text of the statements they were made from, the least
+/// recently used evicted once there are `capacity` of them
+pub(crate) struct PlanCache<V> {
+    cache: &'static str,
+    capacity: usize,
+    /// Least recently used first
+    entries: Mutex<VecDeque<(String, V)>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl<V: Clone> PlanCache<V> {
+    /// A cache of up to `capacity` entries, keeping none if that's zero
+    pub(crate) fn new(cache: &'static str, capacity: usize) -> PlanCache<V> {
+        PlanCache {
+            cache,
+            capacity,
+            entries: Mutex::new(VecDeque::new()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn get(&self, key: &str) -> Option<V> {
+        let mut entries = self.entries.lock().unwrap();
+        let Some(index) = entries.iter().position(|(cached, _)| cached == key) else {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+            return None;
+        };
+        let entry = entries.remove(index)?;
+        let value = entry.1.clone();
+        entries.push_back(entry);
+        self.hits.fetch_add(1, Ordering::Relaxed);
+        Some(value)
+    }
+
+    pub(crate) fn insert(&self, key: String, value: V) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|(cached, _)| *cached != key);
+        if entries.len() >= self.capacity {
+            entries.pop_front();
+        }
+        entries.push_back((key, value));
+    }
+
+    /// Drop every entry, e.g. once what they were made against has changed
+    pub(crate) fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+
+    pub(crate) fn stats(&self) -> CacheStats {
+        CacheStats {
+            cache: self.cache,
+            entries: self.entries.lock().unwrap().len(),
+            capacity: self.capacity,
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// The cache of each query's parsed statements. Parsing doesn't depend on the engine or its
+/// tables, so it's shared by all of them and never goes stale.
+pub(crate) fn statements() -> &'static PlanCache<Arc<Vec<ast::Statement>>> {
+    static STATEMENTS: OnceLock<PlanCache<Arc<Vec<ast::Statement>>>> = OnceLock::new();
+    STATEMENTS.get_or_init(|| PlanCache::new("statements", STATEMENTS_CAPACITY))
+}
+
+/// The statements of `query`, as they were parsed the last time a query normalizing to the same
+/// text was
+pub(crate) fn parse(query: &str) -> Result<Vec<ast::Statement>, ParserError> {
+    let Some(key) = normalize(query) else {
+        return crate::parse(query);
+    };
+    if let Some(statements) = statements().get(&key) {
+        return Ok(statements.as_ref().clone());
+    }
+    let statements = crate::parse(query)?;
+    statements().insert(key, Arc::new(statements.clone()));
+    Ok(statements)
+}
+
+/// The tokens of `query` but for its whitespace and comments, written out unambiguously, or none
+/// if it can't be tokenized
+fn normalize(query: &str) -> Option<String> {
+    let tokens = Tokenizer::new(&GenericDialect, query).tokenize().ok()?;
+    let tokens = tokens
+        .into_iter()
+        .filter(|token| !matches!(token, Token::Whitespace(_) | Token::EOF))
+        .collect::<Vec<_>>();
+    Some(format!("{:?}", tokens))
+}
+
+/// Whether `statement` is `SHOW CACHE`, answered with the [`cache_table`]
+pub(crate) fn is_show_cache(statement: &ast::Statement) -> bool {
+    matches!(
+        statement,
+        ast::Statement::ShowVariable { variable }
+            if variable.len() == 1 && variable[0].value.eq_ignore_ascii_case("cache")
+    )
+}
+
+/// `SHOW CACHE`, run on `engine` by answering it with [`cache_table`] of `stats`
+pub(crate) fn show_cache(
+    engine: Engine,
+    statement: ast::Statement,
+    stats: &[CacheStats],
+) -> anyhow::Result<QueryExecution> {
+    let (schema, batch) = cache_table(stats)?;
+    let stream =
+        datafusion::physical_plan::memory::MemoryStream::try_new(vec![batch], schema, None)?;
+    let metrics = Arc::new(ExecutionMetrics::default());
+    let stream =
+        execution_metrics::metered(engine, Box::pin(stream), metrics.clone(), None, None, None);
+    Ok(QueryExecution::new(statement, stream, metrics))
+}
+
+/// The rows answering `SHOW CACHE`: one per cache of `stats`
+fn cache_table(stats: &[CacheStats]) -> anyhow::Result<(SchemaRef, RecordBatch)> {
+    let schema = Arc::new(Schema::new(vec![
+        Field::new("cache", DataType::Utf8, false),
+        Field::new("entries", DataType::UInt64, false),
+        Field::new("capacity", DataType::UInt64, false),
+        Field::new("hits", DataType::UInt64, false),
+        Field::new("misses", DataType::UInt64, false),
+    ]));
+    let batch = RecordBatch::try_new(
+        schema.clone(),
+        vec![
+            Arc::new(StringArray::from_iter_values(
+                stats.iter().map(|stats| stats.cache),
+            )),
+            Arc::new(UInt64Array::from_iter_values(
+                stats.iter().map(|stats| stats.entries as u64),
+            )),
+            Arc::new(UInt64Array::from_iter_values(
+                stats.iter().map(|stats| stats.capacity as u64),
+            )),
+            Arc::new(UInt64Array::from_iter_values(
+                stats.iter().map(|stats| stats.hits),
+            )),
+            Arc::new(UInt64Array::from_iter_values(
+                stats.iter().map(|stats| stats.misses),
+            )),
+        ],
+    )?;
+    Ok((schema, batch))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counts(cache: &PlanCache<u32>) -> (usize, u64, u64) {
+        let stats = cache.stats();
+        (stats.entries, stats.hits, stats.misses)
+    }
+
+    #[test]
+    fn hits_and_misses_are_counted() {
+        let cache = PlanCache::new("plans", 2);
+        assert_eq!(cache.get("a"), None);
+        cache.insert("a".to_string(), 1);
+        assert_eq!(cache.get("a"), Some(1));
+        assert_eq!(cache.get("a"), Some(1));
+        assert_eq!(counts(&cache), (1, 2, 1));
+
+        // Inserting a key again replaces its value
+        cache.insert("a".to_string(), 2);
+        assert_eq!(cache.get("a"), Some(2));
+        assert_eq!(counts(&cache), (1, 3, 1));
+    }
+
+    #[test]
+    fn the_least_recently_used_entry_is_evicted() {
+        let cache = PlanCache::new("plans", 2);
+        cache.insert("a".to_string(), 1);
+        cache.insert("b".to_string(), 2);
+        // Using `a` leaves `b` the least recently used
+        assert_eq!(cache.get("a"), Some(1));
+        cache.insert("c".to_string(), 3);
+        assert_eq!(cache.get("b"), None);
+        assert_eq!(cache.get("a"), Some(1));
+        assert_eq!(cache.get("c"), Some(3));
+        assert_eq!(cache.stats().capacity, 2);
+    }
+
+    #[test]
+    fn clearing_drops_entries_but_not_counts() {
+        let cache = PlanCache::new("plans", 2);
+        cache.insert("a".to_string(), 1);
+        assert_eq!(cache.get("a"), Some(1));
+        cache.clear();
+        assert_eq!(cache.get("a"), None);
+        assert_eq!(counts(&cache), (0, 1, 1));
+    }
+
+    #[test]
+    fn caches_without_capacity_keep_nothing() {
+        let cache = PlanCache::new("plans", 0);
+        cache.insert("a".to_string(), 1);
+        assert_eq!(cache.get("a"), None);
+        assert_eq!(counts(&cache), (0, 0, 1));
+    }
+
+    #[test]
+    fn queries_are_keyed_without_whitespace_or_comments() {
+        assert_eq!(
+            normalize("SELECT a FROM t"),
+            normalize("SELECT  a\n-- the table\nFROM /* here */ t")
+        );
+        assert_ne!(normalize("SELECT a FROM t"), normalize("SELECT b FROM t"));
+        assert_ne!(normalize("SELECT 'a b'"), normalize("SELECT 'a  b'"));
+        assert_eq!(normalize("SELECT 'unterminated"), None);
+        assert_eq!(
+            parse("select 1 -- one").unwrap(),
+            crate::parse("SELECT 1").unwrap()
+        );
+    }
+
+    #[test]
+    fn show_cache_is_recognized() {
+        for (query, shown) in [
+            ("SHOW CACHE", true),
+            ("show cache", true),
+            ("SHOW TABLES", false),
+        ] {
+            let statements = crate::parse(query).unwrap();
+            assert_eq!(is_show_cache(&statements[0]), shown, "{}", query);
+        }
+    }
+
+    #[cfg(feature = "datafusion-engine")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn datafusion_plans_are_reused_until_tables_change() {
+        use futures::stream::TryStreamExt as _;
+
+        /// The column names of the rows `query` gives
+        async fn columns(engine: &dyn crate::EngineInterface, query: &str) -> Vec<String> {
+            let execution = engine.execute(query).await.unwrap().pop().unwrap();
+            let _: Vec<RecordBatch> = execution.stream.try_collect().await.unwrap();
+            let fields = execution.schema.fields().iter();
+            fields.map(|field| field.name().to_string()).collect()
+        }
+        fn plans(engine: &dyn crate::EngineInterface) -> CacheStats {
+            let mut stats = engine.cache_stats().into_iter();
+            stats.find(|stats| stats.cache == "plans").unwrap()
+        }
+        async fn register(engine: &dyn crate::EngineInterface, column: &str) {
+            let schema = Arc::new(Schema::new(vec![Field::new(column, DataType::Utf8, false)]));
+            let batch = RecordBatch::try_new(
+                schema.clone(),
+                vec![Arc::new(StringArray::from_iter_values(["x"]))],
+            )
+            .unwrap();
+            engine
+                .register_batches("t", schema, vec![batch])
+                .await
+                .unwrap();
+        }
+
+        let engine = Engine::DataFusion.new().unwrap();
+        register(engine.as_ref(), "a").await;
+        assert_eq!(columns(engine.as_ref(), "SELECT * FROM t").await, ["a"]);
+        assert_eq!(columns(engine.as_ref(), "SELECT * FROM t").await, ["a"]);
+        let stats = plans(engine.as_ref());
+        assert_eq!((stats.entries, stats.hits, stats.misses), (1, 1, 1));
+
+        // A plan of the table replaced would read its old columns
+        register(engine.as_ref(), "b").await;
+        assert_eq!(plans(engine.as_ref()).entries, 0);
+        assert_eq!(columns(engine.as_ref(), "SELECT * FROM t").await, ["b"]);
+
+        // As may any statement that isn't a query
+        engine
+            .execute("CREATE TABLE u AS SELECT 1 AS c")
+            .await
+            .unwrap();
+        assert_eq!(plans(engine.as_ref()).entries, 0);
+    }
+}
@@ -1,6 +1,7 @@
 use std::sync::Arc;
 
-use arrow::datatypes::DataType;
+use arrow::datatypes::{DataType, SchemaRef};
+use arrow::record_batch::RecordBatch;
 use polars::datatypes::ArrowDataType as PlDataType;
 
 pub fn _convert_array(
@@ -25,6 +26,20 @@ pub fn _convert_array(
     }
 }
 
+/// The rows of `df` as record batches, passed through the Arrow IPC format
+pub fn convert_frame(
+    mut df: polars::frame::DataFrame,
+) -> anyhow::Result<(SchemaRef, Vec<RecordBatch>)> {
+    use polars::prelude::SerWriter as _;
+
+    let mut buffer = Vec::new();
+    polars_io::ipc::IpcStreamWriter::new(&mut buffer).finish(&mut df)?;
+    let reader = arrow::ipc::reader::StreamReader::try_new(std::io::Cursor::new(buffer), None)?;
+    let schema = reader.schema();
+    let batches = reader.collect::<Result<Vec<_>, _>>()?;
+    Ok((schema, batches))
+}
+
 pub fn convert_schema(
     schema: polars_arrow::datatypes::ArrowSchema,
 ) -> anyhow::Result<arrow::datatypes::Schema> {
@@ -0,0 +1,350 @@
+use core::ops::ControlFlow;
+use std::sync::Arc;
+
+use arrow::datatypes::SchemaRef;
+use datafusion::common::ScalarValue;
+use datafusion::logical_expr::LogicalPlan;
+use sqlparser::ast;
+use tracing::Instrument as _;
+
+use crate::{Engine, EngineInterface, QueryExecution};
+
+/// A statement prepared by an engine, to be executed any number of times with values bound to
+/// its parameters.
+///
+/// Parameters are written `$1`, `$2`, ... or `?`, the latter numbered in the order they appear.
+/// Values are bound as [`ScalarValue`]s, which most Rust values convert into, so they never pass
+/// through the statement's text.
+#[derive(Clone, Debug)]
+pub struct PreparedStatement {
+    pub(crate) engine: Engine,
+    /// The statement with its tables resolved and its parameters numbered
+    pub(crate) statement: ast::Statement,
+    pub(crate) parameters: Vec<Option<ScalarValue>>,
+    /// The statement planned with its parameters left open, if the engine plans ahead
+    pub(crate) plan: Option<LogicalPlan>,
+}
+
+impl PreparedStatement {
+    pub(crate) fn new(
+        engine: Engine,
+        statement: ast::Statement,
+        parameter_count: usize,
+        plan: Option<LogicalPlan>,
+    ) -> PreparedStatement {
+        PreparedStatement {
+            engine,
+            statement,
+            parameters: vec![None; parameter_count],
+            plan,
+        }
+    }
+
+    /// The statement as the engine runs it, its parameters numbered
+    pub fn sql(&self) -> String {
+        self.statement.to_string()
+    }
+
+    pub fn parameter_count(&self) -> usize {
+        self.parameters.len()
+    }
+
+    /// The columns the statement returns, if the engine planned it as it was prepared; only
+    /// DataFusion does
+    pub fn schema(&self) -> Option<SchemaRef> {
+        self.plan
+            .as_ref()
+            .map(|plan| Arc::new(plan.schema().as_ref().into()))
+    }
+
+    /// Bind `value` to the parameter at `position`, counting from 1 as `$1` does
+    pub fn bind(
+        &mut self,
+        position: usize,
+        value: impl Into<ScalarValue>,
+    ) -> anyhow::Result<&mut PreparedStatement> {
+        let count = self.parameters.len();
+        let Some(parameter) = position
+            .checked_sub(1)
+            .and_then(|index| self.parameters.get_mut(index))
+        else {
+            anyhow::bail!(
+                "No parameter ${}: the statement has {} parameter(s)",
+                position,
+                count
+            );
+        };
+        *parameter = Some(value.into());
+        Ok(self)
+    }
+
+    /// Unbind every parameter
+    pub fn clear_bindings(&mut self) {
+        self.parameters
+            .iter_mut()
+            .for_each(|parameter| *parameter = None);
+    }
+
+    /// Execute the statement on `engine`, which must be the engine that prepared it
+    pub async fn execute(&self, engine: &dyn EngineInterface) -> anyhow::Result<QueryExecution> {
+        if engine.kind() != self.engine {
+            anyhow::bail!(
+                "The statement was prepared by the {} engine, not {}",
+                self.engine.name(),
+                engine.kind().name()
+            );
+        }
+        let span = tracing::info_span!(
+            "statement",
+            id = crate::next_statement_id(),
+            engine = self.engine.name(),
+            prepared = true
+        );
+        let execution = engine.execute_prepared(self).instrument(span).await;
+        crate::telemetry::statement(self.engine, execution.is_ok());
+        execution
+    }
+
+    /// The values bound to the parameters, in order
+    pub(crate) fn values(&self) -> anyhow::Result<Vec<ScalarValue>> {
+        self.parameters
+            .iter()
+            .enumerate()
+            .map(|(index, value)| {
+                value
+                    .clone()
+                    .ok_or_else(|| anyhow::anyhow!("No value bound to parameter ${}", index + 1))
+            })
+            .collect()
+    }
+
+    /// The statement with its parameters replaced by literals of their values, for engines
+    /// without parameters of their own
+    #[cfg(feature = "polars-engine")]
+    pub(crate) fn with_literals(&self) -> anyhow::Result<ast::Statement> {
+        let values = self.values()?;
+        let mut statement = self.statement.clone();
+        let flow = ast::visit_expressions_mut(&mut statement, |expr| {
+            if let ast::Expr::Value(ast::Value::Placeholder(placeholder)) = expr {
+                let value = position(placeholder).and_then(|position| values.get(position - 1));
+                match value.map(literal) {
+                    Some(Ok(value)) => *expr = ast::Expr::Value(value),
+                    Some(Err(error)) => return ControlFlow::Break(error),
+                    None => {
+                        return ControlFlow::Break(anyhow::anyhow!(
+                            "No value for parameter {}",
+                            placeholder
+                        ))
+                    }
+                }
+            }
+            ControlFlow::Continue(())
+        });
+        match flow {
+            ControlFlow::Break(error) => Err(error),
+            ControlFlow::Continue(()) => Ok(statement),
+        }
+    }
+}
+
+/// Parse `sql`, which must be a single statement, for preparation
+pub(crate) fn parse(sql: &str) -> anyhow::Result<ast::Statement> {
+    let mut statements = crate::parse(sql)?;
+    if statements.len() != 1 {
+        anyhow::bail!(
+            "Only a single statement can be prepared, not {}",
+            statements.len()
+        );
+    }
+    Ok(statements.remove(0))
+}
+
+/// Number the `?` parameters of `statement` in the order they appear, as `$1`, `$2`, ..., so
+/// every engine sees them the same way, returning how many parameters there are
+pub(crate) fn number_parameters(statement: &mut ast::Statement) -> anyhow::Result<usize> {
+    let (mut anonymous, mut numbered) = (0, 0);
+    let flow = ast::visit_expressions_mut(statement, |expr| {
+        if let ast::Expr::Value(ast::Value::Placeholder(placeholder)) = expr {
+            if placeholder == "?" {
+                anonymous += 1;
+                *placeholder = format!("${}", anonymous);
+            } else if let Some(position) = position(placeholder) {
+                numbered = numbered.max(position);
+            } else {
+                return ControlFlow::Break(anyhow::anyhow!(
+                    "Unsupported parameter {}: write $1, $2, ... or ?",
+                    placeholder
+                ));
+            }
+        }
+        ControlFlow::Continue(())
+    });
+    if let ControlFlow::Break(error) = flow {
+        return Err(error);
+    }
+    if anonymous > 0 && numbered > 0 {
+        anyhow::bail!("Parameters must be all numbered ($1, $2, ...) or all ?, not a mix");
+    }
+    Ok(anonymous.max(numbered))
+}
+
+/// Position of the parameter `$n`, counting from 1
+fn position(placeholder: &str) -> Option<usize> {
+    placeholder
+        .strip_prefix('$')
+        .and_then(|position| position.parse().ok())
+        .filter(|position| *position > 0)
+}
+
+#[cfg(feature = "polars-engine")]
+fn literal(value: &ScalarValue) -> anyhow::Result<ast::Value> {
+    Ok(match value {
+        value if value.is_null() => ast::Value::Null,
+        ScalarValue::Boolean(Some(value)) => ast::Value::Boolean(*value),
+        ScalarValue::Utf8(Some(value)) | ScalarValue::LargeUtf8(Some(value)) => {
+            ast::Value::SingleQuotedString(value.clone())
+        }
+        value if value.data_type().is_numeric() => ast::Value::Number(value.to_string(), false),
+        value => anyhow::bail!("Can't bind a {} value here", value.data_type()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `sql` parsed and its parameters numbered, with how many there are
+    fn numbered(sql: &str) -> anyhow::Result<(String, usize)> {
+        let mut statement = parse(sql)?;
+        let count = number_parameters(&mut statement)?;
+        Ok((statement.to_string(), count))
+    }
+
+    fn prepared(sql: &str) -> PreparedStatement {
+        let mut statement = parse(sql).unwrap();
+        let count = number_parameters(&mut statement).unwrap();
+        PreparedStatement::new(Engine::DataFusion, statement, count, None)
+    }
+
+    #[test]
+    fn anonymous_parameters_are_numbered_in_order() {
+        assert_eq!(
+            numbered("SELECT ? FROM t WHERE a = ? AND b IN (?)").unwrap(),
+            ("SELECT $1 FROM t WHERE a = $2 AND b IN ($3)".to_string(), 3)
+        );
+        // Numbered parameters are counted up to the highest, whether or not each is used
+        assert_eq!(numbered("SELECT $2, $1, $2").unwrap().1, 2);
+        assert_eq!(numbered("SELECT $3").unwrap().1, 3);
+        assert_eq!(numbered("SELECT 1").unwrap().1, 0);
+    }
+
+    #[test]
+    fn parameters_are_all_numbered_or_all_anonymous() {
+        let error = numbered("SELECT $1, ?").unwrap_err();
+        assert!(error.to_string().contains("not a mix"), "{}", error);
+        let error = numbered("SELECT :name").unwrap_err();
+        assert!(error.to_string().contains(":name"), "{}", error);
+    }
+
+    #[test]
+    fn only_single_statements_are_prepared() {
+        let error = parse("SELECT 1; SELECT 2").unwrap_err();
+        assert!(error.to_string().contains("not 2"), "{}", error);
+    }
+
+    #[test]
+    fn values_are_bound_by_position() {
+        let mut statement = prepared("SELECT ?, ?");
+        assert_eq!(statement.parameter_count(), 2);
+        let error = statement.values().unwrap_err();
+        assert!(error.to_string().contains("$1"), "{}", error);
+
+        statement.bind(1, 7i64).unwrap().bind(2, "seven").unwrap();
+        assert_eq!(
+            statement.values().unwrap(),
+            [ScalarValue::from(7i64), ScalarValue::from("seven")]
+        );
+        for position in [0, 3] {
+            let error = statement.bind(position, 1i64).unwrap_err();
+            assert!(error.to_string().contains("2 parameter(s)"), "{}", error);
+        }
+
+        statement.clear_bindings();
+        assert!(statement.values().is_err());
+    }
+
+    #[cfg(feature = "polars-engine")]
+    #[test]
+    fn values_are_bound_as_literals_for_engines_without_parameters() {
+        let mut statement = prepared("SELECT * FROM t WHERE a = ? AND b = ? AND c = ?");
+        statement
+            .bind(1, "O'Brien")
+            .unwrap()
+            .bind(2, 1.5f64)
+            .unwrap()
+            .bind(3, ScalarValue::Boolean(None))
+            .unwrap();
+        assert_eq!(
+            statement.with_literals().unwrap().to_string(),
+            "SELECT * FROM t WHERE a = 'O''Brien' AND b = 1.5 AND c = NULL"
+        );
+
+        statement.bind(3, ScalarValue::Date32(Some(0))).unwrap();
+        assert!(statement.with_literals().is_err());
+    }
+
+    #[cfg(all(
+        feature = "datafusion-engine",
+        feature = "duckdb-engine",
+        feature = "polars-engine"
+    ))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn prepared_statements_run_on_each_engine() {
+        use arrow::array::{Int64Array, StringArray};
+        use arrow::datatypes::{DataType, Field, Schema};
+        use arrow::record_batch::RecordBatch;
+        use futures::stream::TryStreamExt as _;
+
+        async fn rows(statement: &PreparedStatement, engine: &dyn EngineInterface) -> usize {
+            let execution = statement.execute(engine).await.unwrap();
+            let batches: Vec<RecordBatch> = execution.stream.try_collect().await.unwrap();
+            batches.iter().map(RecordBatch::num_rows).sum()
+        }
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("n", DataType::Int64, false),
+            Field::new("s", DataType::Utf8, false),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int64Array::from(vec![1, 2])),
+                Arc::new(StringArray::from(vec!["a", "O'Brien"])),
+            ],
+        )
+        .unwrap();
+        let other = Engine::DataFusion.new().unwrap();
+        for kind in [Engine::Polars, Engine::DuckDB, Engine::DataFusion] {
+            let engine = kind.new().unwrap();
+            engine
+                .register_batches("t", schema.clone(), vec![batch.clone()])
+                .await
+                .unwrap();
+            let mut statement = engine
+                .prepare("SELECT n FROM t WHERE s = ? AND n > ?")
+                .await
+                .unwrap();
+            assert_eq!(statement.schema().is_some(), kind == Engine::DataFusion);
+
+            statement.bind(1, "O'Brien").unwrap().bind(2, 1i64).unwrap();
+            assert_eq!(rows(&statement, engine.as_ref()).await, 1, "{:?}", kind);
+            statement.bind(2, 2i64).unwrap();
+            assert_eq!(rows(&statement, engine.as_ref()).await, 0, "{:?}", kind);
+
+            if kind != Engine::DataFusion {
+                let error = statement.execute(other.as_ref()).await.unwrap_err();
+                assert!(error.to_string().contains("prepared by"), "{}", error);
+            }
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use std::sync::Arc;
+
+use arrow::datatypes::SchemaRef;
+use datafusion::physical_plan::SendableRecordBatchStream;
+
+use crate::{ExecutionMetrics, ExecutionStats};
+
+/// A statement as executed by an engine, its rows yet to be read from `stream`
+pub struct QueryExecution {
+    /// The statement as parsed from the query
+    pub statement: sqlparser::ast::Statement,
+    /// Schema of the rows `stream` yields
+    pub schema: SchemaRef,
+    /// The statement's rows, recorded into `metrics` as they're read
+    pub stream: SendableRecordBatchStream,
+    /// Measurements of the statement, completed as `stream` is read
+    pub metrics: Arc<ExecutionMetrics>,
+}
+
+impl QueryExecution {
+    pub fn new(
+        statement: sqlparser::ast::Statement,
+        stream: SendableRecordBatchStream,
+        metrics: Arc<ExecutionMetrics>,
+    ) -> QueryExecution {
+        QueryExecution {
+            statement,
+            schema: stream.schema(),
+            stream,
+            metrics,
+        }
+    }
+
+    /// The statement's final timings and row count, ready once `stream` has been read to its end
+    /// or dropped
+    pub fn stats(&self) -> impl std::future::Future<Output = ExecutionStats> + Send + 'static {
+        let metrics = self.metrics.clone();
+        async move { metrics.finished().await }
+    }
+}
+
+impl std::fmt::Debug for QueryExecution {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("QueryExecution")
+            .field("statement", &self.statement.to_string())
+            .field("schema", &self.schema)
+            .field("metrics", &self.metrics)
+            .finish_non_exhaustive()
+    }
+}
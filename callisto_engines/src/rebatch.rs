@@ -0,0 +1,231 @@
+use core::pin::Pin;
+use std::collections::VecDeque;
+
+use arrow::compute::concat_batches;
+use arrow::datatypes::SchemaRef;
+use arrow::error::ArrowError;
+use arrow::record_batch::RecordBatch;
+use datafusion::physical_plan::SendableRecordBatchStream;
+use futures::{Stream, StreamExt as _};
+
+/// Sizes results are re-batched to, whatever sizes their engine produces them in, so that they
+/// arrive steadily rather than as many tiny batches or a few huge ones; see
+/// [`EngineBuilder::batch_sizing`](crate::EngineBuilder::batch_sizing)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BatchSizing {
+    /// Rows each batch is made up of, smaller batches being gathered and larger ones split, but
+    /// for the last
+    pub rows: usize,
+    /// Most bytes of memory each batch takes, wide rows making batches of fewer than `rows`
+    pub bytes: usize,
+}
+
+impl Default for BatchSizing {
+    fn default() -> BatchSizing {
+        BatchSizing {
+            rows: 8192,
+            bytes: 8 * 1024 * 1024,
+        }
+    }
+}
+
+/// `stream`, its batches gathered and split to the sizes of `sizing`
+pub(crate) fn rebatched(
+    stream: SendableRecordBatchStream,
+    sizing: BatchSizing,
+) -> SendableRecordBatchStream {
+    Box::pin(Rebatched {
+        schema: stream.schema(),
+        stream,
+        sizing,
+        gathered: Vec::new(),
+        gathered_rows: 0,
+        ready: VecDeque::new(),
+        ended: false,
+    })
+}
+
+struct Rebatched {
+    stream: SendableRecordBatchStream,
+    schema: SchemaRef,
+    sizing: BatchSizing,
+    /// Batches read which don't yet make up one to be passed on
+    gathered: Vec<RecordBatch>,
+    gathered_rows: usize,
+    /// Batches made up to size, to be passed on
+    ready: VecDeque<RecordBatch>,
+    /// Whether `stream` has ended, what was gathered then being passed on as it is
+    ended: bool,
+}
+
+impl Rebatched {
+    /// Gather `batch`, making batches ready of what's been gathered once there are enough rows
+    fn gather(&mut self, batch: RecordBatch) -> Result<(), ArrowError> {
+        if batch.num_rows() == 0 {
+            return Ok(());
+        }
+        // Rows are taken to be as large as those of the batch last read
+        let row_bytes = (batch.get_array_memory_size() / batch.num_rows()).max(1);
+        let rows = self.sizing.rows.min(self.sizing.bytes / row_bytes).max(1);
+        self.gathered_rows += batch.num_rows();
+        self.gathered.push(batch);
+        if self.gathered_rows < rows {
+            return Ok(());
+        }
+        let gathered = self.take_gathered()?;
+        let mut offset = 0;
+        while gathered.num_rows() - offset >= rows {
+            self.ready.push_back(gathered.slice(offset, rows));
+            offset += rows;
+        }
+        if offset < gathered.num_rows() {
+            self.gathered_rows = gathered.num_rows() - offset;
+            self.gathered
+                .push(gathered.slice(offset, self.gathered_rows));
+        }
+        Ok(())
+    }
+
+    /// The batches gathered as one, copied together unless there's only one of them
+    fn take_gathered(&mut self) -> Result<RecordBatch, ArrowError> {
+        self.gathered_rows = 0;
+        let gathered = std::mem::take(&mut self.gathered);
+        match <[RecordBatch; 1]>::try_from(gathered) {
+            Ok([batch]) => Ok(batch),
+            Err(gathered) => concat_batches(&self.schema, &gathered),
+        }
+    }
+}
+
+impl datafusion::physical_plan::RecordBatchStream for Rebatched {
+    fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+}
+
+impl Stream for Rebatched {
+    type Item = Result<RecordBatch, datafusion::common::DataFusionError>;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut futures::task::Context<'_>,
+    ) -> futures::task::Poll<Option<Self::Item>> {
+        use futures::task::Poll;
+
+        loop {
+            if let Some(batch) = self.ready.pop_front() {
+                return Poll::Ready(Some(Ok(batch)));
+            }
+            if self.ended {
+                if self.gathered.is_empty() {
+                    return Poll::Ready(None);
+                }
+                return Poll::Ready(Some(self.take_gathered().map_err(Into::into)));
+            }
+            match futures::ready!(self.stream.poll_next_unpin(cx)) {
+                Some(Ok(batch)) => {
+                    if let Err(error) = self.gather(batch) {
+                        return Poll::Ready(Some(Err(error.into())));
+                    }
+                }
+                Some(Err(error)) => return Poll::Ready(Some(Err(error))),
+                None => self.ended = true,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arrow::array::{AsArray as _, Int64Array};
+    use arrow::datatypes::{DataType, Field, Int64Type, Schema};
+    use datafusion::physical_plan::stream::RecordBatchStreamAdapter;
+    use futures::TryStreamExt as _;
+
+    use super::*;
+
+    /// A stream of batches of the given sizes, numbering their rows from 0
+    fn batches(sizes: &[usize]) -> SendableRecordBatchStream {
+        let schema = Arc::new(Schema::new(vec![Field::new("n", DataType::Int64, false)]));
+        let mut start = 0;
+        let batches = sizes
+            .iter()
+            .map(|&size| {
+                let values = (start..start + size as i64).collect::<Vec<_>>();
+                start += size as i64;
+                let column = Arc::new(Int64Array::from(values));
+                RecordBatch::try_new(schema.clone(), vec![column]).unwrap()
+            })
+            .collect::<Vec<_>>();
+        let batches = futures::stream::iter(batches.into_iter().map(Ok));
+        Box::pin(RecordBatchStreamAdapter::new(schema, batches))
+    }
+
+    /// The sizes batches of `sizes` are re-batched to, checking their rows stay in order
+    async fn rebatch(sizes: &[usize], sizing: BatchSizing) -> Vec<usize> {
+        let batches = rebatched(batches(sizes), sizing)
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        let values = batches
+            .iter()
+            .flat_map(|batch| {
+                batch
+                    .column(0)
+                    .as_primitive::<Int64Type>()
+                    .values()
+                    .to_vec()
+            })
+            .collect::<Vec<_>>();
+        let rows = sizes.iter().sum::<usize>() as i64;
+        assert_eq!(values, (0..rows).collect::<Vec<_>>());
+        batches.iter().map(RecordBatch::num_rows).collect()
+    }
+
+    fn rows(rows: usize) -> BatchSizing {
+        BatchSizing {
+            rows,
+            ..BatchSizing::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn small_batches_are_merged() {
+        assert_eq!(rebatch(&[2, 2, 2, 2, 2], rows(4)).await, [4, 4, 2]);
+        assert_eq!(rebatch(&[1, 2, 3], rows(6)).await, [6]);
+        assert_eq!(rebatch(&[3, 3], rows(4)).await, [4, 2]);
+    }
+
+    #[tokio::test]
+    async fn large_batches_are_split() {
+        assert_eq!(rebatch(&[10], rows(4)).await, [4, 4, 2]);
+        assert_eq!(rebatch(&[8], rows(4)).await, [4, 4]);
+        assert_eq!(rebatch(&[5, 7], rows(4)).await, [4, 4, 4]);
+    }
+
+    #[tokio::test]
+    async fn empty_streams_and_batches() {
+        assert!(rebatch(&[], rows(4)).await.is_empty());
+        assert!(rebatch(&[0, 0], rows(4)).await.is_empty());
+        assert_eq!(rebatch(&[0, 3, 0, 2], rows(4)).await, [4, 1]);
+    }
+
+    #[tokio::test]
+    async fn batches_are_kept_under_the_byte_size() {
+        let sizing = BatchSizing {
+            rows: 100,
+            bytes: 1,
+        };
+        // No batch is made smaller than a row, however large rows are
+        assert_eq!(rebatch(&[3], sizing).await, [1, 1, 1]);
+    }
+
+    #[tokio::test]
+    async fn schema_is_kept() {
+        let stream = batches(&[1]);
+        let schema = stream.schema();
+        assert_eq!(rebatched(stream, rows(4)).schema(), schema);
+    }
+}
@@ -0,0 +1,148 @@
+use core::ops::ControlFlow;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+
+use sqlparser::ast;
+
+use crate::{
+    derive_table_from_fs_name, is_registered_name, source_span, telemetry, Engine, PathPolicy,
+};
+
+/// A pass over each statement before an engine runs it, e.g. expanding macros, adding a LIMIT
+/// to queries without one, or masking columns; see [`crate::EngineBuilder::rewriter`].
+///
+/// Passes run in the order they were added, each given the statement as the one before left it,
+/// ahead of the engine resolving the files the statement names to tables. They're called on the
+/// task executing the statement, so should be quick.
+pub trait StatementRewriter: Send + Sync {
+    /// Rewrite `statement` in place. An error refuses to run it, failing it with that error.
+    fn rewrite(&self, statement: &mut ast::Statement) -> anyhow::Result<()>;
+}
+
+impl<F> StatementRewriter for F
+where
+    F: Fn(&mut ast::Statement) -> anyhow::Result<()> + Send + Sync,
+{
+    fn rewrite(&self, statement: &mut ast::Statement) -> anyhow::Result<()> {
+        self(statement)
+    }
+}
+
+pub(crate) type Rewriters = Vec<Arc<dyn StatementRewriter>>;
+
+/// `statement` rewritten by each of `rewriters` in turn then by `tables`, along with the files it
+/// names which are yet to be loaded, each with the table to load it as. A file the path policy of
+/// `tables` doesn't allow loading fails it.
+pub(crate) fn rewrite(
+    rewriters: &Rewriters,
+    tables: &TableNames,
+    statement: &ast::Statement,
+) -> anyhow::Result<(ast::Statement, Vec<(String, String)>)> {
+    use anyhow::Context as _;
+
+    let _span = tracing::info_span!("rewrite", passes = rewriters.len() + 1).entered();
+    let mut rewritten = statement.clone();
+    for rewriter in rewriters {
+        rewriter.rewrite(&mut rewritten)?;
+    }
+    let unloaded = tables.unloaded(&rewritten);
+    for (fs_name, _) in &unloaded {
+        tables
+            .path_policy
+            .check(fs_name)
+            .with_context(|| source_span::LoadFailure(fs_name.clone()))?;
+    }
+    tables.rewrite(&mut rewritten)?;
+    Ok((rewritten, unloaded))
+}
+
+/// The pass every engine ends with, naming the tables a statement reads as they're registered:
+/// a file already loaded by the table it was loaded as, and one not yet loaded by the table it's
+/// about to be
+pub(crate) struct TableNames<'a> {
+    pub engine: Engine,
+    pub fs_name_to_table_name: &'a BTreeMap<String, String>,
+    pub memory_tables: &'a BTreeSet<String>,
+    pub path_policy: &'a PathPolicy,
+}
+
+impl TableNames<'_> {
+    /// The files `statement` names which haven't been loaded, each with the table to load it as,
+    /// counting those which have
+    fn unloaded(&self, statement: &ast::Statement) -> Vec<(String, String)> {
+        let mut unloaded = Vec::new();
+        let _ = ast::visit_relations(statement, |table| {
+            let symbol_or_file = &table.0[0].value;
+            if self.fs_name_to_table_name.contains_key(symbol_or_file) {
+                telemetry::table_cache_hit(self.engine);
+            }
+            if self.table_name(symbol_or_file).is_none()
+                && !unloaded
+                    .iter()
+                    .any(|(fs_name, _)| fs_name == symbol_or_file)
+            {
+                let table_name = derive_table_from_fs_name(symbol_or_file);
+                unloaded.push((symbol_or_file.clone(), table_name));
+            }
+            ControlFlow::<()>::Continue(())
+        });
+        unloaded
+    }
+
+    /// The registered table `symbol_or_file` names, if it names one
+    fn table_name(&self, symbol_or_file: &str) -> Option<String> {
+        if let Some(table_name) = self.fs_name_to_table_name.get(symbol_or_file) {
+            Some(table_name.clone())
+        } else if is_registered_name(self.fs_name_to_table_name, symbol_or_file)
+            || self.memory_tables.contains(symbol_or_file)
+        {
+            Some(symbol_or_file.to_string())
+        } else {
+            None
+        }
+    }
+}
+
+impl StatementRewriter for TableNames<'_> {
+    fn rewrite(&self, statement: &mut ast::Statement) -> anyhow::Result<()> {
+        let _ = ast::visit_relations_mut(statement, |table| {
+            let symbol_or_file = &table.0[0].value;
+            table.0[0].value = self
+                .table_name(symbol_or_file)
+                .unwrap_or_else(|| derive_table_from_fs_name(symbol_or_file));
+            ControlFlow::<()>::Continue(())
+        });
+        Ok(())
+    }
+}
+
+/// The pass bounding queries by the engine's row limit, see [`crate::EngineBuilder::row_limit`].
+///
+/// A query is limited to one row more than `0`, so that results going past the limit are still
+/// found to, then truncated, without the rest of them being computed. One with a lower LIMIT, or
+/// a LIMIT or FETCH that isn't a plain number, is left as it is.
+pub(crate) struct RowLimit(pub usize);
+
+impl StatementRewriter for RowLimit {
+    fn rewrite(&self, statement: &mut ast::Statement) -> anyhow::Result<()> {
+        let ast::Statement::Query(query) = statement else {
+            return Ok(());
+        };
+        let bound = self.0 + 1;
+        let unbounded = query.fetch.is_none()
+            && match &query.limit {
+                None => true,
+                Some(ast::Expr::Value(ast::Value::Number(limit, _))) => {
+                    limit.parse::<usize>().is_ok_and(|limit| limit > bound)
+                }
+                Some(_) => false,
+            };
+        if unbounded {
+            query.limit = Some(ast::Expr::Value(ast::Value::Number(
+                bound.to_string(),
+                false,
+            )));
+        }
+        Ok(())
+    }
+}
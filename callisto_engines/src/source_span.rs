@@ -0,0 +1,227 @@
+use std::ops::Range;
+
+use sqlparser::dialect::GenericDialect;
+use sqlparser::parser::ParserError;
+use sqlparser::tokenizer::{Location, Token, Tokenizer};
+
+/// Where in the text of a query the failure of one of its statements lies, attached as context to
+/// the errors of executing a query and found by [`SourceSpan::of`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SourceSpan {
+    /// Index of the statement within the query
+    pub statement: usize,
+    /// Byte offsets within the query of the statement, or of the part of it at fault when that's
+    /// known, e.g. the token the parser stopped at or a table that couldn't be loaded
+    pub range: Range<usize>,
+}
+
+impl SourceSpan {
+    /// The `SourceSpan` attached to `error`, if one was
+    pub fn of(error: &anyhow::Error) -> Option<&SourceSpan> {
+        error.downcast_ref::<SourceSpan>()
+    }
+}
+
+impl std::fmt::Display for SourceSpan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "In statement {}", self.statement + 1)
+    }
+}
+
+/// Context of an error loading the table named `0` in a statement, by which its span is narrowed
+/// to the name
+#[derive(Debug)]
+pub(crate) struct LoadFailure(pub String);
+
+impl std::fmt::Display for LoadFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Failed to load {}", self.0)
+    }
+}
+
+/// The byte range of each statement of `query`, without the whitespace and comments around it,
+/// or none if it can't be tokenized
+pub(crate) fn statement_ranges(query: &str) -> Vec<Range<usize>> {
+    let Ok(tokens) = tokens(query) else {
+        return Vec::new();
+    };
+    let mut ranges = Vec::new();
+    let mut current: Option<Range<usize>> = None;
+    for (token, range) in tokens {
+        match token {
+            Token::SemiColon => ranges.extend(current.take()),
+            Token::Whitespace(_) | Token::EOF => {}
+            _ => {
+                let start = current.map_or(range.start, |current| current.start);
+                current = Some(start..range.end);
+            }
+        }
+    }
+    ranges.extend(current);
+    ranges
+}
+
+/// `error` from the statement at `index` of `query`, which spans `range`, with where it lies
+/// attached
+pub(crate) fn locate(
+    query: &str,
+    index: usize,
+    range: Range<usize>,
+    error: anyhow::Error,
+) -> anyhow::Error {
+    let range = error
+        .downcast_ref::<LoadFailure>()
+        .and_then(|failure| find_name(query, range.clone(), &failure.0))
+        .unwrap_or(range);
+    error.context(SourceSpan {
+        statement: index,
+        range,
+    })
+}
+
+/// The parse `error` of `query`, with the token it stopped at attached as its span
+pub(crate) fn locate_parse_error(query: &str, error: ParserError) -> anyhow::Error {
+    let offset = match &error {
+        ParserError::ParserError(message) | ParserError::TokenizerError(message) => {
+            location_in(message).map(|location| offset_of(query, location))
+        }
+        ParserError::RecursionLimitExceeded => None,
+    };
+    let Some(offset) = offset else {
+        return error.into();
+    };
+    let statement = statement_ranges(query)
+        .iter()
+        .take_while(|range| range.end < offset)
+        .count();
+    let range = tokens(query)
+        .ok()
+        .and_then(|tokens| {
+            tokens
+                .into_iter()
+                .find(|(_, range)| range.start == offset)
+                .map(|(_, range)| range)
+        })
+        .unwrap_or(offset..offset);
+    anyhow::Error::from(error).context(SourceSpan { statement, range })
+}
+
+/// The byte range within `query` of the first token in `range` naming `name`
+fn find_name(query: &str, range: Range<usize>, name: &str) -> Option<Range<usize>> {
+    tokens(query)
+        .ok()?
+        .into_iter()
+        .filter(|(_, token_range)| range.start <= token_range.start && token_range.end <= range.end)
+        .find(|(token, _)| match token {
+            Token::Word(word) => word.value == name,
+            Token::SingleQuotedString(value) => value == name,
+            _ => false,
+        })
+        .map(|(_, range)| range)
+}
+
+/// The tokens of `query`, each with the byte range it spans
+fn tokens(query: &str) -> anyhow::Result<Vec<(Token, Range<usize>)>> {
+    let tokens = Tokenizer::new(&GenericDialect, query).tokenize_with_location()?;
+    let starts = tokens
+        .iter()
+        .map(|token| offset_of(query, token.location))
+        .collect::<Vec<_>>();
+    // A token runs up to the next, since whitespace and comments are tokens too
+    let ends = starts.iter().skip(1).copied().chain([query.len()]);
+    Ok(tokens
+        .into_iter()
+        .zip(starts.iter().copied().zip(ends))
+        .map(|(token, (start, end))| (token.token, start..end))
+        .collect())
+}
+
+/// The location sqlparser ends an error message by naming, e.g. `at Line: 1, Column: 10`
+fn location_in(message: &str) -> Option<Location> {
+    let (_, at) = message.rsplit_once("Line: ")?;
+    let (line, rest) = at.split_once(',')?;
+    let column = rest
+        .trim_start_matches(|c: char| !c.is_ascii_digit())
+        .split(|c: char| !c.is_ascii_digit())
+        .next()?;
+    Some(Location {
+        line: line.trim().parse().ok()?,
+        column: column.parse().ok()?,
+    })
+}
+
+/// Byte offset in `query` of a sqlparser `location`, whose line and column count characters from 1
+fn offset_of(query: &str, location: Location) -> usize {
+    let line_start = query
+        .split_inclusive('\n')
+        .take((location.line as usize).saturating_sub(1))
+        .map(str::len)
+        .sum::<usize>();
+    query[line_start..]
+        .char_indices()
+        .nth((location.column as usize).saturating_sub(1))
+        .map_or(query.len(), |(index, _)| line_start + index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The text of `query` each of `ranges` spans
+    fn spanned<'q>(query: &'q str, ranges: &[Range<usize>]) -> Vec<&'q str> {
+        ranges.iter().map(|range| &query[range.clone()]).collect()
+    }
+
+    fn parse_error(query: &str) -> ParserError {
+        sqlparser::parser::Parser::parse_sql(&GenericDialect, query).unwrap_err()
+    }
+
+    #[test]
+    fn statements_span_neither_whitespace_nor_comments() {
+        let query = "  SELECT 1; -- first\n/* second */ SELECT\n  2 ;\n\nSELECT 3";
+        assert_eq!(
+            spanned(query, &statement_ranges(query)),
+            ["SELECT 1", "SELECT\n  2", "SELECT 3"]
+        );
+        assert!(statement_ranges("").is_empty());
+        assert!(statement_ranges("SELECT 'unterminated").is_empty());
+    }
+
+    #[test]
+    fn parse_errors_span_the_token_they_stopped_at() {
+        let query = "SELECT 'é';\nSELECT * FORM t";
+        let error = locate_parse_error(query, parse_error(query));
+        let span = SourceSpan::of(&error).unwrap();
+        assert_eq!(span.statement, 1);
+        assert_eq!(&query[span.range.clone()], "FORM");
+        assert_eq!(span.to_string(), "In statement 2");
+    }
+
+    #[test]
+    fn parse_errors_at_the_end_of_input_are_unlocated() {
+        let error = locate_parse_error("SELECT * FROM", parse_error("SELECT * FROM"));
+        assert!(SourceSpan::of(&error).is_none());
+    }
+
+    #[test]
+    fn load_failures_span_the_name_of_their_table() {
+        // The path appears in the first statement too
+        let query = "SELECT 'data/t.parquet';\nSELECT * FROM 'data/t.parquet' AS t";
+        let range = statement_ranges(query)[1].clone();
+        let error =
+            anyhow::anyhow!("No such file").context(LoadFailure("data/t.parquet".to_string()));
+        let error = locate(query, 1, range, error);
+        let span = SourceSpan::of(&error).unwrap();
+        assert_eq!(span.statement, 1);
+        assert_eq!(&query[span.range.clone()], "'data/t.parquet'");
+    }
+
+    #[test]
+    fn other_failures_span_their_statement() {
+        let query = "SELECT 1; SELECT x FROM t";
+        let range = statement_ranges(query)[1].clone();
+        let error = locate(query, 1, range, anyhow::anyhow!("No column x"));
+        let span = SourceSpan::of(&error).unwrap();
+        assert_eq!(&query[span.range.clone()], "SELECT x FROM t");
+    }
+}
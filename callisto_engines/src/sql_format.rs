@@ -0,0 +1,512 @@
+use sqlparser::dialect::GenericDialect;
+use sqlparser::tokenizer::{Token, Tokenizer};
+
+const INDENT: &str = "    ";
+
+/// Keywords which begin a new clause and so start a new line
+const CLAUSE_KEYWORDS: &[&str] = &[
+    "WITH",
+    "SELECT",
+    "FROM",
+    "WHERE",
+    "GROUP",
+    "HAVING",
+    "WINDOW",
+    "QUALIFY",
+    "ORDER",
+    "LIMIT",
+    "OFFSET",
+    "UNION",
+    "EXCEPT",
+    "INTERSECT",
+    "JOIN",
+    "INNER",
+    "LEFT",
+    "RIGHT",
+    "FULL",
+    "CROSS",
+    "NATURAL",
+    "INSERT",
+    "VALUES",
+    "UPDATE",
+    "SET",
+    "DELETE",
+    "CREATE",
+];
+
+/// Keywords which continue the clause keyword preceding them, e.g. the BY in GROUP BY
+const CLAUSE_CONTINUATIONS: &[&str] = &[
+    "BY", "ALL", "DISTINCT", "OUTER", "INNER", "JOIN", "SEMI", "ANTI", "INTO",
+];
+
+/// Clauses whose bodies are broken on commas when they don't fit on one line
+const LIST_CLAUSES: &[&str] = &[
+    "WITH", "SELECT", "GROUP", "ORDER", "VALUES", "SET", "WINDOW",
+];
+
+/// Clauses whose bodies are broken before AND/OR when they don't fit on one line
+const CONDITION_CLAUSES: &[&str] = &["WHERE", "HAVING", "QUALIFY"];
+
+/// Reserved words which are case-normalized.  Words which commonly double as column names (e.g.
+/// `name`, `count`) are deliberately absent so identifiers are never recased.
+const CASED_KEYWORDS: &[&str] = &[
+    "ALL",
+    "AND",
+    "ANTI",
+    "AS",
+    "ASC",
+    "BETWEEN",
+    "BY",
+    "CASE",
+    "CAST",
+    "CREATE",
+    "CROSS",
+    "DELETE",
+    "DESC",
+    "DISTINCT",
+    "DROP",
+    "ELSE",
+    "END",
+    "EXCEPT",
+    "EXISTS",
+    "FALSE",
+    "FROM",
+    "FULL",
+    "GROUP",
+    "HAVING",
+    "IF",
+    "ILIKE",
+    "IN",
+    "INNER",
+    "INSERT",
+    "INTERSECT",
+    "INTERVAL",
+    "INTO",
+    "IS",
+    "JOIN",
+    "LEFT",
+    "LIKE",
+    "LIMIT",
+    "NATURAL",
+    "NOT",
+    "NULL",
+    "NULLS",
+    "OFFSET",
+    "ON",
+    "OR",
+    "ORDER",
+    "OUTER",
+    "OVER",
+    "PARTITION",
+    "QUALIFY",
+    "RIGHT",
+    "SELECT",
+    "SEMI",
+    "SET",
+    "TABLE",
+    "THEN",
+    "TRUE",
+    "UNION",
+    "UPDATE",
+    "USING",
+    "VALUES",
+    "VIEW",
+    "WHEN",
+    "WHERE",
+    "WINDOW",
+    "WITH",
+];
+
+/// Keywords which are followed by a space before an opening parenthesis, as opposed to function
+/// names
+const SPACED_BEFORE_PAREN: &[&str] = &[
+    "AND", "ANY", "AS", "ALL", "EXISTS", "FROM", "IN", "JOIN", "NOT", "ON", "OR", "OVER", "SOME",
+    "USING", "VALUES", "WHEN", "THEN", "ELSE", "WHERE", "SELECT",
+];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum KeywordCase {
+    #[default]
+    Upper,
+    Lower,
+}
+
+/// Settings for [`format_sql`]
+#[derive(Clone, Debug)]
+pub struct FormatOptions {
+    /// Line width beyond which clauses are broken onto multiple lines
+    pub width: usize,
+    pub keyword_case: KeywordCase,
+}
+
+impl Default for FormatOptions {
+    fn default() -> FormatOptions {
+        FormatOptions {
+            width: 100,
+            keyword_case: KeywordCase::Upper,
+        }
+    }
+}
+
+/// Parse `query` and re-emit it in canonical form, one clause per line, with each statement
+/// terminated by a semicolon.
+///
+/// Formatting works from the parsed AST, so comments in the original text are not preserved.
+pub fn format_sql(query: &str, options: &FormatOptions) -> anyhow::Result<String> {
+    let mut formatted = String::new();
+    for (index, statement) in crate::parse(query)?.iter().enumerate() {
+        if index > 0 {
+            formatted.push('\n');
+        }
+        let tokens: Vec<Token> = Tokenizer::new(&GenericDialect, &statement.to_string())
+            .tokenize()?
+            .into_iter()
+            .filter(|token| !matches!(token, Token::Whitespace(_) | Token::EOF))
+            .collect();
+        let formatter = Formatter { options };
+        for line in formatter.format_query(&tokens, 0) {
+            formatted.push_str(&line);
+            formatted.push('\n');
+        }
+        formatted.pop();
+        formatted.push_str(";\n");
+    }
+    Ok(formatted)
+}
+
+fn keyword(token: &Token) -> Option<String> {
+    match token {
+        Token::Word(word) if word.quote_style.is_none() => Some(word.value.to_uppercase()),
+        _ => None,
+    }
+}
+
+fn is_keyword(token: &Token, keywords: &[&str]) -> bool {
+    keyword(token).is_some_and(|word| keywords.contains(&word.as_str()))
+}
+
+/// Whether `tokens[0]` calls a function sharing a clause keyword's name, e.g. LEFT(...)
+fn is_function_call(tokens: &[Token]) -> bool {
+    is_keyword(&tokens[0], &["LEFT", "RIGHT"]) && matches!(tokens.get(1), Some(Token::LParen))
+}
+
+/// Whether the parenthesis at `tokens[0]` opens a subquery rather than an expression list
+fn opens_subquery(tokens: &[Token]) -> bool {
+    matches!(tokens.first(), Some(Token::LParen))
+        && tokens
+            .get(1)
+            .is_some_and(|token| is_keyword(token, &["SELECT", "WITH"]))
+}
+
+/// Index of the parenthesis closing the one at `tokens[0]`
+fn closing_paren(tokens: &[Token]) -> usize {
+    let mut depth = 0;
+    for (index, token) in tokens.iter().enumerate() {
+        match token {
+            Token::LParen => depth += 1,
+            Token::RParen => {
+                depth -= 1;
+                if depth == 0 {
+                    return index;
+                }
+            }
+            _ => {}
+        }
+    }
+    tokens.len() - 1
+}
+
+/// Split `tokens` at parenthesis depth zero wherever `is_boundary` holds, keeping the boundary
+/// token at the start of the following part
+fn split_top_level<'t>(
+    tokens: &'t [Token],
+    mut is_boundary: impl FnMut(&[Token], usize) -> bool,
+) -> Vec<&'t [Token]> {
+    let mut parts = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (index, token) in tokens.iter().enumerate() {
+        match token {
+            Token::LParen | Token::LBracket => depth += 1,
+            Token::RParen | Token::RBracket => depth = depth.saturating_sub(1),
+            _ if depth == 0 && index > start && is_boundary(tokens, index) => {
+                parts.push(&tokens[start..index]);
+                start = index;
+            }
+            _ => {}
+        }
+    }
+    if start < tokens.len() {
+        parts.push(&tokens[start..]);
+    }
+    parts
+}
+
+struct Formatter<'o> {
+    options: &'o FormatOptions,
+}
+
+impl<'o> Formatter<'o> {
+    fn render_token(&self, token: &Token) -> String {
+        match token {
+            Token::Word(word) if is_keyword(token, CASED_KEYWORDS) => {
+                match self.options.keyword_case {
+                    KeywordCase::Upper => word.value.to_uppercase(),
+                    KeywordCase::Lower => word.value.to_lowercase(),
+                }
+            }
+            _ => token.to_string(),
+        }
+    }
+
+    /// Render tokens on a single line with conventional spacing
+    fn render_inline(&self, tokens: &[Token]) -> String {
+        let mut rendered = String::new();
+        for (index, token) in tokens.iter().enumerate() {
+            if index > 0 {
+                let previous = &tokens[index - 1];
+                // A sign is unary when it doesn't follow an operand
+                let unary_previous = matches!(previous, Token::Minus | Token::Plus)
+                    && (index == 1
+                        || !matches!(
+                            tokens[index - 2],
+                            Token::Word(_) | Token::Number(..) | Token::RParen
+                        )
+                        || is_keyword(&tokens[index - 2], CASED_KEYWORDS));
+                let tight = matches!(
+                    token,
+                    Token::Comma
+                        | Token::RParen
+                        | Token::RBracket
+                        | Token::Period
+                        | Token::DoubleColon
+                        | Token::SemiColon
+                ) || matches!(
+                    previous,
+                    Token::LParen | Token::LBracket | Token::Period | Token::DoubleColon
+                ) || (matches!(token, Token::LParen | Token::LBracket)
+                    && matches!(previous, Token::Word(_))
+                    && !is_keyword(previous, SPACED_BEFORE_PAREN))
+                    || unary_previous;
+                if !tight {
+                    rendered.push(' ');
+                }
+            }
+            if is_function_call(&tokens[index..]) {
+                // Named as written, as any other function is
+                rendered.push_str(&token.to_string());
+            } else {
+                rendered.push_str(&self.render_token(token));
+            }
+        }
+        rendered
+    }
+
+    /// Format a clause item, expanding any subqueries it contains onto their own lines
+    fn format_item(&self, tokens: &[Token], depth: usize) -> Vec<String> {
+        let indent = INDENT.repeat(depth);
+        let mut lines = Vec::new();
+        let mut current: Vec<Token> = Vec::new();
+        let mut index = 0;
+        while index < tokens.len() {
+            if opens_subquery(&tokens[index..]) {
+                let close = index + closing_paren(&tokens[index..]);
+                current.push(Token::LParen);
+                lines.push(format!("{}{}", indent, self.render_inline(&current)));
+                lines.extend(self.format_query(&tokens[index + 1..close], depth + 1));
+                current = vec![Token::RParen];
+                index = close + 1;
+            } else {
+                current.push(tokens[index].clone());
+                index += 1;
+            }
+        }
+        if !current.is_empty() {
+            lines.push(format!("{}{}", indent, self.render_inline(&current)));
+        }
+        lines
+    }
+
+    /// Format a full query or statement as a sequence of clauses
+    fn format_query(&self, tokens: &[Token], depth: usize) -> Vec<String> {
+        let clauses = split_top_level(tokens, |tokens, index| {
+            is_keyword(&tokens[index], CLAUSE_KEYWORDS)
+                && !is_function_call(&tokens[index..])
+                && (is_keyword(&tokens[index], &["SELECT", "FROM", "WHERE"])
+                    || !(is_keyword(&tokens[index - 1], CLAUSE_KEYWORDS)
+                        || is_keyword(&tokens[index - 1], CLAUSE_CONTINUATIONS)))
+        });
+
+        let indent = INDENT.repeat(depth);
+        let mut lines = Vec::new();
+        for clause in clauses {
+            let head_len = clause
+                .iter()
+                .enumerate()
+                .take_while(|(index, token)| {
+                    (is_keyword(token, CLAUSE_KEYWORDS) || is_keyword(token, CLAUSE_CONTINUATIONS))
+                        && !is_function_call(&clause[*index..])
+                })
+                .count()
+                .max(1)
+                .min(clause.len());
+            let (head, body) = clause.split_at(head_len);
+            let head_keyword = keyword(&head[0]).unwrap_or_default();
+            let head = self.render_inline(head);
+
+            let items: Vec<&[Token]> = if LIST_CLAUSES.contains(&head_keyword.as_str()) {
+                split_top_level(body, |tokens, index| {
+                    matches!(tokens[index - 1], Token::Comma)
+                })
+            } else if CONDITION_CLAUSES.contains(&head_keyword.as_str()) {
+                split_top_level(body, |tokens, index| {
+                    is_keyword(&tokens[index], &["AND", "OR"])
+                })
+            } else {
+                vec![body]
+            };
+            let items: Vec<&[Token]> = items.into_iter().filter(|item| !item.is_empty()).collect();
+            let formatted: Vec<Vec<String>> = items
+                .iter()
+                .map(|item| self.format_item(item, depth + 1))
+                .collect();
+
+            let single_line = formatted.iter().all(|item| item.len() == 1) && {
+                let joined: Vec<&str> = formatted.iter().map(|item| item[0].trim_start()).collect();
+                let line = format!("{}{} {}", indent, head, joined.join(" "));
+                line.len() <= self.options.width
+            };
+            if formatted.is_empty() {
+                lines.push(format!("{}{}", indent, head));
+            } else if single_line {
+                let joined: Vec<&str> = formatted.iter().map(|item| item[0].trim_start()).collect();
+                lines.push(format!("{}{} {}", indent, head, joined.join(" ")));
+            } else if items.len() == 1 {
+                // A lone item stays on the clause line, with any expanded subquery following it at
+                // the clause's own depth.
+                let item = self.format_item(items[0], depth);
+                lines.push(format!("{}{} {}", indent, head, item[0].trim_start()));
+                lines.extend(item.into_iter().skip(1));
+            } else {
+                lines.push(format!("{}{}", indent, head));
+                for item in formatted {
+                    lines.extend(item);
+                }
+            }
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Queries exercising each kind of clause, item, and subquery the formatter lays out
+    const QUERIES: &[&str] = &[
+        "select 1",
+        "SELECT a, b AS c FROM t WHERE a > 1 AND b < -2 ORDER BY a DESC LIMIT 10 OFFSET 5",
+        "select count(*), left(name, 3) from people group by left(name, 3) having count(*) > 1",
+        "WITH recent AS (SELECT * FROM events WHERE day > '2024-01-01') SELECT * FROM recent",
+        "select * from a left outer join b on a.id = b.id cross join c natural join d",
+        "SELECT * FROM t WHERE id IN (SELECT id FROM u WHERE u.flag) OR EXISTS (SELECT 1 FROM v)",
+        "select case when x is null then 'none' else cast(x as varchar) end from t",
+        "SELECT a FROM t UNION ALL SELECT b FROM u EXCEPT SELECT c FROM v",
+        "select sum(x) over (partition by y order by z) from t qualify row_number() over () = 1",
+        "insert into t (a, b) values (1, 'one'), (2, 'two')",
+        "UPDATE t SET a = 1, b = \"quoted column\" WHERE c IS NOT NULL",
+        "delete from t where a between 1 and 10",
+        "create table t as select * from 'data/file.parquet'",
+        "SELECT x::int, arr[1], -y, +z, a - -b FROM t",
+        "SELECT 1; select 2 from t",
+    ];
+
+    fn format(query: &str, options: &FormatOptions) -> String {
+        format_sql(query, options).unwrap_or_else(|error| panic!("{}: {}", query, error))
+    }
+
+    fn narrow() -> FormatOptions {
+        FormatOptions {
+            width: 20,
+            ..FormatOptions::default()
+        }
+    }
+
+    #[test]
+    fn formatting_keeps_the_meaning_of_queries() {
+        for options in [FormatOptions::default(), narrow()] {
+            for query in QUERIES {
+                let formatted = format(query, &options);
+                assert_eq!(
+                    crate::parse(&formatted).unwrap(),
+                    crate::parse(query).unwrap(),
+                    "{} was formatted as\n{}",
+                    query,
+                    formatted
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn formatting_is_idempotent() {
+        let lower = FormatOptions {
+            keyword_case: KeywordCase::Lower,
+            ..FormatOptions::default()
+        };
+        for options in [FormatOptions::default(), narrow(), lower] {
+            for query in QUERIES {
+                let formatted = format(query, &options);
+                assert_eq!(format(&formatted, &options), formatted, "{}", query);
+            }
+        }
+    }
+
+    #[test]
+    fn clauses_are_broken_only_when_too_wide() {
+        let query = "select a, b from t where a = 1 and b = 2";
+        assert_eq!(
+            format(query, &FormatOptions::default()),
+            "SELECT a, b\nFROM t\nWHERE a = 1 AND b = 2;\n"
+        );
+        assert_eq!(
+            format(query, &narrow()),
+            "SELECT a, b\nFROM t\nWHERE\n    a = 1\n    AND b = 2;\n"
+        );
+    }
+
+    #[test]
+    fn subqueries_are_indented() {
+        assert_eq!(
+            format(
+                "select * from (select a from t) as s",
+                &FormatOptions::default()
+            ),
+            "SELECT *\nFROM (\n    SELECT a\n    FROM t\n) AS s;\n"
+        );
+    }
+
+    #[test]
+    fn only_keywords_are_recased() {
+        let options = FormatOptions {
+            keyword_case: KeywordCase::Lower,
+            ..FormatOptions::default()
+        };
+        assert_eq!(
+            format("SELECT Name, COUNT(*) FROM People", &options),
+            "select Name, COUNT(*)\nfrom People;\n"
+        );
+        assert_eq!(
+            format("select name from t", &FormatOptions::default()),
+            "SELECT name\nFROM t;\n"
+        );
+    }
+
+    #[test]
+    fn statements_are_each_terminated_and_separated() {
+        assert_eq!(
+            format("select 1; select 2", &FormatOptions::default()),
+            "SELECT 1;\n\nSELECT 2;\n"
+        );
+    }
+}
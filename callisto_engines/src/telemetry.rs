@@ -0,0 +1,90 @@
+use crate::{Engine, ExecutionStats};
+
+const STATEMENTS: &str = "callisto_statements_total";
+const STATEMENT_SECONDS: &str = "callisto_statement_seconds";
+const ROWS: &str = "callisto_rows_streamed_total";
+const BYTES_SCANNED: &str = "callisto_bytes_scanned_total";
+const TABLES_LOADED: &str = "callisto_tables_loaded_total";
+const TABLE_LOAD_FAILURES: &str = "callisto_table_load_failures_total";
+const TABLE_CACHE_HITS: &str = "callisto_table_cache_hits_total";
+
+/// Describe the metrics the engines emit through the [`metrics`] facade to the recorder the
+/// process installed, e.g. a Prometheus exporter, giving their units and help text.
+///
+/// Each is labelled with the `engine` it's about:
+///
+/// - `callisto_statements_total`, statements run, also labelled with their `outcome`
+/// - `callisto_statement_seconds`, how long statements spent in each `phase`
+/// - `callisto_rows_streamed_total`, rows read from results
+/// - `callisto_bytes_scanned_total`, bytes read from storage, where the engine reports it
+/// - `callisto_tables_loaded_total` and `callisto_table_load_failures_total`, files queries named
+///   loaded as tables, or that couldn't be
+/// - `callisto_table_cache_hits_total`, files queries named which had been loaded already
+pub fn describe_metrics() {
+    use metrics::Unit;
+
+    metrics::describe_counter!(STATEMENTS, Unit::Count, "Statements run");
+    metrics::describe_histogram!(
+        STATEMENT_SECONDS,
+        Unit::Seconds,
+        "Time statements spent parsing, loading tables, executing, and streaming results"
+    );
+    metrics::describe_counter!(ROWS, Unit::Count, "Rows read from results");
+    metrics::describe_counter!(BYTES_SCANNED, Unit::Bytes, "Bytes read from storage");
+    metrics::describe_counter!(TABLES_LOADED, Unit::Count, "Files loaded as tables");
+    metrics::describe_counter!(
+        TABLE_LOAD_FAILURES,
+        Unit::Count,
+        "Files that failed to load as tables"
+    );
+    metrics::describe_counter!(
+        TABLE_CACHE_HITS,
+        Unit::Count,
+        "Files named by queries which had been loaded already"
+    );
+}
+
+/// A statement ran on `engine`, or failed before it had results
+pub(crate) fn statement(engine: Engine, succeeded: bool) {
+    let outcome = if succeeded { "ok" } else { "error" };
+    metrics::counter!(STATEMENTS, "engine" => engine.name(), "outcome" => outcome).increment(1);
+}
+
+/// `rows` more were read from the results of a statement on `engine`
+pub(crate) fn rows(engine: Engine, rows: usize) {
+    metrics::counter!(ROWS, "engine" => engine.name()).increment(rows as u64);
+}
+
+/// A statement on `engine` ended, its results read to the end or dropped part way
+pub(crate) fn finished(engine: Engine, stats: &ExecutionStats) {
+    let phases = [
+        ("parse", Some(stats.parse_time)),
+        ("load", Some(stats.load_time)),
+        ("execution", Some(stats.execution_time)),
+        ("stream", stats.stream_time),
+    ];
+    for (phase, time) in phases {
+        if let Some(time) = time {
+            metrics::histogram!(STATEMENT_SECONDS, "engine" => engine.name(), "phase" => phase)
+                .record(time.as_secs_f64());
+        }
+    }
+    if let Some(bytes) = stats.bytes_scanned {
+        metrics::counter!(BYTES_SCANNED, "engine" => engine.name()).increment(bytes as u64);
+    }
+}
+
+/// `engine` loaded a file a query named as a table
+pub(crate) fn table_loaded(engine: Engine) {
+    metrics::counter!(TABLES_LOADED, "engine" => engine.name()).increment(1);
+}
+
+/// `engine` failed to load a file a query named as a table
+pub(crate) fn table_load_failed(engine: Engine) {
+    metrics::counter!(TABLE_LOAD_FAILURES, "engine" => engine.name()).increment(1);
+}
+
+/// `engine` found a file a query named to have been loaded already
+pub(crate) fn table_cache_hit(engine: Engine) {
+    metrics::counter!(TABLE_CACHE_HITS, "engine" => engine.name()).increment(1);
+}
@@ -0,0 +1,159 @@
+use core::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+use arrow::record_batch::RecordBatch;
+use datafusion::common::DataFusionError;
+use datafusion::physical_plan::SendableRecordBatchStream;
+use futures::Stream;
+
+use crate::CallistoError;
+
+/// When a statement allowed to run for `timeout` must be done by
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Deadline {
+    at: tokio::time::Instant,
+    timeout: Duration,
+}
+
+impl Deadline {
+    /// The deadline of a statement starting now, if it has a `timeout`
+    pub(crate) fn after(timeout: Option<Duration>) -> Option<Deadline> {
+        timeout.map(|timeout| Deadline {
+            at: tokio::time::Instant::now() + timeout,
+            timeout,
+        })
+    }
+
+    #[cfg(feature = "duckdb-engine")]
+    pub(crate) fn passed(&self) -> bool {
+        tokio::time::Instant::now() >= self.at
+    }
+
+    pub(crate) fn error(&self) -> CallistoError {
+        CallistoError::Timeout(self.timeout)
+    }
+
+    /// `error`, or the timeout if the deadline had passed by the time it happened, it then being
+    /// what the statement was cancelled with
+    #[cfg(feature = "duckdb-engine")]
+    pub(crate) fn explain(deadline: Option<Deadline>, error: anyhow::Error) -> anyhow::Error {
+        match deadline {
+            Some(deadline) if deadline.passed() => deadline.error().into(),
+            _ => error,
+        }
+    }
+}
+
+/// Run `future`, dropping it if `deadline` passes first
+#[cfg(any(feature = "polars-engine", feature = "datafusion-engine"))]
+pub(crate) async fn run<T>(
+    deadline: Option<Deadline>,
+    future: impl std::future::Future<Output = anyhow::Result<T>>,
+) -> anyhow::Result<T> {
+    let Some(deadline) = deadline else {
+        return future.await;
+    };
+    match tokio::time::timeout_at(deadline.at, future).await {
+        Ok(result) => result,
+        Err(_) => Err(deadline.error().into()),
+    }
+}
+
+/// Run the blocking `work` on a thread of its own, so that it can be given up on once `deadline`
+/// passes or the future is dropped, e.g. on Ctrl-C, though it runs on to the end there with its
+/// result discarded.
+#[cfg(feature = "polars-engine")]
+pub(crate) async fn blocking<T: Send + 'static>(
+    deadline: Option<Deadline>,
+    work: impl FnOnce() -> anyhow::Result<T> + Send + 'static,
+) -> anyhow::Result<T> {
+    run(deadline, async {
+        tokio::task::spawn_blocking(work)
+            .await
+            .map_err(anyhow::Error::from)?
+    })
+    .await
+}
+
+/// Calls its interrupt once the deadline it was set for passes, unless dropped before then
+#[cfg(feature = "duckdb-engine")]
+pub(crate) struct Alarm(tokio::task::JoinHandle<()>);
+
+#[cfg(feature = "duckdb-engine")]
+impl Alarm {
+    /// An alarm calling `interrupt` at `deadline`, if there is one.
+    ///
+    /// It's called from another task, so it can cancel work blocking this one.
+    pub(crate) fn set(
+        deadline: Option<Deadline>,
+        interrupt: impl FnOnce() + Send + 'static,
+    ) -> Option<Alarm> {
+        let deadline = deadline?;
+        Some(Alarm(tokio::spawn(async move {
+            tokio::time::sleep_until(deadline.at).await;
+            interrupt();
+        })))
+    }
+}
+
+#[cfg(feature = "duckdb-engine")]
+impl Drop for Alarm {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// `stream`, failing once `deadline` has passed
+pub(crate) fn limit_stream(
+    stream: SendableRecordBatchStream,
+    deadline: Option<Deadline>,
+) -> SendableRecordBatchStream {
+    match deadline {
+        Some(deadline) => Box::pin(DeadlineStream {
+            stream,
+            sleep: tokio::time::sleep_until(deadline.at),
+            deadline,
+            expired: false,
+        }),
+        None => stream,
+    }
+}
+
+#[pin_project::pin_project]
+struct DeadlineStream {
+    #[pin]
+    stream: SendableRecordBatchStream,
+    #[pin]
+    sleep: tokio::time::Sleep,
+    deadline: Deadline,
+    expired: bool,
+}
+
+impl datafusion::physical_plan::RecordBatchStream for DeadlineStream {
+    fn schema(&self) -> Arc<arrow::datatypes::Schema> {
+        self.stream.schema()
+    }
+}
+
+impl Stream for DeadlineStream {
+    type Item = Result<RecordBatch, DataFusionError>;
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut futures::task::Context<'_>,
+    ) -> futures::task::Poll<Option<Self::Item>> {
+        use std::future::Future as _;
+
+        let this = self.project();
+        if *this.expired {
+            return futures::task::Poll::Ready(None);
+        }
+        if this.sleep.poll(cx).is_ready() {
+            *this.expired = true;
+            let error = DataFusionError::External(Box::new(this.deadline.error()));
+            return futures::task::Poll::Ready(Some(Err(error)));
+        }
+        this.stream.poll_next(cx)
+    }
+}
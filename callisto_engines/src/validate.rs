@@ -0,0 +1,223 @@
+use core::ops::ControlFlow;
+use std::collections::BTreeSet;
+
+use sqlparser::ast::{self, Visit as _};
+
+/// A problem found while checking a query without executing it
+#[derive(Clone, Debug)]
+pub struct ValidationIssue {
+    /// Index of the offending statement within the query, if the issue is tied to one
+    pub statement: Option<usize>,
+    pub message: String,
+}
+
+impl std::fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.statement {
+            Some(index) => write!(f, "statement {}: {}", index + 1, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// Parse `query` and check referenced files and columns without involving an engine.
+///
+/// Files are checked for existence and a readable Parquet schema.  Column references are only
+/// checked in statements whose relations all resolve to readable files, since otherwise the set of
+/// available columns is unknown.
+pub fn validate_query(query: &str) -> Vec<ValidationIssue> {
+    check_statically(query).issues
+}
+
+pub(crate) struct StaticValidation {
+    pub issues: Vec<ValidationIssue>,
+    /// Queries whose relations all resolved to files, so an engine may safely plan them
+    #[cfg_attr(
+        not(any(feature = "polars-engine", feature = "datafusion-engine")),
+        allow(dead_code)
+    )]
+    pub plannable: Vec<(usize, ast::Statement)>,
+}
+
+pub(crate) fn check_statically(query: &str) -> StaticValidation {
+    let mut validation = StaticValidation {
+        issues: Vec::new(),
+        plannable: Vec::new(),
+    };
+    let statements = match crate::parse(query) {
+        Ok(statements) => statements,
+        Err(error) => {
+            validation.issues.push(ValidationIssue {
+                statement: None,
+                message: format!("Failed to parse: {}", error),
+            });
+            return validation;
+        }
+    };
+
+    // Tables created earlier in the query are valid references for later statements.
+    let mut created = BTreeSet::new();
+    for (index, statement) in statements.into_iter().enumerate() {
+        let mut names = NameCollector::default();
+        let _ = statement.visit(&mut names);
+        if let ast::Statement::CreateTable { name, .. } | ast::Statement::CreateView { name, .. } =
+            &statement
+        {
+            names.relations.remove(&name.0[0].value);
+            created.insert(name.0[0].value.clone());
+        }
+
+        let mut fields = BTreeSet::new();
+        let mut fully_resolved = true;
+        for relation in &names.relations {
+            if names.ctes.contains(relation) || created.contains(relation) {
+                fully_resolved = false;
+                continue;
+            }
+            match read_schema(relation) {
+                Ok(Some(schema)) => {
+                    fields.extend(schema.fields().iter().map(|f| f.name().to_lowercase()))
+                }
+                Ok(None) => fully_resolved = false,
+                Err(message) => {
+                    fully_resolved = false;
+                    validation.issues.push(ValidationIssue {
+                        statement: Some(index),
+                        message,
+                    });
+                }
+            }
+        }
+        if !fully_resolved {
+            continue;
+        }
+
+        let known = |ident: &ast::Ident| {
+            let name = ident.value.to_lowercase();
+            fields.contains(&name) || names.aliases.contains(&name)
+        };
+        let mut unknown = BTreeSet::new();
+        let _ = ast::visit_expressions(&statement, |expr| {
+            match expr {
+                ast::Expr::Identifier(ident) if !known(ident) => {
+                    unknown.insert(ident.value.clone());
+                }
+                ast::Expr::CompoundIdentifier(idents)
+                    if idents.len() == 2
+                        && names.qualifiers.contains(&idents[0].value.to_lowercase())
+                        && !known(&idents[1]) =>
+                {
+                    unknown.insert(idents[1].value.clone());
+                }
+                _ => {}
+            }
+            ControlFlow::<()>::Continue(())
+        });
+        for column in &unknown {
+            validation.issues.push(ValidationIssue {
+                statement: Some(index),
+                message: format!(
+                    "Column '{}' not found in referenced files ({})",
+                    column,
+                    names
+                        .relations
+                        .iter()
+                        .cloned()
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            });
+        }
+        if unknown.is_empty() && matches!(statement, ast::Statement::Query(_)) {
+            validation.plannable.push((index, statement));
+        }
+    }
+    validation
+}
+
+/// Read the Arrow schema of the Parquet file at `path`.
+///
+/// Returns `Ok(None)` for glob patterns, which are left for the engine to expand.
+fn read_schema(path: &str) -> Result<Option<arrow::datatypes::SchemaRef>, String> {
+    if path.contains(['*', '?', '[']) {
+        return Ok(None);
+    }
+    let file = std::fs::File::open(path)
+        .map_err(|error| format!("Referenced file '{}' cannot be opened: {}", path, error))?;
+    let builder = parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder::try_new(file)
+        .map_err(|error| {
+            format!(
+                "Referenced file '{}' does not have a readable Parquet schema: {}",
+                path, error
+            )
+        })?;
+    Ok(Some(builder.schema().clone()))
+}
+
+/// Collects the names a statement defines and references
+#[derive(Default)]
+struct NameCollector {
+    /// Relations referenced by name, including CTEs
+    relations: BTreeSet<String>,
+    /// Names of common table expressions
+    ctes: BTreeSet<String>,
+    /// Lowercased projection aliases, usable as columns in e.g. ORDER BY
+    aliases: BTreeSet<String>,
+    /// Lowercased relation names and aliases which may qualify a column
+    qualifiers: BTreeSet<String>,
+}
+
+impl NameCollector {
+    fn collect_projection_aliases(&mut self, body: &ast::SetExpr) {
+        match body {
+            ast::SetExpr::Select(select) => {
+                for item in &select.projection {
+                    if let ast::SelectItem::ExprWithAlias { alias, .. } = item {
+                        self.aliases.insert(alias.value.to_lowercase());
+                    }
+                }
+            }
+            ast::SetExpr::SetOperation { left, right, .. } => {
+                self.collect_projection_aliases(left);
+                self.collect_projection_aliases(right);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl ast::Visitor for NameCollector {
+    type Break = ();
+
+    fn pre_visit_query(&mut self, query: &ast::Query) -> ControlFlow<Self::Break> {
+        if let Some(with) = &query.with {
+            for cte in &with.cte_tables {
+                self.ctes.insert(cte.alias.name.value.clone());
+            }
+        }
+        self.collect_projection_aliases(&query.body);
+        ControlFlow::Continue(())
+    }
+
+    fn pre_visit_relation(&mut self, relation: &ast::ObjectName) -> ControlFlow<Self::Break> {
+        self.relations.insert(relation.0[0].value.clone());
+        self.qualifiers.insert(relation.0[0].value.to_lowercase());
+        ControlFlow::Continue(())
+    }
+
+    fn pre_visit_table_factor(
+        &mut self,
+        table_factor: &ast::TableFactor,
+    ) -> ControlFlow<Self::Break> {
+        if let ast::TableFactor::Table {
+            alias: Some(alias), ..
+        }
+        | ast::TableFactor::Derived {
+            alias: Some(alias), ..
+        } = table_factor
+        {
+            self.qualifiers.insert(alias.name.value.to_lowercase());
+        }
+        ControlFlow::Continue(())
+    }
+}
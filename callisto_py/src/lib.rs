@@ -0,0 +1,168 @@
+use arrow::{
+    datatypes::SchemaRef,
+    ffi_stream::ArrowArrayStreamReader,
+    pyarrow::{FromPyArrow as _, ToPyArrow as _},
+    record_batch::{RecordBatch, RecordBatchReader as _},
+};
+use futures::stream::StreamExt as _;
+use pyo3::{
+    exceptions::{PyRuntimeError, PyValueError},
+    prelude::*,
+    types::PyDict,
+};
+
+use callisto_engines::EngineInterface;
+
+fn failure(error: anyhow::Error) -> PyErr {
+    PyRuntimeError::new_err(format!("{:#}", error))
+}
+
+/// An engine running SQL over the tables registered with it and the parquet files its queries
+/// name.
+///
+/// Results cross into Python through the Arrow C data interface, so they aren't copied on the
+/// way, and the GIL is released while the engine works.
+#[pyclass(name = "Engine")]
+struct Engine {
+    engine: Box<dyn EngineInterface>,
+    runtime: tokio::runtime::Runtime,
+}
+
+#[pymethods]
+impl Engine {
+    /// A new engine of kind `kind`: "polars", "duckdb", or "datafusion"
+    #[new]
+    #[pyo3(signature = (kind = "datafusion"))]
+    fn new(kind: &str) -> PyResult<Engine> {
+        let kind = callisto_engines::Engine::ALL
+            .into_iter()
+            .find(|engine| engine.name() == kind)
+            .ok_or_else(|| {
+                PyValueError::new_err(format!(
+                    "No engine {}: use polars, duckdb, or datafusion",
+                    kind
+                ))
+            })?;
+        Ok(Engine {
+            engine: kind.new().map_err(failure)?,
+            runtime: tokio::runtime::Runtime::new()?,
+        })
+    }
+
+    /// Which engine this is
+    #[getter]
+    fn kind(&self) -> &'static str {
+        self.engine.kind().name()
+    }
+
+    /// Register `data` as a table called `name`, replacing any table of that name.
+    ///
+    /// `data` is anything exporting an Arrow stream, e.g. a pyarrow or polars table, or anything
+    /// `pyarrow.table` takes, e.g. a pandas DataFrame.
+    fn register(&mut self, py: Python<'_>, name: &str, data: &PyAny) -> PyResult<()> {
+        let data = if data.hasattr("__arrow_c_stream__")? {
+            data
+        } else {
+            py.import("pyarrow")?.call_method1("table", (data,))?
+        };
+        let reader = ArrowArrayStreamReader::from_pyarrow(data)?;
+        let schema = reader.schema();
+        let batches = reader.collect::<Result<Vec<_>, _>>().map_err(|error| {
+            PyValueError::new_err(format!("Failed to read {}: {}", name, error))
+        })?;
+        let Engine { engine, runtime } = self;
+        py.allow_threads(|| runtime.block_on(engine.register_batches(name, schema, batches)))
+            .map_err(failure)
+    }
+
+    /// The tables registered, each a dict of its `name`, the `source` path it was read from if
+    /// any, and its pyarrow `schema` if known
+    fn tables(&mut self, py: Python<'_>) -> PyResult<Vec<PyObject>> {
+        let Engine { engine, runtime } = self;
+        let tables = py
+            .allow_threads(|| runtime.block_on(engine.tables()))
+            .map_err(failure)?;
+        tables
+            .into_iter()
+            .map(|table| {
+                let info = PyDict::new(py);
+                info.set_item("name", table.name)?;
+                info.set_item("source", table.source)?;
+                let schema = table
+                    .schema
+                    .map(|schema| schema.as_ref().to_pyarrow(py))
+                    .transpose()?;
+                info.set_item("schema", schema)?;
+                Ok(info.into())
+            })
+            .collect()
+    }
+
+    /// Run `query`, returning the rows of its last statement as a pyarrow Table
+    fn sql<'py>(&mut self, py: Python<'py>, query: &str) -> PyResult<&'py PyAny> {
+        let (schema, batches) = self.run(py, query)?;
+        table(py, schema, batches)
+    }
+
+    /// Run `query`, returning the rows of its last statement as a pandas DataFrame
+    fn pandas<'py>(&mut self, py: Python<'py>, query: &str) -> PyResult<&'py PyAny> {
+        self.sql(py, query)?.call_method0("to_pandas")
+    }
+
+    /// Run `query`, returning the rows of its last statement as a polars DataFrame
+    fn polars<'py>(&mut self, py: Python<'py>, query: &str) -> PyResult<&'py PyAny> {
+        let table = self.sql(py, query)?;
+        py.import("polars")?.call_method1("from_arrow", (table,))
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Engine({:?})", self.kind())
+    }
+}
+
+impl Engine {
+    /// Run `query` with the GIL released, collecting the rows of its last statement once the
+    /// statements before it have run
+    fn run(&mut self, py: Python<'_>, query: &str) -> PyResult<(SchemaRef, Vec<RecordBatch>)> {
+        let Engine { engine, runtime } = self;
+        py.allow_threads(|| {
+            runtime.block_on(async {
+                let mut statements = engine.execute(query).await?;
+                let Some(mut last) = statements.pop() else {
+                    anyhow::bail!("No statements to run");
+                };
+                for mut execution in statements {
+                    while let Some(batch) = execution.stream.next().await {
+                        batch?;
+                    }
+                }
+                let schema = last.schema.clone();
+                let mut batches = Vec::new();
+                while let Some(batch) = last.stream.next().await {
+                    batches.push(batch?);
+                }
+                Ok((schema, batches))
+            })
+        })
+        .map_err(failure)
+    }
+}
+
+/// `batches` as a pyarrow Table, handed over through the Arrow C data interface
+fn table(py: Python<'_>, schema: SchemaRef, batches: Vec<RecordBatch>) -> PyResult<&PyAny> {
+    let batches = batches
+        .iter()
+        .map(|batch| batch.to_pyarrow(py))
+        .collect::<PyResult<Vec<_>>>()?;
+    py.import("pyarrow")?
+        .getattr("Table")?
+        .call_method1("from_batches", (batches, schema.as_ref().to_pyarrow(py)?))
+}
+
+/// Callisto's engines for Python: `callisto.Engine("duckdb").sql("SELECT ...")`
+#[pymodule]
+#[pyo3(name = "callisto")]
+fn module(_py: Python<'_>, module: &PyModule) -> PyResult<()> {
+    module.add_class::<Engine>()?;
+    Ok(())
+}
@@ -0,0 +1,133 @@
+use arrow::{ipc::writer::StreamWriter, record_batch::RecordBatch};
+use futures::stream::StreamExt as _;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use wasm_bindgen::{prelude::*, JsCast as _};
+use wasm_bindgen_futures::JsFuture;
+
+use callisto_engines::{Engine, EngineInterface};
+
+fn failure(error: anyhow::Error) -> JsError {
+    JsError::new(&format!("{:#}", error))
+}
+
+/// DataFusion for the browser, over Parquet registered with it from memory.
+///
+/// The browser has no filesystem for queries to name Parquet files on, so files are fetched (see
+/// [`fetch_parquet`]) or read by the page and handed over as bytes. Queries run to completion
+/// before returning, so a page wanting to stay responsive runs them in a worker.
+#[wasm_bindgen]
+pub struct Callisto {
+    engine: Box<dyn EngineInterface>,
+    runtime: tokio::runtime::Runtime,
+}
+
+#[wasm_bindgen]
+impl Callisto {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Result<Callisto, JsError> {
+        Ok(Callisto {
+            engine: Engine::DataFusion.new().map_err(failure)?,
+            runtime: tokio::runtime::Builder::new_current_thread().build()?,
+        })
+    }
+
+    /// Register the Parquet file `bytes` as a table called `name`, replacing any table of that
+    /// name
+    #[wasm_bindgen(js_name = registerParquet)]
+    pub fn register_parquet(&mut self, name: &str, bytes: Vec<u8>) -> Result<(), JsError> {
+        let registered: anyhow::Result<()> = (|| {
+            let builder = ParquetRecordBatchReaderBuilder::try_new(bytes::Bytes::from(bytes))?;
+            let schema = builder.schema().clone();
+            let batches = builder.build()?.collect::<Result<Vec<_>, _>>()?;
+            self.runtime
+                .block_on(self.engine.register_batches(name, schema, batches))
+        })();
+        registered.map_err(|error| failure(error.context(format!("Failed to register {}", name))))
+    }
+
+    /// The names of the tables registered
+    pub fn tables(&mut self) -> Result<Vec<String>, JsError> {
+        let tables = self
+            .runtime
+            .block_on(self.engine.tables())
+            .map_err(failure)?;
+        Ok(tables.into_iter().map(|table| table.name).collect())
+    }
+
+    /// Run `sql`, returning the rows of its last statement as an Arrow IPC stream, e.g. for
+    /// `tableFromIPC` of the `apache-arrow` package
+    pub fn query(&mut self, sql: &str) -> Result<Vec<u8>, JsError> {
+        let (schema, batches) = self.run(sql).map_err(failure)?;
+        let encoded: anyhow::Result<Vec<u8>> = (|| {
+            let mut writer = StreamWriter::try_new(Vec::new(), &schema)?;
+            for batch in &batches {
+                writer.write(batch)?;
+            }
+            writer.finish()?;
+            Ok(writer.into_inner()?)
+        })();
+        encoded.map_err(failure)
+    }
+
+    /// Run `sql`, returning the rows of its last statement as JSON: an array of objects keyed by
+    /// column name
+    #[wasm_bindgen(js_name = queryJson)]
+    pub fn query_json(&mut self, sql: &str) -> Result<String, JsError> {
+        let (_, batches) = self.run(sql).map_err(failure)?;
+        let encoded: anyhow::Result<String> = (|| {
+            let mut writer = arrow::json::ArrayWriter::new(Vec::new());
+            writer.write_batches(&batches.iter().collect::<Vec<_>>())?;
+            writer.finish()?;
+            Ok(String::from_utf8(writer.into_inner())?)
+        })();
+        encoded.map_err(failure)
+    }
+}
+
+impl Callisto {
+    /// Run `sql`, collecting the rows of its last statement once the statements before it have
+    /// run
+    fn run(
+        &mut self,
+        sql: &str,
+    ) -> anyhow::Result<(arrow::datatypes::SchemaRef, Vec<RecordBatch>)> {
+        let Callisto { engine, runtime } = self;
+        runtime.block_on(async {
+            let mut statements = engine.execute(sql).await?;
+            let Some(mut last) = statements.pop() else {
+                anyhow::bail!("No statements to run");
+            };
+            for mut execution in statements {
+                while let Some(batch) = execution.stream.next().await {
+                    batch?;
+                }
+            }
+            let schema = last.schema.clone();
+            let mut batches = Vec::new();
+            while let Some(batch) = last.stream.next().await {
+                batches.push(batch?);
+            }
+            Ok((schema, batches))
+        })
+    }
+}
+
+/// Fetch the Parquet file at `url`, for [`Callisto::register_parquet`]
+#[wasm_bindgen(js_name = fetchParquet)]
+pub async fn fetch_parquet(url: String) -> Result<js_sys::Uint8Array, JsValue> {
+    let window = web_sys::window().ok_or_else(|| JsError::new("No window to fetch from"))?;
+    let response: web_sys::Response = JsFuture::from(window.fetch_with_str(&url))
+        .await?
+        .dyn_into()?;
+    if !response.ok() {
+        let message = format!(
+            "Failed to fetch {}: {} {}",
+            url,
+            response.status(),
+            response.status_text()
+        );
+        return Err(JsError::new(&message).into());
+    }
+    let buffer = JsFuture::from(response.array_buffer()?).await?;
+    Ok(js_sys::Uint8Array::new(&buffer))
+}